@@ -0,0 +1,211 @@
+use anyhow::Result;
+use console::style;
+
+use crate::chart;
+use crate::config::DatabaseType;
+use crate::database::{self, Database, QueryResult};
+use crate::error::QgoError;
+use crate::ui::prompts;
+
+/// Estimates off by more than this factor either way are highlighted.
+const BAD_ESTIMATE_FACTOR: f64 = 10.0;
+
+/// Node names longer than this are cut short to keep the table narrow.
+const MAX_NODE_WIDTH: usize = 40;
+
+/// One node of an executed plan. Row counts are per loop, as the server
+/// reports them.
+pub struct PlanNode {
+    pub node: String,
+    pub relation: Option<String>,
+    pub estimated_rows: Option<f64>,
+    /// `None` for nodes that never ran.
+    pub actual_rows: Option<f64>,
+    /// Time spent in the node and its children over all loops.
+    pub time_ms: Option<f64>,
+}
+
+impl PlanNode {
+    /// How many times more (or fewer) rows came out than were estimated,
+    /// as a factor of at least 1. Zero counts as one row.
+    pub fn error_factor(&self) -> Option<f64> {
+        let estimated = self.estimated_rows?.max(1.0);
+        let actual = self.actual_rows?.max(1.0);
+        Some(if actual > estimated { actual / estimated } else { estimated / actual })
+    }
+}
+
+/// What `\analyze` found: plan nodes, slowest first, and the summary lines
+/// the server printed after the plan (planning and execution time).
+pub enum Analysis {
+    Nodes { nodes: Vec<PlanNode>, summary: Vec<String> },
+    /// SQLite's `EXPLAIN QUERY PLAN`, shown as it is; nothing was run.
+    QueryPlan(QueryResult),
+}
+
+/// Runs `query` under `EXPLAIN ANALYZE` (Postgres, MySQL 8) and collects
+/// its plan nodes, or gets SQLite's query plan. `EXPLAIN ANALYZE` runs the
+/// statement, so a write needs `allow_writes` and, on production
+/// connections, the same confirmation as any other write.
+///
+/// The text plan is parsed rather than `FORMAT JSON`, which the driver
+/// can't decode; both servers print the estimate and the actual figures
+/// on each node's line.
+pub async fn analyze(database: &mut Database, query: &str) -> Result<Analysis> {
+    let query = query.trim().trim_end_matches(';').trim_end();
+    let db_type = database.get_connection().db_type.clone();
+    let explain = match db_type {
+        DatabaseType::PostgreSQL | DatabaseType::MySQL => format!("EXPLAIN ANALYZE {}", query),
+        DatabaseType::SQLite => {
+            let plan = database.execute_query(&format!("EXPLAIN QUERY PLAN {}", query)).await?;
+            return Ok(Analysis::QueryPlan(plan));
+        }
+        DatabaseType::MariaDB => {
+            return Err(QgoError::InvalidQuery(
+                "\\analyze supports Postgres, MySQL 8 and SQLite; on MariaDB run ANALYZE <query> instead".to_string(),
+            )
+            .into())
+        }
+    };
+
+    if database::modifies_data(query) {
+        let connection = database.get_connection();
+        database::check_not_replica(connection)?;
+        if !connection.allow_writes {
+            return Err(QgoError::InvalidQuery(
+                "EXPLAIN ANALYZE runs the statement, and statements that modify data are not allowed on this connection; enable allow_writes to run them".to_string(),
+            )
+            .into());
+        }
+        prompts::confirm_production_write(connection)?;
+    }
+
+    let result = database.execute_query(&explain).await?;
+    // Postgres returns a row per line, MySQL the whole tree in one value
+    let text: Vec<&str> = result
+        .rows
+        .iter()
+        .filter_map(|row| row.first())
+        .flat_map(|value| value.lines())
+        .collect();
+
+    let mut nodes: Vec<PlanNode> = text.iter().filter_map(|line| parse_node(line)).collect();
+    if nodes.is_empty() {
+        return Err(QgoError::InvalidQuery(
+            "The server's EXPLAIN ANALYZE output had no plan nodes to read; this needs Postgres or MySQL 8.0.18 or later"
+                .to_string(),
+        )
+        .into());
+    }
+    nodes.sort_by(|a, b| b.time_ms.unwrap_or(-1.0).total_cmp(&a.time_ms.unwrap_or(-1.0)));
+    let summary = text
+        .iter()
+        .filter(|line| line.ends_with(" ms") && !line.contains("(actual"))
+        .map(|line| line.trim().to_string())
+        .collect();
+    Ok(Analysis::Nodes { nodes, summary })
+}
+
+/// Reads a node line such as
+/// `->  Seq Scan on fq f  (cost=0.00..17.50 rows=509 width=3) (actual time=0.012..0.154 rows=509 loops=1)`.
+/// Detail lines (`Filter: ...`) have no cost and give `None`.
+fn parse_node(line: &str) -> Option<PlanNode> {
+    let (label, figures) = line.split_once("  (cost=")?;
+    let label = label.trim().trim_start_matches("->").trim();
+    let (node, relation) = match label.split_once(" on ") {
+        Some((node, rest)) => (node, rest.split_whitespace().next().map(str::to_string)),
+        None => (label, None),
+    };
+
+    let (estimate, actual) = figures.split_once("(actual").unwrap_or((figures, ""));
+    let estimated_rows = figure(estimate, "rows=");
+    let actual_rows = figure(actual, "rows=");
+    let loops = figure(actual, "loops=").unwrap_or(1.0);
+    let time_ms = actual
+        .split_once("time=")
+        .and_then(|(_, times)| times.split_once(".."))
+        .and_then(|(_, end)| figure(end, ""))
+        .map(|end| end * loops);
+
+    Some(PlanNode {
+        node: node.to_string(),
+        relation,
+        estimated_rows,
+        actual_rows,
+        time_ms,
+    })
+}
+
+/// The number after `key` in `text`.
+fn figure(text: &str, key: &str) -> Option<f64> {
+    let start = text.find(key)? + key.len();
+    let rest = &text[start..];
+    let end = rest
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == 'e' || c == '+' || c == '-'))
+        .unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+/// The nodes as a table, with estimates off by more than
+/// [`BAD_ESTIMATE_FACTOR`] in red, followed by the summary lines.
+pub fn render(nodes: &[PlanNode], summary: &[String]) -> String {
+    let headers = ["Node", "Relation", "Est. rows", "Actual rows", "Error", "Time (ms)"];
+    let rows: Vec<[String; 6]> = nodes
+        .iter()
+        .map(|node| {
+            [
+                chart::fit_label(&node.node, MAX_NODE_WIDTH),
+                node.relation.clone().unwrap_or_default(),
+                node.estimated_rows.map_or_else(String::new, count),
+                node.actual_rows.map_or_else(|| "never run".to_string(), count),
+                node.error_factor().map_or_else(String::new, |factor| format!("{:.1}x", factor)),
+                node.time_ms.map_or_else(String::new, |time| format!("{:.3}", time)),
+            ]
+        })
+        .collect();
+
+    let mut widths = headers.map(|header| header.chars().count());
+    for row in &rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let line = |values: [&str; 6]| -> String {
+        values
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(column, (value, width))| {
+                if column < 2 {
+                    format!("{:<width$}", value, width = width)
+                } else {
+                    format!("{:>width$}", value, width = width)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" │ ")
+    };
+
+    let mut text = format!("{}\n", style(line(headers)).bold());
+    for (node, row) in nodes.iter().zip(&rows) {
+        let formatted = line([&row[0], &row[1], &row[2], &row[3], &row[4], &row[5]]);
+        if node.error_factor().is_some_and(|factor| factor > BAD_ESTIMATE_FACTOR) {
+            text.push_str(&format!("{}\n", style(formatted).red()));
+        } else {
+            text.push_str(&format!("{}\n", formatted));
+        }
+    }
+    for line in summary {
+        text.push_str(&format!("{}\n", line));
+    }
+    text
+}
+
+fn count(rows: f64) -> String {
+    if rows.fract() == 0.0 {
+        format!("{}", rows as u64)
+    } else {
+        format!("{:.1}", rows)
+    }
+}
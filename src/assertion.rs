@@ -0,0 +1,105 @@
+use anyhow::Result;
+use console::style;
+
+use crate::database::{Database, QueryResult};
+use crate::diff;
+use crate::error::QgoError;
+use qgo::format::{self, CsvOptions};
+
+/// `\assert` and `\baseline`: checking a query's result against an expected
+/// CSV file, and writing that file.
+pub enum Check {
+    /// Compare the result with the file, rows in any order unless `ordered`.
+    Assert {
+        path: String,
+        query: String,
+        ordered: bool,
+    },
+    /// Write the result to the file, replacing what it held.
+    Baseline { path: String, query: String },
+}
+
+impl Check {
+    /// Runs the query and prints the outcome. Returns whether an assertion
+    /// held; writing a baseline always counts as passing.
+    pub async fn run(&self, database: &mut Database) -> Result<bool> {
+        match self {
+            Check::Assert { path, query, ordered } => assert_result(database, path, query, *ordered).await,
+            Check::Baseline { path, query } => {
+                let result = database.execute_query(query).await?;
+                format::export_to_csv(&result, path, CsvOptions::default())?;
+                println!("Wrote baseline {} ({} rows).", path, result.rows.len());
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// Compares the result of `query` with the baseline at `path`, matching
+/// columns by name. Values are compared as the text CSV exports write.
+async fn assert_result(database: &mut Database, path: &str, query: &str, ordered: bool) -> Result<bool> {
+    let expected = read_baseline(path)?;
+    let actual = database.execute_query(query).await?;
+
+    // An empty result has no column names to compare
+    if expected.rows.is_empty() && actual.rows.is_empty() {
+        println!("{} {} (no rows)", style("PASS").green().bold(), path);
+        return Ok(true);
+    }
+
+    let missing: Vec<&str> = expected
+        .columns
+        .iter()
+        .filter(|column| !actual.columns.contains(column))
+        .map(String::as_str)
+        .collect();
+    let unexpected: Vec<&str> = actual
+        .columns
+        .iter()
+        .filter(|column| !expected.columns.contains(column))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() || !unexpected.is_empty() {
+        println!(
+            "{} {}: the columns differ (missing: {}; unexpected: {})",
+            style("FAIL").red().bold(),
+            path,
+            diff::list_or_none(&missing),
+            diff::list_or_none(&unexpected)
+        );
+        return Ok(false);
+    }
+
+    let diff = if ordered {
+        diff::diff_ordered(&expected, &actual, diff::RESULT_DIFF_ROW_LIMIT)?
+    } else {
+        diff::diff_results(&expected, &actual, None, diff::RESULT_DIFF_ROW_LIMIT)?
+    };
+    if !diff.has_differences() {
+        println!("{} {} ({} rows)", style("PASS").green().bold(), path, actual.rows.len());
+        return Ok(true);
+    }
+
+    println!("{} {}", style("FAIL").red().bold(), path);
+    diff::print_result_diff(&diff, ["expected", "actual"]);
+    Ok(false)
+}
+
+/// Reads a CSV file written by `\baseline` (or any CSV with a header row).
+fn read_baseline(path: &str) -> Result<QueryResult> {
+    let unreadable = |e: csv::Error| {
+        QgoError::Usage(format!(
+            "Cannot read baseline '{}': {}; write it with \\baseline <file> <query>",
+            path, e
+        ))
+    };
+    let mut reader = csv::Reader::from_path(path).map_err(unreadable)?;
+    let headers = reader.headers().map_err(unreadable)?.clone();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        rows.push(record.map_err(unreadable)?.iter().map(str::to_string).collect());
+    }
+
+    let columns: Vec<&str> = headers.iter().collect();
+    Ok(QueryResult::from_rows(&columns, rows))
+}
@@ -0,0 +1,159 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Replaces the file at `path` with `contents` so that a crash or a full
+/// disk leaves either the old or the new version, never a mix: the contents
+/// go to `<name>.tmp` in the same directory, are synced to disk, and the
+/// temporary file is then renamed over `path`. If anything fails the
+/// temporary file is removed and `path` is untouched.
+///
+/// With `keep_backup`, the version being replaced is kept as `<name>.bak`,
+/// replacing the previous backup. A new file is only readable by its owner;
+/// a replaced one keeps its permissions.
+pub fn write(path: &Path, contents: &[u8], keep_backup: bool) -> io::Result<()> {
+    let temp = sibling(path, "tmp");
+    let result = write_synced(&temp, path, contents).and_then(|_| {
+        if keep_backup && path.exists() {
+            back_up(path)?;
+        }
+        fs::rename(&temp, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result?;
+
+    sync_parent(path);
+    Ok(())
+}
+
+/// `<name>.<suffix>` next to `path`.
+pub fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn write_synced(temp: &Path, path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(temp)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Points `<name>.bak` at the current version: a hard link where the file
+/// system allows it, otherwise a copy.
+fn back_up(path: &Path) -> io::Result<()> {
+    let backup = sibling(path, "bak");
+    match fs::remove_file(&backup) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    if fs::hard_link(path, &backup).is_err() {
+        fs::copy(path, &backup)?;
+    }
+    Ok(())
+}
+
+/// Makes the rename itself durable. Best effort: not every platform lets a
+/// directory be opened and synced.
+fn sync_parent(path: &Path) {
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("qgo-atomic-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn replaces_the_file_and_keeps_a_backup() {
+        let dir = scratch_dir("replace");
+        let path = dir.join("config.toml");
+        write(&path, b"old", true).unwrap();
+        assert!(!sibling(&path, "bak").exists());
+        write(&path, b"new", true).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read(sibling(&path, "bak")).unwrap(), b"old");
+        assert!(!sibling(&path, "tmp").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn new_files_are_private_and_replaced_files_keep_their_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch_dir("mode");
+        let path = dir.join("config.toml");
+        write(&path, b"one", false).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        write(&path, b"two", false).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_failed_write_leaves_the_original_untouched() {
+        let dir = scratch_dir("tmp-blocked");
+        let path = dir.join("config.toml");
+        fs::write(&path, b"original").unwrap();
+        // A directory where the temporary file should go cannot be opened
+        // for writing, even by root
+        fs::create_dir(sibling(&path, "tmp")).unwrap();
+
+        assert!(write(&path, b"replacement", true).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+        assert!(!sibling(&path, "bak").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_failed_backup_leaves_the_original_and_removes_the_temporary_file() {
+        let dir = scratch_dir("bak-blocked");
+        let path = dir.join("config.toml");
+        fs::write(&path, b"original").unwrap();
+        let backup = sibling(&path, "bak");
+        fs::create_dir(&backup).unwrap();
+        fs::write(backup.join("keep"), b"").unwrap();
+
+        assert!(write(&path, b"replacement", true).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+        assert!(!sibling(&path, "tmp").exists());
+        assert!(backup.join("keep").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_parent_that_is_a_file_fails_without_side_effects() {
+        let dir = scratch_dir("parent-file");
+        let parent = dir.join("not-a-directory");
+        fs::write(&parent, b"original").unwrap();
+
+        assert!(write(&parent.join("config.toml"), b"replacement", false).is_err());
+        assert_eq!(fs::read(&parent).unwrap(), b"original");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::Result;
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
+
+use crate::config::DatabaseType;
+use crate::database::{self, Database};
+use crate::error::QgoError;
+use crate::ui::prompts::Ask;
+
+/// Above this many tables the user is asked for part of the name first.
+const FILTER_TABLES_ABOVE: usize = 15;
+
+const OPERATORS: &[&str] = &["=", "<>", "<", "<=", ">", ">=", "LIKE", "IS NULL", "IS NOT NULL"];
+
+/// A `WHERE` condition picked in `\browse`.
+struct Filter {
+    column: String,
+    operator: &'static str,
+    /// `None` for `IS NULL` and `IS NOT NULL`.
+    value: Option<String>,
+}
+
+/// Walks through picking a table, filters, a sort column and a limit, then
+/// shows the generated `SELECT` and asks whether to run it. Returns the SQL
+/// to run, or [`QgoError::Cancelled`] if the user backs out. Names are
+/// checked against the table's columns and quoted with
+/// [`database::quote_ident`]; values are inlined with
+/// [`database::quote_literal`], so the SQL shown is exactly what runs and
+/// can be edited from the history.
+pub async fn build_query(database: &mut Database, table: Option<&str>, default_limit: usize) -> Result<String> {
+    let tables = database.get_tables().await?;
+    let table = match table {
+        Some(table) if tables.iter().any(|t| t == table) => table.to_string(),
+        Some(table) => return Err(QgoError::InvalidQuery(format!("No table named '{}'", table)).into()),
+        None => choose_table(&tables)?,
+    };
+    let columns = database.get_columns(&table).await?;
+    if columns.is_empty() {
+        return Err(QgoError::InvalidQuery(format!("Could not read the columns of '{}'", table)).into());
+    }
+
+    let filters = choose_filters(&columns)?;
+    let order = choose_order(&columns)?;
+    let limit: usize = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Limit")
+        .default(default_limit)
+        .ask()?;
+
+    let sql = select_statement(&database.get_connection().db_type, &table, &filters, order, limit)?;
+    println!("{}", style(&sql).cyan());
+    let run = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Run this query?")
+        .default(true)
+        .ask()?;
+    if !run {
+        return Err(QgoError::Cancelled.into());
+    }
+    Ok(sql)
+}
+
+/// Picks a table, first narrowing a long list by part of its name.
+fn choose_table(tables: &[String]) -> Result<String> {
+    if tables.is_empty() {
+        return Err(QgoError::InvalidQuery("There are no tables to browse".to_string()).into());
+    }
+
+    let mut matching: Vec<&String> = tables.iter().collect();
+    if tables.len() > FILTER_TABLES_ABOVE {
+        let part: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Table name contains (empty for all)")
+            .allow_empty(true)
+            .ask()?;
+        let part = part.trim().to_lowercase();
+        matching.retain(|table| table.to_lowercase().contains(&part));
+        if matching.is_empty() {
+            return Err(QgoError::InvalidQuery(format!("No table name contains '{}'", part)).into());
+        }
+    }
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Table")
+        .items(&matching)
+        .default(0)
+        .max_length(15)
+        .ask()?;
+    Ok(matching[selection].clone())
+}
+
+fn choose_filters(columns: &[String]) -> Result<Vec<Filter>> {
+    let mut filters = Vec::new();
+    let mut items = vec!["(no more filters)".to_string()];
+    items.extend(columns.iter().cloned());
+
+    loop {
+        let prompt = if filters.is_empty() { "Filter on column" } else { "Also filter on column" };
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .items(&items)
+            .default(0)
+            .max_length(15)
+            .ask()?;
+        if selection == 0 {
+            return Ok(filters);
+        }
+
+        let operator = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Operator")
+            .items(OPERATORS)
+            .default(0)
+            .ask()?;
+        let operator = OPERATORS[operator];
+        let value = if operator.starts_with("IS ") {
+            None
+        } else {
+            let hint = if operator == "LIKE" { " (% matches anything)" } else { "" };
+            let value: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("Value{}", hint))
+                .allow_empty(true)
+                .ask()?;
+            Some(value)
+        };
+        filters.push(Filter {
+            column: columns[selection - 1].clone(),
+            operator,
+            value,
+        });
+    }
+}
+
+/// The column to sort by and whether to sort descending, if any.
+fn choose_order(columns: &[String]) -> Result<Option<(&str, bool)>> {
+    let mut items = vec!["(no sorting)".to_string()];
+    items.extend(columns.iter().cloned());
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Sort by")
+        .items(&items)
+        .default(0)
+        .max_length(15)
+        .ask()?;
+    if selection == 0 {
+        return Ok(None);
+    }
+
+    let descending = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Direction")
+        .items(&["Ascending", "Descending"])
+        .default(0)
+        .ask()?
+        == 1;
+    Ok(Some((columns[selection - 1].as_str(), descending)))
+}
+
+fn select_statement(
+    db_type: &DatabaseType,
+    table: &str,
+    filters: &[Filter],
+    order: Option<(&str, bool)>,
+    limit: usize,
+) -> Result<String> {
+    let mut sql = format!("SELECT * FROM {}", database::quote_ident(db_type, table)?);
+
+    let mut conditions = Vec::new();
+    for filter in filters {
+        let column = database::quote_ident(db_type, &filter.column)?;
+        conditions.push(match &filter.value {
+            Some(value) => format!("{} {} {}", column, filter.operator, database::quote_literal(db_type, value)?),
+            None => format!("{} {}", column, filter.operator),
+        });
+    }
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+
+    if let Some((column, descending)) = order {
+        sql.push_str(&format!(" ORDER BY {}", database::quote_ident(db_type, column)?));
+        if descending {
+            sql.push_str(" DESC");
+        }
+    }
+    sql.push_str(&format!(" LIMIT {}", limit));
+    Ok(sql)
+}
@@ -0,0 +1,79 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::database::QueryResult;
+
+/// Total rows kept across all cached results; the oldest entries are evicted
+/// to stay under it.
+const MAX_CACHED_ROWS: usize = 100_000;
+
+/// Recent read-only query results keyed by their exact text, so repeating a
+/// query within the TTL re-displays it without going to the server.
+pub struct ResultCache {
+    max_entries: usize,
+    ttl: Duration,
+    entries: VecDeque<Entry>,
+}
+
+struct Entry {
+    query: String,
+    result: QueryResult,
+    stored_at: Instant,
+}
+
+impl ResultCache {
+    /// A cache of at most `max_entries` results; 0 turns caching off.
+    pub fn new(max_entries: usize, ttl: Duration) -> Self {
+        Self {
+            max_entries,
+            ttl,
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_entries > 0
+    }
+
+    /// Returns the cached result for `query` and its age, dropping any
+    /// entries that have expired.
+    pub fn get(&mut self, query: &str) -> Option<(&QueryResult, Duration)> {
+        let ttl = self.ttl;
+        self.entries.retain(|entry| entry.stored_at.elapsed() <= ttl);
+        self.entries
+            .iter()
+            .find(|entry| entry.query == query)
+            .map(|entry| (&entry.result, entry.stored_at.elapsed()))
+    }
+
+    /// Stores `result` for `query`, replacing an older result for the same
+    /// text. Results too large to fit at all are not cached.
+    pub fn insert(&mut self, query: &str, result: &QueryResult) {
+        if !self.is_enabled() || result.rows.len() > MAX_CACHED_ROWS {
+            return;
+        }
+
+        self.entries.retain(|entry| entry.query != query);
+        self.entries.push_back(Entry {
+            query: query.to_string(),
+            result: result.clone(),
+            stored_at: Instant::now(),
+        });
+
+        let mut rows: usize = self.entries.iter().map(|entry| entry.result.rows.len()).sum();
+        while self.entries.len() > self.max_entries || rows > MAX_CACHED_ROWS {
+            match self.entries.pop_front() {
+                Some(evicted) => rows -= evicted.result.rows.len(),
+                None => break,
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::Result;
+
+use crate::database::QueryResult;
+use crate::error::QgoError;
+
+/// Eighths of a character cell, for bar ends finer than a whole block.
+const BAR_EIGHTHS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Labels longer than this are cut short so the bars keep most of the width.
+const MAX_LABEL_WIDTH: usize = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChartKind {
+    /// One labeled horizontal bar per row.
+    Bar,
+    /// A single-line sparkline, for series such as counts by day.
+    Line,
+}
+
+impl ChartKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "bar" => Some(ChartKind::Bar),
+            "line" => Some(ChartKind::Line),
+            _ => None,
+        }
+    }
+}
+
+/// Draws a two-column result (a label and a number, in either order) as a
+/// chart `width` characters wide. Results with more than `max_rows` rows or
+/// non-numeric values are refused with a hint to aggregate first.
+pub fn render(result: &QueryResult, kind: ChartKind, max_rows: usize, width: usize) -> Result<String> {
+    let points = points(result, max_rows)?;
+    Ok(match kind {
+        ChartKind::Bar => bars(&points, width),
+        ChartKind::Line => sparkline(&points),
+    })
+}
+
+/// A label, the value as shown in the result, and the value as a number.
+type Point<'a> = (&'a str, &'a str, f64);
+
+fn points(result: &QueryResult, max_rows: usize) -> Result<Vec<Point<'_>>> {
+    if result.columns.len() != 2 {
+        return Err(chart_error(format!(
+            "\\chart needs a result with two columns, a label and a number; this one has {}. Select just those two, or narrow it with \\cols",
+            result.columns.len()
+        )));
+    }
+    if result.rows.is_empty() {
+        return Err(chart_error("The result has no rows to chart".to_string()));
+    }
+    if result.rows.len() > max_rows {
+        return Err(chart_error(format!(
+            "{} rows is more than chart_max_rows ({}); aggregate first, e.g. GROUP BY day, or raise the setting",
+            result.rows.len(),
+            max_rows
+        )));
+    }
+
+    // The value is the second column unless only the first is numeric
+    let numeric = |column: usize| result.rows.iter().all(|row| parse(&row[column]).is_some());
+    let value_column = if !numeric(1) && numeric(0) { 0 } else { 1 };
+    let label_column = 1 - value_column;
+
+    result
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(index, row)| {
+            let value = &row[value_column];
+            let number = parse(value).ok_or_else(|| {
+                chart_error(format!(
+                    "'{}' in row {} of {} is not a number; chart a count or sum, e.g. SELECT label, COUNT(*) ... GROUP BY label",
+                    value,
+                    index + 1,
+                    result.columns[value_column]
+                ))
+            })?;
+            Ok((row[label_column].as_str(), value.as_str(), number))
+        })
+        .collect()
+}
+
+fn parse(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|number| number.is_finite())
+}
+
+fn chart_error(message: String) -> anyhow::Error {
+    QgoError::InvalidQuery(message).into()
+}
+
+fn bars(points: &[Point<'_>], width: usize) -> String {
+    let label_width = points
+        .iter()
+        .map(|(label, _, _)| label.chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(MAX_LABEL_WIDTH);
+    let value_width = points.iter().map(|(_, value, _)| value.chars().count()).max().unwrap_or(0);
+    let bar_width = width.saturating_sub(label_width + value_width + 4).max(10);
+    let largest = points.iter().map(|(_, _, number)| number.abs()).fold(0.0, f64::max);
+
+    let mut chart = String::new();
+    for (label, value, number) in points {
+        let fraction = if largest > 0.0 { number.abs() / largest } else { 0.0 };
+        chart.push_str(&format!(
+            "{:<label_width$} │{:<bar_width$} {:>value_width$}\n",
+            fit_label(label, label_width),
+            bar(fraction, bar_width),
+            value,
+            label_width = label_width,
+            bar_width = bar_width,
+            value_width = value_width
+        ));
+    }
+    chart
+}
+
+/// A bar `fraction` (0 to 1) of `width` cells long, drawn to an eighth of
+/// a cell.
+pub fn bar(fraction: f64, width: usize) -> String {
+    let eighths = (fraction.clamp(0.0, 1.0) * (width * 8) as f64).round() as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    let remainder = eighths % 8;
+    if remainder > 0 {
+        bar.push(BAR_EIGHTHS[remainder - 1]);
+    }
+    bar
+}
+
+/// One block per row scaled between the smallest and largest value, with
+/// the range and the first and last labels underneath.
+fn sparkline(points: &[Point<'_>]) -> String {
+    let smallest = points.iter().map(|(_, _, number)| *number).fold(f64::INFINITY, f64::min);
+    let largest = points.iter().map(|(_, _, number)| *number).fold(f64::NEG_INFINITY, f64::max);
+    let line: String = points
+        .iter()
+        .map(|(_, _, number)| {
+            let level = if largest > smallest {
+                ((number - smallest) / (largest - smallest) * 7.0).round() as usize
+            } else {
+                3
+            };
+            SPARK_LEVELS[level]
+        })
+        .collect();
+
+    let (first, last) = (points[0].0, points[points.len() - 1].0);
+    format!(
+        "{}\n{} → {}  (min {}, max {})\n",
+        line,
+        fit_label(first, MAX_LABEL_WIDTH),
+        fit_label(last, MAX_LABEL_WIDTH),
+        smallest,
+        largest
+    )
+}
+
+pub fn fit_label(label: &str, width: usize) -> String {
+    if label.chars().count() <= width {
+        return label.to_string();
+    }
+    let mut fitted: String = label.chars().take(width.saturating_sub(1)).collect();
+    fitted.push('…');
+    fitted
+}
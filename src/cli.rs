@@ -1,8 +1,49 @@
 use anyhow::Result;
-use console::style;
-use rustyline::{error::ReadlineError, history::FileHistory, Editor};
+use console::{style, Style};
+use rustyline::{
+    error::ReadlineError,
+    history::{FileHistory, History},
+    Editor,
+};
+use std::io::IsTerminal;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use crate::ui::{connection_manager::ConnectionManager, table_display};
+use crate::analyze::{self, Analysis};
+use crate::assertion::Check;
+use crate::browse;
+use crate::cache::ResultCache;
+use crate::chart::{self, ChartKind};
+use crate::config::{self, Config, Connection, DatabaseType, OnError, Settings, Topology};
+use crate::database::{self, CopyOutcome, Database, QueryResult, RowStream, StatementClass};
+use crate::completions::{self, ListFormat};
+use crate::diff;
+use crate::follow;
+use crate::freq;
+use crate::error::QgoError;
+use crate::import::{self, ImportOptions};
+use crate::interrupt;
+use crate::jobs::{JobProgress, Jobs};
+use crate::last_error::LastError;
+use crate::lint;
+use crate::normalize;
+use crate::notify::{self, Notifications, Printer};
+use crate::progress::{self, Progress};
+use crate::project;
+use crate::recent::{self, RecentQuery, SessionLog};
+use crate::script::{self, ConfirmEach, Failure, ScriptOptions};
+use crate::snapshot::{SettingsStack, Snapshot};
+use crate::timezone::SessionZone;
+use crate::transcript::Transcript;
+use crate::variables::{self, Variables};
+use crate::ui::connection_manager::{self, ConnectionManager};
+use crate::ui::editor::{self, SqlHelper};
+use crate::ui::{output, prompts, settings};
+use qgo::format::{self, CsvOptions, CsvRecordEnds, DisplayOptions, ExportWriter, FixedSpec, RecordEnd};
+use qgo::formatter::{self, Registration};
+use qgo::policy::Policy;
 
 pub struct QueryHistory {
     history: Vec<String>,
@@ -60,15 +101,349 @@ impl QueryHistory {
     }
 }
 
-pub async fn run_interactive_session(connection_manager: &mut ConnectionManager) -> Result<()> {
-    let max_rows_display = {
-        let config = connection_manager.get_config();
-        config.settings.max_rows_display
+/// State that lives for the duration of one interactive session.
+pub struct Session<'a> {
+    pub config: &'a mut Config,
+    pub display: DisplayOptions,
+    pub last_result: Option<QueryResult>,
+    /// How many leading rows of the last result have been displayed; where
+    /// `\more` continues.
+    pub shown_rows: usize,
+    /// The table the last result was read from, when it came from a single
+    /// one; what `\follow` looks up foreign keys on.
+    pub last_table: Option<String>,
+    /// Columns of the last result picked with `\cols`, by index.
+    pub projection: Option<Vec<usize>>,
+    /// Result kept with `\mark` for a later `\diffq`.
+    pub marked: Option<QueryResult>,
+    pub timeout: Duration,
+    /// Error handling for multi-statement input, toggled with `\set ON_ERROR_STOP`.
+    pub on_error: OnError,
+    /// Writes asynchronous output above the prompt; `None` without a terminal.
+    pub printer: Option<Printer>,
+    /// Postgres channels subscribed to with `LISTEN`.
+    pub notifications: Option<Notifications>,
+    /// Session options as they were last loaded from or saved to the config.
+    pub saved_options: SessionOptions,
+    /// Recent read-only results, cleared whenever the session writes.
+    pub cache: ResultCache,
+    /// What an export does when its target file already exists.
+    pub existing_files: ExistingFile,
+    /// Where executed statements and their results are recorded, if anywhere.
+    pub transcript: Option<Transcript>,
+    /// Values stored with `\into`, substituted into queries as `:name`.
+    pub variables: Variables,
+    /// Generated SQL (from `\browse`) to add to the line-editor history, so
+    /// it can be recalled and edited.
+    pub history_additions: Vec<String>,
+    /// Every statement run this session, with its timing, for `\slow`.
+    pub query_log: SessionLog,
+    /// Settings kept with `\push`, for `\pop` to restore.
+    pub settings_stack: SettingsStack,
+    /// What ends a statement at the prompt, changed with `\delimiter` or a
+    /// `DELIMITER` line.
+    pub delimiter: String,
+    /// Exports started with `export --background` or `\bg`.
+    pub jobs: Jobs,
+    /// The error of the latest input that failed, for `\errverbose`.
+    pub last_error: Option<LastError>,
+}
+
+/// How exports treat a target file that already exists. Files are never
+/// replaced silently unless `--overwrite` was given.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExistingFile {
+    /// Ask whether to overwrite, pick a new name, or cancel; without a
+    /// terminal the export fails.
+    Ask,
+    /// Replace it (`--overwrite`).
+    Overwrite,
+    /// Skip the export (`--no-clobber`).
+    Keep,
+}
+
+/// The session options that can be changed at runtime and saved back to
+/// the settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionOptions {
+    show_types: bool,
+    timezone: Option<String>,
+    on_error: OnError,
+}
+
+impl SessionOptions {
+    fn current(display: &DisplayOptions, on_error: OnError) -> Self {
+        Self {
+            show_types: display.show_types,
+            timezone: display.timezone.as_ref().map(|zone| zone.to_string()),
+            on_error,
+        }
+    }
+
+    /// `(setting, saved value, current value)` for each option that differs.
+    fn changes(&self, current: &Self) -> Vec<(&'static str, String, String)> {
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        let zone = |zone: &Option<String>| zone.clone().unwrap_or_else(|| "server default".to_string());
+        let mut changes = Vec::new();
+        if self.show_types != current.show_types {
+            changes.push(("show_types", on_off(self.show_types), on_off(current.show_types)));
+        }
+        if self.timezone != current.timezone {
+            changes.push(("timezone", zone(&self.timezone), zone(&current.timezone)));
+        }
+        if self.on_error != current.on_error {
+            changes.push((
+                "on_error",
+                format!("{:?}", self.on_error).to_lowercase(),
+                format!("{:?}", current.on_error).to_lowercase(),
+            ));
+        }
+        changes
+    }
+}
+
+impl Session<'_> {
+    /// Replaces the last result, dropping any `\cols` projection. `table`
+    /// is the table it was read from, if it came from just one, and `shown`
+    /// how many of its rows were displayed.
+    fn set_last_result(&mut self, result: QueryResult, table: Option<String>, shown: usize) {
+        self.last_result = Some(result);
+        self.shown_rows = shown;
+        self.last_table = table;
+        self.projection = None;
+    }
+
+    /// The settings the session runs with now, as `\push` keeps them.
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::new(&self.config.settings, &self.display, self.timeout, self.on_error)
+    }
+
+    /// The last result as currently shown: projected to the `\cols`
+    /// selection if there is one.
+    fn current_result(&self) -> Option<Cow<'_, QueryResult>> {
+        let result = self.last_result.as_ref()?;
+        Some(match self.projection {
+            Some(ref columns) => Cow::Owned(result.select_columns(columns)),
+            None => Cow::Borrowed(result),
+        })
+    }
+}
+
+fn display_options(settings: &Settings, database: &Database) -> DisplayOptions {
+    DisplayOptions {
+        max_rows: settings.max_rows_display,
+        timezone: database.timezone().cloned(),
+        date_format: settings.date_format.clone(),
+        show_types: settings.show_types,
+        scalar_display: settings.scalar_display,
+        max_column_width: settings.max_column_width,
+        fit_to_terminal: true,
+        vertical: false,
+        force_grid: settings.force_grid,
+        formatter: settings.result_format.as_deref().and_then(formatter::find),
+    }
+}
+
+/// Where the statements run without an interactive session come from.
+pub enum ScriptSource {
+    /// A file named with `-f` or as a positional `.sql` argument.
+    File(String),
+    /// SQL given with `-e` or after `--`.
+    Sql(String),
+}
+
+/// Runs every statement in `source` on the current connection, with
+/// `variables` substituted. `on_error` overrides the `on_error` setting,
+/// `confirm_each` asks before each statement, and with `record` the
+/// statements and their results are appended to a transcript. Returns
+/// whether all statements succeeded.
+pub async fn run_script_source(
+    connection_manager: &mut ConnectionManager,
+    source: &ScriptSource,
+    variables: &Variables,
+    on_error: Option<OnError>,
+    single_transaction: bool,
+    confirm_each: bool,
+    record: Option<&Path>,
+) -> Result<bool> {
+    let (database, config) = match connection_manager.session_parts() {
+        Some(parts) => parts,
+        None => return Err(QgoError::Usage("No database connection available".to_string()).into()),
     };
-    
-    // Get database after releasing the borrow on connection_manager
-    let database = match connection_manager.get_database() {
-        Some(db) => db,
+    if confirm_each && !std::io::stdin().is_terminal() {
+        return Err(QgoError::Usage("--confirm needs a terminal to ask on".to_string()).into());
+    }
+    let mut confirm = ConfirmEach::new(confirm_each);
+    let options = ScriptOptions {
+        on_error: on_error.unwrap_or(config.settings.on_error),
+        single_transaction,
+        keep_transaction_open: false,
+    };
+    let display = display_options(&config.settings, database);
+    let mut transcript = match record {
+        Some(record) => Some(Transcript::start(record, database.get_connection())?),
+        None => None,
+    };
+
+    let succeeded = match source {
+        ScriptSource::File(path) => {
+            run_script(database, path, variables, options, &display, transcript.as_mut(), &mut confirm).await?
+        }
+        ScriptSource::Sql(sql) => {
+            run_sql(database, sql, variables, options, &display, transcript.as_mut(), &mut confirm).await?
+        }
+    };
+    connection_manager.disconnect().await;
+    Ok(succeeded)
+}
+
+/// Reads and runs a script file, printing a summary of any failures.
+async fn run_script(
+    database: &mut Database,
+    path: &str,
+    variables: &Variables,
+    options: ScriptOptions,
+    display: &DisplayOptions,
+    transcript: Option<&mut Transcript>,
+    confirm: &mut ConfirmEach,
+) -> Result<bool> {
+    let sql = script::read_script(path)?;
+    run_sql(database, &sql, variables, options, display, transcript, confirm).await
+}
+
+/// Runs each statement in `sql`, printing a summary of any failures.
+/// `\assert` and `\baseline` lines run between the SQL statements around
+/// them; an assertion that does not hold counts as a failed statement.
+/// When any variables are set, a script that references one that isn't is
+/// refused before anything runs.
+async fn run_sql(
+    database: &mut Database,
+    sql: &str,
+    variables: &Variables,
+    options: ScriptOptions,
+    display: &DisplayOptions,
+    mut transcript: Option<&mut Transcript>,
+    confirm: &mut ConfirmEach,
+) -> Result<bool> {
+    if !variables.is_empty() {
+        let undefined = variables.undefined(sql);
+        if !undefined.is_empty() {
+            return Err(QgoError::Usage(format!(
+                "The script uses variables that are not defined: {}; set them with --var, --params or \\params",
+                undefined.join(", ")
+            ))
+            .into());
+        }
+    }
+    let db_type = database.get_connection().db_type.clone();
+    let (lines, statements): (Vec<usize>, Vec<String>) = script::split_statements_with_lines(&db_type, sql).into_iter().unzip();
+    let statements = statements
+        .iter()
+        .map(|statement| Ok(variables.substitute(&db_type, statement)?.into_owned()))
+        .collect::<Result<Vec<_>>>()?;
+    let checks = statements
+        .iter()
+        .map(|statement| parse_check(statement))
+        .collect::<Result<Vec<_>>>()?;
+    if options.single_transaction {
+        if checks.iter().any(Option::is_some) {
+            return Err(QgoError::Usage("\\assert and \\baseline cannot run in a single transaction".to_string()).into());
+        }
+        script::check_single_transaction(&database.get_connection().db_type, &statements, options.on_error)?;
+    }
+
+    let mut failures = Vec::new();
+    let mut start = 0;
+    while start < statements.len() {
+        if let Some(ref check) = checks[start] {
+            let error = match check.run(database).await {
+                Ok(true) => None,
+                Ok(false) => Some(anyhow::anyhow!("the result differs from the baseline")),
+                Err(e) => {
+                    eprintln!("{}", style(format!("Error in statement {}: {}", start + 1, e)).red());
+                    Some(e)
+                }
+            };
+            if let Some(error) = error {
+                failures.push(Failure {
+                    statement: start + 1,
+                    line: Some(lines[start]),
+                    error,
+                });
+            }
+            start += 1;
+        } else {
+            let end = checks[start..]
+                .iter()
+                .position(Option::is_some)
+                .map_or(statements.len(), |n| start + n);
+            let batch = script::run(
+                database,
+                &statements[start..end],
+                options,
+                display,
+                transcript.as_deref_mut(),
+                None,
+                confirm,
+            )
+            .await?;
+            failures.extend(batch.into_iter().map(|failure| Failure {
+                statement: start + failure.statement,
+                line: Some(lines[start + failure.statement - 1]),
+                error: failure.error,
+            }));
+            start = end;
+        }
+        if (options.on_error == OnError::Stop && !failures.is_empty()) || confirm.stopped() {
+            break;
+        }
+    }
+
+    script::print_summary(statements.len(), &failures, options.on_error);
+    Ok(failures.is_empty())
+}
+
+/// Parses `\assert [--ordered] <file.csv> <query>` and
+/// `\baseline <file.csv> <query>`; `None` for any other input.
+fn parse_check(input: &str) -> Result<Option<Check>> {
+    const ASSERT_USAGE: &str = "\\assert [--ordered] <file.csv> <query>";
+    const BASELINE_USAGE: &str = "\\baseline <file.csv> <query>";
+    let input = input.trim();
+    let (command, rest) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    let (usage, baseline) = match command.to_lowercase().as_str() {
+        "\\assert" => (ASSERT_USAGE, false),
+        "\\baseline" => (BASELINE_USAGE, true),
+        _ => return Ok(None),
+    };
+
+    let mut rest = rest.trim_start();
+    let mut ordered = false;
+    if !baseline {
+        if let Some(after) = rest.strip_prefix("--ordered").filter(|after| after.starts_with(char::is_whitespace)) {
+            ordered = true;
+            rest = after;
+        }
+    }
+    let (path, query) = next_arg(rest)?.ok_or_else(|| usage_error(usage, "Missing file name".to_string()))?;
+    let query = query.trim().trim_end_matches(';').trim_end().to_string();
+    if query.is_empty() {
+        return Err(usage_error(usage, "Missing query".to_string()));
+    }
+
+    Ok(Some(if baseline {
+        Check::Baseline { path, query }
+    } else {
+        Check::Assert { path, query, ordered }
+    }))
+}
+
+pub async fn run_interactive_session(
+    connection_manager: &mut ConnectionManager,
+    existing_files: ExistingFile,
+    record: Option<&Path>,
+) -> Result<()> {
+    let (database, config) = match connection_manager.session_parts() {
+        Some(parts) => parts,
         None => {
             println!("{}", style("No database connection available.").red());
             return Ok(());
@@ -76,31 +451,111 @@ pub async fn run_interactive_session(connection_manager: &mut ConnectionManager)
     };
 
     let connection_info = database.get_connection().clone();
-    println!("{}", style(format!("Connected to {} database.", connection_info.db_type)).green());
-    println!("{}", style("Type your SQL queries, 'help' for commands, or 'exit' to quit.").dim());
+    output::banner(connection_info.paint(
+        format!("Connected to {} database '{}'.", connection_info.db_type, connection_info.name),
+        Style::new().green(),
+    ));
+    output::banner(style("Type your SQL queries, 'help' for commands, or 'exit' to quit.").dim());
+
+    let display = display_options(&config.settings, database);
+    let timeout = Duration::from_secs(config.settings.query_timeout_seconds);
+
+    if let Some(interval) = config.settings.keepalive_interval_secs.filter(|secs| *secs > 0) {
+        database.start_keepalive(Duration::from_secs(interval));
+    }
 
     let mut history = QueryHistory::new();
     
     // Setup readline editor
-    let mut rl = Editor::<(), FileHistory>::new()?;
-    let history_file = dirs::config_dir()
-        .map(|dir| dir.join("qgo").join("history.txt"))
-        .unwrap_or_else(|| std::path::PathBuf::from("qgo_history.txt"));
+    let mut rl = Editor::<SqlHelper, FileHistory>::new()?;
+    let mut helper = SqlHelper::new(config.settings.color_output, config.settings.auto_completion);
+    let mut completion_context = database.schema_context();
+    if config.settings.auto_completion {
+        let tables = database.shared_tables().await.unwrap_or_else(|_| Arc::from([]));
+        helper.set_tables(tables, database.get_connection().db_type.clone());
+    }
+    rl.set_helper(Some(helper));
+    editor::bind_clear_screen(&mut rl);
+
+    let on_error = config.settings.on_error;
+    let saved_options = SessionOptions::current(&display, on_error);
+    let cache = ResultCache::new(
+        config.settings.result_cache_entries,
+        Duration::from_secs(config.settings.result_cache_ttl_secs),
+    );
+    let printer = rl
+        .create_external_printer()
+        .ok()
+        .map(|printer| Arc::new(Mutex::new(printer)) as Printer);
+    let mut session = Session {
+        config,
+        display,
+        last_result: None,
+        shown_rows: 0,
+        last_table: None,
+        projection: None,
+        marked: None,
+        timeout,
+        on_error,
+        jobs: Jobs::new(printer.clone()),
+        printer,
+        notifications: None,
+        saved_options,
+        cache,
+        existing_files,
+        transcript: None,
+        variables: Variables::default(),
+        history_additions: Vec::new(),
+        query_log: SessionLog::default(),
+        settings_stack: SettingsStack::default(),
+        delimiter: script::DEFAULT_DELIMITER.to_string(),
+        last_error: None,
+    };
+    if let Some(record) = record {
+        start_recording(&mut session, record, database.get_connection())?;
+    }
+
+    let history_file = history_path();
 
     if history_file.exists() {
         let _ = rl.load_history(&history_file);
     }
 
-    let prompt = format!("{}@{}:({})> ", 
-        connection_info.username, 
-        connection_info.host, 
-        connection_info.database
-    );
-
     loop {
+        // Rebuilt every time so it follows database switches made during the session
+        let prompt = {
+            let connection = database.get_connection();
+            let transaction = if database.in_transaction() {
+                format!("[TX:{}]", database.transaction_writes())
+            } else {
+                String::new()
+            };
+            let endpoint = match database.topology() {
+                Some(_) if connection.replica => "[replica]",
+                Some(_) => "[primary]",
+                None => "",
+            };
+            // The connection name tells apart sessions on the same host and user
+            let prompt = format!("{} {}@{}:({}){}{}> ",
+                connection.name,
+                connection.username,
+                connection.host,
+                connection.database,
+                endpoint,
+                transaction
+            );
+            // A red prompt for the whole session as a reminder of where writes go
+            if connection.is_production() {
+                style(prompt).red().bold().to_string()
+            } else {
+                connection.paint(prompt, Style::new()).to_string()
+            }
+        };
+
         match rl.readline(&prompt) {
             Ok(line) => {
-                let input = line.trim();
+                // Piped input may start with a byte order mark
+                let input = line.trim_start_matches('\u{feff}').trim();
                 
                 if input.is_empty() {
                     continue;
@@ -109,148 +564,3283 @@ pub async fn run_interactive_session(connection_manager: &mut ConnectionManager)
                 rl.add_history_entry(input.to_string())?;
                 history.add(input.to_string());
 
-                if let Err(e) = handle_input(input, database, max_rows_display).await {
-                    println!("{}", style(format!("Error: {}", e)).red());
+                let reconnects = database.reconnects();
+                let flow = handle_input(input, database, &mut session).await;
+                if database.reconnects() > reconnects {
+                    output::info("The connection had been dropped while idle and was reopened.");
+                }
+                for sql in session.history_additions.drain(..) {
+                    rl.add_history_entry(sql.as_str())?;
+                    history.add(sql);
+                }
+                // Completion follows database and schema switches
+                if session.config.settings.auto_completion && database.schema_context() != completion_context {
+                    completion_context = database.schema_context();
+                    let tables = database.shared_tables().await.unwrap_or_else(|_| Arc::from([]));
+                    if let Some(helper) = rl.helper_mut() {
+                        helper.set_tables(tables, database.get_connection().db_type.clone());
+                    }
+                }
+                match flow {
+                    Ok(Flow::Continue) => {}
+                    Ok(Flow::Exit) => {
+                        if resolve_running_jobs(&mut session).await && resolve_open_transaction(database).await {
+                            output::banner("Goodbye!");
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("{}", style(format!("Error: {}", e)).red());
+                        session.last_error = Some(LastError::new(e, input, reconnects, database.reconnects()));
+                    }
                 }
             }
             Err(ReadlineError::Interrupted) => {
                 println!("Ctrl-C pressed. Type 'exit' to quit.");
             }
             Err(ReadlineError::Eof) => {
-                println!("Ctrl-D pressed. Goodbye!");
-                break;
+                if resolve_running_jobs(&mut session).await && resolve_open_transaction(database).await {
+                    output::banner("Goodbye!");
+                    break;
+                }
             }
             Err(err) => {
-                println!("Error reading input: {}", err);
+                eprintln!("Error reading input: {}", err);
                 break;
             }
         }
     }
 
+    offer_to_save_options(&mut session).await;
+
     // Save history
-    if let Some(parent) = history_file.parent() {
-        let _ = std::fs::create_dir_all(parent);
+    if !config::is_ephemeral() {
+        if let Some(parent) = history_file.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let _ = rl.save_history(&history_file);
+    }
+
+    connection_manager.disconnect().await;
+    Ok(())
+}
+
+/// Before the session ends with background jobs still running, asks whether
+/// to wait for them, cancel them (removing their incomplete files), or exit
+/// at once, which aborts them and leaves their files as far as they got.
+/// Without a terminal to ask on, it waits. Returns whether the session may
+/// end.
+async fn resolve_running_jobs(session: &mut Session<'_>) -> bool {
+    let running = session.jobs.running();
+    if running == 0 {
+        return true;
+    }
+
+    let jobs = format!("{} background job{}", running, if running == 1 { "" } else { "s" });
+    let choice = if std::io::stdin().is_terminal() {
+        let message = format!("{} still running — wait, cancel them, or exit anyway?", jobs);
+        let choices = ["Wait for them to finish", "Cancel them", "Exit and abort them", "Cancel exit"];
+        match prompts::select(&message, &choices) {
+            Some(choice @ 0..=2) => choice,
+            _ => return false,
+        }
+    } else {
+        0
+    };
+    match choice {
+        0 => {
+            println!("Waiting for {}...", jobs);
+            session.jobs.wait().await;
+        }
+        1 => {
+            session.jobs.cancel_all();
+            eprintln!("{}", style(format!("Cancelled {}.", jobs)).yellow());
+        }
+        _ => eprintln!("{}", style(format!("Aborted {}; their files are incomplete.", jobs)).yellow()),
+    }
+    true
+}
+
+/// Before the session ends with a transaction still open, asks whether to
+/// commit or roll back its uncommitted statements, or to stay. A transaction
+/// without writes, or one left open without a terminal to ask on, is rolled
+/// back. Returns whether the session may end.
+async fn resolve_open_transaction(database: &mut Database) -> bool {
+    if !database.in_transaction() {
+        return true;
+    }
+
+    let writes = database.transaction_writes();
+    let statements = format!("{} uncommitted statement{}", writes, if writes == 1 { "" } else { "s" });
+    let commit = if writes == 0 || !std::io::stdin().is_terminal() {
+        false
+    } else {
+        let message = format!("You have {} — commit, rollback, or cancel exit?", statements);
+        match prompts::select(&message, &["Commit", "Rollback", "Cancel exit"]) {
+            Some(0) => true,
+            Some(1) => false,
+            _ => return false,
+        }
+    };
+
+    match database.end_transaction(commit).await {
+        Ok(()) if commit => println!("Committed."),
+        Ok(()) if writes > 0 => eprintln!("{}", style(format!("Rolled back {}.", statements)).yellow()),
+        Ok(()) => {}
+        Err(e) => {
+            eprintln!("{}", style(format!("Error: {}", e)).red());
+            return false;
+        }
+    }
+    true
+}
+
+/// Where the input history of interactive sessions is kept.
+fn history_path() -> PathBuf {
+    dirs::config_dir()
+        .map(|dir| dir.join("qgo").join("history.txt"))
+        .unwrap_or_else(|| PathBuf::from("qgo_history.txt"))
+}
+
+/// Runs a reduced session that needs no database, for when none can be
+/// reached: saved connections, their recent queries, the input history and
+/// the settings can be looked at (and settings changed), while SQL and
+/// commands that need a connection are refused with a hint.
+pub async fn run_offline_session(connection_manager: &mut ConnectionManager) -> Result<()> {
+    output::banner(style("Offline mode: no database is connected.").yellow());
+    output::banner(style("Type 'help' for what works offline, or 'exit' to quit.").dim());
+
+    let mut rl = Editor::<(), FileHistory>::new()?;
+    editor::bind_clear_screen(&mut rl);
+    loop {
+        match rl.readline("qgo (offline)> ") {
+            Ok(line) => {
+                let input = line.trim();
+                if input.is_empty() {
+                    continue;
+                }
+                rl.add_history_entry(input)?;
+
+                match handle_offline_input(input, connection_manager).await {
+                    Ok(Flow::Continue) => {}
+                    Ok(Flow::Exit) => break,
+                    Err(e) if prompts::is_cancelled(&e) => println!("{}", style("Cancelled.").dim()),
+                    Err(e) => eprintln!("{}", style(format!("Error: {}", e)).red()),
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                println!("Ctrl-C pressed. Type 'exit' to quit.");
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("Error reading input: {}", err);
+                break;
+            }
+        }
+    }
+
+    output::banner("Goodbye!");
+    Ok(())
+}
+
+async fn handle_offline_input(input: &str, connection_manager: &mut ConnectionManager) -> Result<Flow> {
+    let (command, rest) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    let args = split_args(rest)?;
+
+    match command.to_lowercase().as_str() {
+        "exit" | "quit" | "\\q" => return Ok(Flow::Exit),
+        "help" | "\\h" => show_offline_help(),
+        "clear" | "\\c" => editor::clear_screen(),
+        "\\reset" => editor::reset_terminal(),
+        "\\connections" => completions::list_connections(connection_manager.config(), ListFormat::Table),
+        "\\conninfo" => {
+            const USAGE: &str = "\\conninfo <connection>";
+            let name = match args.as_slice() {
+                [name] => name,
+                [] => return Err(usage_error(USAGE, "Missing connection name".to_string())),
+                [_, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+            };
+            show_saved_connection(saved_connection(connection_manager, name)?);
+        }
+        "\\recent" => {
+            let Some((name, args)) = args.split_first() else {
+                return Err(usage_error(
+                    "\\recent <connection> [n] | \\recent <connection> full <n>",
+                    "Missing connection name".to_string(),
+                ));
+            };
+            show_recent(saved_connection(connection_manager, name)?, args)?;
+        }
+        "\\history" => show_history(&args)?,
+        "\\settings" => connection_manager.manage_settings().await?,
+        _ => {
+            return Err(QgoError::Usage(
+                "Offline mode cannot run SQL or commands that need a database. Exit and connect (qgo -c <connection>) to run it, or type 'help' for what works offline."
+                    .to_string(),
+            )
+            .into())
+        }
     }
-    let _ = rl.save_history(&history_file);
+    Ok(Flow::Continue)
+}
+
+fn saved_connection<'a>(connection_manager: &'a ConnectionManager, name: &str) -> Result<&'a Connection> {
+    connection_manager
+        .config()
+        .get_connection_by_name(name)
+        .ok_or_else(|| QgoError::ConnectionNotFound(name.to_string()).into())
+}
+
+/// `\history [n]` lists the last n lines typed in interactive sessions, as
+/// saved when the last session ended.
+fn show_history(args: &[String]) -> Result<()> {
+    const USAGE: &str = "\\history [n]";
+    let limit = match args {
+        [] => 20,
+        [n] => n
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| usage_error(USAGE, format!("Invalid count '{}'", n)))?,
+        [_, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+    };
 
+    let mut history = FileHistory::new();
+    let path = history_path();
+    if !path.exists() || history.load(&path).is_err() || history.is_empty() {
+        println!("No input history yet.");
+        return Ok(());
+    }
+    let skip = history.len().saturating_sub(limit);
+    for (index, line) in history.iter().enumerate().skip(skip) {
+        println!("{:>5}  {}", index + 1, line);
+    }
     Ok(())
 }
 
+/// What the session loop should do after a line of input has been handled.
+enum Flow {
+    Continue,
+    Exit,
+}
+
 async fn handle_input(
     input: &str,
-    database: &mut crate::database::Database,
-    max_rows_display: Option<usize>,
-) -> Result<()> {
+    database: &mut Database,
+    session: &mut Session<'_>,
+) -> Result<Flow> {
+    let normalized;
+    let input = if session.config.settings.normalize_input {
+        normalized = normalize_input(input, std::io::stdin().is_terminal());
+        normalized.as_ref()
+    } else {
+        input
+    };
     let trimmed = input.trim().to_lowercase();
 
     match trimmed.as_str() {
         "exit" | "quit" | "\\q" => {
-            println!("Goodbye!");
-            std::process::exit(0);
+            return Ok(Flow::Exit);
         }
         "help" | "\\h" => {
             show_help();
-            return Ok(());
+            return Ok(Flow::Continue);
         }
         "clear" | "\\c" => {
-            table_display::clear_screen();
-            return Ok(());
+            editor::clear_screen();
+            return Ok(Flow::Continue);
+        }
+        "\\reset" => {
+            editor::reset_terminal();
+            return Ok(Flow::Continue);
         }
         "version" | "\\v" => {
             println!("qgo version {}", env!("CARGO_PKG_VERSION"));
-            return Ok(());
+            return Ok(Flow::Continue);
         }
-        "tables" | "\\dt" => {
-            let tables = database.get_tables().await?;
-            if tables.is_empty() {
-                println!("No tables found.");
+        "\\cache" => {
+            if session.cache.is_enabled() {
+                println!(
+                    "{} cached results (up to {}, kept {}s).",
+                    session.cache.len(),
+                    session.config.settings.result_cache_entries,
+                    session.config.settings.result_cache_ttl_secs
+                );
             } else {
-                println!("Tables:");
-                for table in tables {
-                    println!("  {}", table);
-                }
+                println!("The result cache is off; set result_cache_entries in the config to turn it on.");
             }
-            return Ok(());
+            return Ok(Flow::Continue);
+        }
+        "\\cache clear" => {
+            session.cache.clear();
+            println!("Result cache cleared.");
+            return Ok(Flow::Continue);
+        }
+        "\\url" => {
+            connection_manager::show_connection_url(database.get_connection())?;
+            return Ok(Flow::Continue);
+        }
+        "\\tz" => {
+            match &session.display.timezone {
+                Some(zone) => println!("Time zone: {}", zone),
+                None => println!("Time zone: server default"),
+            }
+            return Ok(Flow::Continue);
+        }
+        "\\quiet" => {
+            println!("Quiet mode is {}.", if output::is_quiet() { "on" } else { "off" });
+            return Ok(Flow::Continue);
+        }
+        "\\quiet on" | "\\quiet off" => {
+            output::set_quiet(trimmed.ends_with("on"));
+            return Ok(Flow::Continue);
+        }
+        "\\types" => {
+            println!("Column types are {}.", if session.display.show_types { "shown" } else { "hidden" });
+            return Ok(Flow::Continue);
+        }
+        "\\types on" | "\\types off" => {
+            session.display.show_types = trimmed.ends_with("on");
+            return Ok(Flow::Continue);
+        }
+        "\\x" => {
+            println!("Vertical display is {}.", if session.display.vertical { "on" } else { "off" });
+            return Ok(Flow::Continue);
+        }
+        "\\x on" | "\\x off" => {
+            session.display.vertical = trimmed.ends_with("on");
+            return Ok(Flow::Continue);
+        }
+        "\\conninfo" => {
+            show_conninfo(database);
+            return Ok(Flow::Continue);
+        }
+        "\\saveconn" => {
+            save_connection(database, session.config).await?;
+            return Ok(Flow::Continue);
+        }
+        "\\settings" => {
+            edit_settings(database, session).await?;
+            return Ok(Flow::Continue);
+        }
+        "\\replica" | "\\primary" => {
+            switch_endpoint(database, session, trimmed == "\\replica").await?;
+            return Ok(Flow::Continue);
+        }
+        "\\push" => {
+            session.settings_stack.push(session.snapshot());
+            println!("Settings pushed ({} on the stack); \\pop restores them.", session.settings_stack.depth());
+            return Ok(Flow::Continue);
+        }
+        "\\pop" => {
+            pop_settings(database, session).await?;
+            return Ok(Flow::Continue);
+        }
+        "\\pushed" => {
+            show_pushed(session);
+            return Ok(Flow::Continue);
+        }
+        "\\formats" => {
+            show_formats(&session.config.settings);
+            return Ok(Flow::Continue);
+        }
+        "\\errverbose" => {
+            match session.last_error {
+                Some(ref error) => error.print(database.reconnects()),
+                None => println!("No error has occurred in this session."),
+            }
+            return Ok(Flow::Continue);
         }
         _ => {}
     }
 
-    // Handle DESCRIBE commands
-    if trimmed.starts_with("describe ") || trimmed.starts_with("\\d ") {
-        let table_name = if trimmed.starts_with("describe ") {
-            &input[9..].trim()
+    // Handle time zone switches, re-rendering the last result in the new zone
+    if trimmed.starts_with("\\tz ") {
+        let zone = SessionZone::parse(&input[4..])?;
+        report_rollback(database.set_timezone(zone.clone(), session.timeout).await?);
+        println!("Time zone set to {}", zone);
+        session.display.timezone = Some(zone);
+
+        if let Some(result) = session.current_result() {
+            format::display_table(&result, &session.display);
+        }
+        return Ok(Flow::Continue);
+    }
+
+    // Keep the last result for a later \diffq
+    if trimmed == "\\mark" {
+        match session.current_result() {
+            Some(result) => {
+                println!("Marked the last result ({} rows) as A for \\diffq.", result.row_count);
+                session.marked = Some(result.into_owned());
+            }
+            None => println!("No result to mark yet."),
+        }
+        return Ok(Flow::Continue);
+    }
+
+    // Check a query's result against a CSV file, or write that file
+    if let Some(check) = parse_check(input)? {
+        check.run(database).await?;
+        return Ok(Flow::Continue);
+    }
+
+    // Handle row-level comparison of two query results
+    if trimmed == "\\diffq" || trimmed.starts_with("\\diffq ") {
+        const USAGE: &str = "\\diffq [--key <column>] [<query_a> ;; <query_b>]";
+        let mut rest = input.trim()["\\diffq".len()..].trim_start();
+        let mut key = None;
+        if let Some(after) = rest.strip_prefix("--key") {
+            match next_arg(after)? {
+                Some((column, remainder)) => {
+                    key = Some(column);
+                    rest = remainder.trim_start();
+                }
+                None => return Err(usage_error(USAGE, "Missing column after --key".to_string())),
+            }
+        }
+
+        let diff = if rest.is_empty() {
+            let (Some(a), Some(b)) = (session.marked.as_ref(), session.current_result()) else {
+                return Err(usage_error(
+                    USAGE,
+                    "Mark a result with \\mark and run another query first, or give two queries".to_string(),
+                ));
+            };
+            diff::diff_results(a, &b, key.as_deref(), diff::RESULT_DIFF_ROW_LIMIT)?
+        } else {
+            let Some((query_a, query_b)) = rest.split_once(";;") else {
+                return Err(usage_error(USAGE, "Separate the two queries with ;;".to_string()));
+            };
+            let a = database.execute_query(query_a.trim()).await?;
+            let b = database.execute_query(query_b.trim()).await?;
+            print_warnings(&a.warnings);
+            print_warnings(&b.warnings);
+            diff::diff_results(&a, &b, key.as_deref(), diff::RESULT_DIFF_ROW_LIMIT)?
+        };
+        diff::print_result_diff(&diff, ["A", "B"]);
+        return Ok(Flow::Continue);
+    }
+
+    // Handle Postgres LISTEN/UNLISTEN on the session's notification connection
+    if trimmed.starts_with("listen ") || trimmed.starts_with("unlisten ") {
+        let (unlisten, rest) = if trimmed.starts_with("listen ") {
+            (false, &input.trim()[7..])
+        } else {
+            (true, &input.trim()[9..])
+        };
+        let usage = if unlisten { "UNLISTEN <channel|*>" } else { "LISTEN <channel>" };
+        let channel = notify::parse_channel(rest)
+            .ok_or_else(|| usage_error(usage, "Missing channel name".to_string()))?;
+
+        if session.notifications.is_none() {
+            session.notifications = Some(Notifications::new(database, session.printer.clone())?);
+        }
+        let Some(notifications) = session.notifications.as_mut() else {
+            return Ok(Flow::Continue);
+        };
+        if !unlisten {
+            notifications.listen(&channel).await?;
+            output::info(format!("Listening on \"{}\"", channel));
+        } else if channel == "*" {
+            notifications.unlisten(None).await?;
+            output::info("Stopped listening on all channels");
+        } else {
+            notifications.unlisten(Some(&channel)).await?;
+            output::info(format!("Stopped listening on \"{}\"", channel));
+        }
+        return Ok(Flow::Continue);
+    }
+
+    if trimmed == "\\notifications" {
+        let channels = session
+            .notifications
+            .as_ref()
+            .map(|notifications| notifications.channels().iter().map(|channel| vec![channel.clone()]).collect())
+            .unwrap_or_default();
+        let result = QueryResult::from_rows(&["Channel"], channels);
+        if result.is_empty() {
+            println!("Not listening on any channels.");
         } else {
-            &input[3..].trim()
+            let options = DisplayOptions {
+                scalar_display: false,
+                ..session.display.clone()
+            };
+            format::display_table(&result, &options);
+        }
+        return Ok(Flow::Continue);
+    }
+
+    // Handle schema comparison against another saved connection
+    if trimmed.starts_with("\\diff ") {
+        const USAGE: &str = "\\diff <connection> [table-pattern]";
+        let args = split_args(&input[6..])?;
+        let name = match args.as_slice() {
+            [] => return Err(usage_error(USAGE, "Missing connection name".to_string())),
+            [_, _, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+            [name, ..] => name,
         };
-        
-        let columns = database.get_columns(table_name).await?;
-        if columns.is_empty() {
-            println!("Table '{}' not found or has no columns.", table_name);
+        let other = session
+            .config
+            .get_connection_by_name(name)
+            .cloned()
+            .ok_or_else(|| QgoError::ConnectionNotFound(name.to_string()))?;
+
+        let mut other = connection_manager::open_database(&session.config.settings, other).await?;
+        let report = diff::diff_databases(database, &mut other, args.get(1).map(String::as_str), session.timeout).await?;
+        diff::print_report(&report);
+        return Ok(Flow::Continue);
+    }
+
+    // Handle table listings and DESCRIBE commands
+    if let Some(result) = metadata_result(database, input).await? {
+        if result.is_empty() {
+            if trimmed == "tables" || trimmed == "\\dt" {
+                println!("No tables found.");
+            } else {
+                println!("Table not found or has no columns.");
+            }
         } else {
-            println!("Columns in table '{}':", table_name);
-            for column in columns {
-                println!("  {}", column);
+            // Listings always render as a grid, even with a single row
+            let options = DisplayOptions {
+                scalar_display: false,
+                ..session.display.clone()
+            };
+            format::display_table(&result, &options);
+            print_more_hint(rows_shown(&result, &options), result.rows.len(), page_size(session));
+            if let Some(table) = described_table(database, input).await? {
+                show_view_definition(database, &table).await?;
             }
         }
-        return Ok(());
+        let shown = rows_shown(&result, &session.display);
+        session.set_last_result(result, None, shown);
+        return Ok(Flow::Continue);
     }
 
-    // Handle EXPORT commands
-    if trimmed.starts_with("export ") {
-        let parts: Vec<&str> = input[7..].splitn(3, ' ').collect();
-        if parts.len() == 3 {
-            let format = parts[0].to_lowercase();
-            let filename = parts[1];
-            let query = parts[2];
-            
-            let result = database.execute_query(query).await?;
-            
-            match format.as_str() {
-                "csv" => {
-                    table_display::export_to_csv(&result, filename)?;
-                }
-                "json" => {
-                    table_display::export_to_json(&result, filename)?;
-                }
-                _ => {
-                    println!("Unsupported export format. Use 'csv' or 'json'.");
+    // Handle IMPORT commands
+    if trimmed.starts_with("\\import ") {
+        const USAGE: &str = "\\import csv <file> <table> [--skip-errors] [--dry-run] [--mapping <file.json>]";
+        let mut args = Vec::new();
+        let mut skip_errors = false;
+        let mut dry_run = false;
+        let mut mapping = None;
+        let mut rest = split_args(&input[8..])?.into_iter();
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "--skip-errors" => skip_errors = true,
+                "--dry-run" => dry_run = true,
+                "--mapping" => {
+                    let file = rest.next().ok_or_else(|| usage_error(USAGE, "Missing mapping file".to_string()))?;
+                    mapping = Some(PathBuf::from(file));
                 }
+                _ => args.push(arg),
             }
-            return Ok(());
-        } else {
-            println!("Usage: export <format> <filename> <query>");
-            println!("Example: export csv results.csv SELECT * FROM users");
-            return Ok(());
         }
+
+        match args.as_slice() {
+            [format, ..] if !format.eq_ignore_ascii_case("csv") => {
+                return Err(usage_error(USAGE, format!("Unsupported import format '{}'", format)))
+            }
+            [] | [_] => return Err(usage_error(USAGE, "Missing file name".to_string())),
+            [_, _] => return Err(usage_error(USAGE, "Missing table name".to_string())),
+            [_, _, _, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+            [_, _, _] => {}
+        }
+
+        let options = ImportOptions {
+            skip_errors,
+            dry_run,
+            mapping: mapping.as_deref(),
+        };
+        let Some(summary) = import::import_csv(database, &args[1], &args[2], options).await? else {
+            return Ok(Flow::Continue);
+        };
+        session.cache.clear();
+        println!("Imported {} rows into '{}'.", summary.rows_inserted, args[2]);
+        if !summary.skipped_lines.is_empty() {
+            println!("Skipped {} rows with conversion errors.", summary.skipped_lines.len());
+        }
+        return Ok(Flow::Continue);
     }
 
-    // Execute SQL query
-    let result = database.execute_query(input).await?;
-    table_display::display_table(&result, max_rows_display);
-    
-    Ok(())
-}
+    // List and cancel background exports
+    if trimmed == "\\jobs" {
+        session.jobs.print();
+        return Ok(Flow::Continue);
+    }
+    if trimmed == "\\job" || trimmed.starts_with("\\job ") {
+        const USAGE: &str = "\\job cancel <id>";
+        match split_args(&input.trim_start()["\\job".len()..])?.as_slice() {
+            [action, id] if action == "cancel" => {
+                let id = id
+                    .parse()
+                    .map_err(|_| usage_error(USAGE, format!("'{}' is not a job number", id)))?;
+                session.jobs.cancel(id)?;
+            }
+            _ => return Err(usage_error(USAGE, "Expected: \\job cancel <id>".to_string())),
+        }
+        return Ok(Flow::Continue);
+    }
 
-fn show_help() {
-    println!("{}", style("Qgo - SQL Client Commands").bold().blue());
-    println!();
-    println!("{}", style("SQL Commands:").bold());
-    println!("  SELECT, SHOW, DESCRIBE, EXPLAIN  - Execute SQL queries");
-    println!();
-    println!("{}", style("Special Commands:").bold());
-    println!("  help, \\h          - Show this help message");
-    println!("  exit, quit, \\q    - Exit the program");
-    println!("  clear, \\c         - Clear the screen");
-    println!("  version, \\v       - Show version information");
-    println!("  tables, \\dt       - List all tables");
-    println!("  describe <table>, \\d <table> - Describe table structure");
-    println!();
-    println!("{}", style("Export Commands:").bold());
-    println!("  export csv <file> <query>   - Export query results to CSV");
-    println!("  export json <file> <query>  - Export query results to JSON");
+    // \bg runs an export as a background job, like export --background
+    let (input, trimmed, background) = match trimmed.strip_prefix("\\bg ") {
+        Some(_) => {
+            let rest = input.trim_start()["\\bg ".len()..].trim_start();
+            let trimmed = rest.to_lowercase();
+            if !trimmed.starts_with("export ") && !trimmed.starts_with("\\export ") {
+                return Err(usage_error("\\bg export ...", "Only exports can run in the background".to_string()));
+            }
+            (rest, trimmed, true)
+        }
+        None => (input, trimmed, false),
+    };
+
+    // Handle EXPORT commands
+    if trimmed.starts_with("export ") || trimmed.starts_with("\\export ") {
+        const USAGE: &str = "export [--background] [--allow-truncated] [--bom] [--crlf] [<format>] <filename> [<query>]\n       export fixed (--widths 10,4,25 | --spec <file.json>) [--terminator lf|crlf|none] <filename> [<query>]";
+        let args = input.trim_start().split_once(' ').map_or("", |(_, rest)| rest).trim_start();
+        let mut options = ExportOptions::new(&session.config.settings, background);
+        let args = options.parse(args, USAGE)?;
+        let (first, rest) = next_arg(args)?
+            .ok_or_else(|| usage_error(USAGE, "Missing file name".to_string()))?;
+        if first.eq_ignore_ascii_case("fixed") {
+            let rest = options.parse(rest, USAGE)?;
+            let (filename, query) = next_arg(rest)?
+                .ok_or_else(|| usage_error(USAGE, "Missing file name".to_string()))?;
+            fixed_export(database, session, &options, &filename, query.trim(), USAGE).await?;
+            return Ok(Flow::Continue);
+        }
+        if options.is_fixed() {
+            return Err(usage_error(USAGE, "--widths, --spec and --terminator only apply to export fixed".to_string()));
+        }
+        let ExportOptions {
+            background,
+            allow_truncated,
+            csv,
+            ..
+        } = options;
+        let limit = ExportLimit::from_settings(&session.config.settings, allow_truncated);
+
+        // Without an explicit format, go by the file extension, then the setting
+        let (export_format, filename, query) = match formatter::find(&first) {
+            Some(export_format) => {
+                let (filename, query) = next_arg(rest)?
+                    .ok_or_else(|| usage_error(USAGE, "Missing file name".to_string()))?;
+                (export_format, filename, query)
+            }
+            None => {
+                let export_format = match formatter::for_extension(&first) {
+                    Some(export_format) => export_format,
+                    None => default_export_format(&session.config.settings)?,
+                };
+                (export_format, first, rest)
+            }
+        };
+        // Without a query, the last result is written in full, including
+        // rows the display left out
+        let query = query.trim();
+        if background && (query.is_empty() || !export_format.streams) {
+            return Err(usage_error(
+                USAGE,
+                format!(
+                    "Background exports write a query's rows as they arrive, so they need a query and a format that can ({})",
+                    streaming_formats()
+                ),
+            ));
+        }
+        // The job's own connection would see only committed data
+        if background && database.in_transaction() {
+            return Err(QgoError::Usage(
+                "A transaction is open, and a background export runs on a connection of its own that can't see its changes; COMMIT or ROLLBACK it first, or export without --background".to_string(),
+            )
+            .into());
+        }
+        if query.is_empty() {
+            if session.last_result.is_none() {
+                return Err(usage_error(USAGE, "Missing query, and there is no last result to export".to_string()));
+            }
+            let Some(path) = export_path(&filename, database.get_connection(), session)? else {
+                return Ok(Flow::Continue);
+            };
+            if let Some(result) = session.current_result() {
+                export_result(&result, export_format, &path, limit, csv)?;
+            }
+            println!("Results exported to: {}", path.display());
+            return Ok(Flow::Continue);
+        }
+
+        let Some(path) = export_path(&filename, database.get_connection(), session)? else {
+            return Ok(Flow::Continue);
+        };
+        if let Some(result) = metadata_result(database, query).await? {
+            export_result(&result, export_format, &path, limit, csv)?;
+        } else if background {
+            start_background_export(database, session, query, export_format, path, limit, csv).await?;
+            return Ok(Flow::Continue);
+        } else if *export_format == formatter::CSV && copy_export(database, query, &path, limit, csv).await? {
+            return Ok(Flow::Continue);
+        } else if let Some(writer) = ExportWriter::create(export_format, &path.to_string_lossy(), csv)? {
+            // CSV and JSON are written as rows arrive
+            stream_export(database, query, writer, &path, limit).await?;
+            return Ok(Flow::Continue);
+        } else {
+            let result = database.execute_query(query).await?;
+            print_warnings(&result.warnings);
+            export_result(&result, export_format, &path, limit, csv)?;
+        }
+        println!("Results exported to: {}", path.display());
+        return Ok(Flow::Continue);
+    }
+
+    // Handle recent-query listings
+    if trimmed == "\\recent" || trimmed.starts_with("\\recent ") {
+        show_recent(database.get_connection(), &split_args(&input[7..])?)?;
+        return Ok(Flow::Continue);
+    }
+
+    // Slowest queries of this session, or of the connection's history
+    if trimmed == "\\slow" || trimmed.starts_with("\\slow ") {
+        show_slowest(database.get_connection(), &session.query_log, &split_args(&input[5..])?)?;
+        return Ok(Flow::Continue);
+    }
+
+    // Re-render the last result with only some of its columns
+    if trimmed == "\\cols" || trimmed.starts_with("\\cols ") {
+        select_columns(session, input.trim()["\\cols".len()..].trim())?;
+        return Ok(Flow::Continue);
+    }
+
+    // Page through the rows a truncated display left out
+    if trimmed == "\\more" {
+        show_more(session)?;
+        return Ok(Flow::Continue);
+    }
+    if trimmed == "\\all" {
+        show_all(session)?;
+        return Ok(Flow::Continue);
+    }
+
+    // List the columns of the last result
+    if trimmed == "\\columns" {
+        show_columns(session)?;
+        return Ok(Flow::Continue);
+    }
+
+    // Draw the last result as a chart
+    if trimmed == "\\chart" || trimmed.starts_with("\\chart ") {
+        show_chart(session, &split_args(&input[6..])?)?;
+        return Ok(Flow::Continue);
+    }
+
+    // Fetch the row a foreign key in the last result points to
+    if trimmed == "\\follow" || trimmed.starts_with("\\follow ") {
+        follow_key(database, session, &split_args(&input[7..])?).await?;
+        return Ok(Flow::Continue);
+    }
+
+    // Compare the planner's row estimates with what actually happened
+    if trimmed == "\\analyze" || trimmed.starts_with("\\analyze ") {
+        analyze_query(database, session, input.trim()["\\analyze".len()..].trim()).await?;
+        return Ok(Flow::Continue);
+    }
+
+    // Show how often each value of a column occurs
+    if trimmed == "\\freq" || trimmed.starts_with("\\freq ") {
+        show_frequencies(database, session, &split_args(&input[5..])?).await?;
+        return Ok(Flow::Continue);
+    }
+
+    // Print one value of the last result in full
+    if trimmed == "\\cell" || trimmed.starts_with("\\cell ") {
+        show_cell(session, &split_args(&input[5..])?)?;
+        return Ok(Flow::Continue);
+    }
+
+    // Handle script files
+    if trimmed.starts_with("\\i ") {
+        const USAGE: &str = "\\i [--single-transaction] <file>";
+        let mut args = split_args(&input[3..])?;
+        let single_transaction = args.iter().any(|arg| arg == "--single-transaction");
+        args.retain(|arg| arg != "--single-transaction");
+        let path = match args.as_slice() {
+            [path] => path,
+            [] => return Err(usage_error(USAGE, "Missing file name".to_string())),
+            [_, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+        };
+
+        let options = ScriptOptions {
+            on_error: session.on_error,
+            single_transaction,
+            keep_transaction_open: true,
+        };
+        session.cache.clear();
+        let mut confirm = confirm_each(session);
+        run_script(
+            database,
+            path,
+            &session.variables,
+            options,
+            &session.display,
+            session.transcript.as_mut(),
+            &mut confirm,
+        )
+        .await?;
+        return Ok(Flow::Continue);
+    }
+
+    // Start, stop or report the session transcript
+    if trimmed == "\\record" || trimmed.starts_with("\\record ") {
+        record_command(session, &split_args(&input[7..])?, database.get_connection())?;
+        return Ok(Flow::Continue);
+    }
+
+    // Build a simple SELECT step by step and run it
+    if trimmed == "\\browse" || trimmed.starts_with("\\browse ") {
+        const USAGE: &str = "\\browse [table]";
+        let args = split_args(&input[7..])?;
+        let table = match args.as_slice() {
+            [] => None,
+            [table] => Some(table.as_str()),
+            [_, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+        };
+        if !std::io::stdin().is_terminal() {
+            return Err(QgoError::Usage("\\browse asks questions and needs a terminal".to_string()).into());
+        }
+
+        let limit = session.display.max_rows.unwrap_or(100);
+        let sql = match browse::build_query(database, table, limit).await {
+            Err(e) if prompts::is_cancelled(&e) => {
+                println!("Cancelled.");
+                return Ok(Flow::Continue);
+            }
+            sql => sql?,
+        };
+        session.history_additions.push(sql.clone());
+        return Box::pin(handle_input(&sql, database, session)).await;
+    }
+
+    // Store a query's value in a variable, or list the stored ones
+    if trimmed.starts_with("\\into ") {
+        store_into(&input[6..], database, session).await?;
+        return Ok(Flow::Continue);
+    }
+    if trimmed == "\\vars" {
+        show_variables(&session.variables);
+        return Ok(Flow::Continue);
+    }
+
+    // Load variables from a parameter file
+    if trimmed == "\\params" || trimmed.starts_with("\\params ") {
+        load_params(session, &split_args(&input[7..])?)?;
+        return Ok(Flow::Continue);
+    }
+
+    // Change what ends a statement at the prompt
+    if trimmed == "\\delimiter" || trimmed.starts_with("\\delimiter ") {
+        match split_args(&input.trim_start()["\\delimiter".len()..])?.as_slice() {
+            [] => println!("Statements end with {}", session.delimiter),
+            [delimiter] => {
+                session.delimiter = delimiter.clone();
+                println!("Statements now end with {}", session.delimiter);
+            }
+            [_, extra, ..] => {
+                return Err(usage_error("\\delimiter [<terminator>]", format!("Unexpected argument '{}'", extra)))
+            }
+        }
+        return Ok(Flow::Continue);
+    }
+
+    // Handle session variables
+    if trimmed == "\\set" || trimmed.starts_with("\\set ") {
+        set_variable(session, &split_args(&input[4..])?).await?;
+        return Ok(Flow::Continue);
+    }
+
+    // A leading \nocache runs the query even when a cached result exists
+    let (input, use_cache) = match trimmed.strip_prefix("\\nocache ") {
+        Some(_) => (input.trim_start()["\\nocache ".len()..].trim_start(), false),
+        None => (input, true),
+    };
+    let substituted = session.variables.substitute(&database.get_connection().db_type, input)?;
+    let input = substituted.as_ref();
+
+    // Several statements on one line run like a script, as do writes and
+    // transaction control, so a transaction can stay open between lines
+    let delimiter = session.delimiter.clone();
+    let statements: Vec<String> = script::split_with_delimiter(&database.get_connection().db_type, input, &mut session.delimiter)
+        .into_iter()
+        .map(|(_, statement)| statement)
+        .collect();
+    if session.delimiter != delimiter {
+        println!("Statements now end with {}", session.delimiter);
+    }
+    // Past a DELIMITER line or a terminator other than `;`, the statement
+    // is what the splitter found rather than the line as typed
+    let split;
+    let input = match statements.as_slice() {
+        [] if session.delimiter != delimiter => return Ok(Flow::Continue),
+        [statement] if session.delimiter != script::DEFAULT_DELIMITER || session.delimiter != delimiter => {
+            split = statement.clone();
+            split.as_str()
+        }
+        _ => input,
+    };
+    let scripted = statements.len() > 1
+        || (database::classify(input) != StatementClass::Select
+            && (database.in_transaction() || !database::is_session_statement(input)));
+    if scripted {
+        session.cache.clear();
+        let options = ScriptOptions {
+            on_error: session.on_error,
+            single_transaction: false,
+            keep_transaction_open: true,
+        };
+        let mut confirm = confirm_each(session);
+        let reconnects = database.reconnects();
+        let mut failures = script::run(
+            database,
+            &statements,
+            options,
+            &session.display,
+            session.transcript.as_mut(),
+            Some(&mut session.query_log),
+            &mut confirm,
+        )
+        .await?;
+        script::print_summary(statements.len(), &failures, session.on_error);
+        if let Some(failure) = failures.pop() {
+            let statement = &statements[failure.statement - 1];
+            session.last_error = Some(LastError::new(failure.error, statement, reconnects, database.reconnects()));
+        }
+        return Ok(Flow::Continue);
+    }
+
+    if !confirm_each(session).allows(input, false) {
+        println!("Skipped.");
+        return Ok(Flow::Continue);
+    }
+
+    // SET, USE and the like are replayed on every pooled connection
+    if database::is_session_statement(input) {
+        session.cache.clear();
+        let started = Instant::now();
+        let outcome = database.execute_session_statement(input).await;
+        if let Some(transcript) = session.transcript.as_mut() {
+            match &outcome {
+                Ok(()) => transcript.record_note(input, started.elapsed(), "Session setting applied"),
+                Err(e) => transcript.record_error(input, started.elapsed(), e),
+            }
+        }
+        outcome?;
+        output::info("Session setting applied to every connection of this session.");
+        return Ok(Flow::Continue);
+    }
+
+    // A trailing \g, \gx, \gcsv or \gjson picks the output for this query
+    let (input, target) = split_directive(input)?;
+    let target = match target {
+        ResultTarget::Export(export_format, Some(filename)) => {
+            match export_path(&filename, database.get_connection(), session)? {
+                Some(path) => ResultTarget::Export(export_format, Some(path.to_string_lossy().into_owned())),
+                None => return Ok(Flow::Continue),
+            }
+        }
+        target => target,
+    };
+
+    // Repeated read-only queries can be answered from the cache
+    let cacheable =
+        session.cache.is_enabled() && database::classify(input) == StatementClass::Select && !database.in_transaction();
+    if cacheable && use_cache {
+        if let Some((result, age)) = session.cache.get(input) {
+            let result = result.clone();
+            output::info(style(format!("(cached, {}s old — \\nocache to re-run)", age.as_secs())).dim());
+            if let Some(transcript) = session.transcript.as_mut() {
+                transcript.record_result(input, None, &result, &session.display);
+            }
+            let shown = deliver_result(&result, &target, database.get_connection(), session)?;
+            session.set_last_result(result, follow::source_table(input), shown);
+            return Ok(Flow::Continue);
+        }
+    }
+
+    let input = match confirm_table_scan(input, database, session).await {
+        Some(input) => input,
+        None => {
+            println!("Cancelled.");
+            return Ok(Flow::Continue);
+        }
+    };
+    let input = input.as_ref();
+    if session.config.settings.lint {
+        lint::print(&lint::check(database, input, &session.config.settings).await);
+    }
+
+    // Execute SQL query
+    let started_at = chrono::Utc::now();
+    let started = Instant::now();
+    let outcome = database.execute_query(input).await;
+    let elapsed = started.elapsed();
+    let entry = RecentQuery::new(input, started_at, elapsed, &outcome);
+    if let Some(transcript) = session.transcript.as_mut() {
+        match &outcome {
+            Ok(result) => transcript.record_result(input, Some(elapsed), result, &session.display),
+            Err(e) => transcript.record_error(input, elapsed, e),
+        }
+    }
+    if let Err(e) = recent::record(database.get_connection(), &entry, session.config.settings.recent_queries_max_bytes) {
+        eprintln!("{}", style(format!("Could not record query in recent history: {}", e)).yellow());
+    }
+    session.query_log.push(entry);
+
+    let result = outcome?;
+    print_warnings(&result.warnings);
+    if cacheable {
+        session.cache.insert(input, &result);
+    }
+    let shown = deliver_result(&result, &target, database.get_connection(), session)?;
+    session.set_last_result(result, follow::source_table(input), shown);
+    
+    Ok(Flow::Continue)
+}
+
+/// Asking before each statement when the `confirm_each_query` setting is
+/// on and there is a terminal to ask on.
+fn confirm_each(session: &Session<'_>) -> ConfirmEach {
+    ConfirmEach::new(session.config.settings.confirm_each_query && std::io::stdin().is_terminal())
+}
+
+/// Fixes the line endings and trailing whitespace of pasted input, and
+/// points out curly quotes and non-breaking spaces, which give baffling
+/// syntax errors, offering to replace them when `interactive`. Without a
+/// terminal to ask on, they are left as they are.
+fn normalize_input(input: &str, interactive: bool) -> Cow<'_, str> {
+    const LISTED: usize = 10;
+    let input = normalize::line_endings(input);
+    let lookalikes = normalize::find_lookalikes(&input);
+    if lookalikes.is_empty() {
+        return input;
+    }
+
+    eprintln!("{}", style("The input has characters that look like ASCII quotes or spaces but are not:").yellow());
+    for lookalike in lookalikes.iter().take(LISTED) {
+        eprintln!(
+            "  line {}, column {}: {} ({})",
+            lookalike.line, lookalike.column, lookalike.found, lookalike.name
+        );
+    }
+    if lookalikes.len() > LISTED {
+        eprintln!("  ... and {} more", lookalikes.len() - LISTED);
+    }
+    if interactive && prompts::confirm_default("Replace them with ASCII quotes and spaces?", true) {
+        Cow::Owned(normalize::replace_lookalikes(&input))
+    } else {
+        input
+    }
+}
+
+/// Before a plain `SELECT … FROM table` on a table estimated to be larger
+/// than the `row_estimate_warning` setting, asks whether to run it, add a
+/// LIMIT or cancel. Only cached or cheap catalog estimates are used, and
+/// nothing is asked without a terminal. Returns the query to run, or `None`
+/// to cancel.
+async fn confirm_table_scan<'q>(query: &'q str, database: &mut Database, session: &Session<'_>) -> Option<Cow<'q, str>> {
+    let Some(threshold) = session.config.settings.row_estimate_warning else {
+        return Some(Cow::Borrowed(query));
+    };
+    let Some(table) = whole_table_scan(query) else {
+        return Some(Cow::Borrowed(query));
+    };
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Some(Cow::Borrowed(query));
+    }
+    match database.estimated_row_count(table).await {
+        Some(estimate) if estimate > threshold => {
+            let limit = session.display.max_rows.unwrap_or(1000);
+            let message = format!(
+                "{} has ~{} rows — run anyway, add a LIMIT, or cancel?",
+                table,
+                approximate_count(estimate)
+            );
+            let add_limit = format!("Add LIMIT {}", limit);
+            match prompts::select(&message, &["Run anyway", &add_limit, "Cancel"]) {
+                Some(0) => Some(Cow::Borrowed(query)),
+                Some(1) => Some(Cow::Owned(format!(
+                    "{} LIMIT {}",
+                    query.trim().trim_end_matches(';').trim_end(),
+                    limit
+                ))),
+                _ => None,
+            }
+        }
+        _ => Some(Cow::Borrowed(query)),
+    }
+}
+
+/// The table of a `SELECT <columns> FROM <table>` with nothing after the
+/// table name: no WHERE, LIMIT, joins or aliases. Select lists with
+/// parentheses or quotes (aggregates, subqueries, literals) don't count, as
+/// they may not return a row per table row.
+fn whole_table_scan(query: &str) -> Option<&str> {
+    let query = query.trim().trim_end_matches(';').trim_end();
+    let lower: String = query
+        .chars()
+        .map(|c| if c.is_ascii_whitespace() { ' ' } else { c.to_ascii_lowercase() })
+        .collect();
+    if !lower.starts_with("select ") {
+        return None;
+    }
+    let from = lower.find(" from ")?;
+    if lower[..from].contains(['(', '\'', '"']) {
+        return None;
+    }
+    let table = query[from + " from ".len()..].trim();
+    if table.is_empty() || table.contains(|c: char| c.is_whitespace() || matches!(c, ',' | '(' | ')')) {
+        return None;
+    }
+    Some(table)
+}
+
+/// Formats a large count roughly, e.g. `240M` or `1.2B`.
+fn approximate_count(count: u64) -> String {
+    let (value, suffix) = match count {
+        0..=999 => return count.to_string(),
+        1_000..=999_999 => (count as f64 / 1e3, "K"),
+        1_000_000..=999_999_999 => (count as f64 / 1e6, "M"),
+        _ => (count as f64 / 1e9, "B"),
+    };
+    if value < 10.0 {
+        format!("{:.1}{}", value, suffix)
+    } else {
+        format!("{:.0}{}", value, suffix)
+    }
+}
+
+/// Where a query's result goes, as picked by a trailing backslash directive.
+#[derive(Debug, PartialEq)]
+enum ResultTarget {
+    /// The usual display.
+    Display,
+    /// `\g`: the grid, even for a single value.
+    Grid,
+    /// `\gx`: each row as a block of `column | value` lines.
+    Vertical,
+    /// `\gcsv` or `\gjson`: written to the file, or printed without one.
+    Export(&'static Registration, Option<String>),
+}
+
+const DIRECTIVE_USAGE: &str = "<query> \\g | \\gx | \\gcsv [file] | \\gjson [file]";
+
+/// Splits a trailing output directive off `input`. Only a backslash outside
+/// quotes and `--` comments that follows some query text counts, and what
+/// comes after it must be the whole directive; an unknown one is an error,
+/// so the query is never run with the wrong output.
+fn split_directive(input: &str) -> Result<(&str, ResultTarget)> {
+    let Some(start) = last_unquoted_backslash(input).filter(|&start| !input[..start].trim().is_empty()) else {
+        return Ok((input, ResultTarget::Display));
+    };
+    let (query, directive) = (&input[..start], &input[start + 1..]);
+    let name_end = directive.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(directive.len());
+    let (name, args) = directive.split_at(name_end);
+    let file = match next_arg(args)? {
+        Some((file, rest)) if rest.trim().is_empty() => Some(file),
+        Some(_) => return Err(usage_error(DIRECTIVE_USAGE, format!("Too many arguments to \\{}", name))),
+        None => None,
+    };
+
+    let target = match name {
+        "g" | "gx" if file.is_some() => {
+            return Err(usage_error(DIRECTIVE_USAGE, format!("\\{} takes no file; use \\gcsv or \\gjson", name)));
+        }
+        "g" => ResultTarget::Grid,
+        "gx" => ResultTarget::Vertical,
+        "gcsv" => ResultTarget::Export(&formatter::CSV, file),
+        "gjson" => ResultTarget::Export(&formatter::JSON, file),
+        _ => return Err(usage_error(DIRECTIVE_USAGE, format!("Unknown directive '\\{}'", directive.trim_end()))),
+    };
+    Ok((query, target))
+}
+
+/// The byte index of the last backslash in `input` outside quotes and
+/// `--` and `/* */` comments.
+fn last_unquoted_backslash(input: &str) -> Option<usize> {
+    let mut last = None;
+    let mut quote = None;
+    // The character that ends the comment being skipped: `\n`, or `*` for `*/`
+    let mut comment = None;
+    let mut chars = input.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match (quote, c) {
+            // A doubled quote inside a literal is an escaped one
+            (Some(open), c) if c == open && chars.next_if(|&(_, next)| next == open).is_none() => quote = None,
+            (Some(_), _) => {}
+            (None, '\n') if comment == Some('\n') => comment = None,
+            (None, '*') if comment == Some('*') && chars.next_if(|&(_, next)| next == '/').is_some() => comment = None,
+            _ if comment.is_some() => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '-') if chars.next_if(|&(_, next)| next == '-').is_some() => comment = Some('\n'),
+            (None, '/') if chars.next_if(|&(_, next)| next == '*').is_some() => comment = Some('*'),
+            (None, '\\') => last = Some(index),
+            _ => {}
+        }
+    }
+    last
+}
+
+/// Sends a query result where `target` says: to the display, or as an
+/// export to a file or stdout. Returns how many of its rows were shown.
+fn deliver_result(result: &QueryResult, target: &ResultTarget, connection: &Connection, session: &Session<'_>) -> Result<usize> {
+    let ResultTarget::Export(export_format, file) = target else {
+        return show_result(result, target, connection, session);
+    };
+    let csv = CsvOptions::from_settings(&session.config.settings);
+    match file {
+        Some(file) => {
+            let limit = ExportLimit::from_settings(&session.config.settings, false);
+            export_result(result, export_format, Path::new(file), limit, csv)?;
+            println!("Results exported to: {}", file);
+        }
+        None => format::print_export(result, export_format, csv)?,
+    }
+    Ok(result.rows.len())
+}
+
+/// Displays a query result, first asking what to do with a very large one.
+/// Returns how many of its rows were displayed.
+fn show_result(result: &QueryResult, target: &ResultTarget, connection: &Connection, session: &Session<'_>) -> Result<usize> {
+    match confirm_large_result(result, session) {
+        Some(mut options) => {
+            options.scalar_display &= *target == ResultTarget::Display;
+            options.vertical |= *target == ResultTarget::Vertical;
+            format::display_table(result, &options);
+            let shown = rows_shown(result, &options);
+            print_more_hint(shown, result.rows.len(), page_size(session));
+            Ok(shown)
+        }
+        None => {
+            export_interactively(result, connection, session)?;
+            Ok(0)
+        }
+    }
+}
+
+/// How many rows of `result` a display with `options` includes.
+fn rows_shown(result: &QueryResult, options: &DisplayOptions) -> usize {
+    options.max_rows.map_or(result.rows.len(), |max| result.rows.len().min(max))
+}
+
+/// How many rows `\more` shows at a time.
+fn page_size(session: &Session<'_>) -> usize {
+    session
+        .display
+        .max_rows
+        .or(session.config.settings.large_result_threshold)
+        .unwrap_or(1000)
+        .max(1)
+}
+
+/// After a display that left rows out, says how to see or save the rest.
+/// Only printed at an interactive prompt.
+fn print_more_hint(shown: usize, total: usize, page: usize) {
+    if shown >= total || !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return;
+    }
+    output::info(
+        style(format!(
+            "{} of {} rows shown — \\more for the next {}, \\all for all of them in the pager, \\export <file> to save them",
+            format::group_thousands(shown),
+            format::group_thousands(total),
+            format::group_thousands(page.min(total - shown))
+        ))
+        .yellow(),
+    );
+}
+
+/// Runs the table-listing and describe commands (`tables`, `\dt`,
+/// `describe <table>`, `\d <table>`) as client-side results, so they display
+/// and export like query results. Returns `None` for any other input.
+async fn metadata_result(database: &mut Database, input: &str) -> Result<Option<QueryResult>> {
+    let input = input.trim();
+    let lower = input.to_lowercase();
+    if lower == "tables" || lower == "\\dt" {
+        let tables = database.get_tables().await?;
+        let rows = tables.into_iter().map(|table| vec![table]).collect();
+        return Ok(Some(QueryResult::from_rows(&["Table"], rows)));
+    }
+
+    let Some(table_name) = described_table(database, input).await? else {
+        return Ok(None);
+    };
+    let rows = database
+        .get_column_types(&table_name)
+        .await?
+        .into_iter()
+        .map(|(name, type_name)| vec![name, type_name])
+        .collect();
+    Ok(Some(QueryResult::from_rows(&["Column", "Type"], rows)))
+}
+
+/// The table named by `describe <table>` or `\d <table>`, as the catalog
+/// spells it; `None` for any other input.
+async fn described_table(database: &mut Database, input: &str) -> Result<Option<String>> {
+    let input = input.trim();
+    let lower = input.to_lowercase();
+    // Command words match case-insensitively; table names keep their case
+    let args = if lower.starts_with("describe ") {
+        split_args(&input[9..])?
+    } else if lower.starts_with("\\d ") {
+        split_args(&input[3..])?
+    } else {
+        return Ok(None);
+    };
+
+    const USAGE: &str = "\\d <table>";
+    let table_name = match args.as_slice() {
+        [table] => table.as_str(),
+        [] => return Err(usage_error(USAGE, "Missing table name".to_string())),
+        [_, extra, ..] => {
+            return Err(usage_error(
+                USAGE,
+                format!("Unexpected argument '{}'; quote table names containing spaces", extra),
+            ))
+        }
+    };
+
+    // `\d useraccounts` finds "UserAccounts" when that is the only match
+    Ok(Some(database.resolve_table_name(table_name).await?))
+}
+
+/// Prints the defining SQL under `\d` of a view. Not being allowed to read
+/// it is only a notice.
+async fn show_view_definition(database: &mut Database, table: &str) -> Result<()> {
+    let Some(view) = database.get_view_definition(table).await? else {
+        return Ok(());
+    };
+    let label = if view.materialized { "Materialized view" } else { "View" };
+    println!();
+    match view.populated {
+        Some(true) => println!("{} (populated; Postgres does not record when it was last refreshed)", style(label).bold()),
+        Some(false) => println!(
+            "{} {}",
+            style(label).bold(),
+            style("(not populated; run REFRESH MATERIALIZED VIEW to fill it)").yellow()
+        ),
+        None => println!("{}", style(label).bold()),
+    }
+    match view.sql {
+        Ok(sql) => println!("{}", format::reformat_sql(&sql)),
+        Err(reason) => println!("{}", style(format!("The definition could not be read: {}", reason)).yellow()),
+    }
+    Ok(())
+}
+
+/// Reads the next whitespace-separated argument from `input`, returning it
+/// together with the unread remainder. Single quotes are literal, double
+/// quotes allow backslash escapes, and outside quotes a backslash escapes the
+/// next character, so `"my file.csv"`, `'my file.csv'` and `my\ file.csv`
+/// all read as `my file.csv`.
+fn next_arg(input: &str) -> Result<Option<(String, &str)>> {
+    let input = input.trim_start();
+    if input.is_empty() {
+        return Ok(None);
+    }
+
+    let mut arg = String::new();
+    let mut quote = None;
+    let mut chars = input.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => return Ok(Some((arg, &input[index..]))),
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None | Some('"'), '\\') => match chars.next() {
+                Some((_, escaped)) => arg.push(escaped),
+                None => arg.push('\\'),
+            },
+            (_, c) => arg.push(c),
+        }
+    }
+
+    if let Some(q) = quote {
+        let raw = input.split_whitespace().next().unwrap_or(input);
+        return Err(QgoError::Usage(format!("Unterminated {} quote in argument {}", q, raw)).into());
+    }
+
+    Ok(Some((arg, "")))
+}
+
+/// Splits all of `input` into arguments with the quoting rules of `next_arg`.
+fn split_args(mut input: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    while let Some((arg, rest)) = next_arg(input)? {
+        args.push(arg);
+        input = rest;
+    }
+    Ok(args)
+}
+
+fn usage_error(usage: &str, problem: String) -> anyhow::Error {
+    QgoError::Usage(format!("{}\nUsage: {}", problem, usage)).into()
+}
+
+/// Asks how to show a result with more rows than `large_result_threshold`.
+/// Returns the display options to render with, or `None` to export instead.
+/// Without a terminal to ask on, the result is rendered as usual.
+fn confirm_large_result(result: &QueryResult, session: &Session<'_>) -> Option<DisplayOptions> {
+    let mut options = session.display.clone();
+    let shown = rows_shown(result, &options);
+    let threshold = match session.config.settings.large_result_threshold {
+        Some(threshold) if shown > threshold => threshold,
+        _ => return Some(options),
+    };
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Some(options);
+    }
+
+    let message = format!(
+        "Result has {} rows — display all, first {}, or export instead?",
+        format::group_thousands(result.rows.len()),
+        format::group_thousands(threshold)
+    );
+    let display_all = format!("Display all {} rows", format::group_thousands(shown));
+    let display_first = format!("Display the first {} rows", format::group_thousands(threshold));
+    match prompts::select(&message, &[&display_all, &display_first, "Export instead"]) {
+        Some(0) => {}
+        Some(2) => return None,
+        _ => options.max_rows = Some(threshold),
+    }
+    Some(options)
+}
+
+/// Exports an already fetched result to a file chosen at the prompt.
+fn export_interactively(result: &QueryResult, connection: &Connection, session: &Session<'_>) -> Result<()> {
+    let as_csv = match prompts::select("Export format", &["csv", "json"]) {
+        Some(index) => index == 0,
+        None => return Ok(()),
+    };
+    let default = if as_csv { "results.csv" } else { "results.json" };
+    let filename = match prompts::input("File name", default) {
+        Some(filename) => filename,
+        None => return Ok(()),
+    };
+
+    // There is no --allow-truncated here, so ask instead
+    let mut limit = ExportLimit::from_settings(&session.config.settings, false);
+    if let Some(ref mut limit) = limit {
+        if result.rows.len() > limit.max_rows {
+            limit.allow_truncated = prompts::confirm_default(
+                &format!(
+                    "The result has {} rows, more than max_export_rows ({}). Export the first {}?",
+                    result.rows.len(),
+                    limit.max_rows,
+                    limit.max_rows
+                ),
+                false,
+            );
+        }
+    }
+
+    let Some(path) = export_path(&filename, connection, session)? else {
+        return Ok(());
+    };
+    let export_format = if as_csv { &formatter::CSV } else { &formatter::JSON };
+    let csv = CsvOptions::from_settings(&session.config.settings);
+    export_result(result, export_format, &path, limit, csv)?;
+    println!("Results exported to: {}", path.display());
+    Ok(())
+}
+
+/// The options of an `export` command. They come before the format, and
+/// for `export fixed` may follow it too.
+struct ExportOptions {
+    background: bool,
+    allow_truncated: bool,
+    csv: CsvOptions,
+    widths: Option<String>,
+    spec: Option<String>,
+    terminator: Option<RecordEnd>,
+}
+
+impl ExportOptions {
+    fn new(settings: &Settings, background: bool) -> Self {
+        Self {
+            background,
+            allow_truncated: false,
+            csv: CsvOptions::from_settings(settings),
+            widths: None,
+            spec: None,
+            terminator: None,
+        }
+    }
+
+    /// Takes the options at the start of `args`, returning the rest.
+    fn parse<'a>(&mut self, args: &'a str, usage: &str) -> Result<&'a str> {
+        let mut args = args.trim_start();
+        while let Some((option, rest)) = args.split_once(char::is_whitespace).filter(|(arg, _)| arg.starts_with("--")) {
+            args = rest.trim_start();
+            match option {
+                "--background" => self.background = true,
+                "--allow-truncated" => self.allow_truncated = true,
+                "--bom" => self.csv.bom = true,
+                "--crlf" => self.csv.crlf = true,
+                "--widths" | "--spec" | "--terminator" => {
+                    let (value, rest) = next_arg(args)?
+                        .ok_or_else(|| usage_error(usage, format!("{} needs a value", option)))?;
+                    args = rest.trim_start();
+                    match option {
+                        "--widths" => self.widths = Some(value),
+                        "--spec" => self.spec = Some(value),
+                        _ => {
+                            self.terminator = Some(RecordEnd::parse(&value).ok_or_else(|| {
+                                usage_error(usage, format!("Unknown terminator '{}'; use lf, crlf or none", value))
+                            })?)
+                        }
+                    }
+                }
+                _ => return Err(usage_error(usage, format!("Unknown option '{}'", option))),
+            }
+        }
+        Ok(args)
+    }
+
+    fn is_fixed(&self) -> bool {
+        self.widths.is_some() || self.spec.is_some() || self.terminator.is_some()
+    }
+
+    /// The fixed-width layout from `--widths` or `--spec`, whichever was given.
+    fn fixed_spec(&self, usage: &str) -> Result<FixedSpec> {
+        let mut spec = match (&self.widths, &self.spec) {
+            (Some(widths), None) => FixedSpec::from_widths(widths)?,
+            (None, Some(path)) => FixedSpec::load(path)?,
+            (Some(_), Some(_)) => return Err(usage_error(usage, "Give either --widths or --spec, not both".to_string())),
+            (None, None) => return Err(usage_error(usage, "export fixed needs --widths or --spec".to_string())),
+        };
+        if let Some(terminator) = self.terminator {
+            spec.terminator = terminator;
+        }
+        Ok(spec)
+    }
+}
+
+/// Writes a result as fixed-width records: the last result without a
+/// query, otherwise the query's rows, fetched in full. The layout is
+/// checked against the columns before the file is touched.
+async fn fixed_export(
+    database: &mut Database,
+    session: &mut Session<'_>,
+    options: &ExportOptions,
+    filename: &str,
+    query: &str,
+    usage: &str,
+) -> Result<()> {
+    if options.background {
+        return Err(usage_error(usage, "Fixed-width exports can't run in the background".to_string()));
+    }
+    let spec = options.fixed_spec(usage)?;
+    let result = if query.is_empty() {
+        session
+            .current_result()
+            .ok_or_else(|| usage_error(usage, "Missing query, and there is no last result to export".to_string()))?
+            .into_owned()
+    } else if let Some(result) = metadata_result(database, query).await? {
+        result
+    } else {
+        let result = database.execute_query(query).await?;
+        print_warnings(&result.warnings);
+        result
+    };
+    let layout = spec.layout(&result.columns)?;
+
+    let limit = ExportLimit::from_settings(&session.config.settings, options.allow_truncated)
+        .filter(|limit| result.rows.len() > limit.max_rows);
+    if let Some(limit) = limit.filter(|limit| !limit.allow_truncated) {
+        return Err(limit.refusal());
+    }
+    let Some(path) = export_path(filename, database.get_connection(), session)? else {
+        return Ok(());
+    };
+    let rows = limit.map_or(result.rows.len(), |limit| limit.max_rows);
+    let truncated = format::export_fixed(&result, &spec, &layout, rows, &path.to_string_lossy())?;
+    if let Some(limit) = limit {
+        eprintln!("{}", style(limit.note()).yellow());
+    }
+    if !truncated.is_empty() {
+        const SHOWN: usize = 20;
+        let mut rows: Vec<String> = truncated.iter().take(SHOWN).map(ToString::to_string).collect();
+        if truncated.len() > SHOWN {
+            rows.push(format!("and {} more", truncated.len() - SHOWN));
+        }
+        eprintln!(
+            "{}",
+            style(format!(
+                "{} {} had values cut to fit their fields: {}",
+                truncated.len(),
+                if truncated.len() == 1 { "row" } else { "rows" },
+                rows.join(", ")
+            ))
+            .yellow()
+        );
+    }
+    report_replaced(result.rows[..rows].iter().map(|row| database::replaced_chars(row)).sum());
+    println!("Results exported to: {}", path.display());
+    Ok(())
+}
+
+/// The format the `export_format` setting names, for exports whose file
+/// name doesn't say.
+fn default_export_format(settings: &Settings) -> Result<&'static Registration> {
+    formatter::find(&settings.export_format).ok_or_else(|| {
+        QgoError::Usage(format!(
+            "The export_format setting names no known format ('{}'); \\formats lists them",
+            settings.export_format
+        ))
+        .into()
+    })
+}
+
+fn streaming_formats() -> String {
+    let names: Vec<&str> = formatter::FORMATTERS
+        .iter()
+        .filter(|format| format.streams)
+        .map(|format| format.name)
+        .collect();
+    names.join(", ")
+}
+
+/// `\formats`: every registered format, with the extensions that pick it.
+fn show_formats(settings: &Settings) {
+    for format in formatter::FORMATTERS {
+        let extensions: Vec<String> = format.extensions.iter().map(|extension| format!(".{}", extension)).collect();
+        let default = if format.name.eq_ignore_ascii_case(&settings.export_format) { " (export_format)" } else { "" };
+        println!(
+            "  {:<8} {:<12} {}{}",
+            format.name,
+            extensions.join(" "),
+            format.description,
+            style(default).dim()
+        );
+    }
+}
+
+/// The `max_export_rows` setting as it applies to one export.
+#[derive(Clone, Copy)]
+struct ExportLimit {
+    max_rows: usize,
+    /// Write the first `max_rows` rows of a larger result instead of refusing.
+    allow_truncated: bool,
+}
+
+impl ExportLimit {
+    fn from_settings(settings: &Settings, allow_truncated: bool) -> Option<Self> {
+        settings.max_export_rows.map(|max_rows| ExportLimit {
+            max_rows,
+            allow_truncated,
+        })
+    }
+
+    fn refusal(&self) -> anyhow::Error {
+        QgoError::InvalidQuery(format!(
+            "The query returns more than {} rows (max_export_rows); add a LIMIT, or use export --allow-truncated to write the first {}",
+            self.max_rows, self.max_rows
+        ))
+        .into()
+    }
+
+    fn note(&self) -> String {
+        format!("export truncated at {} rows (set max_export_rows to change)", self.max_rows)
+    }
+}
+
+/// Writes an already fetched result, refusing or truncating one with more
+/// rows than `limit` allows.
+fn export_result(
+    result: &QueryResult,
+    export_format: &Registration,
+    path: &Path,
+    limit: Option<ExportLimit>,
+    csv: CsvOptions,
+) -> Result<()> {
+    let file_path = path.to_string_lossy();
+    let Some(limit) = limit.filter(|limit| result.rows.len() > limit.max_rows) else {
+        format::export(result, export_format, &file_path, csv, None)?;
+        report_replaced(result.rows.iter().map(|row| database::replaced_chars(row)).sum());
+        return Ok(());
+    };
+    if !limit.allow_truncated {
+        return Err(limit.refusal());
+    }
+
+    let truncated = QueryResult {
+        columns: result.columns.clone(),
+        column_types: result.column_types.clone(),
+        rows: result.rows[..limit.max_rows].to_vec(),
+        row_count: limit.max_rows,
+        warnings: Vec::new(),
+    };
+    format::export(&truncated, export_format, &file_path, csv, Some(&limit.note()))?;
+    eprintln!("{}", style(limit.note()).yellow());
+    report_replaced(truncated.rows.iter().map(|row| database::replaced_chars(row)).sum());
+    Ok(())
+}
+
+/// Writes the rows of `query` to `writer` as they arrive, with progress on
+/// stderr. Ctrl+C stops the export, keeping the rows written so far.
+/// Fetching stops one row past `limit`; unless truncation is allowed the
+/// file is then removed and the export refused.
+async fn stream_export(
+    database: &mut Database,
+    query: &str,
+    mut writer: ExportWriter,
+    path: &Path,
+    limit: Option<ExportLimit>,
+) -> Result<()> {
+    // Postgres types the driver can't decode are streamed as text
+    let text_query = database.text_fallback_query(query).await?.map(|(text_query, warning)| {
+        output::warn(warning);
+        text_query
+    });
+    let (mut stream, first) = start_stream(database.stream_query(text_query.as_deref().unwrap_or(query)).await?).await;
+    let mut first = Some(first);
+    let mut progress = Progress::start();
+    let mut rows: u64 = 0;
+    let mut replaced = 0;
+    let mut truncated = None;
+    let claim = interrupt::claim();
+    let interrupted = tokio::signal::ctrl_c();
+    tokio::pin!(interrupted);
+
+    loop {
+        tokio::select! {
+            row = async {
+                match first.take() {
+                    Some(row) => row,
+                    None => stream.next_row().await,
+                }
+            } => {
+                let row = match row {
+                    Ok(Some(row)) => row,
+                    Ok(None) => break,
+                    Err(e) => {
+                        progress.finish();
+                        writer.finish()?;
+                        report_incomplete("failed", rows, path);
+                        return Err(e);
+                    }
+                };
+                if let Some(limit) = limit.filter(|limit| rows >= limit.max_rows as u64) {
+                    truncated = Some(limit);
+                    break;
+                }
+                writer.write_row(stream.columns(), &row)?;
+                print_warnings(&stream.take_warnings());
+                replaced += database::replaced_chars(&row);
+                rows += 1;
+                progress.update(rows, writer.bytes_written());
+            }
+            _ = &mut interrupted => {
+                claim.consume();
+                progress.finish();
+                writer.finish()?;
+                report_incomplete("interrupted", rows, path);
+                return Ok(());
+            }
+        }
+    }
+
+    drop(stream);
+    progress.finish();
+    if let Some(limit) = truncated {
+        if !limit.allow_truncated {
+            writer.finish()?;
+            let _ = std::fs::remove_file(path);
+            return Err(limit.refusal());
+        }
+        writer.write_comment(&limit.note())?;
+        eprintln!("{}", style(limit.note()).yellow());
+    }
+    let estimated_size = writer.bytes_written();
+    writer.finish()?;
+    let size = std::fs::metadata(path).map_or(estimated_size, |metadata| metadata.len());
+    println!(
+        "Exported {} rows ({}) to {} in {}",
+        rows,
+        progress::format_bytes(size),
+        path.display(),
+        progress::format_duration(progress.elapsed())
+    );
+    report_replaced(replaced);
+    Ok(())
+}
+
+/// Writes a CSV export through Postgres `COPY ... TO STDOUT`, which skips
+/// decoding rows on the client. Returns `false`, leaving the export to the
+/// row-by-row path, when COPY doesn't apply or the server refused it (with
+/// a notice saying so) or when lines must end in `\r\n`, which COPY can't
+/// write.
+async fn copy_export(
+    database: &mut Database,
+    query: &str,
+    path: &Path,
+    limit: Option<ExportLimit>,
+    csv: CsvOptions,
+) -> Result<bool> {
+    use std::io::Write;
+
+    if csv.crlf {
+        return Ok(false);
+    }
+    let file_path = path.to_string_lossy();
+    let mut out = None;
+    let mut ends = CsvRecordEnds::default();
+    // The header is the first record
+    let mut records: u64 = 0;
+    let mut bytes: u64 = 0;
+    let mut truncated = None;
+    let mut progress = Progress::start();
+    let claim = interrupt::claim();
+
+    let outcome = tokio::select! {
+        outcome = database.copy_csv(query, |chunk| {
+            let out = match out {
+                Some(ref mut out) => out,
+                None => out.insert(std::io::BufWriter::new(csv.create(&file_path)?)),
+            };
+            let mut keep = chunk.len();
+            match limit {
+                Some(limit) => {
+                    // Data after the last row allowed means there were more
+                    let wanted = limit.max_rows as u64 + 1;
+                    if records == wanted {
+                        keep = 0;
+                        truncated = Some(limit);
+                    }
+                    for end in ends.find(chunk) {
+                        if records == wanted {
+                            break;
+                        }
+                        records += 1;
+                        if records == wanted {
+                            keep = end;
+                            if end < chunk.len() {
+                                truncated = Some(limit);
+                            }
+                        }
+                    }
+                }
+                None => records += ends.find(chunk).len() as u64,
+            }
+            out.write_all(&chunk[..keep])?;
+            bytes += keep as u64;
+            progress.update(records.saturating_sub(1), bytes);
+            Ok(truncated.is_none())
+        }) => outcome,
+        _ = tokio::signal::ctrl_c() => {
+            claim.consume();
+            progress.finish();
+            if let Some(mut out) = out {
+                out.flush()?;
+            }
+            report_incomplete("interrupted", records.saturating_sub(1), path);
+            return Ok(true);
+        }
+    };
+    progress.finish();
+    let rows = records.saturating_sub(1);
+    let outcome = match outcome {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            if let Some(mut out) = out {
+                out.flush()?;
+                report_incomplete("failed", rows, path);
+            }
+            return Err(e);
+        }
+    };
+    match outcome {
+        CopyOutcome::Copied => {}
+        CopyOutcome::Unavailable => return Ok(false),
+        CopyOutcome::Refused(reason) => {
+            eprintln!(
+                "{}",
+                style(format!("COPY isn't available ({}); exporting row by row instead.", reason)).yellow()
+            );
+            return Ok(false);
+        }
+    }
+    match out {
+        Some(mut out) => out.flush()?,
+        None => drop(csv.create(&file_path)?),
+    }
+    if let Some(limit) = truncated {
+        if !limit.allow_truncated {
+            let _ = std::fs::remove_file(path);
+            return Err(limit.refusal());
+        }
+        format::append_csv_comment(&file_path, &limit.note(), csv)?;
+        eprintln!("{}", style(limit.note()).yellow());
+    }
+    let size = std::fs::metadata(path).map_or(bytes, |metadata| metadata.len());
+    println!(
+        "Exported {} rows ({}) to {} in {}",
+        rows,
+        progress::format_bytes(size),
+        path.display(),
+        progress::format_duration(progress.elapsed())
+    );
+    Ok(true)
+}
+
+/// Starts exporting `query` to `path` as a background job on a connection
+/// of its own, leaving the prompt free.
+async fn start_background_export(
+    database: &Database,
+    session: &mut Session<'_>,
+    query: &str,
+    export_format: &Registration,
+    path: PathBuf,
+    limit: Option<ExportLimit>,
+    csv: CsvOptions,
+) -> Result<()> {
+    let detached = database.detach().await?;
+    let Some(writer) = ExportWriter::create(export_format, &path.to_string_lossy(), csv)? else {
+        return Ok(());
+    };
+    let description = format!("{} ← {}", path.display(), script::preview(query));
+    let query = query.to_string();
+    let file = path.clone();
+    let id = session.jobs.spawn(description, file, move |progress| {
+        background_export(detached, query, writer, path, limit, progress)
+    });
+    println!("Started job {}; \\jobs shows how far it has got.", id);
+    Ok(())
+}
+
+/// [`stream_export`] for a background job: progress goes to `progress`
+/// rather than the terminal, and the summary is returned for the job's
+/// notice.
+async fn background_export(
+    mut database: Database,
+    query: String,
+    mut writer: ExportWriter,
+    path: PathBuf,
+    limit: Option<ExportLimit>,
+    progress: Arc<JobProgress>,
+) -> Result<String> {
+    let started = Instant::now();
+    let text_query = database.text_fallback_query(&query).await?.map(|(text_query, warning)| {
+        output::warn(warning);
+        text_query
+    });
+    let (mut stream, first) = start_stream(database.stream_query(text_query.as_deref().unwrap_or(&query)).await?).await;
+    let mut next = first;
+    let mut rows: u64 = 0;
+    let mut truncated = None;
+    loop {
+        let row = match next {
+            Ok(Some(row)) => row,
+            Ok(None) => break,
+            Err(e) => {
+                writer.finish()?;
+                return Err(e.context(format!("{} is incomplete after {} rows", path.display(), rows)));
+            }
+        };
+        if let Some(limit) = limit.filter(|limit| rows >= limit.max_rows as u64) {
+            truncated = Some(limit);
+            break;
+        }
+        writer.write_row(stream.columns(), &row)?;
+        print_warnings(&stream.take_warnings());
+        rows += 1;
+        progress.update(rows, writer.bytes_written());
+        next = stream.next_row().await;
+    }
+    drop(stream);
+
+    if let Some(limit) = truncated {
+        if !limit.allow_truncated {
+            writer.finish()?;
+            let _ = std::fs::remove_file(&path);
+            return Err(limit.refusal());
+        }
+        writer.write_comment(&limit.note())?;
+    }
+    writer.finish()?;
+    let size = std::fs::metadata(&path).map_or(0, |metadata| metadata.len());
+    Ok(format!(
+        "Exported {} rows ({}) to {} in {}{}",
+        rows,
+        progress::format_bytes(size),
+        path.display(),
+        progress::format_duration(started.elapsed()),
+        if truncated.is_some() { " (truncated at max_export_rows)" } else { "" }
+    ))
+}
+
+/// Tells the user that reconnecting discarded their open transaction,
+/// given the count of uncommitted statements it had.
+fn report_rollback(rolled_back: Option<usize>) {
+    if let Some(writes) = rolled_back {
+        output::warn(format!("Rolled back the open transaction ({} uncommitted statements).", writes));
+    }
+}
+
+/// Shows what [`QueryResult::warnings`] says about how values were read.
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        output::warn(warning);
+    }
+}
+
+/// `stream` with its first row read.
+async fn start_stream(mut stream: RowStream<'_>) -> (RowStream<'_>, Result<Option<Vec<String>>>) {
+    let first = stream.next_row().await;
+    (stream, first)
+}
+
+/// Notes how many characters of an export were replaced because the
+/// database sent bytes that aren't valid UTF-8.
+fn report_replaced(count: usize) {
+    if count > 0 {
+        eprintln!(
+            "{}",
+            style(format!(
+                "{} characters were not valid UTF-8 and were written as U+FFFD (�); check the column's character set",
+                count
+            ))
+            .yellow()
+        );
+    }
+}
+
+fn report_incomplete(what: &str, rows: u64, path: &Path) {
+    eprintln!(
+        "{}",
+        style(format!(
+            "Export {} after {} rows; {} is incomplete.",
+            what,
+            rows,
+            path.display()
+        ))
+        .yellow()
+    );
+}
+
+/// Works out where an export goes: `{ts}`, `{date}` and `{connection}` in
+/// `filename` are expanded, and a relative name is placed in the
+/// `export_directory` setting. When the file already exists the session's
+/// [`ExistingFile`] policy decides; `None` means the export was cancelled or
+/// skipped. The returned path is absolute.
+fn export_path(filename: &str, connection: &Connection, session: &Session<'_>) -> Result<Option<PathBuf>> {
+    let now = chrono::Local::now();
+    let connection_name: String = connection
+        .name
+        .chars()
+        .map(|c| if std::path::is_separator(c) { '_' } else { c })
+        .collect();
+    let expanded = filename
+        .replace("{ts}", &now.format("%Y%m%d_%H%M%S").to_string())
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{connection}", &connection_name);
+
+    let mut path = PathBuf::from(expanded);
+    if path.is_relative() {
+        if let Some(ref directory) = session.config.settings.export_directory {
+            let directory = match directory.strip_prefix("~/").zip(dirs::home_dir()) {
+                Some((rest, home)) => home.join(rest),
+                None => PathBuf::from(directory),
+            };
+            std::fs::create_dir_all(&directory)?;
+            path = directory.join(path);
+        }
+    }
+    let path = std::path::absolute(&path)?;
+
+    if !path.exists() {
+        return Ok(Some(path));
+    }
+    match session.existing_files {
+        ExistingFile::Overwrite => Ok(Some(path)),
+        ExistingFile::Keep => {
+            println!("{} already exists; skipping the export.", path.display());
+            Ok(None)
+        }
+        ExistingFile::Ask if !std::io::stdin().is_terminal() => Err(QgoError::Usage(format!(
+            "{} already exists; pass --overwrite to replace it or --no-clobber to skip",
+            path.display()
+        ))
+        .into()),
+        ExistingFile::Ask => {
+            let alternative = unused_path(&path);
+            let rename = format!("Save as {}", alternative.display());
+            match prompts::select(&format!("{} already exists", path.display()), &["Overwrite", &rename, "Cancel"]) {
+                Some(0) => Ok(Some(path)),
+                Some(1) => Ok(Some(alternative)),
+                _ => Ok(None),
+            }
+        }
+    }
+}
+
+/// The first of `name-1.ext`, `name-2.ext`, ... that does not exist yet.
+fn unused_path(path: &std::path::Path) -> PathBuf {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = path.extension().map(|extension| format!(".{}", extension.to_string_lossy()));
+    (1..)
+        .map(|n| path.with_file_name(format!("{}-{}{}", stem, n, extension.as_deref().unwrap_or_default())))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Persists a connection that was opened without saving it.
+async fn save_connection(database: &mut Database, config: &mut Config) -> Result<()> {
+    let connection = database.get_connection();
+    if connection.project {
+        return Err(QgoError::Usage(format!(
+            "'{}' comes from the project's {} and is not saved to the global config",
+            connection.name,
+            project::FILE_NAME
+        ))
+        .into());
+    }
+    if !connection.unsaved {
+        println!("Connection '{}' is already saved.", connection.name);
+        return Ok(());
+    }
+    if config.get_saved_connection_by_name(&connection.name).is_some() {
+        return Err(QgoError::Usage(format!(
+            "A saved connection named '{}' already exists; it was not overwritten",
+            connection.name
+        ))
+        .into());
+    }
+
+    if let Some(existing) = config.find_duplicate(connection) {
+        println!(
+            "{}",
+            style(format!("'{}' already points at {}.", existing.name, existing.target())).yellow()
+        );
+        if std::io::stdin().is_terminal() && !prompts::confirm("Save it as another connection anyway?") {
+            return Ok(());
+        }
+    }
+
+    let mut saved = connection.clone();
+    saved.unsaved = false;
+    config.add_connection(saved);
+    config.save().await?;
+    database.mark_saved();
+
+    println!("{}", style(format!("Connection '{}' saved.", database.get_connection().name)).green());
+    Ok(())
+}
+
+/// `\set` lists session variables; `\set <name> <value>` changes one.
+async fn set_variable(session: &mut Session<'_>, args: &[String]) -> Result<()> {
+    const USAGE: &str = "\\set [ON_ERROR_STOP on|off] | \\set save";
+    match args {
+        [] => println!("ON_ERROR_STOP = {}", if session.on_error == OnError::Stop { "on" } else { "off" }),
+        [save] if save.eq_ignore_ascii_case("save") => {
+            let current = SessionOptions::current(&session.display, session.on_error);
+            let changes = session.saved_options.changes(&current);
+            if changes.is_empty() {
+                println!("Session options already match the saved settings.");
+            } else {
+                print_option_changes(&changes);
+                save_options(session, current).await?;
+                println!("Saved to config.");
+            }
+        }
+        [name, ..] if !name.eq_ignore_ascii_case("on_error_stop") => {
+            return Err(usage_error(USAGE, format!("Unknown variable '{}'", name)))
+        }
+        [_, value] => {
+            session.on_error = match value.to_lowercase().as_str() {
+                "on" => OnError::Stop,
+                "off" => OnError::Continue,
+                _ => return Err(usage_error(USAGE, format!("Invalid value '{}' for ON_ERROR_STOP", value))),
+            };
+        }
+        [_] => return Err(usage_error(USAGE, "Missing value".to_string())),
+        [_, _, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+    }
+    Ok(())
+}
+
+/// `\into <var> <query>`: runs the query and stores its single value in
+/// `var`. For a larger result the first column of the first row is kept,
+/// with a warning; an empty result stores nothing.
+async fn store_into(args: &str, database: &mut Database, session: &mut Session<'_>) -> Result<()> {
+    const USAGE: &str = "\\into <var> <query>";
+    let Some((name, query)) = next_arg(args)? else {
+        return Err(usage_error(USAGE, "Missing variable name".to_string()));
+    };
+    variables::check_name(&name)?;
+    let query = query.trim();
+    if query.is_empty() {
+        return Err(usage_error(USAGE, "Missing query".to_string()));
+    }
+    let query = session.variables.substitute(&database.get_connection().db_type, query)?;
+
+    let started = Instant::now();
+    let outcome = database.execute_query(&query).await;
+    if let Some(transcript) = session.transcript.as_mut() {
+        match &outcome {
+            Ok(result) => transcript.record_result(&query, Some(started.elapsed()), result, &session.display),
+            Err(e) => transcript.record_error(&query, started.elapsed(), e),
+        }
+    }
+    let result = outcome?;
+    print_warnings(&result.warnings);
+
+    let Some(value) = result.rows.first().and_then(|row| row.first()) else {
+        return Err(QgoError::InvalidQuery(format!("The query returned no rows; :{} was not set", name)).into());
+    };
+    if result.rows.len() > 1 || result.columns.len() > 1 {
+        eprintln!(
+            "{}",
+            style(format!(
+                "The query returned {} row(s) of {} column(s); keeping the first value.",
+                result.rows.len(),
+                result.columns.len()
+            ))
+            .yellow()
+        );
+    }
+    println!("{} = {}", name, value);
+    session.variables.set(&name, value.clone(), &query);
+    Ok(())
+}
+
+/// `\params <file>`: sets a variable for each top-level key of a JSON or
+/// TOML file, as `--params` does for scripts.
+fn load_params(session: &mut Session<'_>, args: &[String]) -> Result<()> {
+    const USAGE: &str = "\\params <file.json|file.toml>";
+    let path = match args {
+        [path] => path,
+        [] => return Err(usage_error(USAGE, "Missing file name".to_string())),
+        [_, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+    };
+    let count = session.variables.load_params(Path::new(path))?;
+    println!("Set {} variables from {}; list them with \\vars.", count, path);
+    Ok(())
+}
+
+/// `\vars`: lists the stored variables with the query each came from.
+fn show_variables(variables: &Variables) {
+    if variables.is_empty() {
+        println!("No variables set; store one with \\into <var> <query>.");
+        return;
+    }
+    let rows = variables
+        .iter()
+        .map(|(name, variable)| vec![name.clone(), variable.value.clone(), variable.origin.clone()])
+        .collect();
+    let result = QueryResult::from_rows(&["Variable", "Value", "Query"], rows);
+    format::display_table(&result, &DisplayOptions::default());
+}
+
+/// `\record [<file>|off]`: starts recording to a transcript (replacing one
+/// already being recorded), stops, or reports where the session is recorded.
+fn record_command(session: &mut Session<'_>, args: &[String], connection: &Connection) -> Result<()> {
+    const USAGE: &str = "\\record [<file>|off]";
+    match args {
+        [] => match &session.transcript {
+            Some(transcript) => println!("Recording to {}.", transcript.path().display()),
+            None => println!("Not recording."),
+        },
+        [off] if off.eq_ignore_ascii_case("off") => match session.transcript.take() {
+            Some(transcript) => println!("Stopped recording to {}.", transcript.path().display()),
+            None => println!("Not recording."),
+        },
+        [path] => start_recording(session, Path::new(path), connection)?,
+        [_, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+    }
+    Ok(())
+}
+
+fn start_recording(session: &mut Session<'_>, path: &Path, connection: &Connection) -> Result<()> {
+    let transcript = Transcript::start(path, connection)
+        .map_err(|e| QgoError::Usage(format!("Cannot record to {}: {}", path.display(), e)))?;
+    output::info(format!("Recording to {}.", transcript.path().display()));
+    session.transcript = Some(transcript);
+    Ok(())
+}
+
+fn print_option_changes(changes: &[(&str, String, String)]) {
+    for (name, saved, current) in changes {
+        println!("  {}: {} → {}", name, saved, current);
+    }
+}
+
+/// Writes the options that differ from the saved settings into the config.
+async fn save_options(session: &mut Session<'_>, current: SessionOptions) -> Result<()> {
+    let settings = &mut session.config.settings;
+    let saved = &session.saved_options;
+    if saved.show_types != current.show_types {
+        settings.show_types = current.show_types;
+    }
+    if saved.timezone != current.timezone {
+        settings.timezone = current.timezone.clone();
+    }
+    if saved.on_error != current.on_error {
+        settings.on_error = current.on_error;
+    }
+    session.config.save().await?;
+    session.saved_options = current;
+    Ok(())
+}
+
+/// `\replica` and `\primary`: moves the session between the endpoints of
+/// its connection's topology. Replicas are tried in order until one answers.
+async fn switch_endpoint(database: &mut Database, session: &mut Session<'_>, to_replica: bool) -> Result<()> {
+    let Some(topology) = database.topology().cloned() else {
+        return Err(QgoError::Usage(format!(
+            "'{}' has no replica; set replica_of on the replica's connection, or replica_hosts on this one",
+            database.get_connection().name
+        ))
+        .into());
+    };
+    if database.get_connection().replica == to_replica {
+        println!("Already on the {} '{}'.", endpoint_role(database.get_connection()), database.get_connection().name);
+        return Ok(());
+    }
+
+    let endpoints = if to_replica { topology.replicas } else { vec![topology.primary] };
+    let mut failure = None;
+    for endpoint in endpoints {
+        let endpoint = connection_manager::prepare_connection(&session.config.settings, endpoint)?;
+        match database.switch_endpoint(endpoint.clone(), session.timeout).await {
+            Ok(rolled_back) => {
+                report_rollback(rolled_back);
+                // Cached results may differ between endpoints while a replica lags
+                session.cache.clear();
+                println!("Now on the {} {}.", endpoint_role(&endpoint), endpoint.display_name());
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("{}", style(format!("Could not reach {}: {}", endpoint.display_name(), e)).yellow());
+                failure = Some(e);
+            }
+        }
+    }
+    Err(failure.unwrap_or_else(|| QgoError::ConnectionCheck("no endpoint to switch to".to_string()).into()))
+}
+
+fn endpoint_role(connection: &Connection) -> &'static str {
+    if connection.replica {
+        "replica"
+    } else {
+        "primary"
+    }
+}
+
+/// The `\conninfo` lines for a primary and its replicas, marking the one
+/// the session is on.
+fn show_topology(topology: &Topology, active: &Connection) {
+    let endpoints = std::iter::once(&topology.primary).chain(&topology.replicas);
+    for (index, endpoint) in endpoints.enumerate() {
+        let label = if index == 0 { "Topology:" } else { "" };
+        let is_active = endpoint.id == active.id && endpoint.host == active.host && endpoint.port == active.port;
+        println!(
+            "{:<18}{:<8} {}{}",
+            label,
+            endpoint_role(endpoint),
+            endpoint.display_name(),
+            if is_active { " ← active" } else { "" }
+        );
+    }
+}
+
+/// `\settings`: changes settings through the menu, saves them, and applies
+/// what can be applied to the running session.
+async fn edit_settings(database: &mut Database, session: &mut Session<'_>) -> Result<()> {
+    if session.config.managed {
+        settings::print(&session.config.settings);
+        println!("{}", style("This configuration is managed and can't be changed from qgo.").yellow());
+        return Ok(());
+    }
+    let before = session.config.settings.clone();
+    if !settings::edit(&mut session.config.settings)? {
+        return Ok(());
+    }
+    session.config.save().await?;
+    println!("{}", style("Settings saved successfully!").green());
+    apply_settings(&before, database, session).await
+}
+
+/// Brings the session in line with settings changed from `before`. Options
+/// changed during the session are only replaced when their setting changed.
+async fn apply_settings(before: &Settings, database: &mut Database, session: &mut Session<'_>) -> Result<()> {
+    let settings = session.config.settings.clone();
+    let display = &mut session.display;
+    display.max_rows = settings.max_rows_display;
+    display.date_format = settings.date_format.clone();
+    display.scalar_display = settings.scalar_display;
+    display.max_column_width = settings.max_column_width;
+    display.force_grid = settings.force_grid;
+    if settings.show_types != before.show_types {
+        display.show_types = settings.show_types;
+        session.saved_options.show_types = settings.show_types;
+    }
+    if settings.on_error != before.on_error {
+        session.on_error = settings.on_error;
+        session.saved_options.on_error = settings.on_error;
+    }
+    if settings.query_timeout_seconds != before.query_timeout_seconds {
+        session.timeout = Duration::from_secs(settings.query_timeout_seconds);
+    }
+    if settings.result_cache_entries != before.result_cache_entries
+        || settings.result_cache_ttl_secs != before.result_cache_ttl_secs
+    {
+        session.cache = ResultCache::new(
+            settings.result_cache_entries,
+            Duration::from_secs(settings.result_cache_ttl_secs),
+        );
+    }
+    database.set_max_result_memory(settings.max_result_memory_mb);
+    if settings.keepalive_interval_secs != before.keepalive_interval_secs {
+        match settings.keepalive_interval_secs.filter(|secs| *secs > 0) {
+            Some(interval) => database.start_keepalive(Duration::from_secs(interval)),
+            None => database.stop_keepalive(),
+        }
+    }
+    if settings.timezone != before.timezone && database.get_connection().timezone.is_none() {
+        // Clearing the setting leaves the server's zone as it is until reconnecting
+        if let Some(ref zone) = settings.timezone {
+            let zone = SessionZone::parse(zone)?;
+            report_rollback(database.set_timezone(zone.clone(), session.timeout).await?);
+            session.display.timezone = Some(zone);
+        }
+        session.saved_options.timezone = settings.timezone.clone();
+    }
+
+    let next_session: Vec<&str> = [
+        ("color_output", settings.color_output != before.color_output),
+        ("auto_completion", settings.auto_completion != before.auto_completion),
+        ("history_size", settings.history_size != before.history_size),
+        ("application_name", settings.application_name != before.application_name),
+        ("show_banner", settings.show_banner != before.show_banner),
+        ("timezone", settings.timezone.is_none() && before.timezone.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(name, changed)| changed.then_some(name))
+    .collect();
+    if !next_session.is_empty() {
+        println!(
+            "{}",
+            style(format!("Takes effect in the next session: {}.", next_session.join(", "))).dim()
+        );
+    }
+    Ok(())
+}
+
+/// `\pop`: brings back the settings the most recent `\push` kept,
+/// saving the configured ones again if `\settings` changed them since.
+async fn pop_settings(database: &mut Database, session: &mut Session<'_>) -> Result<()> {
+    let Some(snapshot) = session.settings_stack.pop() else {
+        println!("The settings stack is empty; \\push saves the current settings onto it.");
+        return Ok(());
+    };
+    let before = session.snapshot();
+    let left = session.settings_stack.depth();
+    if snapshot.changes(&before).is_empty() {
+        println!("Popped settings that match the current ones ({} left on the stack).", left);
+        return Ok(());
+    }
+
+    if serde_json::to_value(&snapshot.settings)? != serde_json::to_value(&session.config.settings)? {
+        let before = std::mem::replace(&mut session.config.settings, snapshot.settings.clone());
+        session.config.save().await?;
+        apply_settings(&before, database, session).await?;
+    }
+    let mut display = snapshot.display;
+    let zone = |zone: &Option<SessionZone>| zone.as_ref().map(|zone| zone.to_string());
+    if zone(&display.timezone) != zone(&session.display.timezone) {
+        match display.timezone {
+            Some(ref zone) => report_rollback(database.set_timezone(zone.clone(), session.timeout).await?),
+            // Going back to the server's zone needs a new connection
+            None => {
+                display.timezone = session.display.timezone.clone();
+                println!("{}", style("The time zone stays as it is until the next session.").dim());
+            }
+        }
+    }
+    session.display = display;
+    session.timeout = snapshot.timeout;
+    session.on_error = snapshot.on_error;
+    output::set_quiet(snapshot.quiet);
+
+    println!("Restored settings ({} left on the stack):", left);
+    print_option_changes(&before.changes(&session.snapshot()));
+    Ok(())
+}
+
+/// `\pushed`: the stack depth, and how each pushed snapshot differs from
+/// the settings now, most recent first.
+fn show_pushed(session: &Session<'_>) {
+    let depth = session.settings_stack.depth();
+    if depth == 0 {
+        println!("The settings stack is empty.");
+        return;
+    }
+    println!("{} on the settings stack, most recent first:", depth);
+    let current = session.snapshot();
+    for (level, snapshot) in (1..=depth).rev().zip(session.settings_stack.iter()) {
+        let changes = current.changes(snapshot);
+        if changes.is_empty() {
+            println!("  {}. (same as now)", level);
+            continue;
+        }
+        println!("  {}.", level);
+        for (name, now, kept) in changes {
+            println!("     {}: {} → {}", name, now, kept);
+        }
+    }
+}
+
+/// At the end of an interactive session, offers once to save options that
+/// were changed during it. Never prompts without a terminal.
+async fn offer_to_save_options(session: &mut Session<'_>) {
+    if !session.config.settings.ask_to_save_session || !std::io::stdin().is_terminal() || config::is_ephemeral() {
+        return;
+    }
+    let current = SessionOptions::current(&session.display, session.on_error);
+    let changes = session.saved_options.changes(&current);
+    if changes.is_empty() {
+        return;
+    }
+
+    println!("Session options differ from the saved settings:");
+    print_option_changes(&changes);
+    let choice = prompts::select(
+        "Save them to the config?",
+        &["Save", "Don't save", "Don't save, and don't ask again"],
+    );
+    let outcome = match choice {
+        Some(0) => save_options(session, current).await,
+        Some(2) => {
+            session.config.settings.ask_to_save_session = false;
+            session.config.save().await
+        }
+        _ => Ok(()),
+    };
+    if let Err(e) = outcome {
+        eprintln!("{}", style(format!("Could not save settings: {}", e)).red());
+    }
+}
+
+/// `\recent [n]` lists the last n queries; `\recent full <n>` prints entry n in full.
+fn show_recent(connection: &Connection, args: &[String]) -> Result<()> {
+    const USAGE: &str = "\\recent [n] | \\recent full <n>";
+    let parse_count = |arg: &String| {
+        arg.parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| usage_error(USAGE, format!("Invalid count '{}'", arg)))
+    };
+
+    match args {
+        [full, n] if full.eq_ignore_ascii_case("full") => {
+            let n = parse_count(n)?;
+            match recent::load(connection, n).into_iter().nth(n - 1) {
+                Some(entry) => {
+                    println!("{}", entry.query);
+                    if let Some(error) = entry.error {
+                        println!("{}", style(format!("Error: {}", error)).red());
+                    }
+                }
+                None => println!("No recent query #{}.", n),
+            }
+        }
+        [] | [_] => {
+            let limit = args.first().map(parse_count).transpose()?.unwrap_or(20);
+            let entries = recent::load(connection, limit);
+            if entries.is_empty() {
+                println!("No recent queries for this connection.");
+            } else {
+                format::display_table(&recent::to_result(&entries), &DisplayOptions::default());
+            }
+        }
+        [_, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+    }
+    Ok(())
+}
+
+/// `\slow [n]` lists the n slowest queries of this session; `\slow all [n]`
+/// adds the connection's recorded history.
+fn show_slowest(connection: &Connection, log: &SessionLog, args: &[String]) -> Result<()> {
+    const USAGE: &str = "\\slow [all] [n]";
+    let (all, count) = match args {
+        [all, rest @ ..] if all.eq_ignore_ascii_case("all") => (true, rest),
+        rest => (false, rest),
+    };
+    let n = match count {
+        [] => 10,
+        [n] => n
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| usage_error(USAGE, format!("Invalid count '{}'", n)))?,
+        [_, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+    };
+
+    let entries = recent::slowest(connection, log, all, n);
+    if entries.is_empty() {
+        println!("No queries have run {} yet.", if all { "on this connection" } else { "this session" });
+    } else {
+        format::display_table(&recent::to_result(&entries), &DisplayOptions::default());
+    }
+    Ok(())
+}
+
+/// `\cols a,b,c` shows only the named (or 1-based numbered) columns of the
+/// last result, in that order; `\cols *` shows them all again. The
+/// projection stays in effect for commands that read the last result.
+fn select_columns(session: &mut Session<'_>, spec: &str) -> Result<()> {
+    const USAGE: &str = "\\cols <column>[,<column>...] | \\cols *";
+    let result = session
+        .last_result
+        .as_ref()
+        .ok_or_else(|| usage_error(USAGE, "No result to select columns from yet".to_string()))?;
+
+    if spec.is_empty() {
+        return Err(usage_error(
+            USAGE,
+            format!("Missing column list; columns are: {}", result.columns.join(", ")),
+        ));
+    }
+
+    if spec == "*" {
+        session.projection = None;
+    } else {
+        let mut indexes = Vec::new();
+        for name in spec.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let index = match name.parse::<usize>() {
+                Ok(n) => n.checked_sub(1).filter(|index| *index < result.columns.len()),
+                Err(_) => result.columns.iter().position(|column| column.eq_ignore_ascii_case(name)),
+            };
+            match index {
+                Some(index) => indexes.push(index),
+                None => {
+                    return Err(usage_error(
+                        USAGE,
+                        format!("No column '{}'; columns are: {}", name, result.columns.join(", ")),
+                    ))
+                }
+            }
+        }
+        session.projection = Some(indexes);
+    }
+
+    let shown = match session.current_result() {
+        Some(result) => {
+            format::display_table(&result, &session.display);
+            let shown = rows_shown(&result, &session.display);
+            print_more_hint(shown, result.rows.len(), page_size(session));
+            shown
+        }
+        None => 0,
+    };
+    session.shown_rows = shown;
+    Ok(())
+}
+
+/// `\more` shows the next page of the last result after the rows already
+/// displayed.
+fn show_more(session: &mut Session<'_>) -> Result<()> {
+    let result = session
+        .current_result()
+        .ok_or_else(|| QgoError::Usage("No result to show more of yet".to_string()))?;
+    let total = result.rows.len();
+    let start = session.shown_rows.min(total);
+    if start == total {
+        println!("All {} rows of the last result have been shown.", format::group_thousands(total));
+        return Ok(());
+    }
+
+    let page = page_size(session);
+    let end = (start + page).min(total);
+    let rows = QueryResult {
+        columns: result.columns.clone(),
+        column_types: result.column_types.clone(),
+        rows: result.rows[start..end].to_vec(),
+        row_count: result.row_count,
+        warnings: Vec::new(),
+    };
+    let options = DisplayOptions {
+        max_rows: None,
+        scalar_display: false,
+        ..session.display.clone()
+    };
+    format::display_table(&rows, &options);
+    output::info(format!(
+        "Rows {}–{} of {}",
+        format::group_thousands(start + 1),
+        format::group_thousands(end),
+        format::group_thousands(total)
+    ));
+    print_more_hint(end, total, page);
+    session.shown_rows = end;
+    Ok(())
+}
+
+/// `\all` shows every row of the last result through the pager.
+fn show_all(session: &mut Session<'_>) -> Result<()> {
+    let result = session
+        .current_result()
+        .ok_or_else(|| QgoError::Usage("No result to show yet".to_string()))?;
+    let options = DisplayOptions {
+        max_rows: None,
+        scalar_display: false,
+        ..session.display.clone()
+    };
+    let mut text = Vec::new();
+    format::write_table(&mut text, &result, &options)?;
+    let total = result.rows.len();
+    page(&console::strip_ansi_codes(&String::from_utf8_lossy(&text)))?;
+    session.shown_rows = total;
+    Ok(())
+}
+
+/// `\cell <row> <column> [--pager]` prints one value of the last result in
+/// full and unformatted. Rows are numbered from 1; columns are given by name
+/// (ignoring case) or by 1-based position.
+fn show_cell(session: &Session<'_>, args: &[String]) -> Result<()> {
+    const USAGE: &str = "\\cell <row> <column> [--pager]";
+    let use_pager = args.iter().any(|arg| arg == "--pager");
+    let args: Vec<&String> = args.iter().filter(|arg| *arg != "--pager").collect();
+    let (row, column) = match args.as_slice() {
+        [row, column] => (row, column),
+        [] | [_] => return Err(usage_error(USAGE, "Missing row or column".to_string())),
+        [_, _, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+    };
+
+    let result = session
+        .current_result()
+        .ok_or_else(|| usage_error(USAGE, "No result to read from yet".to_string()))?;
+    let values = row
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_sub(1))
+        .and_then(|index| result.rows.get(index))
+        .ok_or_else(|| usage_error(USAGE, format!("No row {} (the result has {})", row, result.rows.len())))?;
+    let index = match column.parse::<usize>() {
+        Ok(n) => n.checked_sub(1).filter(|index| *index < result.columns.len()),
+        Err(_) => result.columns.iter().position(|name| name.eq_ignore_ascii_case(column)),
+    }
+    .ok_or_else(|| {
+        usage_error(
+            USAGE,
+            format!("No column '{}'; columns are: {}", column, result.columns.join(", ")),
+        )
+    })?;
+
+    let value = &values[index];
+    if use_pager {
+        page(value)
+    } else {
+        println!("{}", value);
+        Ok(())
+    }
+}
+
+/// `\follow <column> [row]` fetches the row that the foreign key holding
+/// `column` points to from a row of the last result (the first by
+/// default), and makes it the last result so keys can be followed further.
+async fn follow_key(database: &mut Database, session: &mut Session<'_>, args: &[String]) -> Result<()> {
+    const USAGE: &str = "\\follow <column> [row]";
+    let (column, row) = match args {
+        [column] => (column, 1),
+        [column, row] => (
+            column,
+            row.parse::<usize>()
+                .ok()
+                .filter(|row| *row > 0)
+                .ok_or_else(|| usage_error(USAGE, format!("Invalid row '{}'", row)))?,
+        ),
+        [] => return Err(usage_error(USAGE, "Missing column".to_string())),
+        [_, _, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+    };
+    let result = session
+        .last_result
+        .as_ref()
+        .ok_or_else(|| usage_error(USAGE, "No result to follow a key from yet".to_string()))?;
+    let table = session.last_table.as_deref().ok_or_else(|| {
+        usage_error(
+            USAGE,
+            "The last result was not read from a single table; run SELECT ... FROM <table> first".to_string(),
+        )
+    })?;
+
+    let followed = follow::follow(database, table, result, column, row).await?;
+    println!("{}", style(&followed.sql).dim());
+    print_warnings(&followed.result.warnings);
+    let shown = show_result(&followed.result, &ResultTarget::Display, database.get_connection(), session)?;
+    session.set_last_result(followed.result, Some(followed.table), shown);
+    Ok(())
+}
+
+/// `\analyze <query>` runs the query under `EXPLAIN ANALYZE` and lists the
+/// plan nodes, slowest first, with their estimated and actual row counts.
+async fn analyze_query(database: &mut Database, session: &mut Session<'_>, query: &str) -> Result<()> {
+    const USAGE: &str = "\\analyze <query>";
+    if query.is_empty() {
+        return Err(usage_error(USAGE, "Missing query".to_string()));
+    }
+    let query = session.variables.substitute(&database.get_connection().db_type, query)?;
+    if database::classify(&query) != StatementClass::Select {
+        session.cache.clear();
+    }
+
+    match analyze::analyze(database, &query).await? {
+        Analysis::Nodes { nodes, summary } => print!("{}", analyze::render(&nodes, &summary)),
+        Analysis::QueryPlan(plan) => {
+            println!("{}", style("SQLite has no EXPLAIN ANALYZE; this is the plan it would use.").dim());
+            show_result(&plan, &ResultTarget::Grid, database.get_connection(), session)?;
+        }
+    }
+    Ok(())
+}
+
+/// `\freq <table>.<column>` counts the values of a column with a query;
+/// `\freq <column>` counts them in the last result without one.
+async fn show_frequencies(database: &mut Database, session: &Session<'_>, args: &[String]) -> Result<()> {
+    const USAGE: &str = "\\freq <table>.<column> | <column>";
+    let target = match args {
+        [target] => target,
+        [] => return Err(usage_error(USAGE, "Missing column".to_string())),
+        [_, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+    };
+
+    let frequencies = match target.rsplit_once('.') {
+        Some((table, column)) if !table.is_empty() && !column.is_empty() => {
+            let table = if table.contains('.') {
+                table.to_string()
+            } else {
+                database.resolve_table_name(table).await?
+            };
+            freq::table_frequencies(database, &table, column).await?
+        }
+        _ => {
+            let result = session.current_result().ok_or_else(|| {
+                usage_error(USAGE, "No result to count values in yet; name a table as <table>.<column>".to_string())
+            })?;
+            freq::result_frequencies(&result, target)?
+        }
+    };
+    let width = console::Term::stdout().size_checked().map_or(80, |(_, columns)| columns as usize);
+    print!("{}", freq::render(&frequencies, width));
+    Ok(())
+}
+
+/// `\columns` lists the names and types of every column of the last
+/// result, numbered as `\cols` and `\cell` expect.
+fn show_columns(session: &Session<'_>) -> Result<()> {
+    let result = session
+        .last_result
+        .as_ref()
+        .ok_or_else(|| usage_error("\\columns", "No result to list columns of yet".to_string()))?;
+
+    let width = format::terminal_width();
+    let fitting = width.map_or(result.columns.len(), |width| {
+        format::columns_that_fit(result, &session.display, width)
+    });
+    if fitting < result.columns.len() {
+        println!("{} columns; the first {} fit the terminal", result.columns.len(), fitting);
+    } else {
+        println!("{} columns", result.columns.len());
+    }
+    print!("{}", format::column_list(result, width.unwrap_or(80)));
+    Ok(())
+}
+
+/// `\chart [bar|line]`: draws the last result, as currently shown, across
+/// the terminal width. Purely client-side; nothing is queried.
+fn show_chart(session: &Session<'_>, args: &[String]) -> Result<()> {
+    const USAGE: &str = "\\chart [bar|line]";
+    let kind = match args {
+        [] => ChartKind::Bar,
+        [kind] => ChartKind::from_name(kind)
+            .ok_or_else(|| usage_error(USAGE, format!("Unknown chart type '{}'", kind)))?,
+        [_, extra, ..] => return Err(usage_error(USAGE, format!("Unexpected argument '{}'", extra))),
+    };
+    let result = session
+        .current_result()
+        .ok_or_else(|| usage_error(USAGE, "No result to chart yet".to_string()))?;
+
+    let width = console::Term::stdout().size_checked().map_or(80, |(_, columns)| columns as usize);
+    print!("{}", chart::render(&result, kind, session.config.settings.chart_max_rows, width)?);
+    Ok(())
+}
+
+/// Shows `text` through `$PAGER`, falling back to `less`.
+fn page(text: &str) -> Result<()> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    let mut parts = pager.split_whitespace();
+    let program = parts.next().unwrap_or("less");
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| QgoError::Usage(format!("Could not start pager '{}': {}", pager, e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager may quit before reading everything
+        let _ = writeln!(stdin, "{}", text);
+    }
+    child.wait()?;
+    Ok(())
+}
+
+fn show_conninfo(database: &Database) {
+    let connection = database.get_connection();
+    show_connection_details(connection);
+    if let Some(topology) = database.topology() {
+        show_topology(topology, connection);
+    }
+    match database.timezone() {
+        Some(zone) => println!("Time zone:        {}", zone),
+        None => println!("Time zone:        server default"),
+    }
+    if database.in_transaction() {
+        println!("Transaction:      open, {} uncommitted statements", database.transaction_writes());
+    }
+    for (index, statement) in database.session_statements().iter().enumerate() {
+        let label = if index == 0 { "Session settings:" } else { "" };
+        println!("{:<18}{}", label, statement);
+    }
+    let pool = database.pool_settings();
+    println!(
+        "Pool:             max {}, min {}, idle timeout {}, acquire timeout {}s",
+        pool.max_connections,
+        pool.min_connections,
+        pool.idle_timeout.map_or_else(|| "none".to_string(), |t| format!("{}s", t.as_secs())),
+        pool.acquire_timeout.as_secs()
+    );
+    match (&connection.db_type, &connection.application_name) {
+        (DatabaseType::PostgreSQL, Some(name)) => println!("Application name: {}", name),
+        (DatabaseType::MySQL | DatabaseType::MariaDB, _) => println!("Application name: not sent (unsupported by the MySQL driver)"),
+        _ => {}
+    }
+}
+
+/// `\conninfo <connection>` in offline mode: what is saved for a connection.
+fn show_saved_connection(connection: &Connection) {
+    show_connection_details(connection);
+    println!(
+        "Time zone:        {}",
+        connection.timezone.as_deref().unwrap_or("from settings")
+    );
+    println!("Writes allowed:   {}", if connection.allow_writes { "yes" } else { "no" });
+    if let Some(ref primary) = connection.replica_of {
+        println!("Replica of:       {}", primary);
+    }
+    if !connection.replica_hosts.is_empty() {
+        println!("Replica hosts:    {}", connection.replica_hosts.join(", "));
+    }
+    match connection.last_used {
+        Some(last_used) => println!("Last used:        {}", last_used.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")),
+        None => println!("Last used:        never"),
+    }
+    println!("URL:              {}", connection.masked_connection_string());
+}
+
+/// The lines of `\conninfo` that come from the connection itself.
+fn show_connection_details(connection: &Connection) {
+    if connection.unsaved {
+        println!("Connection:       {} (unsaved; use \\saveconn to keep it)", connection.name);
+    } else {
+        println!("Connection:       {}", connection.name);
+    }
+    if let (Some(color), Some(name)) = (connection.color(), connection.color.as_deref()) {
+        let shown = format!("{} {}", color.swatch(), name);
+        println!("Color:            {}", shown.trim_start());
+    }
+    if let Some(ref environment) = connection.environment {
+        if connection.is_production() {
+            println!("Environment:      {}", style(environment).red().bold());
+        } else {
+            println!("Environment:      {}", environment);
+        }
+    }
+    println!("Type:             {}", connection.db_type);
+    if !matches!(connection.db_type, DatabaseType::SQLite) {
+        println!("Host:             {}:{}", connection.host, connection.port);
+        println!("User:             {}", connection.username);
+    }
+    if connection.database.is_empty() {
+        println!("Database:         (none selected)");
+    } else {
+        println!("Database:         {}", connection.database);
+    }
+    if let Some(ref policy) = connection.policy {
+        show_policy(policy);
+    }
+}
+
+/// The `\conninfo` lines for a connection policy.
+fn show_policy(policy: &Policy) {
+    if policy.is_empty() {
+        println!("Policy:           none (every part left empty)");
+        return;
+    }
+    let mut lines = Vec::new();
+    if !policy.allowed_statements.is_empty() {
+        let kinds: Vec<String> = policy.allowed_statements.iter().map(|kind| kind.to_uppercase()).collect();
+        lines.push(format!("only {}", kinds.join(", ")));
+    }
+    if !policy.denied_tables.is_empty() {
+        lines.push(format!("never {}", policy.denied_tables.join(", ")));
+    }
+    if let Some(max_rows) = policy.max_rows {
+        lines.push(format!("at most {} rows per result", max_rows));
+    }
+    for (index, line) in lines.iter().enumerate() {
+        let label = if index == 0 { "Policy:" } else { "" };
+        println!("{:<18}{}", label, line);
+    }
+}
+
+fn show_offline_help() {
+    println!("{}", style("Qgo - Offline Commands").bold().blue());
+    println!();
+    println!("  help, \\h          - Show this help message");
+    println!("  exit, quit, \\q    - Exit the program");
+    println!("  \\connections      - List saved connections, most recently used first");
+    println!("  \\conninfo <name>  - Show what is saved for a connection (password masked)");
+    println!("  \\recent <name> [n] - List the last n queries run on a connection");
+    println!("  \\recent <name> full <n> - Print query n from that list in full");
+    println!("  \\history [n]      - List the last n lines typed in interactive sessions");
+    println!("  \\settings         - View and change settings");
+    println!();
+    println!("  SQL needs a connection: exit and run qgo -c <name>.");
+}
+
+fn show_help() {
+    println!("{}", style("Qgo - SQL Client Commands").bold().blue());
+    println!();
+    println!("{}", style("SQL Commands:").bold());
+    println!("  SELECT, SHOW, DESCRIBE, EXPLAIN  - Execute SQL queries");
+    println!("  <query> \\g                       - Show a single-value result as a table");
+    println!("  <query> \\gx                      - Show this result vertically, a block per row");
+    println!("  <query> \\gcsv [file]             - Write this result as CSV to a file, or print it");
+    println!("  <query> \\gjson [file]            - Write this result as JSON to a file, or print it");
+    println!();
+    println!("{}", style("Special Commands:").bold());
+    println!("  help, \\h          - Show this help message");
+    println!("  exit, quit, \\q    - Exit the program");
+    println!("  clear, \\c         - Clear the screen and scrollback (also Ctrl+L)");
+    println!("  \\reset            - Reset colors, cursor and alternate screen, then clear");
+    println!("  version, \\v       - Show version information");
+    println!("  tables, \\dt       - List all tables");
+    println!("  describe <table>, \\d <table> - Describe table structure");
+    println!("  \\conninfo         - Show details of the current connection");
+    println!("  \\errverbose       - Show everything known about the last error: SQLSTATE, driver message, position");
+    println!("  \\nocache <query>  - Run a query without using the result cache");
+    println!("  \\cache [clear]    - Show or clear the result cache");
+    println!("  \\url             - Show the connection URL (password masked)");
+    println!("  \\saveconn         - Save a connection that was opened without saving");
+    println!("  \\settings         - View and change settings, applied to this session");
+    println!("  \\push, \\pop      - Keep the current settings on a stack, then bring them back");
+    println!("  \\pushed           - Show the settings stack and how each entry differs from now");
+    println!("  \\replica, \\primary - Move the session to the read replica or the primary (writes only run on the primary)");
+    println!("  \\quiet [on|off]   - Suppress banners and row-count footers");
+    println!("  \\types [on|off]   - Show column types under the column names");
+    println!("  \\x [on|off]       - Show each row as a list of column | value lines");
+    println!("  \\diff <connection> [pattern] - Compare schema and row counts with another connection");
+    println!("  \\mark             - Keep the last result as A for \\diffq");
+    println!("  \\baseline <file.csv> <query> - Write a query's result as the expected result for \\assert");
+    println!("  \\assert [--ordered] <file.csv> <query> - Compare a query's result with that file; in scripts a mismatch fails");
+    println!("  \\diffq [--key col] [a ;; b] - Compare two query results (or the marked and last result) row by row");
+    println!("  \\tz [zone]        - Show or set the session time zone");
+    println!("  \\i [--single-transaction] <file> - Run the SQL statements in a file");
+    println!("  \\delimiter [str]  - Show or change what ends a statement (also DELIMITER <str> lines, as in MySQL)");
+    println!("  \\set save         - Save changed session options (\\types, \\tz, ON_ERROR_STOP) to the config");
+    println!("  \\set ON_ERROR_STOP on|off - Stop or continue after a failing statement in multi-statement input");
+    println!("  LISTEN <channel> / UNLISTEN <channel|*> - Subscribe to Postgres notifications");
+    println!("  \\notifications    - List the channels this session is listening on");
+    println!("  \\cols <a,b,...|*> - Show only some columns of the last result, or all again");
+    println!("  \\columns          - List the names and types of the last result's columns");
+    println!("  \\more             - Show the next page of a last result the display cut short");
+    println!("  \\all              - Show every row of the last result in the pager");
+    println!("  \\cell <row> <column> [--pager] - Print one value of the last result in full");
+    println!("  \\follow <column> [row] - Fetch the row a foreign key in the last result (row 1 by default) points to");
+    println!("  \\analyze <query>  - Run EXPLAIN ANALYZE and list plan nodes, slowest first, with estimated vs actual rows");
+    println!("  \\freq <table>.<column> | <column> - Count each value of a column, in a table or in the last result");
+    println!("  \\chart [bar|line] - Chart a two-column (label, number) last result as bars or a sparkline");
+    println!("  \\browse [table]   - Build a SELECT with filters, sorting and a limit step by step");
+    println!("  \\into <var> <query> - Store the query's single value for use as :var, :'var' (string) or :\"var\" (name)");
+    println!("  \\params <file>    - Set a variable for each key of a JSON or TOML file; strings are inserted by :name as quoted literals");
+    println!("  \\vars             - List stored variables and the queries they came from");
+    println!("  \\record [<file>|off] - Record statements and results to a transcript (.md for Markdown), stop, or show status");
+    println!("  \\recent [n]       - List the last n queries run on this connection");
+    println!("  \\recent full <n>  - Print query n from that list in full");
+    println!("  \\slow [all] [n]   - List the n slowest queries of this session, or with all of this connection's history");
+    println!();
+    println!("  Quote arguments containing spaces: \\d \"My Table\", export csv 'my file.csv' ...");
+    println!();
+    println!("{}", style("Export Commands:").bold());
+    println!("  export csv <file> <query>   - Export query results to CSV");
+    println!("  export json <file> <query>  - Export query results to JSON");
+    println!("  export table <file> <query> - Export the result grid as plain text");
+    println!("  export yaml <file> <query>  - Export in any registered format (\\formats lists them)");
+    println!("  export fixed --widths 10,4,25 <file> [<query>] - Fixed-width records, values cut or padded to fit");
+    println!("  export fixed --spec <file.json> ... - Per-column width, align and pad (e.g. \"0\"); --terminator lf|crlf|none");
+    println!("  export <file> <query>       - Format from the file extension, else the export_format setting");
+    println!("  export [<format>] <file>    - Export every row of the last result");
+    println!("  \\formats          - List the export formats and the file extensions that pick them");
+    println!("  \\export ...                 - Same as export");
+    println!("  export --bom --crlf ...     - CSV with a byte order mark / CRLF line endings (for Excel)");
+    println!("  export --background ..., \\bg export ... - Export on a connection of its own while the prompt stays free");
+    println!("  \\jobs                       - List background exports with rows written and time taken");
+    println!("  \\job cancel <id>             - Stop a background export, leaving its file incomplete");
+    println!();
+    println!("{}", style("Import Commands:").bold());
+    println!("  \\import csv <file> <table> [--skip-errors] [--dry-run] [--mapping <file.json>]");
+    println!("                              - Insert CSV rows into a table (writable connections only)");
+    println!("                                after previewing the column mapping and the first rows;");
+    println!("                                --dry-run stops there, --mapping reuses or saves the mapping");
     println!();
     println!("{}", style("Keyboard Shortcuts:").bold());
     println!("  Ctrl+C            - Cancel current input");
     println!("  Ctrl+D            - Exit program");
     println!("  Up/Down arrows    - Navigate command history");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backslashes_in_comments_and_quotes_are_not_directives() {
+        assert_eq!(last_unquoted_backslash("SELECT 1 /* a\\b */"), None);
+        assert_eq!(last_unquoted_backslash("SELECT 1 /* a\\b\n c */ \\gx"), Some(22));
+        assert_eq!(last_unquoted_backslash("SELECT 1 -- a\\b"), None);
+        assert_eq!(last_unquoted_backslash("SELECT 1 -- a\\b\n\\g"), Some(16));
+        assert_eq!(last_unquoted_backslash("SELECT 'it''s \\x', \"a\\b\" \\gcsv"), Some(25));
+        assert_eq!(last_unquoted_backslash("SELECT 1 /*/ \\g */"), None);
+        assert_eq!(last_unquoted_backslash("SELECT 8 / 2 \\g"), Some(13));
+        assert_eq!(last_unquoted_backslash("SELECT 1 -- it's\n\\g"), Some(17));
+        assert_eq!(last_unquoted_backslash("SELECT 1 /* it's */ \\g"), Some(20));
+    }
+
+    #[test]
+    fn lookalikes_are_kept_without_a_terminal() {
+        let pasted = "SELECT * FROM users WHERE name = \u{2018}Ann\u{2019}  \r\nAND\u{a0}id = 1\r\n";
+        assert_eq!(
+            normalize_input(pasted, false),
+            "SELECT * FROM users WHERE name = \u{2018}Ann\u{2019}\nAND\u{a0}id = 1"
+        );
+        assert_eq!(normalize_input("SELECT 'Ann'", false), "SELECT 'Ann'");
+    }
+
+    fn args(input: &str) -> Vec<String> {
+        split_args(input).unwrap()
+    }
+
+    #[test]
+    fn quoted_filenames_keep_their_spaces() {
+        assert_eq!(args(r#""my file.csv""#), ["my file.csv"]);
+        assert_eq!(args("'my file.csv'"), ["my file.csv"]);
+        assert_eq!(args(r"my\ file.csv"), ["my file.csv"]);
+        assert_eq!(args(r#"  "/tmp/out dir/a b.csv"   extra "#), ["/tmp/out dir/a b.csv", "extra"]);
+        assert_eq!(args("a\tb\n'c d'"), ["a", "b", "c d"]);
+        assert_eq!(args(r#""""#), [""]);
+        assert!(args("   ").is_empty());
+    }
+
+    #[test]
+    fn next_arg_leaves_the_query_after_a_quoted_filename() {
+        let (path, rest) = next_arg(r#" "my file.csv" SELECT 'a b' FROM t"#).unwrap().unwrap();
+        assert_eq!(path, "my file.csv");
+        assert_eq!(rest, " SELECT 'a b' FROM t");
+
+        let (path, rest) = next_arg(r"C:\\data\\x.csv SELECT 1").unwrap().unwrap();
+        assert_eq!(path, r"C:\data\x.csv");
+        assert_eq!(rest, " SELECT 1");
+        assert!(next_arg("  ").unwrap().is_none());
+    }
+
+    #[test]
+    fn quoted_identifiers_lose_only_their_quotes() {
+        assert_eq!(args(r#""Order Items""#), ["Order Items"]);
+        assert_eq!(args(r#""MixedCase""#), ["MixedCase"]);
+        assert_eq!(args(r#""sales"."Order Items""#), ["sales.Order Items"]);
+        assert_eq!(args(r#"sales."Order Items" id"#), ["sales.Order Items", "id"]);
+        assert_eq!(args("`weird name`"), ["`weird", "name`"]);
+    }
+
+    #[test]
+    fn embedded_quotes_survive() {
+        assert_eq!(args(r#""it's here.csv""#), ["it's here.csv"]);
+        assert_eq!(args(r#"'say "hi".csv'"#), [r#"say "hi".csv"#]);
+        assert_eq!(args(r#""say \"hi\".csv""#), [r#"say "hi".csv"#]);
+        assert_eq!(args(r"'it'\''s.csv'"), ["it's.csv"]);
+        assert_eq!(args(r"it\'s"), ["it's"]);
+        // Single quotes are literal, so a backslash inside them stays
+        assert_eq!(args(r"'a\b'"), [r"a\b"]);
+        assert_eq!(args(r#""a\\b""#), [r"a\b"]);
+        assert_eq!(args("trailing\\"), ["trailing\\"]);
+    }
+
+    #[test]
+    fn unterminated_quotes_are_usage_errors() {
+        for input in [r#""my file.csv"#, "'my file.csv", r#"ok "broken"#, r#""ends with \""#] {
+            let error = split_args(input).unwrap_err();
+            assert!(matches!(error.downcast_ref::<QgoError>(), Some(QgoError::Usage(_))), "{input}");
+            assert!(error.to_string().contains("Unterminated"), "{input}: {error}");
+        }
+    }
+
+    async fn mixed_case_database(name: &str, setup: &str) -> (Database, std::path::PathBuf) {
+        sqlx::any::install_default_drivers();
+        let path = std::env::temp_dir().join(format!("qgo-cli-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let connection = crate::config::Connection::new(
+            name.to_string(),
+            DatabaseType::SQLite,
+            String::new(),
+            0,
+            String::new(),
+            String::new(),
+            format!("{}?mode=rwc", path.display()),
+        );
+        let mut database = Database::connect(connection, std::time::Duration::from_secs(5), None).await.unwrap();
+        let mut conn = database.acquire().await.unwrap();
+        sqlx::raw_sql(setup).execute(&mut *conn).await.unwrap();
+        database.release(conn, false);
+        (database, path)
+    }
+
+    #[tokio::test]
+    async fn describe_finds_a_mixed_case_quoted_table_in_any_case() {
+        let (mut database, path) =
+            mixed_case_database("mixed", r#"CREATE TABLE "MixedCase" ("Id" INTEGER, "Display Name" TEXT)"#).await;
+
+        for input in ["\\d mixedcase", "\\d MIXEDCASE", r#"\d "MixedCase""#, "describe MixedCase", "DESCRIBE mixedCASE"] {
+            assert_eq!(described_table(&mut database, input).await.unwrap().as_deref(), Some("MixedCase"), "{input}");
+        }
+        let result = metadata_result(&mut database, "\\d mixedcase").await.unwrap().unwrap();
+        assert_eq!(result.rows, [["Id", "INTEGER"], ["Display Name", "TEXT"]]);
+        let tables = metadata_result(&mut database, "\\dt").await.unwrap().unwrap();
+        assert_eq!(tables.rows, [["MixedCase"]]);
+        assert!(described_table(&mut database, "\\d Mixed Case").await.is_err());
+        assert!(described_table(&mut database, "SELECT 1").await.unwrap().is_none());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn describe_resolves_quoted_names_with_spaces_and_passes_unknown_ones_on() {
+        let (mut database, path) = mixed_case_database("spaced", r#"CREATE TABLE "Order Items" ("SKU" TEXT)"#).await;
+
+        assert_eq!(described_table(&mut database, r#"\d "order items""#).await.unwrap().as_deref(), Some("Order Items"));
+        assert_eq!(described_table(&mut database, r"\d ORDER\ ITEMS").await.unwrap().as_deref(), Some("Order Items"));
+        let result = metadata_result(&mut database, "\\d 'Order Items'").await.unwrap().unwrap();
+        assert_eq!(result.rows, [["SKU", "TEXT"]]);
+        // No table matches, so the name is passed on as typed
+        assert_eq!(described_table(&mut database, "\\d Missing").await.unwrap().as_deref(), Some("Missing"));
+        let _ = std::fs::remove_file(path);
+    }
+}
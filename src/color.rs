@@ -0,0 +1,117 @@
+//! Colors that tell connections apart on the prompt, the connect banner and
+//! in `qgo connections list`. A connection's `color` is one of the eight
+//! ANSI color names or a `#rrggbb` hex value; hex values are shown from the
+//! 256-color palette where the terminal has one, and as the nearest named
+//! color where it doesn't.
+//!
+//! ```
+//! use qgo::color::ConnectionColor;
+//!
+//! assert_eq!(ConnectionColor::parse("Cyan"), Ok(ConnectionColor::Named(console::Color::Cyan)));
+//! assert_eq!(ConnectionColor::parse("#ff8000"), Ok(ConnectionColor::Rgb(255, 128, 0)));
+//! assert!(ConnectionColor::parse("#ff80").is_err());
+//! assert!(ConnectionColor::parse("mauve").is_err());
+//!
+//! let orange = ConnectionColor::Rgb(255, 128, 0);
+//! assert_eq!(orange.to_terminal(true), console::Color::Color256(208));
+//! assert_eq!(orange.to_terminal(false), console::Color::Yellow);
+//! ```
+
+use console::{Color, Style};
+
+/// The color names accepted by [`ConnectionColor::parse`].
+pub const NAMES: [&str; 8] = ["red", "green", "yellow", "blue", "magenta", "cyan", "white", "black"];
+
+const NAMED: [(Color, (u8, u8, u8)); 8] = [
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::White, (229, 229, 229)),
+    (Color::Black, (0, 0, 0)),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectionColor {
+    Named(Color),
+    Rgb(u8, u8, u8),
+}
+
+impl ConnectionColor {
+    /// Parses a color name (ignoring case) or a `#rrggbb` hex value.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        if let Some(hex) = input.strip_prefix('#') {
+            let channel = |range: std::ops::Range<usize>| hex.get(range).and_then(|part| u8::from_str_radix(part, 16).ok());
+            return match (hex.len(), channel(0..2), channel(2..4), channel(4..6)) {
+                (6, Some(r), Some(g), Some(b)) => Ok(ConnectionColor::Rgb(r, g, b)),
+                _ => Err(format!("'{}' is not a #rrggbb color", input)),
+            };
+        }
+        NAMES
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(input))
+            .map(|index| ConnectionColor::Named(NAMED[index].0))
+            .ok_or_else(|| format!("unknown color '{}'; use one of {} or #rrggbb", input, NAMES.join(", ")))
+    }
+
+    /// The color to show: a hex value becomes the nearest entry of the
+    /// 256-color palette when `palette` is set, and the nearest named
+    /// color otherwise.
+    pub fn to_terminal(self, palette: bool) -> Color {
+        match self {
+            ConnectionColor::Named(color) => color,
+            ConnectionColor::Rgb(r, g, b) if palette => Color::Color256(palette_index(r, g, b)),
+            ConnectionColor::Rgb(r, g, b) => {
+                let distance = |(nr, ng, nb): (u8, u8, u8)| {
+                    [(r, nr), (g, ng), (b, nb)]
+                        .iter()
+                        .map(|&(a, b)| (a as i32 - b as i32).pow(2))
+                        .sum::<i32>()
+                };
+                NAMED
+                    .iter()
+                    .min_by_key(|(_, rgb)| distance(*rgb))
+                    .map_or(Color::White, |(color, _)| *color)
+            }
+        }
+    }
+
+    /// A style in this color for the current terminal. Styles are plain
+    /// where the terminal has no colors (see [`console::colors_enabled`]).
+    pub fn style(self) -> Style {
+        Style::new().fg(self.to_terminal(has_palette()))
+    }
+
+    /// A small block of this color, or blanks of the same width where the
+    /// terminal has no colors.
+    pub fn swatch(self) -> String {
+        if console::colors_enabled() {
+            self.style().apply_to("██").to_string()
+        } else {
+            "  ".to_string()
+        }
+    }
+}
+
+/// Whether the terminal advertises 256 colors or more, through `TERM` or
+/// `COLORTERM`.
+pub fn has_palette() -> bool {
+    let term = std::env::var("TERM").unwrap_or_default();
+    term.contains("256") || term.contains("direct") || std::env::var_os("COLORTERM").is_some()
+}
+
+/// The nearest color in the 6×6×6 cube of the 256-color palette.
+fn palette_index(r: u8, g: u8, b: u8) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |value: u8| {
+        LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, level)| (**level as i32 - value as i32).abs())
+            .map_or(0, |(index, _)| index as u8)
+    };
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
@@ -0,0 +1,158 @@
+use clap::Command;
+use clap_complete::Shell;
+
+use chrono::Local;
+use serde_json::json;
+
+use crate::config::{Config, Connection, DatabaseType};
+use qgo::color::ConnectionColor;
+
+pub const INSTALL_HELP: &str = "\
+Print a shell completion script for qgo.
+
+Connection names are completed from your saved connections by calling
+`qgo connections list --names-only`, so new connections show up without
+regenerating the script.
+
+Installation:
+  bash  qgo completions bash > ~/.local/share/bash-completion/completions/qgo
+  zsh   qgo completions zsh > ~/.zfunc/_qgo
+        (with `fpath+=~/.zfunc; autoload -Uz compinit; compinit` in ~/.zshrc)
+  fish  qgo completions fish > ~/.config/fish/completions/qgo.fish
+
+Start a new shell afterwards for the completions to take effect.";
+
+const BASH_CONNECTIONS: &str = r#"
+_qgo_with_connections() {
+    local cur prev name
+    cur="${COMP_WORDS[COMP_CWORD]}"
+    prev="${COMP_WORDS[COMP_CWORD-1]}"
+
+    if [[ "$prev" == "-c" || "$prev" == "--connection" || "$prev" == "--from" || "$prev" == "--to" ]] \
+        || [[ "${COMP_WORDS[1]}" == "ping" && "$COMP_CWORD" -ge 2 && "$cur" != -* && "$prev" != --* ]]; then
+        COMPREPLY=()
+        while IFS= read -r name; do
+            if [[ "$name" == "$cur"* ]]; then
+                COMPREPLY+=("$(printf '%q' "$name")")
+            fi
+        done < <(qgo connections list --names-only 2>/dev/null)
+        return 0
+    fi
+
+    _qgo "$@"
+}
+
+complete -F _qgo_with_connections -o bashdefault -o default qgo
+"#;
+
+const ZSH_CONNECTIONS: &str = r#"
+_qgo_connection_names() {
+    local -a names
+    names=("${(@f)$(qgo connections list --names-only 2>/dev/null)}")
+    compadd -a names
+}
+"#;
+
+const FISH_CONNECTIONS: &str = r#"
+complete -c qgo -n "__fish_qgo_needs_command" -s c -l connection -f -a "(qgo connections list --names-only 2>/dev/null)"
+complete -c qgo -n "__fish_qgo_using_subcommand diff" -l from -f -a "(qgo connections list --names-only 2>/dev/null)"
+complete -c qgo -n "__fish_qgo_using_subcommand diff" -l to -f -a "(qgo connections list --names-only 2>/dev/null)"
+complete -c qgo -n "__fish_qgo_using_subcommand ping" -f -a "(qgo connections list --names-only 2>/dev/null)"
+"#;
+
+/// Writes the completion script for `shell` to stdout. Static completions come
+/// from clap_complete; bash, zsh and fish additionally get a hook that
+/// completes saved connection names.
+pub fn print_completions(shell: Shell, cmd: &mut Command) {
+    let mut buffer = Vec::new();
+    clap_complete::generate(shell, cmd, "qgo", &mut buffer);
+    let script = String::from_utf8_lossy(&buffer);
+
+    match shell {
+        Shell::Bash => print!("{}{}", script, BASH_CONNECTIONS),
+        Shell::Zsh => {
+            // Connection-valued arguments are declared with the CONNECTION value name
+            let script = script
+                .replace(":CONNECTION:_default", ":CONNECTION:_qgo_connection_names")
+                .replace("Connection to ping:_default", "Connection to ping:_qgo_connection_names");
+            // The helper must be defined before the trailing compdef/_qgo invocation runs
+            match script.rfind("if [ \"$funcstack[1]\" = \"_qgo\" ]") {
+                Some(index) => print!("{}{}\n{}", &script[..index], ZSH_CONNECTIONS, &script[index..]),
+                None => print!("{}{}", script, ZSH_CONNECTIONS),
+            }
+        }
+        Shell::Fish => print!("{}{}", script, FISH_CONNECTIONS),
+        _ => print!("{}", script),
+    }
+}
+
+/// How `qgo --list` and `qgo connections list` print the connections.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListFormat {
+    Table,
+    /// One name per line, for shell completion.
+    Names,
+    /// A JSON array with ids but never passwords, for other tools.
+    Json,
+}
+
+/// Prints the saved connections, most recently used first. Only reads the
+/// config; nothing is connected to.
+pub fn list_connections(config: &Config, format: ListFormat) {
+    let mut connections: Vec<&Connection> = config.connections.iter().collect();
+    // Never-used connections sort last, then by name
+    connections.sort_by(|a, b| b.last_used.cmp(&a.last_used).then_with(|| a.name.cmp(&b.name)));
+
+    match format {
+        ListFormat::Names => {
+            for connection in connections {
+                println!("{}", connection.name);
+            }
+        }
+        ListFormat::Json => {
+            let entries: Vec<serde_json::Value> = connections
+                .iter()
+                .map(|connection| {
+                    json!({
+                        "id": connection.id,
+                        "name": connection.name,
+                        "type": connection.db_type.to_string(),
+                        "host": connection.host,
+                        "port": connection.port,
+                        "database": connection.database,
+                        "username": connection.username,
+                        "environment": connection.environment,
+                        "color": connection.color,
+                        "created_at": connection.created_at,
+                        "last_used": connection.last_used,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&entries).unwrap_or_else(|_| "[]".to_string()));
+        }
+        ListFormat::Table if connections.is_empty() => println!("No saved connections."),
+        ListFormat::Table => {
+            println!("   {:<24} {:<10} {:<10} {:<16} LOCATION", "NAME", "TYPE", "ENV", "LAST USED");
+            for connection in connections {
+                let location = match connection.db_type {
+                    DatabaseType::SQLite => connection.database.clone(),
+                    _ => format!("{}:{}/{}", connection.host, connection.port, connection.database),
+                };
+                let last_used = connection.last_used.map_or_else(
+                    || "never".to_string(),
+                    |time| time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(),
+                );
+                let swatch = connection.color().map_or_else(|| "  ".to_string(), ConnectionColor::swatch);
+                println!(
+                    "{} {:<24} {:<10} {:<10} {:<16} {}",
+                    swatch,
+                    connection.name,
+                    connection.db_type.to_string(),
+                    connection.environment.as_deref().unwrap_or("-"),
+                    last_used,
+                    location
+                );
+            }
+        }
+    }
+}
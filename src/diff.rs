@@ -0,0 +1,496 @@
+use anyhow::Result;
+use console::style;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use crate::database::{Database, QueryResult};
+use crate::error::QgoError;
+use qgo::policy::matches_pattern;
+
+/// Tables, columns and row counts of one side of a comparison.
+struct Snapshot {
+    tables: BTreeMap<String, TableSnapshot>,
+}
+
+struct TableSnapshot {
+    columns: Vec<(String, String)>,
+    row_count: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Marker {
+    Added,
+    Removed,
+    Changed,
+}
+
+pub struct Difference {
+    pub marker: Marker,
+    pub table: String,
+    pub detail: String,
+    pub from: String,
+    pub to: String,
+}
+
+pub struct DiffReport {
+    pub from_name: String,
+    pub to_name: String,
+    pub tables_compared: usize,
+    pub differences: Vec<Difference>,
+}
+
+impl DiffReport {
+    pub fn has_differences(&self) -> bool {
+        !self.differences.is_empty()
+    }
+}
+
+/// Compares the schema and row counts of two databases. Both sides are read
+/// concurrently, each bounded by `timeout`. `pattern` filters table names and
+/// accepts `*` or `%` as wildcards.
+pub async fn diff_databases(
+    from: &mut Database,
+    to: &mut Database,
+    pattern: Option<&str>,
+    timeout: Duration,
+) -> Result<DiffReport> {
+    let from_name = from.get_connection().name.clone();
+    let to_name = to.get_connection().name.clone();
+
+    let (from_snapshot, to_snapshot) = tokio::join!(
+        snapshot_with_timeout(from, pattern, timeout),
+        snapshot_with_timeout(to, pattern, timeout),
+    );
+    let from_snapshot = from_snapshot?;
+    let to_snapshot = to_snapshot?;
+
+    let mut table_names: Vec<&String> = from_snapshot
+        .tables
+        .keys()
+        .chain(to_snapshot.tables.keys())
+        .collect();
+    table_names.sort();
+    table_names.dedup();
+
+    let mut differences = Vec::new();
+    for table in &table_names {
+        match (from_snapshot.tables.get(*table), to_snapshot.tables.get(*table)) {
+            (Some(_), None) => differences.push(Difference {
+                marker: Marker::Removed,
+                table: table.to_string(),
+                detail: format!("table missing in {}", to_name),
+                from: "present".to_string(),
+                to: "-".to_string(),
+            }),
+            (None, Some(_)) => differences.push(Difference {
+                marker: Marker::Added,
+                table: table.to_string(),
+                detail: format!("table missing in {}", from_name),
+                from: "-".to_string(),
+                to: "present".to_string(),
+            }),
+            (Some(a), Some(b)) => compare_tables(table, a, b, &mut differences),
+            (None, None) => {}
+        }
+    }
+
+    Ok(DiffReport {
+        from_name,
+        to_name,
+        tables_compared: table_names.len(),
+        differences,
+    })
+}
+
+fn compare_tables(table: &str, from: &TableSnapshot, to: &TableSnapshot, out: &mut Vec<Difference>) {
+    for (column, from_type) in &from.columns {
+        match to.columns.iter().find(|(name, _)| name == column) {
+            None => out.push(Difference {
+                marker: Marker::Removed,
+                table: table.to_string(),
+                detail: format!("column {}", column),
+                from: from_type.clone(),
+                to: "-".to_string(),
+            }),
+            Some((_, to_type)) if !to_type.eq_ignore_ascii_case(from_type) => out.push(Difference {
+                marker: Marker::Changed,
+                table: table.to_string(),
+                detail: format!("column {} type", column),
+                from: from_type.clone(),
+                to: to_type.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    for (column, to_type) in &to.columns {
+        if !from.columns.iter().any(|(name, _)| name == column) {
+            out.push(Difference {
+                marker: Marker::Added,
+                table: table.to_string(),
+                detail: format!("column {}", column),
+                from: "-".to_string(),
+                to: to_type.clone(),
+            });
+        }
+    }
+
+    if from.row_count != to.row_count {
+        let render = |count: Option<i64>| count.map_or_else(|| "?".to_string(), |n| n.to_string());
+        let delta = match (from.row_count, to.row_count) {
+            (Some(a), Some(b)) => format!("row count ({:+})", b - a),
+            _ => "row count".to_string(),
+        };
+        out.push(Difference {
+            marker: Marker::Changed,
+            table: table.to_string(),
+            detail: delta,
+            from: render(from.row_count),
+            to: render(to.row_count),
+        });
+    }
+}
+
+async fn snapshot_with_timeout(
+    database: &mut Database,
+    pattern: Option<&str>,
+    timeout: Duration,
+) -> Result<Snapshot> {
+    let name = database.get_connection().name.clone();
+    tokio::time::timeout(timeout, snapshot(database, pattern))
+        .await
+        .map_err(|_| {
+            QgoError::InvalidQuery(format!(
+                "reading schema from '{}' timed out after {} seconds",
+                name,
+                timeout.as_secs()
+            ))
+        })?
+}
+
+async fn snapshot(database: &mut Database, pattern: Option<&str>) -> Result<Snapshot> {
+    let mut tables = BTreeMap::new();
+
+    for table in database.get_tables().await? {
+        if let Some(pattern) = pattern {
+            if !matches_pattern(pattern, &table) {
+                continue;
+            }
+        }
+
+        let columns = database.get_column_types(&table).await?;
+        let row_count = database.count_rows(&table).await.ok();
+        tables.insert(table, TableSnapshot { columns, row_count });
+    }
+
+    Ok(Snapshot { tables })
+}
+
+pub fn print_report(report: &DiffReport) {
+    println!(
+        "{}",
+        style(format!("Comparing {} → {}", report.from_name, report.to_name)).bold()
+    );
+
+    if !report.has_differences() {
+        println!(
+            "{}",
+            style(format!("No differences found in {} tables.", report.tables_compared)).green()
+        );
+        return;
+    }
+
+    let headers = ["", "Table", "Difference", report.from_name.as_str(), report.to_name.as_str()];
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    widths[0] = 1;
+    for diff in &report.differences {
+        for (i, cell) in [&diff.table, &diff.detail, &diff.from, &diff.to].iter().enumerate() {
+            widths[i + 1] = widths[i + 1].max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: [&str; 5]| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+    };
+
+    println!("{}", style(line(headers)).bold());
+    for diff in &report.differences {
+        let text = line(["", &diff.table, &diff.detail, &diff.from, &diff.to]);
+        let marker = match diff.marker {
+            Marker::Added => style("+").green(),
+            Marker::Removed => style("-").red(),
+            Marker::Changed => style("~").yellow(),
+        };
+        // The first cell is a one-character placeholder for the colored marker
+        println!("{}{}", marker, &text[1..]);
+    }
+
+    println!();
+    println!(
+        "{} differences across {} tables.",
+        report.differences.len(),
+        report.tables_compared
+    );
+}
+
+/// Rows beyond this many on either side are left out of a result comparison,
+/// so two huge results cannot exhaust memory.
+pub const RESULT_DIFF_ROW_LIMIT: usize = 100_000;
+
+/// A row present on both sides of a keyed or ordered comparison whose
+/// values differ.
+pub struct ChangedRow {
+    pub a: Vec<String>,
+    pub b: Vec<String>,
+    /// Indexes of the columns whose values differ.
+    pub columns: Vec<usize>,
+    /// The row's position, counting from 1, in an ordered comparison.
+    pub position: Option<usize>,
+}
+
+/// Row-level differences between two query results, with B's columns in A's
+/// order.
+pub struct ResultDiff {
+    pub columns: Vec<String>,
+    pub key: Option<usize>,
+    pub rows_a: usize,
+    pub rows_b: usize,
+    pub only_a: Vec<Vec<String>>,
+    pub only_b: Vec<Vec<String>>,
+    pub changed: Vec<ChangedRow>,
+    /// Set when either result had more than `limit` rows and was cut short.
+    pub truncated: bool,
+}
+
+impl ResultDiff {
+    pub fn has_differences(&self) -> bool {
+        !self.only_a.is_empty() || !self.only_b.is_empty() || !self.changed.is_empty()
+    }
+}
+
+/// Compares two results row by row. Without `key`, rows are matched on their
+/// full content (duplicates count); with `key`, rows sharing a value in that
+/// column are paired and their other columns compared. Both results must
+/// have the same set of columns, in any order. Only the first `limit` rows
+/// of each result take part.
+pub fn diff_results(a: &QueryResult, b: &QueryResult, key: Option<&str>, limit: usize) -> Result<ResultDiff> {
+    let key = key
+        .map(|name| {
+            a.columns
+                .iter()
+                .position(|column| column == name)
+                .ok_or_else(|| QgoError::InvalidQuery(format!("Key column '{}' is not in the results", name)))
+        })
+        .transpose()?;
+    let (rows_a, rows_b) = aligned_rows(a, b, limit)?;
+
+    let mut diff = ResultDiff {
+        columns: a.columns.clone(),
+        key,
+        rows_a: rows_a.len(),
+        rows_b: rows_b.len(),
+        only_a: Vec::new(),
+        only_b: Vec::new(),
+        changed: Vec::new(),
+        truncated: a.rows.len() > limit || b.rows.len() > limit,
+    };
+
+    match key {
+        Some(k) => {
+            let mut by_key: HashMap<&str, &Vec<String>> = HashMap::new();
+            for row in &rows_b {
+                by_key.entry(row[k].as_str()).or_insert(row);
+            }
+            for row in &rows_a {
+                match by_key.remove(row[k].as_str()) {
+                    Some(other) => {
+                        let columns: Vec<usize> = (0..row.len()).filter(|&i| row[i] != other[i]).collect();
+                        if !columns.is_empty() {
+                            diff.changed.push(ChangedRow {
+                                a: (*row).clone(),
+                                b: other.clone(),
+                                columns,
+                                position: None,
+                            });
+                        }
+                    }
+                    None => diff.only_a.push((*row).clone()),
+                }
+            }
+            // Keep B's order for the rows left over
+            for row in &rows_b {
+                if by_key.remove(row[k].as_str()).is_some() {
+                    diff.only_b.push(row.clone());
+                }
+            }
+        }
+        None => {
+            let mut counts: HashMap<&[String], isize> = HashMap::new();
+            for row in &rows_a {
+                *counts.entry(row.as_slice()).or_default() += 1;
+            }
+            for row in &rows_b {
+                *counts.entry(row.as_slice()).or_default() -= 1;
+            }
+            for row in &rows_a {
+                if let Some(count) = counts.get_mut(row.as_slice()).filter(|count| **count > 0) {
+                    *count -= 1;
+                    diff.only_a.push((*row).clone());
+                }
+            }
+            for row in &rows_b {
+                if let Some(count) = counts.get_mut(row.as_slice()).filter(|count| **count < 0) {
+                    *count += 1;
+                    diff.only_b.push(row.clone());
+                }
+            }
+        }
+    }
+
+    Ok(diff)
+}
+
+/// Compares two results row by row in order: rows at the same position are
+/// paired, and rows past the end of the shorter result are added or
+/// removed. Columns are matched as in [`diff_results`].
+pub fn diff_ordered(a: &QueryResult, b: &QueryResult, limit: usize) -> Result<ResultDiff> {
+    let (rows_a, rows_b) = aligned_rows(a, b, limit)?;
+    let mut diff = ResultDiff {
+        columns: a.columns.clone(),
+        key: None,
+        rows_a: rows_a.len(),
+        rows_b: rows_b.len(),
+        only_a: rows_a.iter().skip(rows_b.len()).map(|row| (*row).clone()).collect(),
+        only_b: rows_b.iter().skip(rows_a.len()).cloned().collect(),
+        changed: Vec::new(),
+        truncated: a.rows.len() > limit || b.rows.len() > limit,
+    };
+    for (index, (row, other)) in rows_a.iter().zip(&rows_b).enumerate() {
+        let columns: Vec<usize> = (0..row.len()).filter(|&i| row[i] != other[i]).collect();
+        if !columns.is_empty() {
+            diff.changed.push(ChangedRow {
+                a: (*row).clone(),
+                b: other.clone(),
+                columns,
+                position: Some(index + 1),
+            });
+        }
+    }
+    Ok(diff)
+}
+
+/// A's rows as they are, and B's rows with their values in A's column order.
+type AlignedRows<'a> = (Vec<&'a Vec<String>>, Vec<Vec<String>>);
+
+/// The first `limit` rows of each result, with B's values in A's column
+/// order. Fails unless both have the same set of columns.
+fn aligned_rows<'a>(a: &'a QueryResult, b: &QueryResult, limit: usize) -> Result<AlignedRows<'a>> {
+    let only_in_a: Vec<&str> = a.columns.iter().filter(|c| !b.columns.contains(c)).map(String::as_str).collect();
+    let only_in_b: Vec<&str> = b.columns.iter().filter(|c| !a.columns.contains(c)).map(String::as_str).collect();
+    if !only_in_a.is_empty() || !only_in_b.is_empty() {
+        return Err(QgoError::InvalidQuery(format!(
+            "Results have different columns (only in A: {}; only in B: {})",
+            list_or_none(&only_in_a),
+            list_or_none(&only_in_b)
+        ))
+        .into());
+    }
+
+    if a.columns.len() != b.columns.len() {
+        return Err(QgoError::InvalidQuery("Results have duplicate column names".to_string()).into());
+    }
+
+    // Reorder B's values into A's column order
+    let order: Vec<usize> = a
+        .columns
+        .iter()
+        .map(|column| b.columns.iter().position(|c| c == column).unwrap_or_default())
+        .collect();
+    let rows_a: Vec<&Vec<String>> = a.rows.iter().take(limit).collect();
+    let rows_b: Vec<Vec<String>> = b
+        .rows
+        .iter()
+        .take(limit)
+        .map(|row| order.iter().map(|&i| row[i].clone()).collect())
+        .collect();
+    Ok((rows_a, rows_b))
+}
+
+pub fn list_or_none(columns: &[&str]) -> String {
+    if columns.is_empty() {
+        "none".to_string()
+    } else {
+        columns.join(", ")
+    }
+}
+
+/// Prints the differences, calling the two sides by `names` (e.g. `A` and
+/// `B`).
+pub fn print_result_diff(diff: &ResultDiff, names: [&str; 2]) {
+    println!(
+        "{}",
+        style(format!(
+            "Comparing {} ({} rows) → {} ({} rows)",
+            names[0], diff.rows_a, names[1], diff.rows_b
+        ))
+        .bold()
+    );
+    if diff.truncated {
+        eprintln!(
+            "{}",
+            style(format!(
+                "Only the first {} rows of each result were compared.",
+                RESULT_DIFF_ROW_LIMIT
+            ))
+            .yellow()
+        );
+    }
+
+    if !diff.has_differences() {
+        println!("{}", style("The results are identical.").green());
+        return;
+    }
+
+    println!("{}", style(format!("  {}", diff.columns.join(" | "))).bold());
+    for row in &diff.only_a {
+        println!("{} {}", style("-").red(), row.join(" | "));
+    }
+    for row in &diff.only_b {
+        println!("{} {}", style("+").green(), row.join(" | "));
+    }
+    for changed in &diff.changed {
+        let key = match (diff.key, changed.position) {
+            (Some(k), _) => changed.a[k].clone(),
+            (None, Some(position)) => format!("row {}", position),
+            (None, None) => String::new(),
+        };
+        let cells: Vec<String> = changed
+            .columns
+            .iter()
+            .map(|&i| {
+                format!(
+                    "{}: {} → {}",
+                    diff.columns[i],
+                    style(&changed.a[i]).red(),
+                    style(&changed.b[i]).green()
+                )
+            })
+            .collect();
+        println!("{} {} {}", style("~").yellow(), style(key).bold(), cells.join(", "));
+    }
+
+    println!();
+    println!(
+        "{} only in {}, {} only in {}, {} changed.",
+        diff.only_a.len(),
+        names[0],
+        diff.only_b.len(),
+        names[1],
+        diff.changed.len()
+    );
+}
@@ -11,19 +11,54 @@ pub enum QgoError {
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
     
+    #[error("Connection timed out after {0} seconds")]
+    ConnectTimeout(u64),
+    
+    #[error("Timed out waiting for a free connection: all {0} pooled connections are busy. Raise max_connections for this connection if this keeps happening")]
+    PoolExhausted(u32),
+    
     #[error("Connection not found: {0}")]
     ConnectionNotFound(String),
     
+    /// Connecting worked, but not to what the connection asks for.
+    #[error("Connection check failed: {0}")]
+    ConnectionCheck(String),
+    
     #[error("Invalid query: {0}")]
     InvalidQuery(String),
     
+    /// The connection's policy does not allow the statement; the message
+    /// names the rule.
+    #[error("Not allowed by the connection policy: {0}")]
+    PolicyViolation(String),
+    
+    /// A result grew past `max_result_memory_mb` while it was read; what
+    /// was read so far has been dropped.
+    #[error("Result exceeded {0} MB in memory; use export streaming or add a LIMIT")]
+    ResultTooLarge(u64),
+    
+    #[error("Invalid time zone: {0}")]
+    InvalidTimeZone(String),
+    
     #[error("Export error: {0}")]
     #[allow(dead_code)]
     Export(String),
     
+    #[error("Import error: {0}")]
+    Import(String),
+    
+    #[error("{0}")]
+    Usage(String),
+    
     #[error("Interactive input error: {0}")]
-    #[allow(dead_code)]
     Input(String),
+    
+    #[error("Secret storage error: {0}")]
+    Secrets(String),
+    
+    /// The user backed out of a prompt with Ctrl+C or Escape.
+    #[error("Cancelled")]
+    Cancelled,
 }
 
 #[allow(dead_code)]
@@ -0,0 +1,145 @@
+use anyhow::Result;
+
+use crate::config::DatabaseType;
+use crate::database::{self, Database, ForeignKey, QueryResult};
+use crate::error::QgoError;
+
+/// The row a foreign key pointed to, fetched by [`follow`].
+pub struct Followed {
+    /// The referenced table, which the result was read from.
+    pub table: String,
+    /// The query that was run, with placeholders for the key values.
+    pub sql: String,
+    pub result: QueryResult,
+}
+
+/// Fetches the row that the foreign key of `table` containing `column`
+/// points to from row `row` (counting from 1) of `result`, which must have
+/// been read from `table`. Composite keys are followed when `result` has
+/// all of their columns. The key values are bound as parameters rather
+/// than written into the SQL.
+pub async fn follow(database: &mut Database, table: &str, result: &QueryResult, column: &str, row: usize) -> Result<Followed> {
+    let keys = database.get_foreign_keys(table).await?;
+    let Some(key) = keys
+        .iter()
+        .find(|key| key.columns.iter().any(|key_column| key_column.eq_ignore_ascii_case(column)))
+    else {
+        return Err(no_key_error(table, column, &keys));
+    };
+
+    let values = row
+        .checked_sub(1)
+        .and_then(|index| result.rows.get(index))
+        .ok_or_else(|| follow_error(format!("No row {} (the result has {})", row, result.rows.len())))?;
+    let mut params = Vec::with_capacity(key.columns.len());
+    for key_column in &key.columns {
+        let index = result
+            .columns
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(key_column))
+            .ok_or_else(|| {
+                follow_error(format!(
+                    "The foreign key {} needs '{}', which is not in the last result; select it too",
+                    key, key_column
+                ))
+            })?;
+        let value = &values[index];
+        if value == "NULL" {
+            return Err(follow_error(format!(
+                "'{}' is NULL in row {}, so it refers to nothing",
+                key_column, row
+            )));
+        }
+        params.push(value.clone());
+    }
+
+    let sql = select_statement(database, key).await?;
+    let result = database.execute_query_with(&sql, &params).await?;
+    Ok(Followed {
+        table: key.referenced_table.clone(),
+        sql,
+        result,
+    })
+}
+
+/// The table a `SELECT ... FROM <table>` reads, when it reads just one:
+/// joins, lists of tables, subqueries and set operations give `None`, as
+/// rows of their results don't map to rows of a single table.
+pub fn source_table(query: &str) -> Option<String> {
+    let query = query.trim().trim_end_matches(';').trim_end();
+    let lower: String = query
+        .chars()
+        .map(|c| if c.is_ascii_whitespace() { ' ' } else { c.to_ascii_lowercase() })
+        .collect();
+    if !lower.starts_with("select ") || lower.matches("select ").count() > 1 {
+        return None;
+    }
+    if [" join ", " union ", " intersect ", " except "].iter().any(|word| lower.contains(word)) {
+        return None;
+    }
+
+    let from = lower.find(" from ")? + " from ".len();
+    let rest = query[from..].trim_start();
+    let (table, after) = match rest.chars().next()? {
+        quote @ ('"' | '`') => {
+            let end = rest[1..].find(quote)? + 1;
+            (&rest[1..end], &rest[end + 1..])
+        }
+        _ => {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | '(' | ')'))
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        }
+    };
+    if table.is_empty() || after.trim_start().starts_with([',', '(']) {
+        return None;
+    }
+    Some(table.to_string())
+}
+
+/// `SELECT * FROM <referenced table> WHERE` each referenced column equals
+/// its placeholder.
+async fn select_statement(database: &Database, key: &ForeignKey) -> Result<String> {
+    let db_type = &database.get_connection().db_type;
+    // Postgres won't compare a text parameter with other types, so it is
+    // cast to the column's type
+    let types = match db_type {
+        DatabaseType::PostgreSQL => database.get_column_types(&key.referenced_table).await?,
+        _ => Vec::new(),
+    };
+
+    let mut conditions = Vec::with_capacity(key.referenced_columns.len());
+    for (index, column) in key.referenced_columns.iter().enumerate() {
+        let placeholder = match db_type {
+            DatabaseType::PostgreSQL => match types.iter().find(|(name, _)| name == column) {
+                Some((_, type_name)) => format!("CAST(${} AS {})", index + 1, database::quote_ident(db_type, type_name)?),
+                None => format!("${}", index + 1),
+            },
+            _ => "?".to_string(),
+        };
+        conditions.push(format!("{} = {}", database::quote_ident(db_type, column)?, placeholder));
+    }
+    Ok(format!(
+        "SELECT * FROM {} WHERE {}",
+        database::quote_ident(db_type, &key.referenced_table)?,
+        conditions.join(" AND ")
+    ))
+}
+
+fn no_key_error(table: &str, column: &str, keys: &[ForeignKey]) -> anyhow::Error {
+    if keys.is_empty() {
+        return follow_error(format!("'{}' has no foreign keys to follow", table));
+    }
+    let listed: Vec<String> = keys.iter().map(|key| format!("  {}", key)).collect();
+    follow_error(format!(
+        "'{}' is not part of a foreign key of '{}'. Its foreign keys are:\n{}",
+        column,
+        table,
+        listed.join("\n")
+    ))
+}
+
+fn follow_error(message: String) -> anyhow::Error {
+    QgoError::InvalidQuery(message).into()
+}
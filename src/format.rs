@@ -0,0 +1,1316 @@
+use anyhow::Result;
+use console::style;
+use csv::{Terminator, WriterBuilder};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::config::Settings;
+use crate::database::{QueryResult, SQL_KEYWORDS};
+use crate::error::QgoError;
+use crate::formatter::{self, Formatter, Registration, Tally, TallyState};
+use crate::output;
+use crate::timezone::{self, SessionZone};
+
+/// How query results are rendered as tables.
+#[derive(Debug, Clone, Default)]
+pub struct DisplayOptions {
+    /// Rows beyond this are left out with a notice; `None` shows everything.
+    pub max_rows: Option<usize>,
+    /// Zone timestamps are converted to before display.
+    pub timezone: Option<SessionZone>,
+    /// A chrono format string for timestamps.
+    pub date_format: Option<String>,
+    /// Show each column's type on a second header line.
+    pub show_types: bool,
+    /// Print a one-row, one-column result as `column: value` instead of a grid.
+    pub scalar_display: bool,
+    /// Cells longer than this are cut short, noting their full length;
+    /// `None` shows values in full.
+    pub max_column_width: Option<usize>,
+    /// On a terminal, draw only the leading columns that fit its width,
+    /// after a line saying how many were left out; elsewhere draw at most
+    /// [`MAX_GRID_COLUMNS`].
+    pub fit_to_terminal: bool,
+    /// Print each row as a block of `column | value` lines instead of a grid.
+    pub vertical: bool,
+    /// Draw a grid even on a terminal narrower than
+    /// [`NARROW_TERMINAL_WIDTH`] that it doesn't fit.
+    pub force_grid: bool,
+    /// Print every row in this format instead of drawing the grid.
+    pub formatter: Option<&'static Registration>,
+}
+
+impl DisplayOptions {
+    /// Renders a cell for display: capped to `max_column_width`, and with
+    /// timestamps converted. The cap comes first so huge values are never
+    /// scanned or measured in full.
+    fn format_cell<'a>(&self, cell: &'a str) -> Cow<'a, str> {
+        if let Some(max) = self.max_column_width {
+            if cell.len() > max {
+                return Cow::Owned(cap_cell(cell, max));
+            }
+        }
+
+        match timezone::format_timestamp(cell, self.timezone.as_ref(), self.date_format.as_deref()) {
+            Some(formatted) => Cow::Owned(formatted),
+            None => Cow::Borrowed(cell),
+        }
+    }
+}
+
+/// Cuts `cell` to about `max` bytes, ending in `… [203,412 chars]` with the
+/// full length. The note is always kept, even if `max` is smaller than it.
+fn cap_cell(cell: &str, max: usize) -> String {
+    let note = format!("… [{} chars]", group_thousands(cell.chars().count()));
+    let keep = max.saturating_sub(note.len());
+
+    let mut capped = String::with_capacity(max);
+    for c in cell.chars() {
+        if capped.len() + c.len_utf8() > keep {
+            break;
+        }
+        capped.push(c);
+    }
+    capped.push_str(&note);
+    capped
+}
+
+/// Column widths are computed from at most this many rows; later cells that
+/// do not fit are truncated.
+const WIDTH_SAMPLE_ROWS: usize = 1000;
+
+/// The most columns [`display_table`] draws in a grid; wider results are
+/// cut off with a notice. Exports always have every column.
+pub const MAX_GRID_COLUMNS: usize = 100;
+
+/// On terminals narrower than this, a result whose grid doesn't fit is
+/// shown vertically instead (see [`DisplayOptions::force_grid`]).
+pub const NARROW_TERMINAL_WIDTH: usize = 70;
+
+/// Set once the switch to vertical display has been explained, so the note
+/// appears only the first time.
+static NARROW_NOTE_SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// Which of a result's leading columns a grid draws.
+#[derive(Debug, Clone, Copy)]
+enum ColumnLimit {
+    All,
+    /// As many as fit in this many characters, up to [`MAX_GRID_COLUMNS`].
+    Width(usize),
+    /// At most this many.
+    Count(usize),
+}
+
+/// Prints `result` to stdout as a box-drawn table, or row by row in
+/// vertical mode. The terminal is measured on every call, so a resize is
+/// picked up by the next result. With a formatter set, every row is
+/// printed in its format instead; JSON comes with the column types (see
+/// [`formatter::json_document`]).
+pub fn display_table(result: &QueryResult, options: &DisplayOptions) {
+    if let Some(format) = options.formatter {
+        let _ = if *format == formatter::JSON {
+            print_json_document(result)
+        } else {
+            print_export(result, format, CsvOptions::default())
+        };
+        return;
+    }
+    let width = if options.fit_to_terminal { terminal_width() } else { None };
+    let limit = match width {
+        Some(width) => ColumnLimit::Width(width),
+        None if options.fit_to_terminal => ColumnLimit::Count(MAX_GRID_COLUMNS),
+        None => ColumnLimit::All,
+    };
+    // A closed pipe (e.g. `| head`) just ends the output early
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    let narrow = width.filter(|width| *width < NARROW_TERMINAL_WIDTH && !options.vertical && !options.force_grid);
+    if let Some(width) = narrow.filter(|width| !shows_scalar(result, options) && !grid_fits(result, options, *width)) {
+        if !NARROW_NOTE_SHOWN.swap(true, Ordering::Relaxed) {
+            let _ = writeln!(
+                out,
+                "{}",
+                style(format!(
+                    "The terminal is {} columns wide, so results that don't fit are shown vertically; set force_grid to keep the grid.",
+                    width
+                ))
+                .dim()
+            );
+        }
+        let vertical = DisplayOptions {
+            vertical: true,
+            ..options.clone()
+        };
+        let _ = write_grid(&mut out, result, &vertical, limit).and_then(|_| out.flush());
+        return;
+    }
+
+    let _ = write_grid(&mut out, result, options, limit).and_then(|_| out.flush());
+}
+
+/// Whether the grid of `result`, with every column, fits in `width`
+/// characters.
+fn grid_fits(result: &QueryResult, options: &DisplayOptions, width: usize) -> bool {
+    let rows = &result.rows[..options.max_rows.map_or(result.rows.len(), |max| max.min(result.rows.len()))];
+    let col_widths = column_widths(result, rows, options, ColumnLimit::Count(MAX_GRID_COLUMNS));
+    col_widths.len() == result.columns.len() && col_widths.iter().map(|width| width + 3).sum::<usize>() < width
+}
+
+/// The width of the terminal stdout is attached to, if it is one.
+pub fn terminal_width() -> Option<usize> {
+    console::Term::stdout().size_checked().map(|(_, columns)| columns as usize)
+}
+
+/// Writes `result` to `out` as a box-drawn table, followed by the truncation
+/// notice and (outside quiet mode) the row-count footer.
+pub fn write_table(out: &mut impl Write, result: &QueryResult, options: &DisplayOptions) -> io::Result<()> {
+    write_grid(out, result, options, ColumnLimit::All)
+}
+
+/// How many of `result`'s leading columns fit in a table `width` characters
+/// wide, as [`display_table`] would draw it. At least one column always
+/// counts as fitting.
+pub fn columns_that_fit(result: &QueryResult, options: &DisplayOptions, width: usize) -> usize {
+    let rows = &result.rows[..options.max_rows.map_or(result.rows.len(), |max| max.min(result.rows.len()))];
+    column_widths(result, rows, options, ColumnLimit::Width(width)).len()
+}
+
+/// The width of each leading column `limit` allows: its widest name, type
+/// (when shown) or cell among the first [`WIDTH_SAMPLE_ROWS`] of `rows`.
+/// Columns are measured one at a time, so the ones past the limit of a
+/// very wide result are never looked at.
+fn column_widths(result: &QueryResult, rows: &[Vec<String>], options: &DisplayOptions, limit: ColumnLimit) -> Vec<usize> {
+    let show_types = show_types(result, options);
+    let sample = &rows[..rows.len().min(WIDTH_SAMPLE_ROWS)];
+    let column_width = |i: usize| {
+        let mut width = result.columns[i].len();
+        if show_types {
+            width = width.max(result.column_types[i].len());
+        }
+        for row in sample {
+            if let Some(cell) = row.get(i) {
+                width = width.max(options.format_cell(cell).len());
+            }
+        }
+        width
+    };
+
+    match limit {
+        ColumnLimit::All => (0..result.columns.len()).map(column_width).collect(),
+        ColumnLimit::Count(count) => (0..result.columns.len().min(count)).map(column_width).collect(),
+        ColumnLimit::Width(width) => {
+            // Each column takes its width plus a space on both sides and a
+            // border; the first is kept even if it overflows, narrowed so
+            // no line is wider than the terminal
+            let mut col_widths = Vec::new();
+            let mut used = 1;
+            for i in 0..result.columns.len().min(MAX_GRID_COLUMNS) {
+                let col_width = column_width(i);
+                used += col_width + 3;
+                if used > width && i > 0 {
+                    break;
+                }
+                col_widths.push(col_width);
+            }
+            if let Some(first) = col_widths.first_mut() {
+                *first = (*first).min(width.saturating_sub(4).max(1));
+            }
+            col_widths
+        }
+    }
+}
+
+/// Whether `result` is a single value printed as `column: value`.
+fn shows_scalar(result: &QueryResult, options: &DisplayOptions) -> bool {
+    options.scalar_display && result.rows.len() == 1 && result.columns.len() == 1
+}
+
+fn show_types(result: &QueryResult, options: &DisplayOptions) -> bool {
+    options.show_types && result.column_types.len() == result.columns.len()
+}
+
+/// [`write_table`], drawing only the columns `limit` allows.
+fn write_grid(out: &mut impl Write, result: &QueryResult, options: &DisplayOptions, limit: ColumnLimit) -> io::Result<()> {
+    if result.is_empty() {
+        return writeln!(out, "Query returned no results.");
+    }
+
+    if shows_scalar(result, options) {
+        let value = options.format_cell(&result.rows[0][0]);
+        let value = format_number(&value).map_or(value, Cow::Owned);
+        return writeln!(out, "{}: {}", result.columns[0], value);
+    }
+
+    if options.vertical {
+        return write_vertical(out, result, options);
+    }
+
+    let max_rows = options.max_rows;
+    let display_rows = if let Some(max) = max_rows {
+        std::cmp::min(result.rows.len(), max)
+    } else {
+        result.rows.len()
+    };
+
+    let rows = &result.rows[..display_rows];
+
+    // Create a simple table using format strings
+    if !result.columns.is_empty() {
+        // Leave out the columns past the terminal's edge rather than wrap every line
+        let col_widths = column_widths(result, rows, options, limit);
+        let show_types = show_types(result, options);
+        if col_widths.len() < result.columns.len() {
+            let shown = match limit {
+                ColumnLimit::Width(_) if col_widths.len() < MAX_GRID_COLUMNS => format!("{} that fit", col_widths.len()),
+                _ => format!("the first {}", col_widths.len()),
+            };
+            writeln!(
+                out,
+                "{}",
+                style(format!(
+                    "{} columns, showing {}; use \\cols, \\columns or \\x to see the rest",
+                    result.columns.len(),
+                    shown
+                ))
+                .yellow()
+            )?;
+        }
+        let last = col_widths.len() - 1;
+
+        // Print header
+        write_border(out, &col_widths, '┌', '┬', '┐')?;
+
+        write!(out, "│")?;
+        for (i, (col, width)) in result.columns.iter().zip(&col_widths).enumerate() {
+            write!(out, " {:<width$} ", truncate(Cow::Borrowed(col), *width), width = width)?;
+            if i < last {
+                write!(out, "│")?;
+            }
+        }
+        writeln!(out, "│")?;
+
+        if show_types {
+            write!(out, "│")?;
+            for (i, (type_name, width)) in result.column_types.iter().zip(&col_widths).enumerate() {
+                let type_name = truncate(Cow::Borrowed(type_name), *width);
+                write!(out, " {} ", style(format!("{:<width$}", type_name, width = width)).dim())?;
+                if i < last {
+                    write!(out, "│")?;
+                }
+            }
+            writeln!(out, "│")?;
+        }
+
+        write_border(out, &col_widths, '├', '┼', '┤')?;
+
+        // Print rows. Timestamps are rendered in the session zone and date
+        // format; exports keep the raw values
+        for row in rows {
+            write!(out, "│")?;
+            for (i, (cell, width)) in row.iter().zip(&col_widths).enumerate() {
+                let cell = truncate(options.format_cell(cell), *width);
+                write!(out, " {:<width$} ", cell, width = width)?;
+                if i < last {
+                    write!(out, "│")?;
+                }
+            }
+            writeln!(out, "│")?;
+        }
+
+        write_border(out, &col_widths, '└', '┴', '┘')?;
+    }
+
+    write_footer(out, result, max_rows)
+}
+
+/// Writes each row as a `-[ RECORD n ]-` heading followed by one
+/// `column | value` line per column, as `\x` shows them. Lines are written
+/// as they are formatted, so a row with thousands of columns is never
+/// held in memory as one block of text.
+fn write_vertical(out: &mut impl Write, result: &QueryResult, options: &DisplayOptions) -> io::Result<()> {
+    let display_rows = options.max_rows.map_or(result.rows.len(), |max| max.min(result.rows.len()));
+    let name_width = result.columns.iter().map(|column| column.chars().count()).max().unwrap_or(0);
+
+    for (index, row) in result.rows[..display_rows].iter().enumerate() {
+        writeln!(out, "{}", style(format!("-[ RECORD {} ]-", index + 1)).dim())?;
+        for (column, cell) in result.columns.iter().zip(row) {
+            writeln!(out, "{:<name_width$} │ {}", column, options.format_cell(cell), name_width = name_width)?;
+        }
+    }
+
+    write_footer(out, result, options.max_rows)
+}
+
+/// The truncation notice and (outside quiet mode) the row-count footer.
+fn write_footer(out: &mut impl Write, result: &QueryResult, max_rows: Option<usize>) -> io::Result<()> {
+    if let Some(max) = max_rows {
+        if result.rows.len() > max {
+            writeln!(out, "\n... and {} more rows (showing first {})", 
+                result.rows.len() - max, max)?;
+        }
+    }
+
+    if !output::is_quiet() {
+        writeln!(out, "\nRows returned: {}", result.row_count)?;
+    }
+
+    Ok(())
+}
+
+/// Lists the columns of `result` as numbered `name type` entries, numbered
+/// down and then across as many columns as fit in `width`.
+pub fn column_list(result: &QueryResult, width: usize) -> String {
+    let has_types = result.column_types.len() == result.columns.len();
+    let number_width = result.columns.len().to_string().len();
+    let entries: Vec<(String, &str)> = result
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let type_name = if has_types { result.column_types[i].as_str() } else { "" };
+            (format!("{:>number_width$} {}", i + 1, name, number_width = number_width), type_name)
+        })
+        .collect();
+
+    let entry_len = |(name, type_name): &(String, &str)| name.chars().count() + type_name.chars().count() + 1;
+    let entry_width = entries.iter().map(entry_len).max().unwrap_or(0) + 2;
+    let per_line = (width / entry_width).max(1);
+    let lines = entries.len().div_ceil(per_line);
+
+    let mut list = String::new();
+    for line in 0..lines {
+        let mut text = String::new();
+        for entry in entries.iter().skip(line).step_by(lines) {
+            let padding = entry_width - entry_len(entry);
+            text.push_str(&format!("{} {}{}", entry.0, style(entry.1).dim(), " ".repeat(padding)));
+        }
+        list.push_str(text.trim_end());
+        list.push('\n');
+    }
+    list
+}
+
+fn write_border(out: &mut impl Write, col_widths: &[usize], left: char, middle: char, right: char) -> io::Result<()> {
+    write!(out, "{}", left)?;
+    for (i, width) in col_widths.iter().enumerate() {
+        write!(out, "{}", "─".repeat(width + 2))?;
+        if i < col_widths.len() - 1 {
+            write!(out, "{}", middle)?;
+        }
+    }
+    writeln!(out, "{}", right)
+}
+
+/// Shortens a cell wider than its column (possible past the width sample),
+/// marking the cut with an ellipsis.
+fn truncate(cell: Cow<'_, str>, width: usize) -> Cow<'_, str> {
+    if cell.len() <= width {
+        return cell;
+    }
+
+    let mut shortened = String::with_capacity(width);
+    for c in cell.chars() {
+        if shortened.len() + c.len_utf8() + '…'.len_utf8() > width {
+            break;
+        }
+        shortened.push(c);
+    }
+    shortened.push('…');
+    Cow::Owned(shortened)
+}
+
+/// Formats `n` with comma thousands separators, e.g. `1,234,567`.
+pub fn group_thousands(n: usize) -> String {
+    group_digits(&n.to_string())
+}
+
+fn group_digits(digits: &str) -> String {
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+/// Adds thousands separators to a plain decimal number such as `-1234567.5`.
+/// Returns `None` for anything else, which is then shown unchanged.
+fn format_number(value: &str) -> Option<String> {
+    let (sign, unsigned) = match value.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", value),
+    };
+    let (integer, fraction) = match unsigned.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (unsigned, None),
+    };
+
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(integer) || !fraction.is_none_or(is_digits) {
+        return None;
+    }
+    // Leading zeros mean an identifier such as a zip code, not a quantity
+    if integer.len() > 1 && integer.starts_with('0') {
+        return None;
+    }
+
+    let mut formatted = format!("{}{}", sign, group_digits(integer));
+    if let Some(fraction) = fraction {
+        formatted.push('.');
+        formatted.push_str(fraction);
+    }
+    Some(formatted)
+}
+
+/// Clauses that start on a new line in [`reformat_sql`], unless nested in
+/// parentheses.
+const CLAUSE_KEYWORDS: &[&str] = &[
+    "EXCEPT", "FROM", "GROUP", "HAVING", "INTERSECT", "JOIN", "LIMIT", "OFFSET", "ORDER", "SELECT",
+    "UNION", "WHERE", "WINDOW",
+];
+
+/// Words that start a join, and so a new line, when `JOIN` or `OUTER`
+/// follows them.
+const JOIN_KEYWORDS: &[&str] = &["CROSS", "FULL", "INNER", "LEFT", "NATURAL", "RIGHT"];
+
+/// Lays out `sql` for reading: known keywords in upper case, whitespace
+/// collapsed, and each top-level clause on a line of its own. String
+/// literals, quoted identifiers and comments are left as they are.
+///
+/// ```
+/// use qgo::format::reformat_sql;
+///
+/// let sql = "select id, 'from here' as note\n  from orders o left join items i on i.order_id = o.id where o.total > 10";
+/// assert_eq!(
+///     reformat_sql(sql),
+///     "SELECT id, 'from here' AS note\nFROM orders o\nLEFT JOIN items i ON i.order_id = o.id\nWHERE o.total > 10"
+/// );
+/// ```
+pub fn reformat_sql(sql: &str) -> String {
+    let tokens = sql_tokens(sql);
+    let word = |token: Option<&SqlToken>| match token {
+        Some(SqlToken::Word(word)) => Some(word.to_ascii_uppercase()),
+        _ => None,
+    };
+
+    let mut out = String::with_capacity(sql.len());
+    let mut depth = 0usize;
+    let mut previous_word: Option<String> = None;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            SqlToken::Space => {
+                if !out.is_empty() && !out.ends_with([' ', '\n', '(']) {
+                    out.push(' ');
+                }
+            }
+            SqlToken::Word(text) => {
+                let upper = text.to_ascii_uppercase();
+                let next_word = tokens[i + 1..].iter().find(|token| !matches!(token, SqlToken::Space));
+                let starts_join = JOIN_KEYWORDS.contains(&upper.as_str())
+                    && matches!(word(next_word).as_deref(), Some("JOIN" | "OUTER"));
+                let continues_join = upper == "JOIN"
+                    && previous_word.as_deref().is_some_and(|previous| JOIN_KEYWORDS.contains(&previous) || previous == "OUTER");
+                // `UNION ALL SELECT` stays on one line
+                let after_set_operation = previous_word
+                    .as_deref()
+                    .is_some_and(|previous| matches!(previous, "UNION" | "EXCEPT" | "INTERSECT" | "ALL"));
+                let breaks = starts_join || (CLAUSE_KEYWORDS.contains(&upper.as_str()) && !continues_join);
+                if breaks && depth == 0 && !out.is_empty() && !after_set_operation {
+                    out.truncate(out.trim_end().len());
+                    out.push('\n');
+                }
+                if SQL_KEYWORDS.binary_search(&upper.as_str()).is_ok() {
+                    out.push_str(&upper);
+                } else {
+                    out.push_str(text);
+                }
+                previous_word = Some(upper);
+            }
+            SqlToken::Literal(text) => out.push_str(text),
+            SqlToken::Comment(text) => {
+                out.push_str(text);
+                out.push('\n');
+            }
+            SqlToken::Other(c) => {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                if matches!(c, ')' | ',' | ';') && out.ends_with(' ') {
+                    out.pop();
+                }
+                out.push(*c);
+            }
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// A piece of SQL as split by [`sql_tokens`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlToken {
+    /// A keyword, identifier or number.
+    Word(String),
+    /// A string literal or quoted identifier, quotes included.
+    Literal(String),
+    /// A `--` comment, without its line break.
+    Comment(String),
+    /// A run of whitespace.
+    Space,
+    /// Any other character: punctuation and operators.
+    Other(char),
+}
+
+/// Splits `sql` into words, quoted literals, comments, whitespace and
+/// single characters. Unterminated quotes run to the end; it never fails.
+pub fn sql_tokens(sql: &str) -> Vec<SqlToken> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            tokens.push(SqlToken::Space);
+        } else if matches!(c, '\'' | '"' | '`') {
+            let mut literal = c.to_string();
+            while let Some(next) = chars.next() {
+                literal.push(next);
+                // A doubled quote is an escaped one
+                if next == c && chars.next_if_eq(&c).map(|quote| literal.push(quote)).is_none() {
+                    break;
+                }
+            }
+            tokens.push(SqlToken::Literal(literal));
+        } else if c == '-' && chars.peek() == Some(&'-') {
+            let mut comment = c.to_string();
+            while let Some(next) = chars.next_if(|c| *c != '\n') {
+                comment.push(next);
+            }
+            tokens.push(SqlToken::Comment(comment));
+        } else if c.is_alphanumeric() || c == '_' || c == '$' {
+            let mut word = c.to_string();
+            while let Some(next) = chars.next_if(|c| c.is_alphanumeric() || *c == '_' || *c == '$') {
+                word.push(next);
+            }
+            tokens.push(SqlToken::Word(word));
+        } else {
+            tokens.push(SqlToken::Other(c));
+        }
+    }
+    tokens
+}
+
+/// How CSV exports are written, for tools that don't read plain UTF-8
+/// with `\n` line endings.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CsvOptions {
+    /// Start the file with a UTF-8 byte order mark.
+    pub bom: bool,
+    /// End lines with `\r\n` instead of `\n`.
+    pub crlf: bool,
+}
+
+impl CsvOptions {
+    /// The `csv_bom` and `csv_crlf` defaults.
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            bom: settings.csv_bom,
+            crlf: settings.csv_crlf,
+        }
+    }
+
+    pub(crate) fn line_ending(&self) -> &'static str {
+        if self.crlf {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    }
+
+    /// Creates `file_path`, writing the byte order mark if there is one.
+    pub fn create(&self, file_path: &str) -> Result<File> {
+        let mut file = File::create(file_path)?;
+        if self.bom {
+            file.write_all(UTF8_BOM)?;
+        }
+        Ok(file)
+    }
+
+    pub(crate) fn builder(&self) -> WriterBuilder {
+        let terminator = if self.crlf { Terminator::CRLF } else { Terminator::Any(b'\n') };
+        let mut builder = WriterBuilder::new();
+        builder.terminator(terminator);
+        builder
+    }
+}
+
+pub(crate) const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Writes the raw (unformatted) values of `result` to a CSV file with a header row.
+pub fn export_to_csv(result: &QueryResult, file_path: &str, options: CsvOptions) -> Result<()> {
+    export(result, &formatter::CSV, file_path, options, None)
+}
+
+/// Prints `result` to stdout as `format` would write it to a file, ending
+/// on a new line. CSV gets no byte order mark.
+pub fn print_export(result: &QueryResult, format: &Registration, csv: CsvOptions) -> Result<()> {
+    let (out, tally) = Tally::new(BufWriter::new(io::stdout()));
+    let csv = CsvOptions { bom: false, ..csv };
+    format.write_result(Box::new(out), result, csv, None)?;
+    if !tally.ends_line() {
+        println!();
+    }
+    Ok(())
+}
+
+fn print_json_document(result: &QueryResult) -> Result<()> {
+    let mut out = BufWriter::new(io::stdout());
+    serde_json::to_writer_pretty(&mut out, &formatter::json_document(result))?;
+    writeln!(out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Appends a `# comment` line to an exported CSV file.
+pub fn append_csv_comment(file_path: &str, comment: &str, options: CsvOptions) -> Result<()> {
+    let mut file = OpenOptions::new().append(true).open(file_path)?;
+    write!(file, "# {}{}", comment, options.line_ending())?;
+    Ok(())
+}
+
+/// Writes `result` to `file_path` in `format`, followed by `note` where the
+/// format has comments; `csv` only applies to CSV.
+pub fn export(result: &QueryResult, format: &Registration, file_path: &str, csv: CsvOptions, note: Option<&str>) -> Result<()> {
+    let out = BufWriter::new(File::create(file_path)?);
+    format.write_result(Box::new(out), result, csv, note)
+}
+
+/// Writes rows to an export file one at a time, for results too large to
+/// collect first. The output matches [`export`].
+pub struct ExportWriter {
+    formatter: Box<dyn Formatter>,
+    begun: bool,
+    tally: Arc<TallyState>,
+}
+
+impl ExportWriter {
+    /// Creates `file_path`. Returns `None` for formats that need the whole
+    /// result up front, such as the table.
+    pub fn create(format: &Registration, file_path: &str, csv: CsvOptions) -> Result<Option<Self>> {
+        if !format.streams {
+            return Ok(None);
+        }
+        let (out, tally) = Tally::new(BufWriter::new(File::create(file_path)?));
+        Ok(Some(Self {
+            formatter: (format.create)(Box::new(out), csv)?,
+            begun: false,
+            tally,
+        }))
+    }
+
+    /// Appends a row, after the header when it is the first.
+    pub fn write_row(&mut self, columns: &[String], row: &[String]) -> Result<()> {
+        if !self.begun {
+            self.formatter.begin(columns)?;
+            self.begun = true;
+        }
+        self.formatter.write(columns, row)
+    }
+
+    /// Ends the file with a comment line, in formats that have them.
+    pub fn write_comment(&mut self, comment: &str) -> Result<()> {
+        self.formatter.comment(comment)
+    }
+
+    /// Bytes written so far, give or take what is still buffered.
+    pub fn bytes_written(&self) -> u64 {
+        self.tally.bytes()
+    }
+
+    /// Completes the file and flushes it to disk.
+    pub fn finish(mut self) -> Result<()> {
+        self.formatter.end()
+    }
+}
+
+/// How one field of a fixed-width export is laid out.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FixedField {
+    /// The result column the field holds. Required in spec files; with
+    /// `--widths` fields take the columns in order.
+    #[serde(default)]
+    pub column: Option<String>,
+    pub width: usize,
+    #[serde(default)]
+    pub align: Align,
+    /// What short values are padded with. `0` on a right-aligned number
+    /// goes after its sign, as in `-0042`.
+    #[serde(default = "default_pad")]
+    pub pad: char,
+}
+
+fn default_pad() -> char {
+    ' '
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    #[default]
+    Left,
+    Right,
+}
+
+/// What follows each record of a fixed-width export.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordEnd {
+    #[default]
+    Lf,
+    Crlf,
+    /// Records follow each other directly, as some mainframe formats expect.
+    None,
+}
+
+impl RecordEnd {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "lf" => Some(RecordEnd::Lf),
+            "crlf" => Some(RecordEnd::Crlf),
+            "none" => Some(RecordEnd::None),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            RecordEnd::Lf => "\n",
+            RecordEnd::Crlf => "\r\n",
+            RecordEnd::None => "",
+        }
+    }
+}
+
+/// The layout of a fixed-width export: its fields in the order they are
+/// written, from `--widths` or a spec file.
+///
+/// ```
+/// use qgo::format::{Align, FixedSpec};
+///
+/// let mut spec = FixedSpec::from_widths("5, 3").unwrap();
+/// assert_eq!(spec.fit(0, "abcdef"), ("abcde".to_string(), true));
+/// spec.fields[1].align = Align::Right;
+/// spec.fields[1].pad = '0';
+/// assert_eq!(spec.fit(1, "-7"), ("-07".to_string(), false));
+/// assert!(FixedSpec::from_widths("5,,3").is_err());
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct FixedSpec {
+    #[serde(rename = "columns")]
+    pub fields: Vec<FixedField>,
+    #[serde(default)]
+    pub terminator: RecordEnd,
+}
+
+impl FixedSpec {
+    /// Left-aligned, space-padded fields from a list such as `10,4,25`.
+    pub fn from_widths(widths: &str) -> Result<Self> {
+        let fields = widths
+            .split(',')
+            .map(|width| match width.trim().parse() {
+                Ok(width) if width > 0 => Ok(FixedField {
+                    column: None,
+                    width,
+                    align: Align::Left,
+                    pad: default_pad(),
+                }),
+                _ => Err(QgoError::Export(format!("'{}' is not a field width in --widths {}", width.trim(), widths))),
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(Self {
+            fields,
+            terminator: RecordEnd::default(),
+        })
+    }
+
+    /// Reads a spec file: `{"columns": [{"column": "id", "width": 10,
+    /// "align": "right", "pad": "0"}, ...], "terminator": "crlf"}`.
+    pub fn load(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| QgoError::Export(format!("Can't read the spec file {}: {}", path, e)))?;
+        let spec: Self = serde_json::from_str(&text)
+            .map_err(|e| QgoError::Export(format!("Invalid spec file {}: {}", path, e)))?;
+        if let Some(field) = spec.fields.iter().find(|field| field.column.is_none() || field.width == 0) {
+            return Err(QgoError::Export(format!(
+                "Every field of {} needs a column and a width above 0 (found {:?})",
+                path, field
+            ))
+            .into());
+        }
+        Ok(spec)
+    }
+
+    /// For each field, the index of the result column it holds. Fails when
+    /// the fields and `columns` don't match up one to one.
+    pub fn layout(&self, columns: &[String]) -> Result<Vec<usize>> {
+        if self.fields.len() != columns.len() {
+            return Err(QgoError::Export(format!(
+                "The layout has {} fields but the result has {} columns ({})",
+                self.fields.len(),
+                columns.len(),
+                columns.join(", ")
+            ))
+            .into());
+        }
+        let mut layout = Vec::new();
+        for (position, field) in self.fields.iter().enumerate() {
+            let index = match field.column {
+                Some(ref name) => columns
+                    .iter()
+                    .position(|column| column.eq_ignore_ascii_case(name))
+                    .ok_or_else(|| QgoError::Export(format!("The result has no column '{}'", name)))?,
+                None => position,
+            };
+            if layout.contains(&index) {
+                return Err(QgoError::Export(format!("Column '{}' is laid out twice", columns[index])).into());
+            }
+            layout.push(index);
+        }
+        Ok(layout)
+    }
+
+    /// `value` laid out as field `field`, and whether it had to be cut
+    /// short to fit.
+    pub fn fit(&self, field: usize, value: &str) -> (String, bool) {
+        let field = &self.fields[field];
+        let length = value.chars().count();
+        if length >= field.width {
+            return (value.chars().take(field.width).collect(), length > field.width);
+        }
+        let padding: String = std::iter::repeat_n(field.pad, field.width - length).collect();
+        let text = match field.align {
+            Align::Left => format!("{}{}", value, padding),
+            Align::Right if field.pad == '0' && value.starts_with(['-', '+']) => {
+                format!("{}{}{}", &value[..1], padding, &value[1..])
+            }
+            Align::Right => format!("{}{}", padding, value),
+        };
+        (text, false)
+    }
+}
+
+/// Writes `result` to `file_path` as fixed-width records laid out by
+/// `spec`, with no header, stopping after `rows` rows. `layout` comes from
+/// [`FixedSpec::layout`]. Returns the 1-based rows with a value that was
+/// cut short.
+pub fn export_fixed(
+    result: &QueryResult,
+    spec: &FixedSpec,
+    layout: &[usize],
+    rows: usize,
+    file_path: &str,
+) -> Result<Vec<usize>> {
+    let mut out = BufWriter::new(File::create(file_path)?);
+    let mut truncated = Vec::new();
+    for (number, row) in result.rows.iter().take(rows).enumerate() {
+        let mut cut = false;
+        for (field, &column) in layout.iter().enumerate() {
+            let (text, short) = spec.fit(field, row.get(column).map_or("", String::as_str));
+            cut |= short;
+            out.write_all(text.as_bytes())?;
+        }
+        out.write_all(spec.terminator.as_str().as_bytes())?;
+        if cut {
+            truncated.push(number + 1);
+        }
+    }
+    out.flush()?;
+    Ok(truncated)
+}
+
+/// Finds where records end in CSV that arrives in chunks: a newline ends
+/// a record unless it is inside a quoted value.
+///
+/// ```
+/// use qgo::format::CsvRecordEnds;
+///
+/// let mut ends = CsvRecordEnds::default();
+/// assert_eq!(ends.find(b"id,note\n1,\"two\nlines"), vec![8]);
+/// assert_eq!(ends.find(b"\"\n2,\"say \"\"hi\"\"\"\n"), vec![2, 17]);
+/// ```
+#[derive(Debug, Default)]
+pub struct CsvRecordEnds {
+    quoted: bool,
+}
+
+impl CsvRecordEnds {
+    /// The offsets just past each record's newline in `chunk`, carrying
+    /// on from the chunks before it.
+    pub fn find(&mut self, chunk: &[u8]) -> Vec<usize> {
+        let mut ends = Vec::new();
+        for (i, byte) in chunk.iter().enumerate() {
+            match byte {
+                // An escaped quote ("") toggles twice
+                b'"' => self.quoted = !self.quoted,
+                b'\n' if !self.quoted => ends.push(i + 1),
+                _ => {}
+            }
+        }
+        ends
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_file(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("qgo-format-{}-{}", name, std::process::id()))
+            .display()
+            .to_string()
+    }
+
+    fn sample() -> QueryResult {
+        QueryResult::from_rows(
+            &["id", "note"],
+            vec![
+                vec!["1".to_string(), "plain".to_string()],
+                vec!["2".to_string(), "a, \"quoted\"\nline".to_string()],
+                vec!["3".to_string(), "café".to_string()],
+            ],
+        )
+    }
+
+    fn exported(name: &str, options: CsvOptions) -> Vec<u8> {
+        let path = scratch_file(name);
+        export_to_csv(&sample(), &path, options).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        bytes
+    }
+
+    const BODY_LF: &[u8] = b"id,note\n1,plain\n2,\"a, \"\"quoted\"\"\nline\"\n3,caf\xC3\xA9\n";
+    const BODY_CRLF: &[u8] = b"id,note\r\n1,plain\r\n2,\"a, \"\"quoted\"\"\nline\"\r\n3,caf\xC3\xA9\r\n";
+
+    #[test]
+    fn csv_defaults_to_plain_utf8_with_lf() {
+        assert_eq!(exported("plain", CsvOptions::default()), BODY_LF);
+    }
+
+    #[test]
+    fn csv_bom_prefixes_exactly_three_bytes() {
+        let bytes = exported("bom", CsvOptions { bom: true, crlf: false });
+        assert_eq!(&bytes[..3], b"\xEF\xBB\xBF");
+        assert_eq!(&bytes[3..], BODY_LF);
+    }
+
+    #[test]
+    fn csv_crlf_ends_records_but_not_embedded_newlines() {
+        assert_eq!(exported("crlf", CsvOptions { bom: false, crlf: true }), BODY_CRLF);
+    }
+
+    #[test]
+    fn csv_bom_and_crlf_combine() {
+        let bytes = exported("both", CsvOptions { bom: true, crlf: true });
+        assert_eq!(bytes, [UTF8_BOM, BODY_CRLF].concat());
+    }
+
+    #[test]
+    fn streamed_csv_and_comments_match_the_line_ending() {
+        let options = CsvOptions { bom: true, crlf: true };
+        let result = sample();
+        let path = scratch_file("stream");
+        let mut writer = ExportWriter::create(&formatter::CSV, &path, options).unwrap().unwrap();
+        for row in &result.rows {
+            writer.write_row(&result.columns, row).unwrap();
+        }
+        writer.write_comment("3 rows").unwrap();
+        writer.finish().unwrap();
+        append_csv_comment(&path, "exported by qgo", options).unwrap();
+
+        let expected = [UTF8_BOM, BODY_CRLF, b"# 3 rows\r\n# exported by qgo\r\n"].concat();
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn an_empty_result_still_gets_the_bom_only_once() {
+        let path = scratch_file("empty");
+        let options = CsvOptions { bom: true, crlf: true };
+        export_to_csv(&QueryResult::from_rows(&["id"], Vec::new()), &path, options).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"\xEF\xBB\xBFid\r\n");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    const WIDE_COLUMNS: usize = 1000;
+    const WIDE_ROWS: usize = 20;
+
+    /// `SELECT *` from a generated SQLite table of [`WIDE_COLUMNS`] columns.
+    async fn wide_result() -> QueryResult {
+        sqlx::any::install_default_drivers();
+        let path = std::env::temp_dir().join(format!("qgo-format-wide-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut connection = crate::config::Connection::new(
+            "wide".to_string(),
+            crate::config::DatabaseType::SQLite,
+            String::new(),
+            0,
+            String::new(),
+            String::new(),
+            format!("{}?mode=rwc", path.display()),
+        );
+        connection.max_connections = Some(1);
+        let mut database = crate::database::Database::connect(connection, std::time::Duration::from_secs(5), None)
+            .await
+            .unwrap();
+
+        let columns: Vec<String> = (1..=WIDE_COLUMNS).map(|i| format!("c{:04} INTEGER", i)).collect();
+        let rows: Vec<String> = (1..=WIDE_ROWS)
+            .map(|row| {
+                let values: Vec<String> = (1..=WIDE_COLUMNS).map(|column| (row * column).to_string()).collect();
+                format!("({})", values.join(", "))
+            })
+            .collect();
+        let mut conn = database.acquire().await.unwrap();
+        for sql in [
+            format!("CREATE TABLE wide ({})", columns.join(", ")),
+            format!("INSERT INTO wide VALUES {}", rows.join(", ")),
+        ] {
+            sqlx::raw_sql(&sql).execute(&mut *conn).await.unwrap();
+        }
+        database.release(conn, false);
+
+        let result = database.execute_query("SELECT * FROM wide").await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    fn rendered(result: &QueryResult, options: &DisplayOptions, limit: ColumnLimit) -> String {
+        let mut out = Vec::new();
+        write_grid(&mut out, result, options, limit).unwrap();
+        console::strip_ansi_codes(&String::from_utf8(out).unwrap()).into_owned()
+    }
+
+    #[tokio::test]
+    async fn a_thousand_columns_render_as_grid_vertical_and_csv() {
+        let result = wide_result().await;
+        assert_eq!(result.columns.len(), WIDE_COLUMNS);
+        assert_eq!(result.rows.len(), WIDE_ROWS);
+        let options = DisplayOptions::default();
+
+        // Off a terminal the grid stops at MAX_GRID_COLUMNS
+        let grid = rendered(&result, &options, ColumnLimit::Count(MAX_GRID_COLUMNS));
+        let mut lines = grid.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "1000 columns, showing the first 100; use \\cols, \\columns or \\x to see the rest"
+        );
+        let table: Vec<&str> = lines.take_while(|line| !line.is_empty()).collect();
+        assert_eq!(table.len(), WIDE_ROWS + 4);
+        assert!(table[1].contains(" c0100 ") && !table[1].contains("c0101"));
+        let width = table[0].chars().count();
+        assert!(table.iter().all(|line| line.chars().count() == width));
+
+        // On a terminal only the columns that fit are drawn
+        let fitted = rendered(&result, &options, ColumnLimit::Width(120));
+        assert!(fitted.lines().next().unwrap().contains("that fit"));
+        assert!(fitted.lines().skip(1).take_while(|line| !line.is_empty()).all(|line| line.chars().count() <= 120));
+
+        // Without a limit every column is drawn
+        let full = rendered(&result, &options, ColumnLimit::All);
+        assert!(full.lines().nth(1).unwrap().contains(" c1000 "));
+
+        let vertical = rendered(&result, &DisplayOptions { vertical: true, ..options.clone() }, ColumnLimit::All);
+        let records: Vec<&str> = vertical.lines().filter(|line| line.starts_with("-[ RECORD")).collect();
+        assert_eq!(records.len(), WIDE_ROWS);
+        let first: Vec<&str> = vertical.lines().skip(1).take(WIDE_COLUMNS).collect();
+        assert_eq!(first[0], "c0001 │ 1");
+        assert_eq!(first[WIDE_COLUMNS - 1], "c1000 │ 1000");
+        assert!(vertical.contains("\nc1000 │ 20000\n"));
+
+        let path = std::env::temp_dir()
+            .join(format!("qgo-format-wide-{}.csv", std::process::id()))
+            .display()
+            .to_string();
+        export_to_csv(&result, &path, CsvOptions::default()).unwrap();
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        let header = reader.headers().unwrap().clone();
+        assert_eq!(header.len(), WIDE_COLUMNS);
+        assert_eq!(&header[WIDE_COLUMNS - 1], "c1000");
+        let records: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(records.len(), WIDE_ROWS);
+        assert!(records.iter().all(|record| record.len() == WIDE_COLUMNS));
+        assert_eq!(&records[WIDE_ROWS - 1][WIDE_COLUMNS - 1], "20000");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    fn table_text(result: &QueryResult, options: &DisplayOptions) -> String {
+        let mut out = Vec::new();
+        write_table(&mut out, result, options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// The grid as it was drawn before widths were sampled: every displayed
+    /// row measured up front, nothing truncated.
+    fn unsampled_table(result: &QueryResult, options: &DisplayOptions) -> String {
+        let rows = &result.rows[..options.max_rows.map_or(result.rows.len(), |max| max.min(result.rows.len()))];
+        let mut widths: Vec<usize> = result.columns.iter().map(String::len).collect();
+        for row in rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(options.format_cell(cell).len());
+            }
+        }
+        let border = |left: &str, middle: &str, right: &str| {
+            let lines: Vec<String> = widths.iter().map(|width| "─".repeat(width + 2)).collect();
+            format!("{}{}{}\n", left, lines.join(middle), right)
+        };
+        let line = |cells: Vec<Cow<'_, str>>| {
+            let cells: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!(" {:<width$} ", cell, width = width))
+                .collect();
+            format!("│{}│\n", cells.join("│"))
+        };
+
+        let mut text = border("┌", "┬", "┐");
+        text.push_str(&line(result.columns.iter().map(|column| Cow::Borrowed(column.as_str())).collect()));
+        text.push_str(&border("├", "┼", "┤"));
+        for row in rows {
+            text.push_str(&line(row.iter().map(|cell| options.format_cell(cell)).collect()));
+        }
+        text.push_str(&border("└", "┴", "┘"));
+        if let Some(max) = options.max_rows.filter(|max| result.rows.len() > *max) {
+            text.push_str(&format!("\n... and {} more rows (showing first {})\n", result.rows.len() - max, max));
+        }
+        text.push_str(&format!("\nRows returned: {}\n", result.row_count));
+        text
+    }
+
+    fn generated(rows: usize) -> QueryResult {
+        QueryResult::from_rows(
+            &["id", "name", "amount", "created_at"],
+            (0..rows)
+                .map(|i| {
+                    vec![
+                        i.to_string(),
+                        "x".repeat(i % 37),
+                        if i % 5 == 0 { "NULL".to_string() } else { format!("{}.{:02}", i * 13, i % 100) },
+                        format!("2024-01-{:02} 10:{:02}:00", i % 28 + 1, i % 60),
+                    ]
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn small_results_render_exactly_as_before() {
+        let result = QueryResult::from_rows(
+            &["id", "name"],
+            vec![
+                vec!["1".to_string(), "alice".to_string()],
+                vec!["22".to_string(), "bo".to_string()],
+                vec!["333".to_string(), "NULL".to_string()],
+            ],
+        );
+        assert_eq!(
+            table_text(&result, &DisplayOptions::default()),
+            "┌─────┬───────┐\n\
+             │ id  │ name  │\n\
+             ├─────┼───────┤\n\
+             │ 1   │ alice │\n\
+             │ 22  │ bo    │\n\
+             │ 333 │ NULL  │\n\
+             └─────┴───────┘\n\
+             \n\
+             Rows returned: 3\n"
+        );
+
+        for rows in [1, 2, 17, 999, WIDTH_SAMPLE_ROWS] {
+            let result = generated(rows);
+            let options = DisplayOptions::default();
+            assert_eq!(table_text(&result, &options), unsampled_table(&result, &options), "{rows} rows");
+            let options = DisplayOptions {
+                max_rows: Some(10),
+                ..DisplayOptions::default()
+            };
+            assert_eq!(table_text(&result, &options), unsampled_table(&result, &options), "{rows} rows, max 10");
+        }
+    }
+
+    #[test]
+    fn cells_past_the_width_sample_are_cut_to_fit() {
+        let mut result = generated(WIDTH_SAMPLE_ROWS + 1);
+        result.rows[WIDTH_SAMPLE_ROWS][1] = "y".repeat(200);
+        let text = table_text(&result, &DisplayOptions::default());
+        let widths: Vec<usize> = text.lines().take(WIDTH_SAMPLE_ROWS + 4).map(|line| line.chars().count()).collect();
+        assert!(widths.iter().all(|width| *width == widths[0]));
+        // The widest sampled name is 36 bytes; the ellipsis takes three of them
+        assert!(text.contains(&format!("│ {}…   │", "y".repeat(33))));
+        assert!(!text.contains(&"y".repeat(34)));
+    }
+
+    /// Counts what is written to it: calls, bytes and lines.
+    #[derive(Default)]
+    struct CountingWriter {
+        writes: usize,
+        bytes: usize,
+        lines: usize,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            self.bytes += buf.len();
+            self.lines += buf.iter().filter(|byte| **byte == b'\n').count();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn large_results_render_in_linear_time() {
+        // The work done per row must not grow with the rows: the writes and
+        // bytes per line may only grow by a little, as the ids get longer
+        let render = |rows: usize| {
+            let mut out = CountingWriter::default();
+            write_table(&mut out, &generated(rows), &DisplayOptions::default()).unwrap();
+            assert_eq!(out.lines, rows + 6, "one line per row plus the header, borders and footer");
+            out
+        };
+        let small = render(20_000);
+        let large = render(200_000);
+        let per_line = |count: usize, out: &CountingWriter| count as f64 / out.lines as f64;
+        let (small_writes, large_writes) = (per_line(small.writes, &small), per_line(large.writes, &large));
+        assert!(large_writes < small_writes * 1.2, "{small_writes:.1} writes per line, then {large_writes:.1}");
+        let (small_bytes, large_bytes) = (per_line(small.bytes, &small), per_line(large.bytes, &large));
+        assert!(large_bytes < small_bytes * 1.2, "{small_bytes:.1} bytes per line, then {large_bytes:.1}");
+
+        // Widths come from the first rows only, so a longer cell further
+        // down is cut to fit rather than widening every line
+        let mut result = generated(WIDTH_SAMPLE_ROWS + 10);
+        result.rows.last_mut().unwrap()[1] = "y".repeat(200);
+        let mut out = Vec::new();
+        write_table(&mut out, &result, &DisplayOptions::default()).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let widths: Vec<usize> = text
+            .lines()
+            .filter(|line| line.starts_with(['┌', '│', '├', '└']))
+            .map(|line| line.chars().count())
+            .collect();
+        assert_eq!(widths.len(), result.rows.len() + 4);
+        assert!(widths.iter().all(|width| *width == widths[0]), "lines of differing widths");
+        assert!(!text.contains(&"y".repeat(100)));
+    }
+}
@@ -0,0 +1,370 @@
+//! Output formats for query results, each a [`Formatter`] registered by
+//! name in [`FORMATTERS`]. `export <name>`, the `export_format` setting and
+//! `--format` look formats up there, so adding one takes an implementation
+//! and a [`Registration`] in the list.
+
+use anyhow::Result;
+use csv::Writer;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::database::QueryResult;
+use crate::format::{self, CsvOptions, DisplayOptions};
+
+/// Where a formatter writes: an export file or stdout.
+pub type Output = Box<dyn Write + Send>;
+
+/// Writes rows in one format. Rows arrive one at a time between
+/// [`Formatter::begin`] and [`Formatter::end`], so formats that can stream
+/// never hold the whole result.
+pub trait Formatter: Send {
+    /// Called with the columns before the first row. A streamed export
+    /// whose query returns no rows never calls it.
+    fn begin(&mut self, _columns: &[String]) -> Result<()> {
+        Ok(())
+    }
+
+    fn write(&mut self, columns: &[String], row: &[String]) -> Result<()>;
+
+    /// Adds a note after the rows, for formats that have comments.
+    fn comment(&mut self, _comment: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Completes the output and flushes it.
+    fn end(&mut self) -> Result<()>;
+}
+
+/// A format as offered by name.
+#[derive(Debug)]
+pub struct Registration {
+    pub name: &'static str,
+    /// File extensions that pick the format when `export` names none.
+    pub extensions: &'static [&'static str],
+    pub description: &'static str,
+    /// Whether rows can be written as they arrive. Formats that can't are
+    /// given a fetched result instead.
+    pub streams: bool,
+    pub create: fn(Output, CsvOptions) -> Result<Box<dyn Formatter>>,
+}
+
+/// Names are unique, so they tell formats apart.
+impl PartialEq for Registration {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+pub static CSV: Registration = Registration {
+    name: "csv",
+    extensions: &["csv"],
+    description: "Comma-separated values with a header row",
+    streams: true,
+    create: |out, csv| Ok(Box::new(CsvFormatter::new(out, csv)?)),
+};
+
+pub static JSON: Registration = Registration {
+    name: "json",
+    extensions: &["json"],
+    description: "An array of objects keyed by column name",
+    streams: true,
+    create: |out, _| Ok(Box::new(JsonFormatter { out, rows: 0 })),
+};
+
+static TABLE: Registration = Registration {
+    name: "table",
+    extensions: &["txt"],
+    description: "The result grid as plain text",
+    // Every value is measured before the first line is drawn
+    streams: false,
+    create: |out, _| {
+        Ok(Box::new(TableFormatter {
+            out,
+            columns: Vec::new(),
+            rows: Vec::new(),
+        }))
+    },
+};
+
+static YAML: Registration = Registration {
+    name: "yaml",
+    extensions: &["yaml", "yml"],
+    description: "A YAML list with one mapping per row",
+    streams: true,
+    create: |out, _| Ok(Box::new(YamlFormatter { out, rows: 0 })),
+};
+
+/// Every format, in the order they are listed.
+pub static FORMATTERS: &[&Registration] = &[&CSV, &JSON, &TABLE, &YAML];
+
+/// The format called `name`, ignoring case.
+///
+/// ```
+/// use qgo::formatter;
+///
+/// assert_eq!(formatter::find("YAML").map(|format| format.name), Some("yaml"));
+/// assert_eq!(formatter::for_extension("out/rows.yml").map(|format| format.name), Some("yaml"));
+/// assert!(formatter::find("xml").is_none());
+/// ```
+pub fn find(name: &str) -> Option<&'static Registration> {
+    FORMATTERS.iter().copied().find(|format| format.name.eq_ignore_ascii_case(name))
+}
+
+/// The format a file name's extension stands for.
+pub fn for_extension(path: &str) -> Option<&'static Registration> {
+    let extension = std::path::Path::new(path).extension()?.to_str()?;
+    FORMATTERS
+        .iter()
+        .copied()
+        .find(|format| format.extensions.iter().any(|known| known.eq_ignore_ascii_case(extension)))
+}
+
+pub fn names() -> Vec<&'static str> {
+    FORMATTERS.iter().map(|format| format.name).collect()
+}
+
+impl Registration {
+    /// Writes every row of `result` to `out`, followed by `note` where the
+    /// format has comments.
+    pub fn write_result(&self, out: Output, result: &QueryResult, csv: CsvOptions, note: Option<&str>) -> Result<()> {
+        let mut formatter = (self.create)(out, csv)?;
+        formatter.begin(&result.columns)?;
+        for row in &result.rows {
+            formatter.write(&result.columns, row)?;
+        }
+        if let Some(note) = note {
+            formatter.comment(note)?;
+        }
+        formatter.end()
+    }
+}
+
+/// What a [`Tally`] has seen pass through it.
+#[derive(Default)]
+pub(crate) struct TallyState {
+    bytes: AtomicU64,
+    ends_line: AtomicBool,
+}
+
+impl TallyState {
+    pub(crate) fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn ends_line(&self) -> bool {
+        self.ends_line.load(Ordering::Relaxed)
+    }
+}
+
+/// Passes writes through, counting the bytes and noting whether the last
+/// of them ended a line.
+pub(crate) struct Tally<W> {
+    inner: W,
+    state: Arc<TallyState>,
+}
+
+impl<W: Write> Tally<W> {
+    pub(crate) fn new(inner: W) -> (Self, Arc<TallyState>) {
+        let state = Arc::new(TallyState::default());
+        (Self { inner, state: state.clone() }, state)
+    }
+}
+
+impl<W: Write> Write for Tally<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if written > 0 {
+            self.state.bytes.fetch_add(written as u64, Ordering::Relaxed);
+            self.state.ends_line.store(buf[written - 1] == b'\n', Ordering::Relaxed);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct CsvFormatter {
+    /// Taken while a comment is written past it.
+    writer: Option<Writer<Output>>,
+    csv: CsvOptions,
+}
+
+impl CsvFormatter {
+    fn new(mut out: Output, csv: CsvOptions) -> Result<Self> {
+        if csv.bom {
+            out.write_all(format::UTF8_BOM)?;
+        }
+        Ok(Self {
+            writer: Some(csv.builder().from_writer(out)),
+            csv,
+        })
+    }
+
+    fn writer(&mut self) -> &mut Writer<Output> {
+        self.writer.as_mut().expect("only taken within comment")
+    }
+}
+
+impl Formatter for CsvFormatter {
+    fn begin(&mut self, columns: &[String]) -> Result<()> {
+        self.writer().write_record(columns)?;
+        Ok(())
+    }
+
+    fn write(&mut self, _columns: &[String], row: &[String]) -> Result<()> {
+        self.writer().write_record(row)?;
+        Ok(())
+    }
+
+    fn comment(&mut self, comment: &str) -> Result<()> {
+        let writer = self.writer.take().expect("only taken within comment");
+        let mut out = writer.into_inner().map_err(|e| e.into_error())?;
+        write!(out, "# {}{}", comment, self.csv.line_ending())?;
+        self.writer = Some(self.csv.builder().from_writer(out));
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.writer().flush()?;
+        Ok(())
+    }
+}
+
+/// Written as `serde_json::to_string_pretty` would write the whole array.
+struct JsonFormatter {
+    out: Output,
+    rows: u64,
+}
+
+impl Formatter for JsonFormatter {
+    fn write(&mut self, columns: &[String], row: &[String]) -> Result<()> {
+        // Indent each object to sit inside the array
+        let object = serde_json::to_string_pretty(&json_row(columns, row))?.replace('\n', "\n  ");
+        write!(self.out, "{}\n  {}", if self.rows == 0 { "[" } else { "," }, object)?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.out.write_all(if self.rows == 0 { b"[]" } else { b"\n]" })?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+fn json_row(columns: &[String], row: &[String]) -> serde_json::Map<String, serde_json::Value> {
+    columns
+        .iter()
+        .zip(row)
+        .map(|(column, value)| (column.clone(), serde_json::Value::String(value.clone())))
+        .collect()
+}
+
+/// `result` as one JSON document for `--format json`: the columns with
+/// their types (`null` where the driver gave none), then the rows as
+/// [`JSON`] writes them. Exports stay an array of rows.
+///
+/// ```
+/// use qgo::database::QueryResult;
+/// use qgo::formatter;
+///
+/// let mut result = QueryResult::from_rows(&["id"], vec![vec!["7".to_string()]]);
+/// result.column_types = vec!["int8".to_string()];
+/// assert_eq!(
+///     formatter::json_document(&result),
+///     serde_json::json!({
+///         "columns": [{ "name": "id", "type": "int8" }],
+///         "rows": [{ "id": "7" }],
+///     })
+/// );
+/// ```
+pub fn json_document(result: &QueryResult) -> serde_json::Value {
+    let columns: Vec<serde_json::Value> = result
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, name)| serde_json::json!({ "name": name, "type": result.column_types.get(i) }))
+        .collect();
+    let rows: Vec<serde_json::Value> = result
+        .rows
+        .iter()
+        .map(|row| serde_json::Value::Object(json_row(&result.columns, row)))
+        .collect();
+    serde_json::json!({ "columns": columns, "rows": rows })
+}
+
+/// The grid [`format::display_table`] draws, with every row and full values.
+struct TableFormatter {
+    out: Output,
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Formatter for TableFormatter {
+    fn begin(&mut self, columns: &[String]) -> Result<()> {
+        self.columns = columns.to_vec();
+        Ok(())
+    }
+
+    fn write(&mut self, _columns: &[String], row: &[String]) -> Result<()> {
+        self.rows.push(row.to_vec());
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        let result = QueryResult {
+            columns: std::mem::take(&mut self.columns),
+            column_types: Vec::new(),
+            row_count: self.rows.len(),
+            rows: std::mem::take(&mut self.rows),
+            warnings: Vec::new(),
+        };
+        format::write_table(&mut self.out, &result, &DisplayOptions::default())?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Values are double-quoted, which YAML reads with JSON's escapes, so
+/// they stay strings as in the other formats.
+struct YamlFormatter {
+    out: Output,
+    rows: u64,
+}
+
+impl Formatter for YamlFormatter {
+    fn write(&mut self, columns: &[String], row: &[String]) -> Result<()> {
+        for (i, (column, value)) in columns.iter().zip(row).enumerate() {
+            let key = if is_plain_key(column) {
+                column.clone()
+            } else {
+                serde_json::to_string(column)?
+            };
+            let indent = if i == 0 { "- " } else { "  " };
+            writeln!(self.out, "{}{}: {}", indent, key, serde_json::to_string(value)?)?;
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        if self.rows == 0 {
+            self.out.write_all(b"[]\n")?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Whether `key` can be written as a YAML mapping key without quotes.
+fn is_plain_key(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !matches!(
+            key.to_ascii_lowercase().as_str(),
+            "true" | "false" | "yes" | "no" | "on" | "off" | "null" | "y" | "n"
+        )
+}
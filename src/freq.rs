@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::chart;
+use crate::database::{self, Database, QueryResult};
+use crate::error::QgoError;
+
+/// At most this many of the most frequent values are shown.
+pub const FREQ_LIMIT: usize = 50;
+
+/// Values longer than this are cut short so the bars keep most of the width.
+const MAX_VALUE_WIDTH: usize = 30;
+
+/// How often each value of one column occurs, most frequent first. NULL is
+/// a value of its own.
+pub struct Frequencies {
+    pub column: String,
+    /// The most frequent values with their counts, at most [`FREQ_LIMIT`].
+    pub buckets: Vec<(String, u64)>,
+    /// Rows counted, including those whose value is not in `buckets`.
+    pub total: u64,
+    /// Distinct values, including NULL and those not in `buckets`.
+    pub distinct: u64,
+}
+
+/// Counts the values of `column` in `table` with a `GROUP BY` on the server.
+/// The totals are only queried when more values exist than are shown.
+pub async fn table_frequencies(database: &mut Database, table: &str, column: &str) -> Result<Frequencies> {
+    let db_type = database.get_connection().db_type.clone();
+    let quoted_table = table
+        .split('.')
+        .map(|part| database::quote_ident(&db_type, part))
+        .collect::<Result<Vec<_>>>()?
+        .join(".");
+    let quoted_column = database::quote_ident(&db_type, column)?;
+
+    let result = database
+        .execute_query(&format!(
+            "SELECT {column}, COUNT(*) FROM {table} GROUP BY {column} ORDER BY COUNT(*) DESC, 1 LIMIT {limit}",
+            column = quoted_column,
+            table = quoted_table,
+            limit = FREQ_LIMIT
+        ))
+        .await?;
+    let buckets = result
+        .rows
+        .iter()
+        .map(|row| Ok((row[0].clone(), parse_count(&row[1])?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let (total, distinct) = if buckets.len() < FREQ_LIMIT {
+        (buckets.iter().map(|(_, count)| count).sum(), buckets.len() as u64)
+    } else {
+        // COUNT(DISTINCT) leaves NULL out, so it is added back when present
+        let totals = database
+            .execute_query(&format!(
+                "SELECT COUNT(*), COUNT({column}), COUNT(DISTINCT {column}) FROM {table}",
+                column = quoted_column,
+                table = quoted_table
+            ))
+            .await?;
+        let row = totals
+            .rows
+            .first()
+            .ok_or_else(|| QgoError::InvalidQuery("The count query returned no rows".to_string()))?;
+        let (total, non_null, distinct) = (parse_count(&row[0])?, parse_count(&row[1])?, parse_count(&row[2])?);
+        (total, distinct + u64::from(total > non_null))
+    };
+
+    Ok(Frequencies {
+        column: column.to_string(),
+        buckets,
+        total,
+        distinct,
+    })
+}
+
+/// Counts the values of `column` in `result` without querying anything.
+pub fn result_frequencies(result: &QueryResult, column: &str) -> Result<Frequencies> {
+    let index = result
+        .columns
+        .iter()
+        .position(|name| name == column)
+        .or_else(|| result.columns.iter().position(|name| name.eq_ignore_ascii_case(column)))
+        .ok_or_else(|| {
+            QgoError::InvalidQuery(format!(
+                "The last result has no column '{}'; its columns are {}",
+                column,
+                result.columns.join(", ")
+            ))
+        })?;
+
+    let mut counts: HashMap<&str, u64> = HashMap::new();
+    for row in &result.rows {
+        *counts.entry(row[index].as_str()).or_default() += 1;
+    }
+    let distinct = counts.len() as u64;
+    let mut buckets: Vec<(String, u64)> = counts.into_iter().map(|(value, count)| (value.to_string(), count)).collect();
+    buckets.sort_by(|(a, count_a), (b, count_b)| count_b.cmp(count_a).then_with(|| a.cmp(b)));
+    buckets.truncate(FREQ_LIMIT);
+
+    Ok(Frequencies {
+        column: result.columns[index].clone(),
+        buckets,
+        total: result.rows.len() as u64,
+        distinct,
+    })
+}
+
+/// Draws one line per value: the value, its count, its share of all rows
+/// and a bar scaled to the most frequent value, `width` characters wide in
+/// all. A note follows when values were left out.
+pub fn render(frequencies: &Frequencies, width: usize) -> String {
+    if frequencies.buckets.is_empty() {
+        return format!("{}: no rows\n", frequencies.column);
+    }
+
+    let value_width = frequencies
+        .buckets
+        .iter()
+        .map(|(value, _)| value.chars().count())
+        .chain([frequencies.column.chars().count()])
+        .max()
+        .unwrap_or(0)
+        .min(MAX_VALUE_WIDTH);
+    let count_width = frequencies.buckets[0].1.to_string().len().max("count".len());
+    let bar_width = width.saturating_sub(value_width + count_width + 14).max(10);
+    let largest = frequencies.buckets[0].1;
+
+    let mut text = format!(
+        "{:<value_width$} │ {:>count_width$} {:>7}\n",
+        chart::fit_label(&frequencies.column, value_width),
+        "count",
+        "%",
+        value_width = value_width,
+        count_width = count_width
+    );
+    for (value, count) in &frequencies.buckets {
+        text.push_str(&format!(
+            "{:<value_width$} │ {:>count_width$} {:>6.2}% {}\n",
+            chart::fit_label(value, value_width),
+            count,
+            percent(*count, frequencies.total),
+            chart::bar(*count as f64 / largest as f64, bar_width),
+            value_width = value_width,
+            count_width = count_width
+        ));
+    }
+
+    let shown = frequencies.buckets.len() as u64;
+    if frequencies.distinct > shown {
+        let covered: u64 = frequencies.buckets.iter().map(|(_, count)| count).sum();
+        text.push_str(&format!(
+            "{} distinct values; {} less frequent ones not shown ({:.2}% of {} rows)\n",
+            frequencies.distinct,
+            frequencies.distinct - shown,
+            percent(frequencies.total - covered, frequencies.total),
+            frequencies.total
+        ));
+    } else {
+        text.push_str(&format!("{} distinct values in {} rows\n", frequencies.distinct, frequencies.total));
+    }
+    text
+}
+
+fn percent(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 * 100.0 / total as f64
+    }
+}
+
+fn parse_count(value: &str) -> Result<u64> {
+    value
+        .parse()
+        .map_err(|_| QgoError::InvalidQuery(format!("Expected a count, got '{}'", value)).into())
+}
@@ -0,0 +1,457 @@
+use anyhow::Result;
+use console::style;
+use serde::{Deserialize, Serialize};
+use sqlx::any::AnyArguments;
+use sqlx::query::Query;
+use sqlx::Any;
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+
+use crate::atomic;
+use crate::config::DatabaseType;
+use crate::database::{self, quote_ident, Database, QueryResult};
+use crate::error::QgoError;
+use crate::ui::prompts;
+use qgo::format::{self, DisplayOptions};
+
+/// Rows per INSERT statement are chosen so a batch stays under this many bind parameters.
+const MAX_BATCH_PARAMETERS: usize = 900;
+const MAX_BATCH_ROWS: usize = 500;
+const PROGRESS_EVERY_ROWS: usize = 10_000;
+/// Rows read from the file to show and check before importing.
+const PREVIEW_ROWS: usize = 20;
+
+#[derive(Debug, Clone, Copy)]
+enum ColumnKind {
+    Integer,
+    Float,
+    Boolean,
+    Text,
+}
+
+impl ColumnKind {
+    fn from_type_name(type_name: &str) -> Self {
+        let lower = type_name.to_lowercase();
+        let base = lower
+            .split(['(', ' '])
+            .next()
+            .unwrap_or("");
+
+        if lower == "tinyint(1)" || base == "bool" || base == "boolean" {
+            return ColumnKind::Boolean;
+        }
+
+        match base {
+            "int" | "integer" | "bigint" | "smallint" | "tinyint" | "mediumint" | "int2"
+            | "int4" | "int8" | "serial" | "bigserial" | "smallserial" => ColumnKind::Integer,
+            "real" | "float" | "double" | "decimal" | "numeric" | "float4" | "float8" => {
+                ColumnKind::Float
+            }
+            _ => ColumnKind::Text,
+        }
+    }
+}
+
+enum ImportValue {
+    Integer(Option<i64>),
+    Float(Option<f64>),
+    Boolean(Option<bool>),
+    Text(Option<String>),
+}
+
+struct TargetColumn {
+    /// The position of the CSV field loaded into this column.
+    field: usize,
+    name: String,
+    type_name: String,
+    kind: ColumnKind,
+}
+
+pub struct ImportSummary {
+    pub rows_inserted: usize,
+    pub skipped_lines: Vec<u64>,
+}
+
+pub struct ImportOptions<'a> {
+    /// Skip rows with values that can't be converted instead of aborting.
+    pub skip_errors: bool,
+    /// Stop after the preview.
+    pub dry_run: bool,
+    /// Where the header-to-column mapping is read from, or saved to when
+    /// the file doesn't exist yet.
+    pub mapping: Option<&'a Path>,
+}
+
+/// Which table column each CSV header is loaded into; `None` skips it.
+/// Kept as JSON for `--mapping`.
+#[derive(Serialize, Deserialize)]
+struct ColumnMapping {
+    columns: BTreeMap<String, Option<String>>,
+}
+
+/// Imports a CSV file with a header row into `table`, matching CSV headers to
+/// table columns by name or by the `--mapping` file. Headers without a
+/// column are resolved at the terminal (load into another column, skip,
+/// abort). A preview of the mapping and of the first rows comes first, and
+/// at a terminal the import only starts once confirmed. All rows are
+/// inserted in a single transaction; a value that cannot be converted aborts
+/// the import unless `skip_errors` is set. Returns `None` when nothing was
+/// imported because of `dry_run` or a declined confirmation.
+pub async fn import_csv(
+    database: &Database,
+    file_path: &str,
+    table: &str,
+    options: ImportOptions<'_>,
+) -> Result<Option<ImportSummary>> {
+    let connection = database.get_connection();
+    if !options.dry_run {
+        database::check_not_replica(connection)?;
+    }
+    if !connection.allow_writes && !options.dry_run {
+        return Err(QgoError::Import(format!(
+            "connection '{}' is read-only; enable writes for it to use \\import",
+            connection.name
+        ))
+        .into());
+    }
+    let db_type = connection.db_type.clone();
+
+    let open = || {
+        csv::ReaderBuilder::new()
+            .has_headers(true)
+            .from_path(file_path)
+            .map_err(|e| QgoError::Import(format!("cannot read '{}': {}", file_path, e)))
+    };
+    let mut reader = open()?;
+
+    let table_columns = database.get_column_types(table).await?;
+    if table_columns.is_empty() {
+        return Err(QgoError::Import(format!("table '{}' not found or has no columns", table)).into());
+    }
+
+    let headers = reader.headers()?.clone();
+    let saved = match options.mapping.filter(|path| path.exists()) {
+        Some(path) => Some(read_mapping(path)?),
+        None => None,
+    };
+    let targets = map_columns(&headers, &table_columns, table, saved.as_ref())?;
+    if targets.is_empty() {
+        return Err(QgoError::Import("every CSV column is skipped; there is nothing to import".to_string()).into());
+    }
+    if let Some(path) = options.mapping.filter(|_| saved.is_none()) {
+        write_mapping(path, &headers, &targets)?;
+        println!("Saved the column mapping to {}.", path.display());
+    }
+
+    preview(&mut reader, &headers, &targets, &table_columns)?;
+    if options.dry_run {
+        println!("Dry run; nothing was imported.");
+        return Ok(None);
+    }
+    if std::io::stdin().is_terminal() && !prompts::confirm_default(&format!("Import into '{}'?", table), true) {
+        println!("Cancelled; nothing was imported.");
+        return Ok(None);
+    }
+    prompts::confirm_production_write(connection)?;
+    let mut reader = open()?;
+
+    let batch_rows = (MAX_BATCH_PARAMETERS / targets.len()).clamp(1, MAX_BATCH_ROWS);
+    let mut summary = ImportSummary {
+        rows_inserted: 0,
+        skipped_lines: Vec::new(),
+    };
+    let mut batch: Vec<Vec<ImportValue>> = Vec::with_capacity(batch_rows);
+    let mut tx = database.begin().await?;
+
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+
+        match convert_record(&record, headers.len(), &targets) {
+            Ok(values) => batch.push(values),
+            Err(message) if options.skip_errors => {
+                eprintln!("Skipping line {}: {}", line, message);
+                summary.skipped_lines.push(line);
+                continue;
+            }
+            Err(message) => {
+                return Err(QgoError::Import(format!(
+                    "line {}: {} (no rows were imported; use --skip-errors to skip bad rows)",
+                    line, message
+                ))
+                .into());
+            }
+        }
+
+        if batch.len() == batch_rows {
+            insert_batch(&mut tx, &db_type, table, &targets, std::mem::take(&mut batch)).await?;
+            let before = summary.rows_inserted;
+            summary.rows_inserted += batch_rows;
+            if summary.rows_inserted / PROGRESS_EVERY_ROWS > before / PROGRESS_EVERY_ROWS {
+                print!("\rImported {} rows...", summary.rows_inserted);
+                let _ = std::io::stdout().flush();
+            }
+        }
+    }
+
+    if !batch.is_empty() {
+        summary.rows_inserted += batch.len();
+        insert_batch(&mut tx, &db_type, table, &targets, batch).await?;
+    }
+
+    tx.commit().await.map_err(QgoError::Database)?;
+    if summary.rows_inserted >= PROGRESS_EVERY_ROWS {
+        println!();
+    }
+
+    Ok(Some(summary))
+}
+
+/// Works out the target column of each CSV header: from `saved` when
+/// given, otherwise by name. At a terminal, a header without a match can be
+/// loaded into a column no other header uses, skipped, or the import
+/// aborted; without one it is an error.
+fn map_columns(
+    headers: &csv::StringRecord,
+    table_columns: &[(String, String)],
+    table: &str,
+    saved: Option<&ColumnMapping>,
+) -> Result<Vec<TargetColumn>> {
+    let target = |field: usize, (name, type_name): &(String, String)| TargetColumn {
+        field,
+        name: name.clone(),
+        type_name: type_name.clone(),
+        kind: ColumnKind::from_type_name(type_name),
+    };
+    let column = |name: &str| table_columns.iter().find(|(column, _)| column.eq_ignore_ascii_case(name.trim()));
+
+    let mut targets: Vec<TargetColumn> = Vec::new();
+    let mut unmatched = Vec::new();
+    for (field, header) in headers.iter().enumerate() {
+        let mapped = match saved {
+            Some(saved) => match saved.columns.get(header) {
+                Some(Some(name)) => Some(column(name).ok_or_else(|| {
+                    QgoError::Import(format!("the mapping loads '{}' into '{}', which table '{}' doesn't have", header, name, table))
+                })?),
+                Some(None) => continue,
+                None => column(header),
+            },
+            None => column(header),
+        };
+        match mapped {
+            Some(mapped) => targets.push(target(field, mapped)),
+            None => unmatched.push((field, header)),
+        }
+    }
+
+    if !unmatched.is_empty() && !std::io::stdin().is_terminal() {
+        let names: Vec<&str> = unmatched.iter().map(|(_, header)| *header).collect();
+        return Err(QgoError::Import(format!(
+            "CSV columns not present in table '{}': {}; pass --mapping to map or skip them",
+            table,
+            names.join(", ")
+        ))
+        .into());
+    }
+    for (field, header) in unmatched {
+        let free: Vec<&(String, String)> = table_columns
+            .iter()
+            .filter(|(name, _)| !targets.iter().any(|target| target.name == *name))
+            .collect();
+        let mut items: Vec<String> = free.iter().map(|(name, type_name)| format!("Load into {} ({})", name, type_name)).collect();
+        items.push("Skip this column".to_string());
+        items.push("Abort the import".to_string());
+        let items: Vec<&str> = items.iter().map(String::as_str).collect();
+        match prompts::select(&format!("Table '{}' has no column '{}'", table, header), &items) {
+            Some(choice) if choice < free.len() => targets.push(target(field, free[choice])),
+            Some(choice) if choice == free.len() => {}
+            _ => return Err(QgoError::Import("aborted; nothing was imported".to_string()).into()),
+        }
+    }
+
+    for (index, target) in targets.iter().enumerate() {
+        if targets[..index].iter().any(|other| other.name == target.name) {
+            return Err(QgoError::Import(format!("more than one CSV column is loaded into '{}'", target.name)).into());
+        }
+    }
+    targets.sort_by_key(|target| target.field);
+    Ok(targets)
+}
+
+fn read_mapping(path: &Path) -> Result<ColumnMapping> {
+    let content = std::fs::read_to_string(path)?;
+    serde_json::from_str(&content)
+        .map_err(|e| QgoError::Import(format!("invalid mapping file '{}': {}", path.display(), e)).into())
+}
+
+fn write_mapping(path: &Path, headers: &csv::StringRecord, targets: &[TargetColumn]) -> Result<()> {
+    let columns = headers
+        .iter()
+        .enumerate()
+        .map(|(field, header)| {
+            let column = targets.iter().find(|target| target.field == field).map(|target| target.name.clone());
+            (header.to_string(), column)
+        })
+        .collect();
+    let json = serde_json::to_string_pretty(&ColumnMapping { columns })?;
+    atomic::write(path, json.as_bytes(), false)?;
+    Ok(())
+}
+
+/// Shows where each CSV column goes, the table columns left to their
+/// defaults, and which of the first [`PREVIEW_ROWS`] rows would fail to
+/// convert.
+fn preview(
+    reader: &mut csv::Reader<std::fs::File>,
+    headers: &csv::StringRecord,
+    targets: &[TargetColumn],
+    table_columns: &[(String, String)],
+) -> Result<()> {
+    let records: Vec<csv::StringRecord> = reader.records().take(PREVIEW_ROWS).collect::<csv::Result<_>>()?;
+
+    let mut rows: Vec<Vec<String>> = headers
+        .iter()
+        .enumerate()
+        .map(|(field, header)| {
+            let target = targets.iter().find(|target| target.field == field);
+            vec![
+                header.to_string(),
+                target.map_or("(skipped)".to_string(), |target| target.name.clone()),
+                target.map_or(String::new(), |target| target.type_name.clone()),
+                records.first().and_then(|record| record.get(field)).unwrap_or_default().to_string(),
+            ]
+        })
+        .collect();
+    rows.extend(
+        table_columns
+            .iter()
+            .filter(|(name, _)| !targets.iter().any(|target| target.name == *name))
+            .map(|(name, type_name)| vec!["(not in file)".to_string(), name.clone(), type_name.clone(), String::new()]),
+    );
+    let mapping = QueryResult::from_rows(&["CSV column", "Table column", "Type", "First value"], rows);
+    format::display_table(&mapping, &DisplayOptions::default());
+
+    let failures: Vec<String> = records
+        .iter()
+        .filter_map(|record| {
+            let line = record.position().map_or(0, |position| position.line());
+            convert_record(record, headers.len(), targets)
+                .err()
+                .map(|message| format!("  line {}: {}", line, message))
+        })
+        .collect();
+    if failures.is_empty() {
+        println!("The first {} rows convert cleanly.", records.len());
+    } else {
+        println!(
+            "{}",
+            style(format!("{} of the first {} rows would fail:", failures.len(), records.len())).yellow()
+        );
+        for failure in failures {
+            println!("{}", failure);
+        }
+    }
+    Ok(())
+}
+
+fn convert_record(
+    record: &csv::StringRecord,
+    fields: usize,
+    targets: &[TargetColumn],
+) -> std::result::Result<Vec<ImportValue>, String> {
+    if record.len() != fields {
+        return Err(format!(
+            "expected {} fields but found {}",
+            fields,
+            record.len()
+        ));
+    }
+
+    targets
+        .iter()
+        .map(|target| convert_value(target, &record[target.field]))
+        .collect()
+}
+
+fn convert_value(target: &TargetColumn, field: &str) -> std::result::Result<ImportValue, String> {
+    // Exports write NULL for missing values, so accept it back; empty fields are NULL for non-text columns
+    let is_null = field == "NULL" || (field.is_empty() && !matches!(target.kind, ColumnKind::Text));
+    let invalid = || format!("'{}' is not a valid {} for column '{}'", field, target.type_name, target.name);
+
+    let value = match target.kind {
+        ColumnKind::Integer if is_null => ImportValue::Integer(None),
+        ColumnKind::Integer => ImportValue::Integer(Some(field.trim().parse().map_err(|_| invalid())?)),
+        ColumnKind::Float if is_null => ImportValue::Float(None),
+        ColumnKind::Float => ImportValue::Float(Some(field.trim().parse().map_err(|_| invalid())?)),
+        ColumnKind::Boolean if is_null => ImportValue::Boolean(None),
+        ColumnKind::Boolean => ImportValue::Boolean(Some(
+            match field.trim().to_lowercase().as_str() {
+                "true" | "t" | "1" | "yes" | "y" => true,
+                "false" | "f" | "0" | "no" | "n" => false,
+                _ => return Err(invalid()),
+            },
+        )),
+        ColumnKind::Text if is_null => ImportValue::Text(None),
+        ColumnKind::Text => ImportValue::Text(Some(field.to_string())),
+    };
+
+    Ok(value)
+}
+
+async fn insert_batch(
+    tx: &mut sqlx::Transaction<'static, Any>,
+    db_type: &DatabaseType,
+    table: &str,
+    targets: &[TargetColumn],
+    rows: Vec<Vec<ImportValue>>,
+) -> Result<()> {
+    let sql = insert_statement(db_type, table, targets, rows.len())?;
+    let mut query: Query<'_, Any, AnyArguments<'_>> = sqlx::query(&sql);
+
+    for value in rows.into_iter().flatten() {
+        query = match value {
+            ImportValue::Integer(v) => query.bind(v),
+            ImportValue::Float(v) => query.bind(v),
+            ImportValue::Boolean(v) => query.bind(v),
+            ImportValue::Text(v) => query.bind(v),
+        };
+    }
+
+    query.execute(&mut **tx).await.map_err(QgoError::Database)?;
+    Ok(())
+}
+
+fn insert_statement(db_type: &DatabaseType, table: &str, targets: &[TargetColumn], row_count: usize) -> Result<String> {
+    let column_list = targets
+        .iter()
+        .map(|t| quote_ident(db_type, &t.name))
+        .collect::<Result<Vec<String>>>()?;
+    let mut placeholder = 0;
+    let mut value_rows = Vec::with_capacity(row_count);
+
+    for _ in 0..row_count {
+        let row = targets
+            .iter()
+            .map(|target| {
+                placeholder += 1;
+                Ok(match (db_type, target.kind) {
+                    // Postgres will not implicitly cast a text parameter to e.g. timestamp or uuid
+                    (DatabaseType::PostgreSQL, ColumnKind::Text) => {
+                        format!("${}::{}", placeholder, quote_ident(db_type, &target.type_name)?)
+                    }
+                    (DatabaseType::PostgreSQL, _) => format!("${}", placeholder),
+                    _ => "?".to_string(),
+                })
+            })
+            .collect::<Result<Vec<String>>>()?;
+        value_rows.push(format!("({})", row.join(", ")));
+    }
+
+    Ok(format!(
+        "INSERT INTO {} ({}) VALUES {}",
+        quote_ident(db_type, table)?,
+        column_list.join(", "),
+        value_rows.join(", ")
+    ))
+}
@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Prompts and streaming exports that currently handle Ctrl+C themselves.
+static CLAIMS: AtomicUsize = AtomicUsize::new(0);
+
+/// Interrupts handled by a claimant since the watcher last looked.
+static CONSUMED: AtomicUsize = AtomicUsize::new(0);
+
+/// How long the watcher waits for a claimant to report an interrupt before
+/// treating it as unhandled.
+const GRACE: Duration = Duration::from_millis(200);
+
+/// Takes over Ctrl+C for the process. It still exits qgo as before, except
+/// while a [`Claim`] is held: dialoguer prompts treat it as "go back" and a
+/// streaming export stops and keeps what it has written.
+pub fn install() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            tokio::time::sleep(GRACE).await;
+            if CONSUMED.swap(0, Ordering::SeqCst) == 0 && CLAIMS.load(Ordering::SeqCst) == 0 {
+                eprintln!();
+                std::process::exit(130);
+            }
+        }
+    });
+}
+
+/// Keeps Ctrl+C from exiting qgo until dropped.
+pub struct Claim(());
+
+pub fn claim() -> Claim {
+    CLAIMS.fetch_add(1, Ordering::SeqCst);
+    Claim(())
+}
+
+impl Claim {
+    /// Records that an interrupt was acted on, so the watcher doesn't exit
+    /// even if this claim has been dropped by the time it checks.
+    pub fn consume(&self) {
+        CONSUMED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        CLAIMS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
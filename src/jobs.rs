@@ -0,0 +1,218 @@
+use anyhow::Result;
+use console::style;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+use crate::error::QgoError;
+use crate::notify::{self, Printer};
+use crate::progress;
+use qgo::format;
+
+/// How far a background job has got, updated by the job as it writes.
+#[derive(Default)]
+pub struct JobProgress {
+    rows: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl JobProgress {
+    pub fn update(&self, rows: u64, bytes: u64) {
+        self.rows.store(rows, Ordering::Relaxed);
+        self.bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    fn rows(&self) -> u64 {
+        self.rows.load(Ordering::Relaxed)
+    }
+
+    fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum JobStatus {
+    Running,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+struct JobState {
+    status: JobStatus,
+    ended: Option<Instant>,
+}
+
+impl JobState {
+    fn end(&mut self, status: JobStatus) {
+        self.status = status;
+        self.ended = Some(Instant::now());
+    }
+}
+
+struct Job {
+    id: usize,
+    description: String,
+    /// The file the job writes, incomplete if it is cancelled.
+    file: PathBuf,
+    started: Instant,
+    progress: Arc<JobProgress>,
+    state: Arc<Mutex<JobState>>,
+    task: JoinHandle<()>,
+}
+
+impl Job {
+    fn status(&self) -> JobStatus {
+        self.state.lock().map_or(JobStatus::Running, |state| state.status.clone())
+    }
+
+    fn is_running(&self) -> bool {
+        matches!(self.status(), JobStatus::Running)
+    }
+
+    fn elapsed(&self) -> Duration {
+        let ended = self.state.lock().ok().and_then(|state| state.ended);
+        ended.unwrap_or_else(Instant::now).duration_since(self.started)
+    }
+}
+
+/// Exports running on tokio tasks while the prompt stays usable, numbered
+/// from 1 for the session. Each job holds its own connection; when one
+/// ends, a notice is printed above the prompt.
+pub struct Jobs {
+    jobs: Vec<Job>,
+    printer: Option<Printer>,
+}
+
+impl Jobs {
+    pub fn new(printer: Option<Printer>) -> Self {
+        Self {
+            jobs: Vec::new(),
+            printer,
+        }
+    }
+
+    /// Starts `work` on a task of its own, handing it the progress to
+    /// update. Its result is the summary printed when it finishes. Returns
+    /// the job's number.
+    pub fn spawn<F>(&mut self, description: String, file: PathBuf, work: impl FnOnce(Arc<JobProgress>) -> F) -> usize
+    where
+        F: Future<Output = Result<String>> + Send + 'static,
+    {
+        let id = self.jobs.len() + 1;
+        let progress = Arc::new(JobProgress::default());
+        let state = Arc::new(Mutex::new(JobState {
+            status: JobStatus::Running,
+            ended: None,
+        }));
+        let work = work(progress.clone());
+        let task_state = state.clone();
+        let printer = self.printer.clone();
+        let task = tokio::spawn(async move {
+            let (status, notice) = match work.await {
+                Ok(summary) => (
+                    JobStatus::Done,
+                    style(format!("[job {}] {}", id, summary)).green().to_string(),
+                ),
+                Err(e) => (
+                    JobStatus::Failed(e.to_string()),
+                    style(format!("[job {}] Failed: {}", id, e)).red().to_string(),
+                ),
+            };
+            if let Ok(mut state) = task_state.lock() {
+                state.end(status);
+            }
+            notify::print(&printer, notice);
+        });
+
+        self.jobs.push(Job {
+            id,
+            description,
+            file,
+            started: Instant::now(),
+            progress,
+            state,
+            task,
+        });
+        id
+    }
+
+    pub fn running(&self) -> usize {
+        self.jobs.iter().filter(|job| job.is_running()).count()
+    }
+
+    /// Prints every job of the session with its progress.
+    pub fn print(&self) {
+        if self.jobs.is_empty() {
+            println!("No background jobs. Start one with export --background or \\bg export ...");
+            return;
+        }
+        for job in &self.jobs {
+            let status = match job.status() {
+                JobStatus::Running => style("running".to_string()).cyan(),
+                JobStatus::Done => style("done".to_string()).green(),
+                JobStatus::Failed(error) => style(format!("failed: {}", error)).red(),
+                JobStatus::Cancelled => style("cancelled".to_string()).yellow(),
+            };
+            println!(
+                "  {:>3}  {}  {} rows, {} in {}  {}",
+                job.id,
+                status,
+                format::group_thousands(job.progress.rows() as usize),
+                progress::format_bytes(job.progress.bytes()),
+                progress::format_duration(job.elapsed()),
+                job.description
+            );
+        }
+    }
+
+    /// Stops a running job, leaving what it wrote so far.
+    pub fn cancel(&mut self, id: usize) -> Result<()> {
+        let job = self
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == id)
+            .ok_or_else(|| QgoError::Usage(format!("There is no job {}; \\jobs lists them", id)))?;
+        if !job.is_running() {
+            println!("Job {} has already ended.", id);
+            return Ok(());
+        }
+        stop(job);
+        println!(
+            "Cancelled job {} after {} rows; {} is incomplete.",
+            id,
+            job.progress.rows(),
+            job.file.display()
+        );
+        Ok(())
+    }
+
+    /// Stops every running job, removing the files they were writing.
+    pub fn cancel_all(&mut self) {
+        for job in self.jobs.iter_mut().filter(|job| job.is_running()) {
+            stop(job);
+            let _ = std::fs::remove_file(&job.file);
+        }
+    }
+
+    /// Waits until no job is running.
+    pub async fn wait(&mut self) {
+        for job in &mut self.jobs {
+            let _ = (&mut job.task).await;
+        }
+    }
+}
+
+fn stop(job: &mut Job) {
+    job.task.abort();
+    if let Ok(mut state) = job.state.lock() {
+        // It may have finished in the meantime
+        if matches!(state.status, JobStatus::Running) {
+            state.end(JobStatus::Cancelled);
+        }
+    }
+}
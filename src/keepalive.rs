@@ -0,0 +1,84 @@
+use sqlx::AnyPool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// Keeps an idle pool warm by running `SELECT 1` whenever nothing has used it
+/// for `interval`. A failed ping never prints anything; it only marks the
+/// pool as possibly stale so the next real query reconnects first.
+pub struct Keepalive {
+    interval: Duration,
+    timeout: Duration,
+    last_activity: Arc<Mutex<Instant>>,
+    stale: Arc<AtomicBool>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl Keepalive {
+    pub fn new(interval: Duration, timeout: Duration) -> Self {
+        Self {
+            interval,
+            timeout,
+            last_activity: Arc::new(Mutex::new(Instant::now())),
+            stale: Arc::new(AtomicBool::new(false)),
+            task: None,
+        }
+    }
+
+    /// Starts pinging `pool`, replacing any task watching a previous pool.
+    pub fn start(&mut self, pool: AnyPool) {
+        self.stop();
+
+        let interval = self.interval;
+        let timeout = self.timeout;
+        let last_activity = Arc::clone(&self.last_activity);
+        let stale = Arc::clone(&self.stale);
+
+        self.task = Some(tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+
+                let idle = last_activity
+                    .lock()
+                    .map(|instant| instant.elapsed())
+                    .unwrap_or_default();
+                if idle < interval {
+                    continue;
+                }
+
+                let ping = tokio::time::timeout(timeout, sqlx::query("SELECT 1").execute(&pool)).await;
+                if !matches!(ping, Ok(Ok(_))) {
+                    stale.store(true, Ordering::SeqCst);
+                }
+                if let Ok(mut instant) = last_activity.lock() {
+                    *instant = Instant::now();
+                }
+            }
+        }));
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+
+    /// Records that the pool was just used by a real query.
+    pub fn touch(&self) {
+        if let Ok(mut instant) = self.last_activity.lock() {
+            *instant = Instant::now();
+        }
+    }
+
+    /// Returns whether a ping failed since the last call, clearing the flag.
+    pub fn take_stale(&self) -> bool {
+        self.stale.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl Drop for Keepalive {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
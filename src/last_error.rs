@@ -0,0 +1,155 @@
+use chrono::{DateTime, Local};
+use console::style;
+use sqlx::mysql::MySqlDatabaseError;
+use sqlx::postgres::{PgDatabaseError, PgErrorPosition};
+
+use crate::error::QgoError;
+use crate::progress;
+
+/// The most recent error of a session, kept whole for `\errverbose`: the
+/// short message at the prompt leaves out most of what the driver knows.
+pub struct LastError {
+    error: anyhow::Error,
+    /// The input that failed, as typed.
+    statement: String,
+    at: DateTime<Local>,
+    /// The session's reconnect count before the input ran and once it had
+    /// failed (see [`crate::database::Database::reconnects`]).
+    reconnects_before: u32,
+    reconnects_after: u32,
+}
+
+impl LastError {
+    pub fn new(error: anyhow::Error, statement: &str, reconnects_before: u32, reconnects_after: u32) -> Self {
+        Self {
+            error,
+            statement: statement.trim().to_string(),
+            at: Local::now(),
+            reconnects_before,
+            reconnects_after,
+        }
+    }
+
+    /// Prints everything known about the error. `reconnects` is the
+    /// session's reconnect count now, to tell whether it reconnected since.
+    pub fn print(&self, reconnects: u32) {
+        let ago = (Local::now() - self.at).to_std().unwrap_or_default();
+        println!(
+            "{}",
+            style(format!(
+                "Last error at {} ({} ago)",
+                self.at.format("%Y-%m-%d %H:%M:%S"),
+                progress::format_duration(ago)
+            ))
+            .bold()
+        );
+        field("Statement", &self.statement);
+        field("Shown as", &format!("Error: {}", self.error));
+
+        match self.error.downcast_ref::<QgoError>() {
+            Some(QgoError::Database(error)) => print_driver_error(error, &self.statement),
+            _ => {
+                for cause in self.error.chain().skip(1) {
+                    field("Caused by", &cause.to_string());
+                }
+            }
+        }
+
+        let reconnected = if self.reconnects_after > self.reconnects_before {
+            "yes, the connection had been dropped and was reopened before the statement ran".to_string()
+        } else if reconnects > self.reconnects_after {
+            "yes, on a later statement".to_string()
+        } else if self.is_connection_error() {
+            "no; the broken connection is dropped and the next statement opens a new one".to_string()
+        } else {
+            "no".to_string()
+        };
+        field("Reconnected", &reconnected);
+    }
+
+    fn is_connection_error(&self) -> bool {
+        matches!(
+            self.error.downcast_ref::<QgoError>(),
+            Some(QgoError::Database(
+                sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::Protocol(_) | sqlx::Error::PoolClosed
+            ))
+        )
+    }
+}
+
+/// The fields of a driver error: SQLSTATE and the server's own message,
+/// detail and hint, and for Postgres where in the statement it went wrong.
+fn print_driver_error(error: &sqlx::Error, statement: &str) {
+    let sqlx::Error::Database(database_error) = error else {
+        field("Driver error", &format!("{:?}", error));
+        return;
+    };
+
+    if let Some(error) = database_error.try_downcast_ref::<PgDatabaseError>() {
+        field("SQLSTATE", error.code());
+        field("Severity", &format!("{:?}", error.severity()).to_uppercase());
+        field("Message", error.message());
+        let optional = [
+            ("Detail", error.detail()),
+            ("Hint", error.hint()),
+            ("Where", error.r#where()),
+            ("Schema", error.schema()),
+            ("Table", error.table()),
+            ("Column", error.column()),
+            ("Data type", error.data_type()),
+            ("Constraint", error.constraint()),
+            ("Routine", error.routine()),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value {
+                field(name, value);
+            }
+        }
+        match error.position() {
+            Some(PgErrorPosition::Original(position)) => print_position(statement, position),
+            Some(PgErrorPosition::Internal { position, query }) => {
+                field("Internal query", query);
+                print_position(query, position);
+            }
+            None => {}
+        }
+    } else if let Some(error) = database_error.try_downcast_ref::<MySqlDatabaseError>() {
+        field("Error number", &error.number().to_string());
+        if let Some(code) = error.code() {
+            field("SQLSTATE", code);
+        }
+        field("Message", error.message());
+        if let Some(constraint) = database_error.constraint() {
+            field("Constraint", constraint);
+        }
+    } else {
+        if let Some(code) = database_error.code() {
+            field("Code", &code);
+        }
+        field("Message", database_error.message());
+        if let Some(constraint) = database_error.constraint() {
+            field("Constraint", constraint);
+        }
+    }
+    field("Kind", &format!("{:?}", database_error.kind()));
+}
+
+/// Points at character `position` (counted from 1) of `statement`.
+fn print_position(statement: &str, position: usize) {
+    let before: String = statement.chars().take(position.saturating_sub(1)).collect();
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |text| text.chars().count()) + 1;
+    field("Position", &format!("line {}, column {}", line, column));
+    if let Some(text) = statement.lines().nth(line - 1) {
+        println!("    {}", text);
+        println!("    {}{}", " ".repeat(column - 1), style("^").red());
+    }
+}
+
+fn field(name: &str, value: &str) {
+    let mut lines = value.lines();
+    println!("  {:<14} {}", format!("{}:", name), lines.next().unwrap_or(""));
+    for line in lines {
+        println!("  {:<14} {}", "", line);
+    }
+}
@@ -0,0 +1,47 @@
+//! Query execution and result formatting behind the `qgo` SQL client.
+//!
+//! The interactive client is built on top of these modules and is only
+//! compiled with the default `cli` feature; depend on the crate with
+//! `default-features = false` to get just the library.
+//!
+//! ```
+//! use std::time::Duration;
+//! use qgo::config::{Connection, DatabaseType};
+//! use qgo::database::Database;
+//! use qgo::format::{self, DisplayOptions};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! sqlx::any::install_default_drivers();
+//!
+//! let connection = Connection::new(
+//!     "scratch".to_string(),
+//!     DatabaseType::SQLite,
+//!     String::new(),
+//!     0,
+//!     String::new(),
+//!     String::new(),
+//!     ":memory:".to_string(),
+//! );
+//! let mut database = Database::connect(connection, Duration::from_secs(5), None).await?;
+//!
+//! let result = database.execute_query("SELECT 'hello' AS greeting").await?;
+//! assert_eq!(result.columns, vec!["greeting"]);
+//! assert_eq!(result.rows, vec![vec!["hello"]]);
+//!
+//! format::display_table(&result, &DisplayOptions::default());
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod atomic;
+pub mod color;
+pub mod config;
+pub mod database;
+pub mod error;
+pub mod format;
+pub mod formatter;
+mod keepalive;
+pub mod output;
+pub mod policy;
+pub mod timezone;
@@ -0,0 +1,510 @@
+use console::style;
+use qgo::format::{self, sql_tokens, SqlToken};
+
+use crate::config::{DatabaseType, Settings};
+use crate::database::Database;
+
+/// A lint check, named in warnings and in the `lint_disabled` setting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rule {
+    SelectStar,
+    CartesianJoin,
+    OrderWithoutLimit,
+    LeadingWildcard,
+    TypeCoercion,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 5] = [
+        Rule::SelectStar,
+        Rule::CartesianJoin,
+        Rule::OrderWithoutLimit,
+        Rule::LeadingWildcard,
+        Rule::TypeCoercion,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::SelectStar => "select-star",
+            Rule::CartesianJoin => "cartesian-join",
+            Rule::OrderWithoutLimit => "order-without-limit",
+            Rule::LeadingWildcard => "leading-wildcard",
+            Rule::TypeCoercion => "type-coercion",
+        }
+    }
+
+    fn enabled(self, settings: &Settings) -> bool {
+        !settings
+            .lint_disabled
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(self.name()))
+    }
+}
+
+pub struct Warning {
+    pub rule: Rule,
+    pub message: String,
+}
+
+/// Words that end the FROM clause of a SELECT.
+const FROM_END: &[&str] = &[
+    "EXCEPT", "FETCH", "FOR", "GROUP", "HAVING", "INTERSECT", "LIMIT", "OFFSET", "ORDER", "UNION",
+    "WHERE", "WINDOW",
+];
+
+/// Words that may come between a FROM item and the `JOIN` that follows it.
+const JOIN_WORDS: &[&str] = &["CROSS", "FULL", "INNER", "LEFT", "NATURAL", "OUTER", "RIGHT"];
+
+/// One table or subquery of a FROM clause.
+struct FromItem {
+    /// The table as written; `None` for a subquery or function.
+    table: Option<String>,
+    alias: Option<String>,
+    /// Joined with a condition, or deliberately with `CROSS` or `NATURAL`.
+    /// The first item counts as linked.
+    linked: bool,
+}
+
+/// What the checks need to know about a single SELECT statement.
+struct Select {
+    star: bool,
+    from: Vec<FromItem>,
+    /// The WHERE clause compares a column with another column.
+    where_links: bool,
+    order_by: bool,
+    limit: bool,
+    /// Patterns of `LIKE` and `ILIKE` anywhere in the statement.
+    like_patterns: Vec<String>,
+    /// WHERE conditions that are a plain `column = literal`.
+    comparisons: Vec<Comparison>,
+}
+
+/// A WHERE condition comparing a column with a literal, either way round.
+struct Comparison {
+    /// The table or alias the column is qualified with.
+    qualifier: Option<String>,
+    column: String,
+    literal: Literal,
+}
+
+enum Literal {
+    /// An unquoted number, as written.
+    Number(String),
+    /// A quoted string, quotes included.
+    Text(String),
+}
+
+/// How a column type compares with literals.
+#[derive(PartialEq)]
+enum TypeKind {
+    Text,
+    Number,
+}
+
+/// Runs the enabled checks on `query`. Statements other than a single
+/// SELECT, and anything that doesn't parse as expected, give no warnings.
+/// Table sizes and column counts come from the catalog; a failed lookup
+/// skips its check.
+pub async fn check(database: &mut Database, query: &str, settings: &Settings) -> Vec<Warning> {
+    let Some(select) = parse(query) else {
+        return Vec::new();
+    };
+    let tables: Vec<&str> = select.from.iter().filter_map(|item| item.table.as_deref()).collect();
+    let mut warnings = Vec::new();
+
+    if select.star && Rule::SelectStar.enabled(settings) && tables.len() == select.from.len() {
+        let mut columns = 0;
+        for table in &tables {
+            // Column lookups take the bare table name
+            let name = table.rsplit('.').next().unwrap_or(table);
+            columns += database.get_columns(name).await.map_or(0, |columns| columns.len());
+        }
+        if columns > settings.lint_max_star_columns {
+            warnings.push(Warning {
+                rule: Rule::SelectStar,
+                message: format!(
+                    "SELECT * returns {} columns from {}; naming the ones you need is quicker to fetch and read",
+                    columns,
+                    tables.join(", ")
+                ),
+            });
+        }
+    }
+
+    let unlinked: Vec<&str> = select
+        .from
+        .iter()
+        .skip(1)
+        .filter(|item| !item.linked)
+        .map(|item| item.table.as_deref().unwrap_or("a subquery"))
+        .collect();
+    if !unlinked.is_empty() && !select.where_links && Rule::CartesianJoin.enabled(settings) {
+        warnings.push(Warning {
+            rule: Rule::CartesianJoin,
+            message: format!(
+                "nothing links {} to the rest of the FROM clause, so every combination of rows is returned; add ON, USING or a WHERE condition",
+                unlinked.join(", ")
+            ),
+        });
+    }
+
+    if select.order_by && !select.limit && Rule::OrderWithoutLimit.enabled(settings) {
+        if let Some(threshold) = settings.row_estimate_warning {
+            for table in &tables {
+                match database.estimated_row_count(table).await {
+                    Some(estimate) if estimate > threshold => {
+                        warnings.push(Warning {
+                            rule: Rule::OrderWithoutLimit,
+                            message: format!(
+                                "ORDER BY without LIMIT sorts all of {} (~{} rows)",
+                                table,
+                                format::group_thousands(estimate as usize)
+                            ),
+                        });
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    if Rule::LeadingWildcard.enabled(settings) {
+        for pattern in select.like_patterns.iter().filter(|pattern| pattern.starts_with("'%")) {
+            warnings.push(Warning {
+                rule: Rule::LeadingWildcard,
+                message: format!("LIKE {} starts with a wildcard, so no index can be used and every row is checked", pattern),
+            });
+        }
+    }
+
+    // Postgres refuses these comparisons outright, and SQLite compares by
+    // the column's affinity, so only MySQL converts silently
+    let converts = matches!(database.get_connection().db_type, DatabaseType::MySQL | DatabaseType::MariaDB);
+    if converts && Rule::TypeCoercion.enabled(settings) {
+        for comparison in &select.comparisons {
+            let Some(column_type) = column_type(database, &select.from, comparison).await else {
+                continue;
+            };
+            let message = match (type_kind(&column_type), &comparison.literal) {
+                (Some(TypeKind::Text), Literal::Number(number)) => format!(
+                    "{} is {} but is compared with the number {}, so every value is converted to a number: no index is used and values such as '{}abc' match too; quote the number",
+                    comparison.column, column_type, number, number
+                ),
+                (Some(TypeKind::Number), Literal::Text(text)) if !is_number(&text[1..text.len() - 1]) => format!(
+                    "{} is {} but is compared with the string {}, which is converted to a number by its leading digits and may match rows you don't expect",
+                    comparison.column, column_type, text
+                ),
+                _ => continue,
+            };
+            warnings.push(Warning {
+                rule: Rule::TypeCoercion,
+                message,
+            });
+        }
+    }
+
+    warnings
+}
+
+/// The type of the column `comparison` names, when exactly one table of
+/// the FROM clause has it. Unqualified columns next to a subquery, and
+/// failed lookups, give `None`.
+async fn column_type(database: &mut Database, from: &[FromItem], comparison: &Comparison) -> Option<String> {
+    let candidates: Vec<&str> = match comparison.qualifier {
+        Some(ref qualifier) => from
+            .iter()
+            .filter(|item| {
+                let name = item.alias.as_deref().or(item.table.as_deref().and_then(|table| table.rsplit('.').next()));
+                name.is_some_and(|name| name.eq_ignore_ascii_case(qualifier))
+            })
+            .filter_map(|item| item.table.as_deref())
+            .collect(),
+        None if from.iter().any(|item| item.table.is_none()) => return None,
+        None => from.iter().filter_map(|item| item.table.as_deref()).collect(),
+    };
+    let mut found = Vec::new();
+    for table in candidates {
+        let name = table.rsplit('.').next().unwrap_or(table);
+        let columns = database.cached_column_types(name).await.ok()?;
+        found.extend(
+            columns
+                .into_iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case(&comparison.column))
+                .map(|(_, column_type)| column_type),
+        );
+    }
+    match found.as_slice() {
+        [column_type] => Some(column_type.clone()),
+        _ => None,
+    }
+}
+
+/// Whether `column_type` (e.g. `varchar(20)`, `int(11) unsigned`) holds
+/// text or numbers; `None` for anything else.
+fn type_kind(column_type: &str) -> Option<TypeKind> {
+    let base = column_type
+        .split(|c: char| c == '(' || c.is_whitespace())
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match base.as_str() {
+        "char" | "varchar" | "nchar" | "nvarchar" | "tinytext" | "text" | "mediumtext" | "longtext" => Some(TypeKind::Text),
+        "tinyint" | "smallint" | "mediumint" | "int" | "integer" | "bigint" | "decimal" | "numeric" | "float"
+        | "double" | "real" => Some(TypeKind::Number),
+        _ => None,
+    }
+}
+
+/// Whether `text` reads as a number in full, such as `42`, `-1.5` or `1e3`.
+fn is_number(text: &str) -> bool {
+    let text = text.trim();
+    !text.is_empty() && text.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+/// Prints `warnings` dimmed, each with the rule that raised it.
+pub fn print(warnings: &[Warning]) {
+    for warning in warnings {
+        eprintln!("{}", style(format!("lint: {} [{}]", warning.message, warning.rule.name())).dim());
+    }
+}
+
+fn parse(query: &str) -> Option<Select> {
+    // Each token with the parenthesis depth it is at
+    let mut depth = 0usize;
+    let mut tokens: Vec<(SqlToken, usize)> = Vec::new();
+    for token in sql_tokens(query) {
+        match token {
+            SqlToken::Space | SqlToken::Comment(_) => continue,
+            SqlToken::Other('(') => {
+                tokens.push((token, depth));
+                depth += 1;
+            }
+            SqlToken::Other(')') => {
+                depth = depth.checked_sub(1)?;
+                tokens.push((token, depth));
+            }
+            token => tokens.push((token, depth)),
+        }
+    }
+    while matches!(tokens.last(), Some((SqlToken::Other(';'), _))) {
+        tokens.pop();
+    }
+    if depth != 0 || tokens.iter().any(|(token, _)| *token == SqlToken::Other(';')) {
+        return None;
+    }
+
+    let word = |i: usize| match tokens.get(i) {
+        Some((SqlToken::Word(word), 0)) => Some(word.to_ascii_uppercase()),
+        _ => None,
+    };
+    if word(0).as_deref() != Some("SELECT") {
+        return None;
+    }
+    let top_level = |names: &[&str], from: usize| {
+        (from..tokens.len()).find(|&i| word(i).is_some_and(|word| names.contains(&word.as_str())))
+    };
+
+    let from_start = top_level(&["FROM"], 1);
+    let select_end = from_start.unwrap_or(tokens.len());
+    let star = (1..select_end).any(|i| {
+        tokens[i] == (SqlToken::Other('*'), 0)
+            && (matches!(word(i - 1).as_deref(), Some("SELECT" | "DISTINCT" | "ALL"))
+                || matches!(tokens[i - 1].0, SqlToken::Other(',' | '.')))
+    });
+
+    let mut from = Vec::new();
+    if let Some(start) = from_start {
+        let end = top_level(FROM_END, start + 1).unwrap_or(tokens.len());
+        let mut i = start + 1;
+        let mut linked = true;
+        while i < end {
+            let table = table_name(&tokens[i..end]);
+            // The item runs to the next top-level comma or JOIN
+            let mut next = i + 1;
+            let mut condition = false;
+            while next < end && tokens[next] != (SqlToken::Other(','), 0) && word(next).as_deref() != Some("JOIN") {
+                condition |= matches!(word(next).as_deref(), Some("ON" | "USING"));
+                next += 1;
+            }
+            // The words before JOIN belong to it, not to this item
+            let mut item_end = next;
+            while item_end > i + 1 && word(item_end - 1).is_some_and(|word| JOIN_WORDS.contains(&word.as_str())) {
+                item_end -= 1;
+            }
+            let deliberate = (item_end..next).any(|j| matches!(word(j).as_deref(), Some("CROSS" | "NATURAL")));
+            from.push(FromItem {
+                alias: table.as_ref().and_then(|_| table_alias(&tokens[i..item_end])),
+                table,
+                linked: linked || condition,
+            });
+            linked = deliberate;
+            i = next + 1;
+        }
+    }
+
+    let where_clause = top_level(&["WHERE"], 1).map(|start| {
+        let end = top_level(&["GROUP", "HAVING", "ORDER", "LIMIT", "OFFSET", "FETCH", "WINDOW", "UNION", "EXCEPT", "INTERSECT"], start + 1)
+            .unwrap_or(tokens.len());
+        (start, end)
+    });
+    let where_links = where_clause.is_some_and(|(start, end)| {
+        (start + 2..end.saturating_sub(1)).any(|i| {
+            tokens[i].0 == SqlToken::Other('=') && is_column(&tokens[i - 1].0) && is_column(&tokens[i + 1].0)
+        })
+    });
+    let comparisons = where_clause.map_or_else(Vec::new, |(start, end)| comparisons(&tokens[start + 1..end]));
+
+    let like_patterns = tokens
+        .windows(2)
+        .filter_map(|pair| match pair {
+            [(SqlToken::Word(word), _), (SqlToken::Literal(pattern), _)]
+                if word.eq_ignore_ascii_case("like") || word.eq_ignore_ascii_case("ilike") =>
+            {
+                Some(pattern.clone())
+            }
+            _ => None,
+        })
+        .collect();
+
+    Some(Select {
+        star,
+        from,
+        where_links,
+        order_by: top_level(&["ORDER"], 1).is_some(),
+        limit: top_level(&["LIMIT", "FETCH"], 1).is_some() || word(1).as_deref() == Some("TOP"),
+        like_patterns,
+        comparisons,
+    })
+}
+
+/// The conditions of a WHERE clause that are exactly `column = literal`
+/// or `literal = column`, taking the clause apart at `AND`, `OR`, `NOT`
+/// and parentheses. A clause with a subquery gives none.
+fn comparisons(tokens: &[(SqlToken, usize)]) -> Vec<Comparison> {
+    let is_word = |token: &SqlToken, words: &[&str]| {
+        matches!(token, SqlToken::Word(word) if words.iter().any(|w| word.eq_ignore_ascii_case(w)))
+    };
+    if tokens.iter().any(|(token, _)| is_word(token, &["SELECT"])) {
+        return Vec::new();
+    }
+    tokens
+        .split(|(token, _)| is_word(token, &["AND", "OR", "NOT"]) || matches!(token, SqlToken::Other('(' | ')')))
+        .filter_map(|condition| {
+            let condition: Vec<&SqlToken> = condition.iter().map(|(token, _)| token).collect();
+            let equals = condition.iter().position(|token| **token == SqlToken::Other('='))?;
+            let (left, right) = (&condition[..equals], &condition[equals + 1..]);
+            let ((qualifier, column), literal) = match (column_ref(left), literal(right)) {
+                (Some(column), Some(literal)) => (column, literal),
+                _ => (column_ref(right)?, literal(left)?),
+            };
+            Some(Comparison {
+                qualifier,
+                column,
+                literal,
+            })
+        })
+        .collect()
+}
+
+/// `tokens` as a column reference, `name` or `qualifier.name`, with
+/// identifier quotes removed.
+fn column_ref(tokens: &[&SqlToken]) -> Option<(Option<String>, String)> {
+    let name = |token: &SqlToken| match token {
+        SqlToken::Word(word) if is_column(token) => Some(word.clone()),
+        SqlToken::Literal(quoted) if is_column(token) => Some(quoted[1..quoted.len().saturating_sub(1).max(1)].to_string()),
+        _ => None,
+    };
+    match tokens {
+        [column] => Some((None, name(column)?)),
+        [qualifier, SqlToken::Other('.'), column] => Some((Some(name(qualifier)?), name(column)?)),
+        _ => None,
+    }
+}
+
+/// `tokens` as a single number or quoted string.
+fn literal(tokens: &[&SqlToken]) -> Option<Literal> {
+    let digits = |token: &SqlToken| match token {
+        SqlToken::Word(word) if word.chars().all(|c| c.is_ascii_digit()) => Some(word.clone()),
+        _ => None,
+    };
+    let (sign, unsigned) = match tokens {
+        [SqlToken::Other('-'), rest @ ..] => ("-", rest),
+        rest => ("", rest),
+    };
+    match unsigned {
+        [SqlToken::Literal(text)] if sign.is_empty() && text.starts_with('\'') && text.len() >= 2 => {
+            Some(Literal::Text(text.clone()))
+        }
+        [whole] => Some(Literal::Number(format!("{}{}", sign, digits(whole)?))),
+        [whole, SqlToken::Other('.'), fraction] => {
+            Some(Literal::Number(format!("{}{}.{}", sign, digits(whole)?, digits(fraction)?)))
+        }
+        _ => None,
+    }
+}
+
+/// The alias a FROM item gives its table, as in `orders o` or
+/// `orders AS o`.
+fn table_alias(tokens: &[(SqlToken, usize)]) -> Option<String> {
+    let mut rest = tokens.iter().map(|(token, _)| token).peekable();
+    // Past the (possibly qualified) table name
+    loop {
+        rest.next()?;
+        if rest.next_if_eq(&&SqlToken::Other('.')).is_none() {
+            break;
+        }
+    }
+    rest.next_if(|token| matches!(token, SqlToken::Word(word) if word.eq_ignore_ascii_case("as")));
+    match rest.next()? {
+        SqlToken::Word(word)
+            if !["ON", "USING", "JOIN", "WHERE"].iter().chain(JOIN_WORDS).chain(FROM_END).any(|w| word.eq_ignore_ascii_case(w)) =>
+        {
+            Some(word.clone())
+        }
+        SqlToken::Literal(quoted) if quoted.starts_with(['"', '`']) => {
+            Some(quoted[1..quoted.len().saturating_sub(1).max(1)].to_string())
+        }
+        _ => None,
+    }
+}
+
+/// The table a FROM item starts with, e.g. `orders` or `sales.orders`,
+/// with identifier quotes removed; `None` for a subquery or function call.
+fn table_name(tokens: &[(SqlToken, usize)]) -> Option<String> {
+    let mut name = String::new();
+    let mut rest = tokens.iter().map(|(token, _)| token).peekable();
+    if matches!(rest.peek(), Some(SqlToken::Word(word)) if word.eq_ignore_ascii_case("lateral")) {
+        return None;
+    }
+    loop {
+        match rest.next()? {
+            SqlToken::Word(word) => name.push_str(word),
+            SqlToken::Literal(quoted) if quoted.starts_with(['"', '`']) => {
+                name.push_str(&quoted[1..quoted.len().saturating_sub(1).max(1)]);
+            }
+            _ => return None,
+        }
+        match rest.peek() {
+            Some(SqlToken::Other('.')) => {
+                rest.next();
+                name.push('.');
+            }
+            Some(SqlToken::Other('(')) => return None,
+            _ => return Some(name),
+        }
+    }
+}
+
+/// Whether `token` can be (the end or start of) a column reference rather
+/// than a literal or keyword.
+fn is_column(token: &SqlToken) -> bool {
+    match token {
+        SqlToken::Word(word) => {
+            !word.starts_with(|c: char| c.is_ascii_digit())
+                && !matches!(word.to_ascii_uppercase().as_str(), "TRUE" | "FALSE" | "NULL")
+        }
+        SqlToken::Literal(quoted) => quoted.starts_with(['"', '`']),
+        _ => false,
+    }
+}
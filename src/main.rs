@@ -1,40 +1,49 @@
 use anyhow::Result;
-use clap::{Arg, Command};
+use clap::{Arg, ArgGroup, Command};
+use std::io::IsTerminal;
 use std::process;
 
+mod analyze;
+mod assertion;
+mod browse;
+mod cache;
+mod chart;
 mod cli;
-mod config;
-mod database;
-mod error;
+mod completions;
+mod diff;
+mod follow;
+mod freq;
+mod import;
+mod interrupt;
+mod jobs;
+mod last_error;
+mod lint;
+mod normalize;
+mod notify;
+mod ping;
+mod progress;
+mod project;
+mod recent;
+mod script;
+mod secrets;
+mod snapshot;
+mod transcript;
+mod variables;
 mod ui;
 
-use config::Config;
-use ui::connection_manager::ConnectionManager;
+use qgo::{atomic, config, database, error, timezone};
+
+use config::{Config, OnError, PasswordStorage};
+use std::time::Duration;
+use ui::connection_manager::{self, ConnectionManager};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize SQLx drivers for the "any" module
     sqlx::any::install_default_drivers();
+    interrupt::install();
     
-    let matches = Command::new("qgo")
-        .version("0.1.0")
-        .author("Ishan Ravindu")
-        .about("A command-line SQL client written in Rust")
-        .arg(
-            Arg::new("connection")
-                .short('c')
-                .long("connection")
-                .value_name("NAME")
-                .help("Connect to a specific saved connection")
-        )
-        .arg(
-            Arg::new("version")
-                .short('v')
-                .long("version")
-                .help("Display version information")
-                .action(clap::ArgAction::SetTrue)
-        )
-        .get_matches();
+    let matches = build_cli().get_matches();
 
     if matches.get_flag("version") {
         println!("qgo version {}", env!("CARGO_PKG_VERSION"));
@@ -42,21 +51,212 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
-    let config = match Config::load().await {
+    if let Some(("completions", completion_matches)) = matches.subcommand() {
+        let shell = *completion_matches.get_one::<clap_complete::Shell>("shell").expect("required argument");
+        completions::print_completions(shell, &mut build_cli());
+        return Ok(());
+    }
+
+    ui::output::set_quiet(matches.get_flag("quiet") || !std::io::stdout().is_terminal());
+    if matches.get_flag("ephemeral") {
+        config::set_ephemeral();
+    }
+
+    let mut config = match Config::load().await {
         Ok(config) => config,
         Err(err) => {
             eprintln!("Error loading configuration: {}", err);
             process::exit(1);
         }
     };
+    match config.override_settings(|settings| {
+        if let Some(timeout) = matches.get_one::<u64>("timeout") {
+            settings.query_timeout_seconds = *timeout;
+        }
+        if let Some(max_rows) = matches.get_one::<Option<usize>>("max-rows") {
+            settings.max_rows_display = *max_rows;
+        }
+        if matches.get_flag("confirm") {
+            settings.confirm_each_query = true;
+        }
+        if let Some(format) = matches.get_one::<String>("format") {
+            settings.result_format = Some(format.clone());
+        }
+    }) {
+        Ok(changed) if !changed.is_empty() => {
+            let changed: Vec<String> = changed.iter().map(|(name, value)| format!("{} = {}", name, value)).collect();
+            ui::output::info(format!("For this run only: {} (config.json is unchanged)", changed.join(", ")));
+        }
+        Ok(_) => {}
+        Err(err) => {
+            eprintln!("Error applying command-line settings: {}", err);
+            process::exit(1);
+        }
+    }
+    ui::output::set_banner(config.settings.show_banner && !matches.get_flag("no-banner"));
+    secrets::set_backend(config.settings.password_storage);
+    if !matches.get_flag("no-project-config") {
+        if let Err(err) = project::load(&mut config).await {
+            eprintln!("{}", console::style(format!("Project connections not loaded: {}", err)).yellow());
+        }
+    }
+
+    if matches.get_flag("list") {
+        let format = if matches.get_flag("json") {
+            completions::ListFormat::Json
+        } else {
+            completions::ListFormat::Table
+        };
+        completions::list_connections(&config, format);
+        return Ok(());
+    }
+
+    if let Some(("connections", connections_matches)) = matches.subcommand() {
+        if let Some(("list", list_matches)) = connections_matches.subcommand() {
+            let format = if list_matches.get_flag("names-only") {
+                completions::ListFormat::Names
+            } else if list_matches.get_flag("json") {
+                completions::ListFormat::Json
+            } else {
+                completions::ListFormat::Table
+            };
+            completions::list_connections(&config, format);
+        }
+        return Ok(());
+    }
+
+    if let Some(("secrets", secrets_matches)) = matches.subcommand() {
+        let result = match secrets_matches.subcommand() {
+            Some(("list", _)) => secrets::list(&config),
+            Some(("set", set_matches)) => {
+                secrets::set(&config, set_matches.get_one::<String>("name").expect("required argument"))
+            }
+            Some(("del", del_matches)) => {
+                secrets::delete(&config, del_matches.get_one::<String>("name").expect("required argument"))
+            }
+            Some(("migrate", migrate_matches)) => {
+                let target = migrate_matches.get_one::<String>("to").expect("required argument");
+                let target = PasswordStorage::from_name(target).expect("checked by the value parser");
+                secrets::migrate(&mut config, target).await
+            }
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(("diff", diff_matches)) = matches.subcommand() {
+        let from = diff_matches.get_one::<String>("from").expect("required argument");
+        let to = diff_matches.get_one::<String>("to").expect("required argument");
+        let tables = diff_matches.get_one::<String>("tables").map(String::as_str);
+
+        match run_diff(&config, from, to, tables).await {
+            Ok(false) => return Ok(()),
+            Ok(true) => process::exit(1),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
+    if let Some(("offline", _)) = matches.subcommand() {
+        let mut connection_manager = ConnectionManager::new(config);
+        if let Err(err) = cli::run_offline_session(&mut connection_manager).await {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(("ping", ping_matches)) = matches.subcommand() {
+        let result = if ping_matches.get_flag("all") {
+            ping::ping_all(&config).await
+        } else {
+            let name = ping_matches.get_one::<String>("name").expect("required argument");
+            let count = *ping_matches.get_one::<u32>("count").expect("has default");
+            let interval = *ping_matches.get_one::<u64>("interval").expect("has default");
+            ping::ping_connection(&config, name, count, Duration::from_secs(interval)).await
+        };
+
+        match result {
+            Ok(true) => return Ok(()),
+            Ok(false) => process::exit(1),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
+    // The introduction is only for interactive starts without a connection picked
+    let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+    if !config.settings.onboarding_done
+        && interactive
+        && !config::is_ephemeral()
+        && !matches.contains_id("connection")
+        && !onboard(&mut config).await
+    {
+        return Ok(());
+    }
+
+    let existing_files = if matches.get_flag("overwrite") {
+        cli::ExistingFile::Overwrite
+    } else if matches.get_flag("no-clobber") {
+        cli::ExistingFile::Keep
+    } else {
+        cli::ExistingFile::Ask
+    };
 
+    let record = matches.get_one::<String>("record").map(std::path::Path::new);
+    // Quiet or unattended script runs only ask before each statement when --confirm says so
+    let confirm_script = matches.get_flag("confirm")
+        || (config.settings.confirm_each_query && !ui::output::is_quiet() && std::io::stdin().is_terminal());
     let mut connection_manager = ConnectionManager::new(config);
+    if matches.get_flag("no-probe") {
+        connection_manager.disable_probe();
+    }
 
     if let Some(connection_name) = matches.get_one::<String>("connection") {
         match connection_manager.connect_by_name(connection_name).await {
             Ok(_) => {
-                println!("Connected to database '{}'", connection_name);
-                cli::run_interactive_session(&mut connection_manager).await?;
+                ui::output::banner(format!("Connected to database '{}'", connection_name));
+                if let Some(source) = script_source(&matches) {
+                    let on_error = match matches.get_one::<String>("on-error").map(String::as_str) {
+                        Some("continue") => Some(OnError::Continue),
+                        Some(_) => Some(OnError::Stop),
+                        None => None,
+                    };
+                    let variables = match script_variables(&matches) {
+                        Ok(variables) => variables,
+                        Err(err) => {
+                            eprintln!("Error: {}", err);
+                            process::exit(1);
+                        }
+                    };
+                    match cli::run_script_source(
+                        &mut connection_manager,
+                        &source,
+                        &variables,
+                        on_error,
+                        matches.get_flag("single-transaction"),
+                        confirm_script,
+                        record,
+                    )
+                    .await {
+                        Ok(true) => {}
+                        Ok(false) => process::exit(1),
+                        Err(err) => {
+                            eprintln!("Error: {}", err);
+                            process::exit(1);
+                        }
+                    }
+                } else {
+                    cli::run_interactive_session(&mut connection_manager, existing_files, record).await?;
+                }
             }
             Err(err) => {
                 eprintln!("Error connecting to '{}': {}", connection_name, err);
@@ -67,17 +267,18 @@ async fn main() -> Result<()> {
         loop {
             match connection_manager.select_or_manage_connection().await {
                 Ok(true) => {
-                    cli::run_interactive_session(&mut connection_manager).await?;
+                    cli::run_interactive_session(&mut connection_manager, existing_files, record).await?;
                     
                     if !ui::prompts::confirm("Do you want to connect to another database?") {
-                        println!("Goodbye!");
+                        ui::output::banner("Goodbye!");
                         break;
                     }
                 }
                 Ok(false) => {
-                    println!("Goodbye!");
+                    ui::output::banner("Goodbye!");
                     break;
                 }
+                Err(err) if ui::prompts::is_cancelled(&err) => continue,
                 Err(err) => {
                     eprintln!("Error: {}", err);
                     process::exit(1);
@@ -88,3 +289,390 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Introduces qgo on first run and records that it has been shown. Returns
+/// whether to go on to creating the first connection.
+async fn onboard(config: &mut Config) -> bool {
+    println!("{}", console::style("Welcome to qgo!").bold());
+    if let Ok(path) = Config::get_config_path() {
+        println!("Connections and settings are stored in {}.", path.display());
+    }
+    println!("Once connected, type SQL to run it, 'help' to list commands, or \\dt to list tables.");
+    println!();
+
+    config.settings.onboarding_done = true;
+    if let Err(err) = config.save().await {
+        eprintln!("Warning: could not save configuration: {}", err);
+    }
+
+    if config.connections.is_empty() && !ui::prompts::confirm_default("Create your first connection now?", true) {
+        println!("Run qgo again when you are ready to add a connection.");
+        return false;
+    }
+    true
+}
+
+/// The statements to run instead of an interactive session: a `-f` file,
+/// `-e` SQL, or the positional arguments. A single positional argument that
+/// ends in `.sql` and contains no spaces is a file name; anything else is
+/// SQL, joined with spaces.
+fn script_source(matches: &clap::ArgMatches) -> Option<cli::ScriptSource> {
+    if let Some(file) = matches.get_one::<String>("file") {
+        return Some(cli::ScriptSource::File(file.clone()));
+    }
+    if let Some(sql) = matches.get_one::<String>("execute") {
+        return Some(cli::ScriptSource::Sql(sql.clone()));
+    }
+
+    let args: Vec<&String> = matches.get_many::<String>("sql")?.collect();
+    match args.as_slice() {
+        [name] if name.to_lowercase().ends_with(".sql") && !name.contains(char::is_whitespace) => {
+            Some(cli::ScriptSource::File(name.to_string()))
+        }
+        _ => Some(cli::ScriptSource::Sql(
+            args.iter().map(|arg| arg.as_str()).collect::<Vec<_>>().join(" "),
+        )),
+    }
+}
+
+/// Variables for a script: those of the `--params` file, then each
+/// `--var`, which overrides a file value of the same name.
+fn script_variables(matches: &clap::ArgMatches) -> Result<variables::Variables> {
+    let mut variables = variables::Variables::default();
+    if let Some(path) = matches.get_one::<String>("params") {
+        variables.load_params(std::path::Path::new(path))?;
+    }
+    for arg in matches.get_many::<String>("var").into_iter().flatten() {
+        variables.set_from_arg(arg)?;
+    }
+    Ok(variables)
+}
+
+/// Reads `--max-rows`: a row count, or `none` for no limit.
+fn parse_max_rows(value: &str) -> std::result::Result<Option<usize>, String> {
+    if value.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| format!("expected a number of rows or 'none', got '{}'", value))
+}
+
+fn build_cli() -> Command {
+    Command::new("qgo")
+        .version("0.1.0")
+        .author("Ishan Ravindu")
+        .about("A command-line SQL client written in Rust")
+        // -v/--version is declared below so it can print the longer banner
+        .disable_version_flag(true)
+        .arg(
+            Arg::new("connection")
+                .short('c')
+                .long("connection")
+                .value_name("CONNECTION")
+                .help("Connect to a specific saved connection")
+        )
+        .arg(
+            Arg::new("version")
+                .short('v')
+                .long("version")
+                .help("Display version information")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("list")
+                .long("list")
+                .conflicts_with("connection")
+                .help("List saved connections, most recently used first, and exit")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .requires("list")
+                .help("With --list, print the connections as JSON (never including passwords)")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Suppress banners, progress messages and row-count footers (default when output is not a terminal)")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("no-banner")
+                .long("no-banner")
+                .help("Don't print the connect and goodbye messages")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("confirm")
+                .long("confirm")
+                .help("Show each statement's kind and tables and ask before running it (the confirm_each_query setting)")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("SECONDS")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Query timeout for this run, instead of the query_timeout_seconds setting")
+        )
+        .arg(
+            Arg::new("max-rows")
+                .long("max-rows")
+                .value_name("N|none")
+                .value_parser(parse_max_rows)
+                .help("Rows shown per result for this run, instead of the max_rows_display setting")
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("NAME")
+                .value_parser(clap::builder::PossibleValuesParser::new(qgo::formatter::names()))
+                .help("Print results in this export format instead of the grid, for this run; json also lists the column types")
+        )
+        .arg(
+            Arg::new("no-probe")
+                .long("no-probe")
+                .help("Don't check which saved connections are reachable before showing the main menu")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("ephemeral")
+                .long("ephemeral")
+                .help("Write nothing to the config directory: settings, history and recent queries last for this run only")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("no-project-config")
+                .long("no-project-config")
+                .help("Don't load connections from a .qgo.toml in this directory or its parents")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("overwrite")
+                .long("overwrite")
+                .conflicts_with("no-clobber")
+                .help("Let exports replace existing files without asking")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("no-clobber")
+                .long("no-clobber")
+                .help("Skip exports whose target file already exists instead of asking")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("record")
+                .long("record")
+                .value_name("FILE")
+                .help("Append every statement run and its result to a transcript in FILE (Markdown if it ends in .md)")
+        )
+        .arg(
+            Arg::new("file")
+                .short('f')
+                .long("file")
+                .value_name("FILE")
+                .requires("connection")
+                .help("Run the SQL statements in FILE on the --connection database, then exit")
+        )
+        .arg(
+            Arg::new("execute")
+                .short('e')
+                .long("execute")
+                .value_name("SQL")
+                .requires("connection")
+                .help("Run SQL on the --connection database, then exit")
+        )
+        .arg(
+            Arg::new("sql")
+                .value_name("SQL")
+                .num_args(1..)
+                .trailing_var_arg(true)
+                .allow_hyphen_values(true)
+                .requires("connection")
+                .help("SQL to run, usually after -- (qgo -c db -- \"SELECT 1\"), or a FILE.sql to run like -f")
+        )
+        .group(
+            ArgGroup::new("script")
+                .args(["file", "execute", "sql"])
+        )
+        .arg(
+            Arg::new("on-error")
+                .long("on-error")
+                .value_name("MODE")
+                .value_parser(["stop", "continue"])
+                .requires("script")
+                .help("Stop at the first failing statement or continue and report all failures (default: on_error setting)")
+        )
+        .arg(
+            Arg::new("single-transaction")
+                .long("single-transaction")
+                .requires("script")
+                .help("Run the whole file in one transaction, rolling everything back if any statement fails")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("params")
+                .long("params")
+                .value_name("FILE")
+                .requires("script")
+                .help("Set a variable for :name substitution from each top-level key of a JSON or TOML file")
+        )
+        .arg(
+            Arg::new("var")
+                .long("var")
+                .value_name("NAME=VALUE")
+                .requires("script")
+                .help("Set a variable for :name substitution, overriding --params; repeatable")
+                .action(clap::ArgAction::Append)
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Compare tables, columns and row counts between two saved connections")
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .value_name("CONNECTION")
+                        .required(true)
+                        .help("Connection to compare from")
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .value_name("CONNECTION")
+                        .required(true)
+                        .help("Connection to compare to")
+                )
+                .arg(
+                    Arg::new("tables")
+                        .long("tables")
+                        .value_name("PATTERN")
+                        .help("Only compare tables matching PATTERN (* and % are wildcards)")
+                )
+        )
+        .subcommand(
+            Command::new("offline")
+                .about("Look at saved connections, recent queries, input history and settings without connecting")
+        )
+        .subcommand(
+            Command::new("ping")
+                .about("Check that saved connections are reachable and measure latency")
+                .arg(
+                    Arg::new("name")
+                        .value_name("CONNECTION")
+                        .required_unless_present("all")
+                        .help("Connection to ping")
+                )
+                .arg(
+                    Arg::new("count")
+                        .long("count")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .default_value("1")
+                        .help("Number of attempts")
+                )
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("1")
+                        .help("Seconds to wait between attempts")
+                )
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .help("Ping every saved connection concurrently")
+                        .conflicts_with("name")
+                        .action(clap::ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            Command::new("connections")
+                .about("Work with saved connections")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("list")
+                        .about("List saved connections")
+                        .arg(
+                            Arg::new("names-only")
+                                .long("names-only")
+                                .help("Print only connection names, one per line")
+                                .action(clap::ArgAction::SetTrue)
+                        )
+                        .arg(
+                            Arg::new("json")
+                                .long("json")
+                                .conflicts_with("names-only")
+                                .help("Print the connections as JSON (never including passwords)")
+                                .action(clap::ArgAction::SetTrue)
+                        )
+                )
+        )
+        .subcommand(
+            Command::new("secrets")
+                .about("Manage remembered connection passwords")
+                .long_about(
+                    "Manage remembered connection passwords. They are kept in the system keyring, or with the \
+                     password_storage setting set to \"encrypted\", in secrets.enc next to the config file, \
+                     encrypted with a master passphrase that is asked for once per run."
+                )
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("List connections with a stored password"))
+                .subcommand(
+                    Command::new("set")
+                        .about("Store the password for a saved connection")
+                        .arg(Arg::new("name").value_name("CONNECTION").required(true))
+                )
+                .subcommand(
+                    Command::new("del")
+                        .about("Remove the stored password for a saved connection")
+                        .arg(Arg::new("name").value_name("CONNECTION").required(true))
+                )
+                .subcommand(
+                    Command::new("migrate")
+                        .about("Move every stored password to another backend and switch to it")
+                        .arg(
+                            Arg::new("to")
+                                .value_name("BACKEND")
+                                .value_parser(["keyring", "encrypted"])
+                                .required(true)
+                        )
+                )
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print a shell completion script")
+                .long_about(completions::INSTALL_HELP)
+                .arg(
+                    Arg::new("shell")
+                        .value_name("SHELL")
+                        .required(true)
+                        .value_parser(clap::value_parser!(clap_complete::Shell))
+                        .help("Shell to generate completions for")
+                )
+        )
+}
+
+/// Runs `qgo diff`, returning whether any differences were found.
+async fn run_diff(config: &Config, from: &str, to: &str, tables: Option<&str>) -> Result<bool> {
+    let open = |name: &str| {
+        config
+            .get_connection_by_name(name)
+            .cloned()
+            .ok_or_else(|| error::QgoError::ConnectionNotFound(name.to_string()))
+    };
+
+    let mut from_db = connection_manager::open_database(&config.settings, open(from)?).await?;
+    let mut to_db = connection_manager::open_database(&config.settings, open(to)?).await?;
+
+    let timeout = Duration::from_secs(config.settings.query_timeout_seconds);
+    let report = diff::diff_databases(&mut from_db, &mut to_db, tables, timeout).await?;
+    diff::print_report(&report);
+
+    Ok(report.has_differences())
+}
@@ -0,0 +1,122 @@
+use std::borrow::Cow;
+
+/// Characters that chat apps and word processors put in place of ASCII
+/// quotes and spaces, with the ASCII character each stands for.
+const LOOKALIKES: &[(char, char, &str)] = &[
+    ('\u{2018}', '\'', "left single quotation mark"),
+    ('\u{2019}', '\'', "right single quotation mark"),
+    ('\u{201A}', '\'', "single low quotation mark"),
+    ('\u{201C}', '"', "left double quotation mark"),
+    ('\u{201D}', '"', "right double quotation mark"),
+    ('\u{201E}', '"', "double low quotation mark"),
+    ('\u{00A0}', ' ', "non-breaking space"),
+    ('\u{202F}', ' ', "narrow non-breaking space"),
+];
+
+/// A typographic character found in input, counting lines and columns from 1.
+pub struct Lookalike {
+    pub line: usize,
+    pub column: usize,
+    pub found: char,
+    pub name: &'static str,
+}
+
+/// Converts CRLF line endings to LF and strips trailing whitespace from
+/// each line.
+pub fn line_endings(input: &str) -> Cow<'_, str> {
+    if !input.contains('\r') && !input.lines().any(|line| line.len() != line.trim_end().len()) {
+        return Cow::Borrowed(input);
+    }
+    let lines: Vec<&str> = input.lines().map(str::trim_end).collect();
+    Cow::Owned(lines.join("\n"))
+}
+
+/// Finds the curly quotes and non-breaking spaces in `input`. They are only
+/// reported, not replaced: inside a string literal they may be meant.
+pub fn find_lookalikes(input: &str) -> Vec<Lookalike> {
+    let mut found = Vec::new();
+    for (line_index, line) in input.lines().enumerate() {
+        for (column_index, c) in line.chars().enumerate() {
+            if let Some(&(_, _, name)) = LOOKALIKES.iter().find(|(lookalike, _, _)| *lookalike == c) {
+                found.push(Lookalike {
+                    line: line_index + 1,
+                    column: column_index + 1,
+                    found: c,
+                    name,
+                });
+            }
+        }
+    }
+    found
+}
+
+/// `input` with every character [`find_lookalikes`] reports replaced by its
+/// ASCII equivalent.
+pub fn replace_lookalikes(input: &str) -> String {
+    input
+        .chars()
+        .map(|c| {
+            LOOKALIKES
+                .iter()
+                .find(|(lookalike, _, _)| *lookalike == c)
+                .map_or(c, |&(_, ascii, _)| ascii)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A query as a chat app or word processor tends to mangle it.
+    const PASTED: &str = "SELECT * FROM users\r\nWHERE name = \u{2018}O\u{2019}Brien\u{2019}   \r\n  AND note = \u{201C}vip\u{201D}\u{00A0}LIMIT\u{202F}1;";
+
+    #[test]
+    fn line_endings_become_lf_without_trailing_whitespace() {
+        assert_eq!(
+            line_endings(PASTED),
+            "SELECT * FROM users\nWHERE name = \u{2018}O\u{2019}Brien\u{2019}\n  AND note = \u{201C}vip\u{201D}\u{00A0}LIMIT\u{202F}1;"
+        );
+        assert_eq!(line_endings("a \t\nb\r\n"), "a\nb");
+        assert!(matches!(line_endings("SELECT 1;\nSELECT 2;\n"), Cow::Borrowed(_)));
+        assert!(matches!(line_endings("  SELECT 1"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn finds_smart_quotes_and_odd_spaces_by_line_and_column() {
+        let input = line_endings(PASTED);
+        let found: Vec<(usize, usize, char, &str)> = find_lookalikes(&input)
+            .into_iter()
+            .map(|lookalike| (lookalike.line, lookalike.column, lookalike.found, lookalike.name))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (2, 14, '\u{2018}', "left single quotation mark"),
+                (2, 16, '\u{2019}', "right single quotation mark"),
+                (2, 22, '\u{2019}', "right single quotation mark"),
+                (3, 14, '\u{201C}', "left double quotation mark"),
+                (3, 18, '\u{201D}', "right double quotation mark"),
+                (3, 19, '\u{00A0}', "non-breaking space"),
+                (3, 25, '\u{202F}', "narrow non-breaking space"),
+            ]
+        );
+        // Columns count characters, not bytes
+        let found = find_lookalikes("SELECT 'é' \u{201E}x\u{201D}");
+        assert_eq!(found.iter().map(|lookalike| lookalike.column).collect::<Vec<_>>(), [12, 14]);
+        assert_eq!(find_lookalikes("\u{201A}")[0].name, "single low quotation mark");
+        assert!(find_lookalikes("SELECT 'plain', \"ascii\" -- « guillemets »").is_empty());
+    }
+
+    #[test]
+    fn replaces_each_lookalike_with_its_ascii_character() {
+        assert_eq!(
+            replace_lookalikes(&line_endings(PASTED)),
+            "SELECT * FROM users\nWHERE name = 'O'Brien'\n  AND note = \"vip\" LIMIT 1;"
+        );
+        assert_eq!(replace_lookalikes("\u{201A}a\u{2018} \u{201E}b\u{201C}"), "'a' \"b\"");
+        // Everything else, including other non-ASCII text, is left alone
+        assert_eq!(replace_lookalikes("SELECT 'café' -- « ok »"), "SELECT 'café' -- « ok »");
+        assert!(find_lookalikes(&replace_lookalikes(PASTED)).is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::Result;
+use chrono::Local;
+use console::style;
+use rustyline::ExternalPrinter;
+use sqlx::postgres::PgListener;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::config::DatabaseType;
+use crate::database::Database;
+use crate::error::QgoError;
+
+/// Prints above the readline prompt without disturbing the line being typed.
+pub type Printer = Arc<Mutex<dyn ExternalPrinter + Send>>;
+
+/// Postgres `LISTEN` subscriptions for the session. Notifications arrive on a
+/// dedicated connection, separate from the query pool, and are printed by a
+/// background task as they come in.
+pub struct Notifications {
+    url: String,
+    channels: BTreeSet<String>,
+    printer: Option<Printer>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl Notifications {
+    /// Fails for anything but Postgres, which is the only backend with
+    /// LISTEN/NOTIFY.
+    pub fn new(database: &Database, printer: Option<Printer>) -> Result<Self> {
+        let connection = database.get_connection();
+        if !matches!(connection.db_type, DatabaseType::PostgreSQL) {
+            return Err(QgoError::InvalidQuery("LISTEN and UNLISTEN are Postgres only".to_string()).into());
+        }
+
+        Ok(Self {
+            url: connection.connection_string(),
+            channels: BTreeSet::new(),
+            printer,
+            task: None,
+        })
+    }
+
+    pub fn channels(&self) -> &BTreeSet<String> {
+        &self.channels
+    }
+
+    pub async fn listen(&mut self, channel: &str) -> Result<()> {
+        let mut channels = self.channels.clone();
+        channels.insert(channel.to_string());
+        self.subscribe(channels).await
+    }
+
+    /// Stops listening on `channel`, or on every channel when it is `None`.
+    pub async fn unlisten(&mut self, channel: Option<&str>) -> Result<()> {
+        let mut channels = self.channels.clone();
+        match channel {
+            Some(channel) => {
+                channels.remove(channel);
+            }
+            None => channels.clear(),
+        }
+        self.subscribe(channels).await
+    }
+
+    /// Replaces the listening connection with one subscribed to `channels`.
+    /// The new connection is set up before the old one is dropped, so a
+    /// failed LISTEN leaves the existing subscriptions in place.
+    async fn subscribe(&mut self, channels: BTreeSet<String>) -> Result<()> {
+        let listener = if channels.is_empty() {
+            None
+        } else {
+            let mut listener = PgListener::connect(&self.url).await?;
+            listener.listen_all(channels.iter().map(String::as_str)).await?;
+            Some(listener)
+        };
+
+        self.stop();
+        self.channels = channels;
+        if let Some(listener) = listener {
+            self.task = Some(tokio::spawn(receive(listener, self.printer.clone())));
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for Notifications {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+async fn receive(mut listener: PgListener, printer: Option<Printer>) {
+    loop {
+        let message = match listener.recv().await {
+            Ok(notification) => format!(
+                "[{}] Notification on \"{}\" from server process {}: {}",
+                Local::now().format("%H:%M:%S"),
+                notification.channel(),
+                notification.process_id(),
+                notification.payload()
+            ),
+            Err(e) => {
+                print(&printer, style(format!("Stopped receiving notifications: {}", e)).red().to_string());
+                return;
+            }
+        };
+        print(&printer, message);
+    }
+}
+
+/// Prints `message` above the prompt, or plainly without a terminal.
+pub fn print(printer: &Option<Printer>, message: String) {
+    match printer.as_ref().and_then(|printer| printer.lock().ok()) {
+        Some(mut printer) => {
+            let _ = printer.print(format!("{}\n", message));
+        }
+        None => println!("{}", message),
+    }
+}
+
+/// Reads a channel name the way Postgres does: double-quoted names are
+/// taken as written, unquoted ones are folded to lower case.
+pub fn parse_channel(text: &str) -> Option<String> {
+    let text = text.trim().trim_end_matches(';').trim_end();
+    if text.is_empty() {
+        return None;
+    }
+    match text.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        Some(quoted) => Some(quoted.replace("\"\"", "\"")),
+        None => Some(text.to_lowercase()),
+    }
+}
@@ -0,0 +1,41 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static BANNER: AtomicBool = AtomicBool::new(true);
+
+/// Turns quiet mode on or off. In quiet mode banners, progress messages and
+/// the row-count footer are suppressed; tables, command output and errors
+/// (which always go to stderr) are unaffected.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Turns the connect and goodbye messages on or off, independently of quiet
+/// mode.
+pub fn set_banner(banner: bool) {
+    BANNER.store(banner, Ordering::Relaxed);
+}
+
+/// Prints a connect or goodbye message unless banners are off or quiet mode
+/// is on.
+pub fn banner(message: impl std::fmt::Display) {
+    if BANNER.load(Ordering::Relaxed) {
+        info(message);
+    }
+}
+
+/// Prints an informational message unless quiet mode is on.
+pub fn info(message: impl std::fmt::Display) {
+    if !is_quiet() {
+        println!("{}", message);
+    }
+}
+
+/// Prints a warning to stderr, in quiet mode too.
+pub fn warn(message: impl std::fmt::Display) {
+    eprintln!("{}", console::style(message).yellow());
+}
@@ -0,0 +1,194 @@
+use anyhow::Result;
+use console::style;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+use crate::config::{Config, Connection, PasswordStorage};
+use crate::database::Database;
+use crate::error::QgoError;
+use crate::secrets;
+use crate::ui::connection_manager;
+
+/// The longest a startup probe of one connection may take, password lookup
+/// included.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What a startup probe found out about one saved connection.
+pub enum ProbeStatus {
+    Reachable(Duration),
+    /// Refused, failed or took longer than [`PROBE_TIMEOUT`].
+    Unreachable,
+    /// No password was at hand without asking for one, so it wasn't tried.
+    AuthNeeded,
+}
+
+impl ProbeStatus {
+    /// The annotation shown after the connection in the main menu.
+    pub fn label(&self) -> String {
+        match self {
+            ProbeStatus::Reachable(latency) => style(format!("✓ {:.0} ms", millis(*latency))).green().to_string(),
+            ProbeStatus::Unreachable => style("✗").red().to_string(),
+            ProbeStatus::AuthNeeded => style("? auth needed").dim().to_string(),
+        }
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Pings one saved connection `count` times, printing each attempt and a
+/// summary. Returns whether every attempt succeeded.
+pub async fn ping_connection(config: &Config, name: &str, count: u32, interval: Duration) -> Result<bool> {
+    let connection = config
+        .get_connection_by_name(name)
+        .cloned()
+        .ok_or_else(|| QgoError::ConnectionNotFound(name.to_string()))?;
+    let connection = connection_manager::prepare_connection(&config.settings, connection)?;
+    let timeout = Duration::from_secs(config.settings.query_timeout_seconds);
+
+    println!("PING {} ({})", connection.display_name(), connection.db_type);
+
+    let mut latencies = Vec::new();
+    for seq in 1..=count {
+        match Database::test_connection(&connection, timeout, false).await {
+            Ok(report) => {
+                let latency = report.latency;
+                println!("seq={} time={:.1} ms", seq, millis(latency));
+                latencies.push(latency);
+            }
+            Err(e) => println!("seq={} {}", seq, style(format!("error: {}", e)).red()),
+        }
+
+        if seq < count {
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    let failed = count as usize - latencies.len();
+    println!();
+    println!("--- {} ping statistics ---", connection.name);
+    println!("{} attempts, {} succeeded, {} failed", count, latencies.len(), failed);
+
+    if let (Some(min), Some(max)) = (latencies.iter().min(), latencies.iter().max()) {
+        let avg = latencies.iter().sum::<Duration>() / latencies.len() as u32;
+        println!(
+            "min/avg/max = {:.1}/{:.1}/{:.1} ms",
+            millis(*min),
+            millis(avg),
+            millis(*max)
+        );
+    }
+
+    Ok(failed == 0)
+}
+
+/// Probes `connections` in parallel for the main menu, each for at most
+/// [`PROBE_TIMEOUT`]. Nothing is prompted for: passwords come from
+/// `known_passwords` or the keyring, and connections without one are
+/// reported as [`ProbeStatus::AuthNeeded`].
+pub async fn probe_all(connections: &[Connection], known_passwords: &HashMap<Uuid, String>) -> HashMap<Uuid, ProbeStatus> {
+    let mut tasks = JoinSet::new();
+    for connection in connections {
+        let mut connection = connection.clone();
+        if connection.password.is_empty() {
+            if let Some(password) = known_passwords.get(&connection.id) {
+                connection.password = password.clone();
+            }
+        }
+        tasks.spawn(async move {
+            let id = connection.id;
+            let status = tokio::time::timeout(PROBE_TIMEOUT, probe(connection))
+                .await
+                .unwrap_or(ProbeStatus::Unreachable);
+            (id, status)
+        });
+    }
+
+    let mut results = HashMap::new();
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok((id, status)) = joined {
+            results.insert(id, status);
+        }
+    }
+    results
+}
+
+async fn probe(mut connection: Connection) -> ProbeStatus {
+    if connection.password.is_empty() && connection.requires_password() {
+        // Unlocking the encrypted file would mean asking for its passphrase
+        if secrets::backend() != PasswordStorage::Keyring {
+            return ProbeStatus::AuthNeeded;
+        }
+        let lookup = connection.clone();
+        match tokio::task::spawn_blocking(move || secrets::load_password(&lookup)).await {
+            Ok(Some(password)) => connection.password = password,
+            _ => return ProbeStatus::AuthNeeded,
+        }
+    }
+
+    match Database::test_connection(&connection, PROBE_TIMEOUT, false).await {
+        Ok(report) => ProbeStatus::Reachable(report.latency),
+        Err(_) => ProbeStatus::Unreachable,
+    }
+}
+
+/// Pings every saved connection concurrently and prints a status table.
+/// Returns whether all of them answered.
+pub async fn ping_all(config: &Config) -> Result<bool> {
+    if config.connections.is_empty() {
+        println!("No saved connections.");
+        return Ok(true);
+    }
+
+    // Passwords are prompted for up front so the concurrent checks never race for the terminal
+    let mut connections = Vec::new();
+    for connection in &config.connections {
+        connections.push(connection_manager::prepare_connection(&config.settings, connection.clone())?);
+    }
+
+    let timeout = Duration::from_secs(config.settings.query_timeout_seconds);
+    let mut tasks = JoinSet::new();
+    for (index, connection) in connections.iter().cloned().enumerate() {
+        tasks.spawn(async move {
+            let result = Database::test_connection(&connection, timeout, false).await;
+            (index, result.map(|report| report.latency))
+        });
+    }
+
+    let mut results: Vec<Option<Result<Duration>>> = connections.iter().map(|_| None).collect();
+    while let Some(joined) = tasks.join_next().await {
+        let (index, result) = joined?;
+        results[index] = Some(result);
+    }
+
+    let name_width = connections.iter().map(|c| c.name.chars().count()).max().unwrap_or(0).max(4);
+    let type_width = 10;
+    println!("{:<name_width$}  {:<type_width$}  {:<6}  Latency", "Name", "Type", "Status");
+
+    let mut all_ok = true;
+    for (connection, result) in connections.iter().zip(results) {
+        let prefix = format!(
+            "{:<name_width$}  {:<type_width$}  ",
+            connection.name,
+            connection.db_type.to_string()
+        );
+        match result {
+            Some(Ok(latency)) => println!(
+                "{}{}  {:.1} ms",
+                prefix,
+                style(format!("{:<6}", "OK")).green(),
+                millis(latency)
+            ),
+            Some(Err(e)) => {
+                all_ok = false;
+                println!("{}{}  {}", prefix, style(format!("{:<6}", "FAIL")).red(), e);
+            }
+            None => all_ok = false,
+        }
+    }
+
+    Ok(all_ok)
+}
@@ -0,0 +1,520 @@
+//! Per-connection policies limiting which statements may run and which
+//! tables they may touch, for handing qgo to people who should only read
+//! some of the data.
+//!
+//! Statements are classified by their first keyword, and tables are found
+//! by name after `FROM`, `JOIN`, `INTO`, `UPDATE`, `TABLE` and the like.
+//! Names may be schema-qualified and quoted.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::QgoError;
+
+/// What may run over a connection. Each part left empty places no limit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Policy {
+    /// Statement kinds that may run, by first keyword (`select`, `explain`,
+    /// `insert`, ...). A `WITH` query counts as the statement after its
+    /// common table expressions. Empty allows every kind.
+    #[serde(default)]
+    pub allowed_statements: Vec<String>,
+    /// Tables no statement may name, as `table` or `schema.table` patterns
+    /// where `*` or `%` matches any run of characters, compared ignoring case.
+    #[serde(default)]
+    pub denied_tables: Vec<String>,
+    /// Results with more rows than this are refused rather than shown.
+    #[serde(default)]
+    pub max_rows: Option<usize>,
+}
+
+/// A table named in a statement; `schema` is `None` when it was not
+/// qualified.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableName {
+    pub schema: Option<String>,
+    pub name: String,
+}
+
+impl std::fmt::Display for TableName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.schema {
+            Some(ref schema) => write!(f, "{}.{}", schema, self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl Policy {
+    pub fn is_empty(&self) -> bool {
+        self.allowed_statements.is_empty() && self.denied_tables.is_empty() && self.max_rows.is_none()
+    }
+
+    /// Checks the statement kind and the tables `sql` names. Unqualified
+    /// tables are taken to be in `default_schema`; when that is unknown
+    /// they are matched on their name alone, so a `schema.table` pattern
+    /// errs towards refusing.
+    pub fn check(&self, sql: &str, default_schema: Option<&str>) -> Result<(), QgoError> {
+        if !self.allowed_statements.is_empty() {
+            for kind in statement_kinds(sql) {
+                if !self.allowed_statements.iter().any(|allowed| allowed.eq_ignore_ascii_case(&kind)) {
+                    return Err(QgoError::PolicyViolation(format!(
+                        "allowed_statements ({}) does not include {}",
+                        self.allowed_statements.join(", "),
+                        kind.to_uppercase()
+                    )));
+                }
+            }
+        }
+
+        if !self.denied_tables.is_empty() {
+            for table in table_references(sql) {
+                if let Some(pattern) = self
+                    .denied_tables
+                    .iter()
+                    .find(|pattern| matches_table(pattern, &table, default_schema))
+                {
+                    let resolved = TableName {
+                        schema: table.schema.clone().or_else(|| default_schema.map(str::to_string)),
+                        name: table.name,
+                    };
+                    return Err(QgoError::PolicyViolation(format!(
+                        "denied_tables pattern '{}' matches {}",
+                        pattern, resolved
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuses a result of `rows` rows when that is more than `max_rows`.
+    pub fn check_rows(&self, rows: usize) -> Result<(), QgoError> {
+        match self.max_rows {
+            Some(max_rows) if rows > max_rows => Err(QgoError::PolicyViolation(format!(
+                "max_rows is {} and the result has more rows; add a LIMIT or a narrower WHERE",
+                max_rows
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The kinds a statement is checked as, lowercase: its first keyword, the
+/// statement after the common table expressions of a `WITH` query along
+/// with any of them that writes (`WITH gone AS (DELETE ...) SELECT ...`),
+/// and for `EXPLAIN ANALYZE` also the explained statement, which it runs.
+pub fn statement_kinds(sql: &str) -> Vec<String> {
+    let tokens = tokenize(sql);
+    let mut words = tokens.iter().filter_map(|token| match token {
+        Token::Word(word, false) => Some(word.to_lowercase()),
+        _ => None,
+    });
+    let Some(first) = words.next() else {
+        return Vec::new();
+    };
+
+    match first.as_str() {
+        "with" => {
+            let mut kinds = vec![main_statement(&tokens).unwrap_or(first)];
+            for kind in writing_ctes(&tokens) {
+                if !kinds.contains(&kind) {
+                    kinds.push(kind);
+                }
+            }
+            kinds
+        }
+        "explain" => {
+            let mut kinds = vec![first];
+            if matches!(words.next().as_deref(), Some("analyze" | "analyse")) {
+                if let Some(explained) = words.find(|word| STATEMENT_WORDS.contains(&word.as_str())) {
+                    kinds.push(explained);
+                }
+            }
+            kinds
+        }
+        _ => vec![first],
+    }
+}
+
+/// The tables `sql` names, in order, including those in subqueries.
+pub fn table_references(sql: &str) -> Vec<TableName> {
+    let tokens = tokenize(sql);
+    let mut tables = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let is_keyword = matches!(&tokens[i], Token::Word(word, false) if TABLE_KEYWORDS.contains(&word.to_lowercase().as_str()));
+        i += 1;
+        if !is_keyword {
+            continue;
+        }
+
+        // A list of tables, each possibly followed by an alias
+        loop {
+            while matches!(&tokens.get(i), Some(Token::Word(word, false)) if SKIPPED_WORDS.contains(&word.to_lowercase().as_str())) {
+                i += 1;
+            }
+            let Some((table, next)) = qualified_name(&tokens, i) else {
+                break;
+            };
+            tables.push(table);
+            i = next;
+
+            if matches!(&tokens.get(i), Some(Token::Word(word, false)) if word.eq_ignore_ascii_case("as")) {
+                i += 2;
+            } else if match tokens.get(i) {
+                Some(Token::Word(_, true)) => true,
+                Some(Token::Word(word, false)) => !CLAUSE_WORDS.contains(&word.to_lowercase().as_str()),
+                _ => false,
+            } {
+                i += 1;
+            }
+            if tokens.get(i) != Some(&Token::Symbol(',')) {
+                break;
+            }
+            i += 1;
+        }
+    }
+    tables
+}
+
+/// Whether `table` matches a `table` or `schema.table` pattern.
+pub fn matches_table(pattern: &str, table: &TableName, default_schema: Option<&str>) -> bool {
+    match pattern.rsplit_once('.') {
+        Some((schema_pattern, name_pattern)) => {
+            let schema_matches = match table.schema.as_deref().or(default_schema) {
+                Some(schema) => matches_pattern(schema_pattern, schema),
+                None => true,
+            };
+            schema_matches && matches_pattern(name_pattern, &table.name)
+        }
+        None => matches_pattern(pattern, &table.name),
+    }
+}
+
+/// Case-insensitive match where `*` and `%` match any run of characters.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '*' || pattern[p] == '%') {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == name[n] {
+            p += 1;
+            n += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*' || *c == '%')
+}
+
+/// Keywords followed by a table name.
+const TABLE_KEYWORDS: &[&str] = &["from", "join", "into", "update", "table", "truncate", "describe", "desc", "using"];
+
+/// Words that may come between a table keyword and the name.
+const SKIPPED_WORDS: &[&str] = &["only", "lateral", "if", "not", "exists", "table", "ignore"];
+
+/// Words that end a list of tables rather than giving one an alias.
+const CLAUSE_WORDS: &[&str] = &[
+    "where", "join", "inner", "left", "right", "full", "cross", "natural", "straight_join", "on", "using", "group",
+    "order", "limit", "having", "union", "intersect", "except", "window", "offset", "fetch", "for", "set", "values",
+    "returning", "select", "default", "partition", "lock", "when", "then", "else", "end",
+];
+
+/// Keywords that start the statement a `WITH` or `EXPLAIN` leads up to.
+const STATEMENT_WORDS: &[&str] = &["select", "insert", "update", "delete", "merge", "values", "table"];
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// A word or identifier; `true` when it was quoted.
+    Word(String, bool),
+    Symbol(char),
+}
+
+/// The first statement keyword outside parentheses after a `WITH`.
+fn main_statement(tokens: &[Token]) -> Option<String> {
+    let mut depth = 0usize;
+    for token in tokens.iter().skip(1) {
+        match token {
+            Token::Symbol('(') => depth += 1,
+            Token::Symbol(')') => depth = depth.saturating_sub(1),
+            Token::Word(word, false) if depth == 0 => {
+                let word = word.to_lowercase();
+                if STATEMENT_WORDS.contains(&word.as_str()) {
+                    return Some(word);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The statement keyword opening each common table expression of a `WITH`
+/// that modifies data.
+fn writing_ctes(tokens: &[Token]) -> Vec<String> {
+    let mut kinds = Vec::new();
+    let mut depth = 0usize;
+    let mut opened = false;
+    for token in tokens.iter().skip(1) {
+        match token {
+            Token::Symbol('(') => {
+                opened = depth == 0;
+                depth += 1;
+            }
+            Token::Symbol(')') => depth = depth.saturating_sub(1),
+            Token::Word(word, false) if opened => {
+                let word = word.to_lowercase();
+                if matches!(word.as_str(), "insert" | "update" | "delete" | "merge") {
+                    kinds.push(word);
+                }
+            }
+            _ => {}
+        }
+        if !matches!(token, Token::Symbol('(')) {
+            opened = false;
+        }
+    }
+    kinds
+}
+
+/// A possibly qualified name starting at `tokens[start]`, and the index
+/// after it. The last part is the table and the one before it the schema.
+fn qualified_name(tokens: &[Token], start: usize) -> Option<(TableName, usize)> {
+    let mut parts = Vec::new();
+    let mut i = start;
+    loop {
+        match tokens.get(i) {
+            Some(Token::Word(word, quoted)) if *quoted || !word.starts_with(|c: char| c.is_ascii_digit()) => {
+                parts.push(word.clone());
+            }
+            _ => return None,
+        }
+        i += 1;
+        if tokens.get(i) != Some(&Token::Symbol('.')) {
+            break;
+        }
+        i += 1;
+    }
+
+    let name = parts.pop()?;
+    Some((TableName { schema: parts.pop(), name }, i))
+}
+
+/// Splits `sql` into words and symbols, leaving out string literals and
+/// comments and unquoting `"..."` and `` `...` `` identifiers.
+fn tokenize(sql: &str) -> Vec<Token> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && chars.get(i + 1) == Some(&'-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '\'' {
+            i = quoted_end(&chars, i, '\'').1;
+        } else if c == '"' || c == '`' {
+            let (text, end) = quoted_end(&chars, i, c);
+            tokens.push(Token::Word(text, true));
+            i = end;
+        } else if c == '$' && dollar_tag(&chars, i).is_some() {
+            // A Postgres dollar-quoted string, $$...$$ or $tag$...$tag$
+            let tag = dollar_tag(&chars, i).unwrap_or_default();
+            i += tag.len();
+            while i < chars.len() && !chars[i..].starts_with(&tag) {
+                i += 1;
+            }
+            i += tag.len();
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().collect(), false));
+        } else {
+            tokens.push(Token::Symbol(c));
+            i += 1;
+        }
+    }
+    tokens
+}
+
+/// The text of the quoted run starting at `chars[start]`, with doubled
+/// quotes read as one, and the index after its closing quote.
+fn quoted_end(chars: &[char], start: usize, quote: char) -> (String, usize) {
+    let mut text = String::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                text.push(quote);
+                i += 2;
+                continue;
+            }
+            return (text, i + 1);
+        }
+        text.push(chars[i]);
+        i += 1;
+    }
+    (text, i)
+}
+
+/// The `$tag$` opening a dollar-quoted string at `chars[start]`, if one does.
+fn dollar_tag(chars: &[char], start: usize) -> Option<Vec<char>> {
+    let mut i = start + 1;
+    while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+        i += 1;
+    }
+    if chars.get(i) == Some(&'$') && !chars.get(start + 1).is_some_and(|c| c.is_ascii_digit()) {
+        Some(chars[start..=i].to_vec())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn denying(patterns: &[&str]) -> Policy {
+        Policy {
+            allowed_statements: vec!["select".to_string(), "explain".to_string()],
+            denied_tables: patterns.iter().map(|pattern| pattern.to_string()).collect(),
+            max_rows: None,
+        }
+    }
+
+    fn table(schema: Option<&str>, name: &str) -> TableName {
+        TableName { schema: schema.map(str::to_string), name: name.to_string() }
+    }
+
+    fn names(sql: &str) -> Vec<String> {
+        table_references(sql).iter().map(TableName::to_string).collect()
+    }
+
+    #[test]
+    fn checks_statement_kinds_and_denied_tables() {
+        let policy = denying(&["payments.*", "*.secrets"]);
+        assert!(policy.check("SELECT * FROM orders", Some("public")).is_ok());
+        assert!(policy.check("DELETE FROM orders", Some("public")).is_err());
+        assert!(policy.check("SELECT * FROM payments.cards", Some("public")).is_err());
+        assert!(policy.check(r#"SELECT * FROM "Payments"."cards""#, Some("public")).is_err());
+        assert!(policy.check("SELECT * FROM `payments`.`cards`", Some("shop")).is_err());
+        assert!(policy.check("SELECT * FROM orders o JOIN app.secrets s ON true", Some("public")).is_err());
+        // Unqualified names resolve against the default schema
+        assert!(policy.check("SELECT * FROM cards", Some("payments")).is_err());
+        assert!(policy.check("SELECT * FROM cards", Some("public")).is_ok());
+        // Without one, a schema.table pattern errs towards refusing
+        assert!(policy.check("SELECT * FROM cards", None).is_err());
+        // Names inside string literals and comments are not table references
+        assert!(policy.check("SELECT 'from payments.cards' -- join payments.cards", Some("public")).is_ok());
+        assert!(policy.check("SELECT 1 /* FROM payments.cards */", Some("public")).is_ok());
+        assert!(policy.check("SELECT $x$ FROM payments.cards $x$", Some("public")).is_ok());
+    }
+
+    #[test]
+    fn violations_name_the_pattern_and_the_resolved_table() {
+        let error = denying(&["payments.*"]).check("SELECT * FROM cards", Some("payments")).unwrap_err();
+        assert!(error.to_string().contains("'payments.*' matches payments.cards"), "{error}");
+        let error = denying(&[]).check("DROP TABLE t", None).unwrap_err();
+        assert!(error.to_string().contains("does not include DROP"), "{error}");
+    }
+
+    #[test]
+    fn classifies_statements() {
+        assert_eq!(statement_kinds("select 1"), vec!["select"]);
+        assert_eq!(statement_kinds("WITH d AS (SELECT 1) DELETE FROM t"), vec!["delete"]);
+        assert_eq!(
+            statement_kinds("WITH gone AS (DELETE FROM t RETURNING id), n AS (SELECT 1) SELECT * FROM gone"),
+            vec!["select", "delete"]
+        );
+        assert_eq!(statement_kinds("EXPLAIN ANALYZE UPDATE t SET a = 1"), vec!["explain", "update"]);
+        assert_eq!(statement_kinds("EXPLAIN UPDATE t SET a = 1"), vec!["explain"]);
+        assert_eq!(statement_kinds("-- note\n/* x */ Select 1"), vec!["select"]);
+        assert!(statement_kinds("  ").is_empty());
+    }
+
+    #[test]
+    fn finds_schema_qualified_and_quoted_tables() {
+        assert_eq!(
+            names(r#"SELECT * FROM app."Orders" o, items JOIN public.t ON true"#),
+            ["app.Orders", "items", "public.t"]
+        );
+        assert_eq!(names("SELECT * FROM `shop`.`Order Items`"), ["shop.Order Items"]);
+        assert_eq!(names(r#"SELECT * FROM db.sales."Orders""#), ["sales.Orders"]);
+        assert_eq!(names("SELECT * FROM (SELECT * FROM inner_t) AS x JOIN other y ON true"), ["inner_t", "other"]);
+        assert_eq!(names("INSERT INTO audit.log SELECT * FROM ONLY events"), ["audit.log", "events"]);
+        assert_eq!(names("UPDATE accounts SET x = 1 FROM ledger WHERE true"), ["accounts", "ledger"]);
+    }
+
+    #[test]
+    fn quoted_names_may_contain_dots_and_quotes() {
+        assert_eq!(table_references(r#"SELECT * FROM "pay.ments"."cards""#), [table(Some("pay.ments"), "cards")]);
+        assert_eq!(table_references(r#"SELECT * FROM "payments.cards""#), [table(None, "payments.cards")]);
+        assert_eq!(table_references("SELECT * FROM `pay``ments`.`ca\"rds`"), [table(Some("pay`ments"), "ca\"rds")]);
+        assert_eq!(table_references(r#"SELECT * FROM "a""b".c"#), [table(Some("a\"b"), "c")]);
+
+        // The dot in a quoted schema is part of the name, not a separator
+        let dotted = table(Some("pay.ments"), "cards");
+        assert!(matches_table("pay.ments.*", &dotted, None));
+        assert!(!matches_table("payments.*", &dotted, None));
+        assert!(!matches_table("ments.cards", &dotted, None));
+
+        // A single quoted name with a dot is an unqualified table
+        let policy = denying(&["payments.*"]);
+        assert!(policy.check(r#"SELECT * FROM "payments.cards""#, Some("public")).is_ok());
+        assert!(denying(&["payments%cards"]).check(r#"SELECT * FROM "payments.cards""#, Some("public")).is_err());
+    }
+
+    #[test]
+    fn matches_table_patterns() {
+        let cards = table(Some("payments"), "cards");
+        assert!(matches_table("payments.*", &cards, None));
+        assert!(matches_table("CARDS", &cards, None));
+        assert!(matches_table("*.cards", &cards, None));
+        assert!(!matches_table("billing.*", &cards, None));
+
+        let unqualified = table(None, "cards");
+        assert!(matches_table("payments.*", &unqualified, Some("payments")));
+        assert!(!matches_table("payments.*", &unqualified, Some("public")));
+        assert!(matches_table("payments.*", &unqualified, None));
+    }
+
+    #[test]
+    fn percent_and_star_match_any_run() {
+        assert!(matches_pattern("pay%", "payments"));
+        assert!(matches_pattern("%ments", "payments"));
+        assert!(matches_pattern("p%y*s", "payments"));
+        assert!(matches_pattern("%", ""));
+        assert!(matches_pattern("**", "anything"));
+        assert!(matches_pattern("a%b%c", "aXbYbZc"));
+        assert!(!matches_pattern("a%b%c", "aXbYbZ"));
+        assert!(!matches_pattern("pay", "payments"));
+        assert!(matches_pattern("ÜBER%", "über_tabelle"));
+
+        let secrets = table(Some("app_2024"), "user_secrets");
+        assert!(matches_table("app%.%secret%", &secrets, None));
+        assert!(matches_table("%.user_%", &secrets, None));
+        assert!(!matches_table("app%.orders", &secrets, None));
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+/// How often the status line is redrawn on a terminal.
+const REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often a log line is written when stderr is not a terminal.
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Reports how far a long-running export has got on stderr, leaving stdout
+/// for results. On a terminal a single status line is redrawn in place;
+/// otherwise a plain line is logged every [`LOG_INTERVAL`].
+pub struct Progress {
+    started: Instant,
+    last_report: Instant,
+    interval: Duration,
+    terminal: bool,
+    /// Whether a status line is on screen and needs clearing.
+    drawn: bool,
+}
+
+impl Progress {
+    pub fn start() -> Self {
+        let terminal = std::io::stderr().is_terminal();
+        let now = Instant::now();
+        Self {
+            started: now,
+            last_report: now,
+            interval: if terminal { REDRAW_INTERVAL } else { LOG_INTERVAL },
+            terminal,
+            drawn: false,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Reports `rows` and `bytes` written if the interval has passed. Cheap
+    /// enough to call for every row.
+    pub fn update(&mut self, rows: u64, bytes: u64) {
+        if self.last_report.elapsed() < self.interval {
+            return;
+        }
+        self.last_report = Instant::now();
+
+        let elapsed = self.elapsed();
+        let status = format!(
+            "{} rows, {} written, {:.0} rows/s, {} elapsed",
+            rows,
+            format_bytes(bytes),
+            rows as f64 / elapsed.as_secs_f64().max(0.001),
+            format_duration(elapsed)
+        );
+        let mut stderr = std::io::stderr();
+        if self.terminal {
+            let _ = write!(stderr, "\r\x1b[2K{}", status);
+            let _ = stderr.flush();
+            self.drawn = true;
+        } else {
+            let _ = writeln!(stderr, "Exporting: {}", status);
+        }
+    }
+
+    /// Clears the status line so a summary can be printed in its place.
+    pub fn finish(&mut self) {
+        if self.drawn {
+            let mut stderr = std::io::stderr();
+            let _ = write!(stderr, "\r\x1b[2K");
+            let _ = stderr.flush();
+            self.drawn = false;
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", bytes as f64 / MB)
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::Result;
+use console::style;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::config::{normalize_connection_name, normalize_host, validate_port, Config, Connection, DatabaseType};
+use crate::error::QgoError;
+use qgo::color::ConnectionColor;
+use crate::ui::{output, prompts};
+
+/// The project file looked for in the current directory and its parents.
+pub const FILE_NAME: &str = ".qgo.toml";
+
+/// The contents of a project file: connections only. Passwords come from
+/// `password_env` or the keyring, never from the file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectFile {
+    #[serde(default)]
+    connections: Vec<ProjectConnection>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectConnection {
+    name: String,
+    #[serde(rename = "type")]
+    db_type: String,
+    #[serde(default)]
+    host: String,
+    port: Option<u16>,
+    #[serde(default)]
+    username: String,
+    /// The database name, or for SQLite the file, relative to the project
+    /// file's directory.
+    #[serde(default)]
+    database: String,
+    /// The environment variable holding the password.
+    password_env: Option<String>,
+    environment: Option<String>,
+    /// A color name or `#rrggbb` for the prompt.
+    color: Option<String>,
+    #[serde(default)]
+    allow_writes: bool,
+    /// Only present to refuse it with a helpful message.
+    password: Option<toml::Value>,
+}
+
+/// A project file that was found and read.
+pub struct Project {
+    path: PathBuf,
+    /// SHA-256 of the file's content, as recorded in
+    /// [`Config::trusted_projects`].
+    hash: String,
+    connections: Vec<Connection>,
+}
+
+/// The nearest [`FILE_NAME`] in `start` or one of its parents.
+pub fn find(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Reads the project file at `path`.
+pub fn read(path: &Path) -> Result<Project> {
+    let path = path.canonicalize()?;
+    let content = std::fs::read_to_string(&path)?;
+    let invalid = |message: String| QgoError::Usage(format!("Invalid project file '{}': {}", path.display(), message));
+
+    let file: ProjectFile = toml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+    let dir = path.parent().unwrap_or(Path::new("/"));
+    let mut connections: Vec<Connection> = Vec::new();
+    for entry in file.connections {
+        let connection = entry_connection(entry, &path, dir).map_err(invalid)?;
+        if connections.iter().any(|other| other.name == connection.name) {
+            return Err(invalid(format!("connection '{}' is defined twice", connection.name)).into());
+        }
+        connections.push(connection);
+    }
+
+    Ok(Project {
+        hash: format!("{:x}", Sha256::digest(content.as_bytes())),
+        path,
+        connections,
+    })
+}
+
+fn entry_connection(entry: ProjectConnection, path: &Path, dir: &Path) -> std::result::Result<Connection, String> {
+    let name = normalize_connection_name(&entry.name)?;
+    if entry.password.is_some() {
+        return Err(format!(
+            "connection '{}' has a password; set password_env to the variable holding it, or store it with `qgo secrets set {}`",
+            name, name
+        ));
+    }
+    let db_type = DatabaseType::from_name(&entry.db_type)
+        .ok_or_else(|| format!("connection '{}' has unknown type '{}'", name, entry.db_type))?;
+
+    let (host, port, database) = match db_type {
+        DatabaseType::SQLite => {
+            if entry.database.is_empty() {
+                return Err(format!("connection '{}' needs a database file", name));
+            }
+            let file = Path::new(&entry.database);
+            let database = if entry.database == ":memory:" || file.is_absolute() {
+                entry.database
+            } else {
+                dir.join(file).to_string_lossy().into_owned()
+            };
+            (String::new(), 0, database)
+        }
+        _ => {
+            let host = if entry.host.is_empty() { "localhost".to_string() } else { normalize_host(&entry.host)? };
+            let port = entry.port.unwrap_or(match db_type {
+                DatabaseType::PostgreSQL => 5432,
+                _ => 3306,
+            });
+            validate_port(port)?;
+            (host, port, entry.database)
+        }
+    };
+
+    if let Some(ref color) = entry.color {
+        ConnectionColor::parse(color).map_err(|e| format!("connection '{}': {}", name, e))?;
+    }
+
+    let password = match entry.password_env {
+        Some(ref variable) => std::env::var(variable).unwrap_or_default(),
+        None => String::new(),
+    };
+
+    let mut connection = Connection::new(name, db_type, host, port, entry.username, password, database);
+    // Stable across runs so a password in the keyring is found again
+    let key = Sha256::digest(format!("{}\0{}", path.display(), connection.name).as_bytes());
+    let mut id = [0; 16];
+    id.copy_from_slice(&key[..16]);
+    connection.id = Uuid::from_bytes(id);
+    connection.environment = entry.environment;
+    connection.color = entry.color;
+    connection.allow_writes = entry.allow_writes;
+    connection.unsaved = true;
+    connection.project = true;
+    Ok(connection)
+}
+
+/// Offers the connections of the project file nearest the current
+/// directory, if there is one. A file is only loaded once the user has
+/// agreed to it; the agreement is kept in the config and lasts until the
+/// file changes. Without a terminal to ask on, an unapproved file is
+/// skipped with a note.
+pub async fn load(config: &mut Config) -> Result<()> {
+    let Some(path) = std::env::current_dir().ok().and_then(|dir| find(&dir)) else {
+        return Ok(());
+    };
+    let project = read(&path)?;
+    if project.connections.is_empty() {
+        return Ok(());
+    }
+
+    let key = project.path.to_string_lossy().into_owned();
+    let trusted = config.trusted_projects.get(&key);
+    if trusted != Some(&project.hash) {
+        if !std::io::stdin().is_terminal() {
+            eprintln!(
+                "{}",
+                style(format!(
+                    "Skipped the connections in {}: run qgo at a terminal once to review them.",
+                    project.path.display()
+                ))
+                .yellow()
+            );
+            return Ok(());
+        }
+        let changed = if trusted.is_some() { " (changed since you last allowed it)" } else { "" };
+        println!("{}", style(format!("Found {}{}:", project.path.display(), changed)).bold());
+        for connection in &project.connections {
+            println!("  {} {} {}", connection.name, connection.db_type, connection.target());
+        }
+        if !prompts::confirm("Load these connections?") {
+            println!("Not loaded; qgo will ask again next time (or pass --no-project-config).");
+            return Ok(());
+        }
+        config.trusted_projects.insert(key, project.hash.clone());
+        config.save().await?;
+    }
+
+    output::info(style(format!("Using {} connections from {}.", project.connections.len(), project.path.display())).dim());
+    config.project_connections = project.connections;
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::atomic;
+use crate::config::{self, Connection};
+use crate::database::QueryResult;
+
+/// One executed query, as recorded in the per-connection recent-queries file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentQuery {
+    pub query: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub row_count: Option<usize>,
+    /// The error message for a failed query; `None` means it succeeded.
+    pub error: Option<String>,
+}
+
+impl RecentQuery {
+    pub fn new(query: &str, started_at: DateTime<Utc>, duration: Duration, outcome: &Result<QueryResult>) -> Self {
+        Self {
+            query: query.to_string(),
+            started_at,
+            duration_ms: duration.as_millis() as u64,
+            row_count: outcome.as_ref().ok().map(|result| result.row_count),
+            error: outcome.as_ref().err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// The statements run in this session, for `\slow`. Only the latest
+/// [`SessionLog::MAX_ENTRIES`] are kept, each with its SQL cut to
+/// [`SessionLog::MAX_QUERY_CHARS`] characters.
+#[derive(Default)]
+pub struct SessionLog {
+    entries: VecDeque<RecentQuery>,
+}
+
+impl SessionLog {
+    pub const MAX_ENTRIES: usize = 1000;
+    pub const MAX_QUERY_CHARS: usize = 500;
+
+    pub fn push(&mut self, mut entry: RecentQuery) {
+        if let Some((cut, _)) = entry.query.char_indices().nth(Self::MAX_QUERY_CHARS) {
+            entry.query.truncate(cut);
+            entry.query.push('…');
+        }
+        if self.entries.len() == Self::MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &RecentQuery> {
+        self.entries.iter()
+    }
+}
+
+/// The `n` slowest of `session` and, with `all`, of the connection's
+/// recorded history, slowest first. A query that is in both counts once.
+pub fn slowest(connection: &Connection, session: &SessionLog, all: bool, n: usize) -> Vec<RecentQuery> {
+    let mut entries = if all { load(connection, usize::MAX) } else { Vec::new() };
+    let recorded: HashSet<DateTime<Utc>> = entries.iter().map(|entry| entry.started_at).collect();
+    entries.extend(session.entries().filter(|entry| !recorded.contains(&entry.started_at)).cloned());
+    entries.sort_by_key(|entry| Reverse(entry.duration_ms));
+    entries.truncate(n);
+    entries
+}
+
+/// Recent queries live next to the config, one JSONL file per connection id.
+fn recent_path(connection: &Connection) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| {
+        dir.join("qgo")
+            .join("recent")
+            .join(format!("{}.jsonl", connection.id))
+    })
+}
+
+/// Appends `entry` to the connection's file. Once the file grows past
+/// `max_bytes` it is moved aside to `<id>.jsonl.1`, replacing the previous
+/// rotation, and a fresh file is started. Nothing is kept when qgo is
+/// ephemeral.
+pub fn record(connection: &Connection, entry: &RecentQuery, max_bytes: u64) -> Result<()> {
+    let path = match recent_path(connection) {
+        Some(path) if !config::is_ephemeral() => path,
+        _ => return Ok(()),
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+
+    if file.metadata()?.len() > max_bytes {
+        fs::rename(&path, path.with_extension("jsonl.1"))?;
+    }
+    Ok(())
+}
+
+/// Moves the recent queries of `from` into the history of `into`, keeping
+/// them in the order they ran, for when two saved connections are merged.
+pub fn merge(from: &Connection, into: &Connection) -> Result<()> {
+    let (Some(from_path), Some(into_path)) = (recent_path(from), recent_path(into)) else {
+        return Ok(());
+    };
+    let files = |path: &PathBuf| [path.with_extension("jsonl.1"), path.clone()];
+
+    let mut entries: Vec<RecentQuery> = files(&into_path)
+        .iter()
+        .chain(files(&from_path).iter())
+        .flat_map(|file| {
+            let content = fs::read_to_string(file).unwrap_or_default();
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str::<RecentQuery>(line).ok())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    if entries.is_empty() {
+        return Ok(());
+    }
+    entries.sort_by_key(|entry| entry.started_at);
+
+    let mut content = String::new();
+    for entry in &entries {
+        content.push_str(&serde_json::to_string(entry)?);
+        content.push('\n');
+    }
+    if let Some(parent) = into_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    atomic::write(&into_path, content.as_bytes(), false)?;
+    for file in files(&from_path).iter().chain(&files(&into_path)[..1]) {
+        let _ = fs::remove_file(file);
+    }
+    Ok(())
+}
+
+/// Loads up to `limit` entries for the connection, most recent first.
+/// Lines that fail to parse are skipped.
+pub fn load(connection: &Connection, limit: usize) -> Vec<RecentQuery> {
+    let path = match recent_path(connection) {
+        Some(path) => path,
+        None => return Vec::new(),
+    };
+
+    let mut entries = Vec::new();
+    for file in [path.clone(), path.with_extension("jsonl.1")] {
+        let content = fs::read_to_string(file).unwrap_or_default();
+        let parsed = content
+            .lines()
+            .rev()
+            .filter_map(|line| serde_json::from_str::<RecentQuery>(line).ok());
+        entries.extend(parsed.take(limit - entries.len()));
+        if entries.len() == limit {
+            break;
+        }
+    }
+    entries
+}
+
+/// Lays `entries` out as a result table, numbered from 1 (most recent) so
+/// `\recent full <n>` can refer to them.
+pub fn to_result(entries: &[RecentQuery]) -> QueryResult {
+    const QUERY_WIDTH: usize = 60;
+
+    let rows: Vec<Vec<String>> = entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let query = entry.query.split_whitespace().collect::<Vec<_>>().join(" ");
+            let query = if query.chars().count() > QUERY_WIDTH {
+                format!("{}…", query.chars().take(QUERY_WIDTH - 1).collect::<String>())
+            } else {
+                query
+            };
+            vec![
+                (index + 1).to_string(),
+                entry.started_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string(),
+                format!("{} ms", entry.duration_ms),
+                entry.row_count.map_or_else(|| "-".to_string(), |count| count.to_string()),
+                if entry.error.is_some() { "error" } else { "ok" }.to_string(),
+                query,
+            ]
+        })
+        .collect();
+
+    QueryResult::from_rows(&["#", "Started", "Duration", "Rows", "Status", "Query"], rows)
+}
@@ -0,0 +1,729 @@
+use anyhow::Result;
+use console::style;
+use std::time::Instant;
+
+use crate::config::{DatabaseType, OnError};
+use crate::error::QgoError;
+use crate::database::{self, Database, StatementOutcome};
+use crate::recent::{RecentQuery, SessionLog};
+use crate::transcript::Transcript;
+use crate::ui::{output, prompts};
+use qgo::format::{self, DisplayOptions};
+use qgo::policy;
+
+/// How a script is run.
+#[derive(Debug, Clone, Copy)]
+pub struct ScriptOptions {
+    pub on_error: OnError,
+    /// Wrap the whole script in one transaction that is rolled back if any
+    /// statement fails.
+    pub single_transaction: bool,
+    /// Leave a transaction the statements opened running for later input,
+    /// as at the interactive prompt, instead of rolling it back at the end.
+    pub keep_transaction_open: bool,
+}
+
+/// Asking before each statement, for the `confirm_each_query` setting.
+/// Answering "run the rest" stops the asking for the remainder of the
+/// script, and "stop" ends the script.
+#[derive(Debug, Default)]
+pub struct ConfirmEach {
+    asking: bool,
+    stopped: bool,
+}
+
+impl ConfirmEach {
+    /// Asks before each statement when `asking` is set; otherwise every
+    /// statement is allowed. The caller makes sure there is a terminal.
+    pub fn new(asking: bool) -> Self {
+        Self { asking, stopped: false }
+    }
+
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Prints the kind of `statement` and the tables it names, and asks
+    /// whether to run it. Returns whether it should run. `batch` offers
+    /// running the rest without asking, and stopping; a single statement
+    /// can only be run or skipped.
+    pub fn allows(&mut self, statement: &str, batch: bool) -> bool {
+        if !self.asking {
+            return !self.stopped;
+        }
+
+        let class = database::classify(statement);
+        let kinds: Vec<String> = policy::statement_kinds(statement).iter().map(|kind| kind.to_uppercase()).collect();
+        let mut tables: Vec<String> = Vec::new();
+        for table in policy::table_references(statement) {
+            let table = table.to_string();
+            if !tables.contains(&table) {
+                tables.push(table);
+            }
+        }
+        let class = class.to_string();
+        let mut summary = match kinds.as_slice() {
+            [] => class,
+            [kind] if *kind == class => class,
+            kinds => format!("{} ({})", class, kinds.join(", ")),
+        };
+        if !tables.is_empty() {
+            summary = format!("{} on {}", summary, tables.join(", "));
+        }
+        eprintln!("{}", style(summary).bold());
+        eprintln!("  {}", preview(statement));
+
+        let items: &[&str] = if batch {
+            &["Run it", "Skip it", "Run it and the rest without asking", "Stop the script"]
+        } else {
+            &["Run it", "Skip it"]
+        };
+        match prompts::select("Run this statement?", items) {
+            Some(0) => true,
+            Some(2) => {
+                self.asking = false;
+                true
+            }
+            Some(1) => false,
+            // Backing out of the prompt skips a single statement and stops a script
+            _ => {
+                if batch {
+                    self.asking = false;
+                    self.stopped = true;
+                }
+                false
+            }
+        }
+    }
+}
+
+/// The first line of `statement`, cut short, for showing what is about to run.
+pub fn preview(statement: &str) -> String {
+    const MAX_CHARS: usize = 100;
+    let statement = database::skip_leading_comments(statement).trim();
+    let first_line = statement.lines().next().unwrap_or_default();
+    if first_line.chars().count() > MAX_CHARS {
+        format!("{}…", first_line.chars().take(MAX_CHARS).collect::<String>())
+    } else if first_line.len() < statement.len() {
+        format!("{} …", first_line)
+    } else {
+        first_line.to_string()
+    }
+}
+
+/// A statement that failed while running a script.
+pub struct Failure {
+    /// 1-based position of the statement in the script.
+    pub statement: usize,
+    /// 1-based line of the script file the statement starts on, when known.
+    pub line: Option<usize>,
+    pub error: anyhow::Error,
+}
+
+/// Reads a script file, without the byte order mark editors on Windows
+/// often start one with.
+pub fn read_script(path: &str) -> Result<String> {
+    let sql = std::fs::read_to_string(path)?;
+    Ok(match sql.strip_prefix('\u{feff}') {
+        Some(rest) => rest.to_string(),
+        None => sql,
+    })
+}
+
+/// Splits SQL text into statements on `;`, ignoring semicolons inside
+/// quotes, comments and Postgres dollar-quoted bodies, along with the
+/// 1-based line each statement's first keyword is on (comments and blank
+/// lines before it count). Statements consisting only of whitespace and
+/// comments are dropped. `DELIMITER` lines change the terminator as in the
+/// MySQL client; see [`split_with_delimiter`]. Quoting follows `db_type`:
+/// backslash escapes inside strings are MySQL's, and dollar quoting is
+/// Postgres's.
+pub fn split_statements_with_lines(db_type: &DatabaseType, sql: &str) -> Vec<(usize, String)> {
+    split_with_delimiter(db_type, sql, &mut DEFAULT_DELIMITER.to_string())
+}
+
+/// The statement terminator scripts start with.
+pub const DEFAULT_DELIMITER: &str = ";";
+
+/// [`split_statements_with_lines`], ending statements on `delimiter`
+/// instead of `;`. A `DELIMITER <str>` line at the start of a statement
+/// switches the terminator for the statements after it, so routine bodies
+/// can hold semicolons (`DELIMITER //` ... `END //` ... `DELIMITER ;`); the
+/// line is handled here and never sent to the server. `delimiter` is left
+/// as the terminator in effect at the end.
+pub fn split_with_delimiter(db_type: &DatabaseType, sql: &str, delimiter: &mut String) -> Vec<(usize, String)> {
+    let backslash_escapes = matches!(db_type, DatabaseType::MySQL | DatabaseType::MariaDB);
+    let dollar_quotes = matches!(db_type, DatabaseType::PostgreSQL);
+    let bytes = sql.as_bytes();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    // Where the current statement's first non-comment text is
+    let mut content_start = None;
+    let mut line = 1;
+    let mut counted = 0;
+    let mut line_at = |position: usize| {
+        line += sql[counted..position].matches('\n').count();
+        counted = position;
+        line
+    };
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            // Checked first, so a terminator such as `$$` or `//` isn't
+            // taken for the start of a quote or a comment. Compared as bytes:
+            // `i` steps through multi-byte characters one byte at a time
+            _ if delimiter != DEFAULT_DELIMITER && bytes[i..].starts_with(delimiter.as_bytes()) => {
+                if let Some(content) = content_start.take() {
+                    statements.push((line_at(content), sql[start..i].trim().to_string()));
+                }
+                i += delimiter.len();
+                start = i;
+                continue;
+            }
+            _ if content_start.is_none() && sql.get(i..).and_then(delimiter_directive).is_some() => {
+                let line_end = sql[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                *delimiter = delimiter_directive(&sql[i..line_end]).unwrap_or_default().to_string();
+                i = line_end;
+                start = i;
+                continue;
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = sql[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = sql[i + 2..].find("*/").map_or(bytes.len(), |n| i + 2 + n + 2);
+                continue;
+            }
+            quote @ (b'\'' | b'"' | b'`') => {
+                content_start.get_or_insert(i);
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    // MySQL strings (not identifiers) take backslash escapes;
+                    // elsewhere a backslash is an ordinary character
+                    if backslash_escapes && quote != b'`' && bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            // Only where it can't be part of a name, as in `a$b`
+            b'$' if dollar_quotes && !(i > 0 && is_identifier_byte(bytes[i - 1])) => {
+                content_start.get_or_insert(i);
+                if let Some(tag_len) = dollar_tag(&sql[i..]) {
+                    let tag = &sql[i..i + tag_len];
+                    i = sql[i + tag_len..]
+                        .find(tag)
+                        .map_or(bytes.len(), |n| i + tag_len + n + tag_len);
+                    continue;
+                }
+            }
+            b';' if delimiter == DEFAULT_DELIMITER => {
+                if let Some(content) = content_start.take() {
+                    statements.push((line_at(content), sql[start..i].trim().to_string()));
+                }
+                start = i + 1;
+            }
+            c if !c.is_ascii_whitespace() => {
+                content_start.get_or_insert(i);
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if let Some(content) = content_start {
+        statements.push((line_at(content), sql[start..].trim().to_string()));
+    }
+    statements
+}
+
+fn is_identifier_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'$' || !byte.is_ascii()
+}
+
+/// The terminator a `DELIMITER <str>` directive at the start of `text`
+/// sets; the keyword is matched ignoring case and must be followed by the
+/// terminator on the same line.
+pub fn delimiter_directive(text: &str) -> Option<&str> {
+    let keyword = text.get(..9).filter(|word| word.eq_ignore_ascii_case("delimiter"))?;
+    let rest = &text[keyword.len()..];
+    let line = rest.split('\n').next().unwrap_or_default();
+    if !line.starts_with([' ', '\t']) {
+        return None;
+    }
+    line.split_whitespace().next()
+}
+
+/// Returns the length of a dollar-quote opening tag (`$$` or `$name$`) at
+/// the start of `text`, if there is one.
+pub fn dollar_tag(text: &str) -> Option<usize> {
+    let rest = &text[1..];
+    let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))?;
+    (rest[end..].starts_with('$') && !rest[..end].starts_with(|c: char| c.is_ascii_digit()))
+        .then_some(end + 2)
+}
+
+/// Checks that a script can run inside one transaction: the error mode must
+/// be `stop`, and no statement may manage transactions itself or be one the
+/// server refuses (or silently commits) inside a transaction.
+pub fn check_single_transaction(db_type: &DatabaseType, statements: &[String], on_error: OnError) -> Result<()> {
+    if on_error == OnError::Continue {
+        return Err(QgoError::Usage(
+            "--single-transaction cannot be combined with on_error=continue: any failure rolls back the whole script"
+                .to_string(),
+        )
+        .into());
+    }
+
+    for (index, statement) in statements.iter().enumerate() {
+        if let Some(reason) = non_transactional(db_type, statement) {
+            let first_line = database::skip_leading_comments(statement).lines().next().unwrap_or_default();
+            return Err(QgoError::Usage(format!(
+                "Statement {} ({}) {}, so the script cannot run with --single-transaction",
+                index + 1,
+                first_line,
+                reason
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+fn non_transactional(db_type: &DatabaseType, statement: &str) -> Option<&'static str> {
+    let words: Vec<String> = database::skip_leading_comments(statement)
+        .split_whitespace()
+        .take(4)
+        .map(|word| word.to_lowercase())
+        .collect();
+    let starts_with = |prefix: &[&str]| words.len() >= prefix.len() && prefix.iter().zip(&words).all(|(p, w)| p == w);
+
+    if database::is_transaction_control(statement) {
+        return Some("manages transactions itself");
+    }
+
+    match db_type {
+        DatabaseType::PostgreSQL => {
+            let concurrently = words.iter().any(|word| word == "concurrently");
+            if starts_with(&["vacuum"])
+                || starts_with(&["create", "database"])
+                || starts_with(&["drop", "database"])
+                || starts_with(&["alter", "system"])
+                || (concurrently && (starts_with(&["create"]) || starts_with(&["drop"]) || starts_with(&["reindex"])))
+            {
+                Some("cannot run inside a transaction in PostgreSQL")
+            } else {
+                None
+            }
+        }
+        DatabaseType::MySQL | DatabaseType::MariaDB => {
+            let ddl = ["create", "alter", "drop", "truncate", "rename"];
+            if ddl.iter().any(|keyword| starts_with(&[keyword])) {
+                Some("causes an implicit commit in MySQL and MariaDB")
+            } else {
+                None
+            }
+        }
+        DatabaseType::SQLite => starts_with(&["vacuum"]).then_some("cannot run inside a transaction in SQLite"),
+    }
+}
+
+/// Runs `statements` in order on a single connection, printing results as
+/// it goes. With [`OnError::Stop`] it stops at the first failure, rolling
+/// back an explicit transaction that is still open; with
+/// [`OnError::Continue`] every statement is attempted. With
+/// `single_transaction` everything runs in one transaction that is
+/// committed at the end or rolled back on the first failure. Each statement
+/// is added to `transcript` if one is being recorded, and to `log` when
+/// given. With `confirm` asking, each statement waits for the user first;
+/// a skipped one is not run and not counted as a failure. Returns the
+/// failures.
+pub async fn run(
+    database: &mut Database,
+    statements: &[String],
+    options: ScriptOptions,
+    display: &DisplayOptions,
+    mut transcript: Option<&mut Transcript>,
+    mut log: Option<&mut SessionLog>,
+    confirm: &mut ConfirmEach,
+) -> Result<Vec<Failure>> {
+    // Asked once for the whole batch; without allow_writes the writes fail anyway
+    let writes = statements
+        .iter()
+        .any(|statement| database::modifies_data(statement));
+    if writes && database.get_connection().allow_writes {
+        prompts::confirm_production_write(database.get_connection())?;
+    }
+
+    if options.single_transaction && database.in_transaction() {
+        return Err(QgoError::Usage(
+            "A transaction is already open; COMMIT or ROLLBACK it before running a script in a single transaction"
+                .to_string(),
+        )
+        .into());
+    }
+
+    let mut conn = database.acquire().await?;
+    let mut failures = Vec::new();
+
+    if options.single_transaction {
+        if let Err(e) = database.execute_statement(&mut conn, "BEGIN").await {
+            database.release(conn, false);
+            return Err(e);
+        }
+    }
+
+    for (index, statement) in statements.iter().enumerate() {
+        if !confirm.allows(statement, true) {
+            if confirm.stopped() {
+                eprintln!("{}", style(format!("Stopped before statement {}.", index + 1)).yellow());
+                break;
+            }
+            output::info("Skipped.");
+            continue;
+        }
+        let started_at = chrono::Utc::now();
+        let started = Instant::now();
+        let outcome = database.execute_statement(&mut conn, statement).await;
+        if let Some(log) = log.as_deref_mut() {
+            log.push(RecentQuery {
+                query: statement.clone(),
+                started_at,
+                duration_ms: started.elapsed().as_millis() as u64,
+                row_count: match &outcome {
+                    Ok(StatementOutcome::Rows(result)) => Some(result.row_count),
+                    Ok(StatementOutcome::Affected(count)) => Some(*count as usize),
+                    Err(_) => None,
+                },
+                error: outcome.as_ref().err().map(|e| e.to_string()),
+            });
+        }
+        if let Some(transcript) = transcript.as_deref_mut() {
+            match &outcome {
+                Ok(StatementOutcome::Rows(result)) => {
+                    transcript.record_result(statement, Some(started.elapsed()), result, display)
+                }
+                Ok(StatementOutcome::Affected(count)) => transcript.record_affected(statement, started.elapsed(), *count),
+                Err(e) => transcript.record_error(statement, started.elapsed(), e),
+            }
+        }
+        match outcome {
+            Ok(StatementOutcome::Rows(result)) => {
+                for warning in &result.warnings {
+                    output::warn(warning);
+                }
+                format::display_table(&result, display)
+            }
+            Ok(StatementOutcome::Affected(count)) => output::info(format!("{} rows affected", count)),
+            Err(e) => {
+                eprintln!("{}", style(format!("Error in statement {}: {}", index + 1, e)).red());
+                failures.push(Failure {
+                    statement: index + 1,
+                    line: None,
+                    error: e,
+                });
+                if options.on_error == OnError::Stop {
+                    break;
+                }
+                continue;
+            }
+        }
+    }
+
+    let mut outcome = Ok(());
+    if options.single_transaction && failures.is_empty() {
+        outcome = database.execute_statement(&mut conn, "COMMIT").await.map(|_| ());
+    }
+
+    // Never hand a connection with a half-finished transaction back to the
+    // pool; one left open on purpose stays with the session instead
+    let keep_open = options.keep_transaction_open && !options.single_transaction && failures.is_empty();
+    if database.in_transaction() && !keep_open {
+        let rolled_back = database.execute_statement(&mut conn, "ROLLBACK").await.map(|_| ());
+        outcome = outcome.and(rolled_back);
+        if options.single_transaction {
+            eprintln!("{}", style("Rolled back the transaction; no changes from this script were kept.").yellow());
+        } else {
+            eprintln!("{}", style("Rolled back the open transaction.").yellow());
+        }
+    }
+
+    database.release(conn, keep_open);
+    outcome?;
+    Ok(failures)
+}
+
+/// Prints which statements failed, if any.
+pub fn print_summary(total: usize, failures: &[Failure], on_error: OnError) {
+    if failures.is_empty() {
+        return;
+    }
+
+    let at_line = |failure: &Failure| failure.line.map(|line| format!(" (line {})", line)).unwrap_or_default();
+    match on_error {
+        OnError::Stop => eprintln!(
+            "{}",
+            style(format!("Stopped at statement {} of {}{}.", failures[0].statement, total, at_line(&failures[0]))).red()
+        ),
+        OnError::Continue => {
+            eprintln!("{}", style(format!("{} of {} statements failed:", failures.len(), total)).red());
+            for failure in failures {
+                eprintln!("  #{}{}: {}", failure.statement, at_line(failure), failure.error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(db_type: DatabaseType, sql: &str) -> Vec<String> {
+        split_statements_with_lines(&db_type, sql).into_iter().map(|(_, statement)| statement).collect()
+    }
+
+    #[test]
+    fn splits_on_semicolons_outside_quotes_and_comments() {
+        let sql = "SELECT 'a;b'; -- not; here\nSELECT \"c;d\" /* nor; here */; SELECT 3";
+        assert_eq!(
+            split(DatabaseType::SQLite, sql),
+            ["SELECT 'a;b'", "-- not; here\nSELECT \"c;d\" /* nor; here */", "SELECT 3"]
+        );
+    }
+
+    #[test]
+    fn reports_the_line_each_statement_starts_on() {
+        let sql = "-- header\n\nSELECT 1;\n\n  SELECT\n2;\n/* trailing */";
+        let lines: Vec<usize> = split_statements_with_lines(&DatabaseType::PostgreSQL, sql).into_iter().map(|(line, _)| line).collect();
+        assert_eq!(lines, [3, 5]);
+    }
+
+    #[test]
+    fn keeps_postgres_dollar_quoted_bodies_whole() {
+        let sql = "CREATE FUNCTION f() RETURNS int AS $body$\nBEGIN\n  RAISE NOTICE 'x;y';\n  RETURN 1;\nEND;\n$body$ LANGUAGE plpgsql;\nDO $$ BEGIN PERFORM f(); END $$;\nSELECT f()";
+        let statements = split(DatabaseType::PostgreSQL, sql);
+        assert_eq!(statements.len(), 3);
+        assert!(statements[0].starts_with("CREATE FUNCTION") && statements[0].ends_with("LANGUAGE plpgsql"));
+        assert_eq!(statements[1], "DO $$ BEGIN PERFORM f(); END $$");
+        assert_eq!(statements[2], "SELECT f()");
+    }
+
+    #[test]
+    fn delimiter_lines_keep_mysql_routine_bodies_whole() {
+        let sql = "DELIMITER //\n\
+                   CREATE PROCEDURE p()\nBEGIN\n  SELECT 1;\n  SELECT 2;\nEND //\n\
+                   CREATE TRIGGER t BEFORE INSERT ON orders FOR EACH ROW\nBEGIN\n  SET NEW.total = NEW.qty * NEW.price;\nEND//\n\
+                   DELIMITER ;\n\
+                   SELECT 3; SELECT 4";
+        let mut delimiter = DEFAULT_DELIMITER.to_string();
+        let statements = split_with_delimiter(&DatabaseType::MySQL, sql, &mut delimiter);
+        let lines: Vec<usize> = statements.iter().map(|(line, _)| *line).collect();
+        let statements: Vec<&str> = statements.iter().map(|(_, statement)| statement.as_str()).collect();
+        assert_eq!(
+            statements,
+            [
+                "CREATE PROCEDURE p()\nBEGIN\n  SELECT 1;\n  SELECT 2;\nEND",
+                "CREATE TRIGGER t BEFORE INSERT ON orders FOR EACH ROW\nBEGIN\n  SET NEW.total = NEW.qty * NEW.price;\nEND",
+                "SELECT 3",
+                "SELECT 4",
+            ]
+        );
+        assert_eq!(lines, [2, 7, 12, 12]);
+        assert_eq!(delimiter, ";");
+    }
+
+    #[test]
+    fn delimiter_carries_over_between_calls() {
+        let mut delimiter = DEFAULT_DELIMITER.to_string();
+        assert!(split_with_delimiter(&DatabaseType::MariaDB, "DELIMITER $$", &mut delimiter).is_empty());
+        assert_eq!(delimiter, "$$");
+        let statements = split_with_delimiter(&DatabaseType::MariaDB, "SELECT 1; SELECT 2 $$ SELECT 3", &mut delimiter);
+        let statements: Vec<&str> = statements.iter().map(|(_, statement)| statement.as_str()).collect();
+        assert_eq!(statements, ["SELECT 1; SELECT 2", "SELECT 3"]);
+    }
+
+    #[test]
+    fn handles_non_ascii_text_with_any_delimiter() {
+        assert_eq!(
+            split(DatabaseType::PostgreSQL, "SELECT 'café; crème'; SELECT naïve FROM tëst; SELECT '日本語'"),
+            ["SELECT 'café; crème'", "SELECT naïve FROM tëst", "SELECT '日本語'"]
+        );
+
+        let sql = "DELIMITER //\nCREATE PROCEDURE p() BEGIN SELECT café FROM t; END //\nSELECT 'ü' //";
+        let mut delimiter = DEFAULT_DELIMITER.to_string();
+        let statements = split_with_delimiter(&DatabaseType::MySQL, sql, &mut delimiter);
+        let statements: Vec<&str> = statements.iter().map(|(_, statement)| statement.as_str()).collect();
+        assert_eq!(statements, ["CREATE PROCEDURE p() BEGIN SELECT café FROM t; END", "SELECT 'ü'"]);
+
+        // A multi-byte terminator
+        let mut delimiter = "§".to_string();
+        let statements = split_with_delimiter(&DatabaseType::PostgreSQL, "SELECT 'é' § SELECT 2§", &mut delimiter);
+        let statements: Vec<&str> = statements.iter().map(|(_, statement)| statement.as_str()).collect();
+        assert_eq!(statements, ["SELECT 'é'", "SELECT 2"]);
+    }
+
+    #[test]
+    fn drops_statements_that_are_only_comments() {
+        assert!(split(DatabaseType::SQLite, "-- nothing\n/* here */ ; ;").is_empty());
+    }
+
+    #[test]
+    fn backslashes_escape_only_in_mysql_strings() {
+        let sql = r"SELECT 'C:\'; SELECT 2;";
+        assert_eq!(split(DatabaseType::PostgreSQL, sql), [r"SELECT 'C:\'", "SELECT 2"]);
+        assert_eq!(split(DatabaseType::SQLite, sql), [r"SELECT 'C:\'", "SELECT 2"]);
+        // An unclosed string runs to the end
+        assert_eq!(split(DatabaseType::MySQL, sql), [r"SELECT 'C:\'; SELECT 2;"]);
+        assert_eq!(
+            split(DatabaseType::MariaDB, r"SELECT 'it\'s; fine'; SELECT `a\`; SELECT 3"),
+            [r"SELECT 'it\'s; fine'", r"SELECT `a\`", "SELECT 3"]
+        );
+    }
+
+    #[test]
+    fn dollar_quotes_only_in_postgres() {
+        let sql = "SELECT a$b$c FROM t; SELECT 2";
+        assert_eq!(split(DatabaseType::MySQL, sql), ["SELECT a$b$c FROM t", "SELECT 2"]);
+        assert_eq!(split(DatabaseType::PostgreSQL, sql), ["SELECT a$b$c FROM t", "SELECT 2"]);
+        assert_eq!(
+            split(DatabaseType::SQLite, "SELECT $$x; y$$; SELECT 2"),
+            ["SELECT $$x", "y$$", "SELECT 2"]
+        );
+        assert_eq!(split(DatabaseType::PostgreSQL, "SELECT $1; SELECT 2"), ["SELECT $1", "SELECT 2"]);
+    }
+
+    async fn scratch(name: &str) -> (Database, std::path::PathBuf) {
+        sqlx::any::install_default_drivers();
+        let path = std::env::temp_dir().join(format!("qgo-script-{}-{}.db", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        let mut connection = crate::config::Connection::new(
+            name.to_string(),
+            DatabaseType::SQLite,
+            String::new(),
+            0,
+            String::new(),
+            String::new(),
+            format!("{}?mode=rwc", path.display()),
+        );
+        connection.allow_writes = true;
+        let database = Database::connect(connection, std::time::Duration::from_secs(5), None).await.unwrap();
+        (database, path)
+    }
+
+    async fn run_script(database: &mut Database, sql: &str, on_error: OnError) -> Vec<Failure> {
+        let statements: Vec<String> = split(DatabaseType::SQLite, sql);
+        let options = ScriptOptions {
+            on_error,
+            single_transaction: false,
+            keep_transaction_open: false,
+        };
+        run(database, &statements, options, &DisplayOptions::default(), None, None, &mut ConfirmEach::new(false))
+            .await
+            .unwrap()
+    }
+
+    async fn values(database: &mut Database) -> Vec<Vec<String>> {
+        database.execute_query("SELECT CAST(x AS TEXT) FROM t ORDER BY x").await.unwrap().rows
+    }
+
+    const BROKEN_MIDDLE: &str = "CREATE TABLE t (x INTEGER);
+        INSERT INTO t VALUES (1);
+        INSERT INTO missing VALUES (2);
+        INSERT INTO t VALUES (3);";
+
+    #[tokio::test]
+    async fn stop_mode_ends_at_the_broken_statement() {
+        let (mut database, path) = scratch("stop").await;
+        let failures = run_script(&mut database, BROKEN_MIDDLE, OnError::Stop).await;
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].statement, 3);
+        assert!(failures[0].error.to_string().contains("missing"));
+        assert_eq!(values(&mut database).await, [["1"]]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn continue_mode_runs_the_rest_and_reports_each_failure() {
+        let (mut database, path) = scratch("continue").await;
+        let failures = run_script(&mut database, BROKEN_MIDDLE, OnError::Continue).await;
+        assert_eq!(failures.iter().map(|failure| failure.statement).collect::<Vec<_>>(), [3]);
+        assert_eq!(values(&mut database).await, [["1"], ["3"]]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn stop_mode_rolls_back_an_explicit_transaction() {
+        let (mut database, path) = scratch("rollback").await;
+        run_script(&mut database, "CREATE TABLE t (x INTEGER)", OnError::Stop).await;
+        let sql = "BEGIN; INSERT INTO t VALUES (1); INSERT INTO missing VALUES (2); INSERT INTO t VALUES (3); COMMIT";
+        let failures = run_script(&mut database, sql, OnError::Stop).await;
+        assert_eq!(failures[0].statement, 3);
+        assert!(!database.in_transaction());
+        assert!(values(&mut database).await.is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+
+    fn fixture(name: &str) -> String {
+        read_script(&format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+    }
+
+    #[test]
+    fn reading_a_script_drops_the_byte_order_mark() {
+        let raw = std::fs::read(format!("{}/tests/fixtures/bom_comment_header.sql", env!("CARGO_MANIFEST_DIR"))).unwrap();
+        assert!(raw.starts_with(b"\xEF\xBB\xBF"));
+        let sql = fixture("bom_comment_header.sql");
+        assert!(sql.starts_with("/*\r\n"));
+        assert_eq!(sql.len(), raw.len() - 3);
+    }
+
+    #[test]
+    fn comment_headers_do_not_shift_line_numbers() {
+        let statements = split_statements_with_lines(&DatabaseType::SQLite, &fixture("bom_comment_header.sql"));
+        let lines: Vec<usize> = statements.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [7, 10, 12, 14]);
+        assert_eq!(preview(&statements[0].1), "CREATE TABLE t (x INTEGER)");
+        assert_eq!(preview(&statements[2].1), "INSERT INTO missing VALUES (2)");
+        assert!(statements.iter().all(|(_, statement)| !database::is_read_only(statement)));
+
+        let statements = split_statements_with_lines(&DatabaseType::PostgreSQL, &fixture("bom_first_line.sql"));
+        let lines: Vec<usize> = statements.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [1, 3]);
+        assert_eq!(preview(&statements[1].1), "SELECT 3");
+        assert!(statements.iter().all(|(_, statement)| database::is_read_only(statement)));
+    }
+
+    #[test]
+    fn a_byte_order_mark_left_in_place_changes_nothing() {
+        let raw = String::from_utf8(
+            std::fs::read(format!("{}/tests/fixtures/bom_first_line.sql", env!("CARGO_MANIFEST_DIR"))).unwrap(),
+        )
+        .unwrap();
+        let statements = split_statements_with_lines(&DatabaseType::SQLite, &raw);
+        assert_eq!(statements.iter().map(|(line, _)| *line).collect::<Vec<_>>(), [1, 3]);
+        assert!(database::is_read_only(&statements[0].1));
+        assert_eq!(preview(&statements[0].1), "SELECT 1");
+    }
+
+    #[tokio::test]
+    async fn a_failure_under_a_comment_header_reports_its_line() {
+        let (mut database, path) = scratch("fixture").await;
+        let (lines, statements): (Vec<usize>, Vec<String>) =
+            split_statements_with_lines(&DatabaseType::SQLite, &fixture("bom_comment_header.sql")).into_iter().unzip();
+        let options = ScriptOptions {
+            on_error: OnError::Continue,
+            single_transaction: false,
+            keep_transaction_open: false,
+        };
+        let failures =
+            run(&mut database, &statements, options, &DisplayOptions::default(), None, None, &mut ConfirmEach::new(false))
+                .await
+                .unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].statement, 3);
+        assert_eq!(lines[failures[0].statement - 1], 12);
+        assert_eq!(values(&mut database).await, [["1"], ["3"]]);
+        let _ = std::fs::remove_file(path);
+    }
+}
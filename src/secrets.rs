@@ -0,0 +1,342 @@
+use anyhow::Result;
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use console::style;
+use keyring::Entry;
+use rpassword::prompt_password;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::atomic;
+use crate::config::{Config, Connection, PasswordStorage};
+use crate::error::QgoError;
+
+const SERVICE: &str = "qgo";
+
+/// Start of every `secrets.enc`, followed by the salt, the nonce and the
+/// encrypted JSON map of connection id to password.
+const MAGIC: &[u8] = b"qgo-secrets-v1\n";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Wrong passphrases allowed before giving up on the encrypted file.
+const PASSPHRASE_ATTEMPTS: u32 = 3;
+
+static BACKEND: Mutex<PasswordStorage> = Mutex::new(PasswordStorage::Keyring);
+
+/// The encrypted file once unlocked; the passphrase is asked for at most
+/// once per run.
+static VAULT: Mutex<Option<Vault>> = Mutex::new(None);
+
+/// Set once unlocking has failed, so the passphrase isn't asked for again
+/// for every connection.
+static GAVE_UP: AtomicBool = AtomicBool::new(false);
+
+/// Chooses where passwords are remembered, from the `password_storage`
+/// setting.
+pub fn set_backend(storage: PasswordStorage) {
+    if let Ok(mut backend) = BACKEND.lock() {
+        *backend = storage;
+    }
+}
+
+pub fn backend() -> PasswordStorage {
+    BACKEND.lock().map_or(PasswordStorage::Keyring, |backend| *backend)
+}
+
+/// Looks up a remembered password. Any failure (no entry, no keyring service
+/// available, the encrypted file left locked) is treated as "not remembered".
+pub fn load_password(connection: &Connection) -> Option<String> {
+    load_from(backend(), connection)
+}
+
+pub fn store_password(connection: &Connection, password: &str) -> Result<()> {
+    store_in(backend(), connection, password)
+}
+
+pub fn delete_password(connection: &Connection) {
+    delete_from(backend(), connection);
+}
+
+fn load_from(storage: PasswordStorage, connection: &Connection) -> Option<String> {
+    match storage {
+        PasswordStorage::Keyring => entry(connection).and_then(|e| e.get_password()).ok(),
+        PasswordStorage::Encrypted => match with_vault(false, |vault| Ok(vault.passwords.get(&key_for(connection)).cloned())) {
+            Ok(password) => password.flatten(),
+            Err(e) => {
+                eprintln!("{}", style(e.to_string()).yellow());
+                None
+            }
+        },
+    }
+}
+
+fn store_in(storage: PasswordStorage, connection: &Connection, password: &str) -> Result<()> {
+    match storage {
+        PasswordStorage::Keyring => entry(connection)?.set_password(password)?,
+        PasswordStorage::Encrypted => {
+            with_vault(true, |vault| {
+                vault.passwords.insert(key_for(connection), password.to_string());
+                vault.save()
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn delete_from(storage: PasswordStorage, connection: &Connection) {
+    match storage {
+        PasswordStorage::Keyring => {
+            if let Ok(entry) = entry(connection) {
+                let _ = entry.delete_password();
+            }
+        }
+        PasswordStorage::Encrypted => {
+            let _ = with_vault(false, |vault| match vault.passwords.remove(&key_for(connection)) {
+                Some(_) => vault.save(),
+                None => Ok(()),
+            });
+        }
+    }
+}
+
+fn entry(connection: &Connection) -> keyring::Result<Entry> {
+    Entry::new(SERVICE, &connection.id.to_string())
+}
+
+fn key_for(connection: &Connection) -> String {
+    connection.id.to_string()
+}
+
+/// Runs `f` on the unlocked encrypted file, prompting for the passphrase if
+/// it hasn't been given yet. Returns `None` when there is no file and
+/// `create` is false.
+fn with_vault<T>(create: bool, f: impl FnOnce(&mut Vault) -> Result<T>) -> Result<Option<T>> {
+    let mut guard = VAULT
+        .lock()
+        .map_err(|_| QgoError::Secrets("the secrets store is unavailable".to_string()))?;
+    if guard.is_none() {
+        if GAVE_UP.load(Ordering::Relaxed) {
+            if create {
+                return Err(QgoError::Secrets("secrets.enc stays locked for the rest of this run".to_string()).into());
+            }
+            return Ok(None);
+        }
+        match Vault::unlock(create) {
+            Ok(vault) => *guard = vault,
+            Err(e) => {
+                GAVE_UP.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+        }
+    }
+    match guard.as_mut() {
+        Some(vault) => f(vault).map(Some),
+        None => Ok(None),
+    }
+}
+
+struct Vault {
+    key: Key,
+    salt: [u8; SALT_LEN],
+    passwords: BTreeMap<String, String>,
+}
+
+impl Vault {
+    fn path() -> Result<PathBuf> {
+        Ok(Config::get_config_path()?.with_file_name("secrets.enc"))
+    }
+
+    /// Opens `secrets.enc`, allowing a few passphrase attempts. A missing
+    /// file is created with a new passphrase when `create` is set.
+    fn unlock(create: bool) -> Result<Option<Vault>> {
+        let path = Self::path()?;
+        if !path.exists() {
+            if !create {
+                return Ok(None);
+            }
+            return Self::create().map(Some);
+        }
+
+        let data = fs::read(&path)?;
+        let (salt, nonce, ciphertext) = Self::split(&data)?;
+        for attempt in 1..=PASSPHRASE_ATTEMPTS {
+            let passphrase = prompt_password("Master passphrase: ")?;
+            let key = derive_key(&passphrase, &salt)?;
+            let cipher = ChaCha20Poly1305::new(&key);
+            match cipher.decrypt(Nonce::from_slice(nonce), ciphertext) {
+                Ok(plaintext) => {
+                    let passwords = serde_json::from_slice(&plaintext)?;
+                    return Ok(Some(Vault { key, salt, passwords }));
+                }
+                Err(_) if attempt < PASSPHRASE_ATTEMPTS => {
+                    eprintln!("{}", style("Wrong passphrase, try again.").red());
+                }
+                Err(_) => {}
+            }
+        }
+        Err(QgoError::Secrets(format!(
+            "wrong passphrase {} times; {} was left locked",
+            PASSPHRASE_ATTEMPTS,
+            path.display()
+        ))
+        .into())
+    }
+
+    fn create() -> Result<Vault> {
+        println!("Passwords will be encrypted with a master passphrase, asked for once per run.");
+        let passphrase = loop {
+            let passphrase = prompt_password("New master passphrase: ")?;
+            if passphrase.is_empty() {
+                eprintln!("{}", style("The passphrase can't be empty.").red());
+                continue;
+            }
+            if prompt_password("Repeat the passphrase: ")? == passphrase {
+                break passphrase;
+            }
+            eprintln!("{}", style("The passphrases don't match, try again.").red());
+        };
+
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let vault = Vault {
+            key: derive_key(&passphrase, &salt)?,
+            salt,
+            passwords: BTreeMap::new(),
+        };
+        vault.save()?;
+        Ok(vault)
+    }
+
+    fn split(data: &[u8]) -> Result<([u8; SALT_LEN], &[u8], &[u8])> {
+        let corrupt = || QgoError::Secrets("secrets.enc is not a qgo secrets file or is damaged".to_string());
+        let rest = data.strip_prefix(MAGIC).ok_or_else(corrupt)?;
+        if rest.len() < SALT_LEN + NONCE_LEN {
+            return Err(corrupt().into());
+        }
+        let (salt, rest) = rest.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let salt = salt.try_into().map_err(|_| corrupt())?;
+        Ok((salt, nonce, ciphertext))
+    }
+
+    /// Encrypts the passwords under a fresh nonce and replaces the file, so
+    /// an interrupted write never leaves it half written.
+    fn save(&self) -> Result<()> {
+        let cipher = ChaCha20Poly1305::new(&self.key);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plaintext = serde_json::to_vec(&self.passwords)?;
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| QgoError::Secrets("could not encrypt the passwords".to_string()))?;
+
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let contents = [MAGIC, &self.salt, &nonce, &ciphertext].concat();
+        atomic::write(&path, &contents, false)?;
+        Ok(())
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut_slice())
+        .map_err(|e| QgoError::Secrets(format!("could not derive the key: {}", e)))?;
+    Ok(key)
+}
+
+fn find_connection<'a>(config: &'a Config, name: &str) -> Result<&'a Connection> {
+    Ok(config
+        .get_connection_by_name(name)
+        .ok_or_else(|| QgoError::ConnectionNotFound(name.to_string()))?)
+}
+
+/// `qgo secrets list`: names the connections with a remembered password.
+pub fn list(config: &Config) -> Result<()> {
+    let storage = backend();
+    let names: Vec<&str> = config
+        .connections
+        .iter()
+        .filter(|connection| load_from(storage, connection).is_some())
+        .map(|connection| connection.name.as_str())
+        .collect();
+
+    if names.is_empty() {
+        println!("No passwords are stored in the {}.", storage);
+    } else {
+        println!("Passwords stored in the {}:", storage);
+        for name in names {
+            println!("  {}", name);
+        }
+    }
+    Ok(())
+}
+
+/// `qgo secrets set <connection>`: prompts for a password and remembers it.
+pub fn set(config: &Config, name: &str) -> Result<()> {
+    let connection = find_connection(config, name)?;
+    let password = prompt_password(format!("Password for '{}': ", connection.name))?;
+    store_password(connection, &password)?;
+    println!("{}", style(format!("Password for '{}' stored in the {}.", connection.name, backend())).green());
+    Ok(())
+}
+
+/// `qgo secrets del <connection>`: forgets a remembered password.
+pub fn delete(config: &Config, name: &str) -> Result<()> {
+    let connection = find_connection(config, name)?;
+    if load_password(connection).is_none() {
+        println!("No password is stored for '{}'.", connection.name);
+        return Ok(());
+    }
+    delete_password(connection);
+    println!("Password for '{}' removed from the {}.", connection.name, backend());
+    Ok(())
+}
+
+/// `qgo secrets migrate <keyring|encrypted>`: moves every remembered
+/// password to `target` and makes it the `password_storage` setting. The
+/// originals are removed only after everything has been stored in `target`.
+pub async fn migrate(config: &mut Config, target: PasswordStorage) -> Result<()> {
+    let source = backend();
+    if source == target {
+        println!("Passwords are already stored in the {}.", target);
+        return Ok(());
+    }
+
+    println!("Moving stored passwords from the {} to the {}.", source, target);
+    let found: Vec<(Connection, String)> = config
+        .connections
+        .iter()
+        .filter_map(|connection| load_from(source, connection).map(|password| (connection.clone(), password)))
+        .collect();
+
+    for (connection, password) in &found {
+        store_in(target, connection, password).map_err(|e| {
+            QgoError::Secrets(format!(
+                "could not store the password for '{}' in the {} ({}); nothing was removed from the {}",
+                connection.name, target, e, source
+            ))
+        })?;
+    }
+    for (connection, _) in &found {
+        delete_from(source, connection);
+        println!("  {}", connection.name);
+    }
+
+    config.settings.password_storage = target;
+    config.save().await?;
+    set_backend(target);
+    println!(
+        "{}",
+        style(format!("Moved {} password(s); new passwords will be stored in the {}.", found.len(), target)).green()
+    );
+    Ok(())
+}
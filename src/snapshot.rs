@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use crate::config::{OnError, Settings};
+use crate::ui::settings::SETTINGS;
+use qgo::format::DisplayOptions;
+use qgo::output;
+
+/// The settings a session runs with at one moment, as `\push` keeps them:
+/// the configured settings along with the options changed at the prompt
+/// (`\types`, `\x`, `\tz`, `\set ON_ERROR_STOP`, `\quiet`).
+#[derive(Clone)]
+pub struct Snapshot {
+    pub settings: Settings,
+    pub display: DisplayOptions,
+    pub timeout: Duration,
+    pub on_error: OnError,
+    pub quiet: bool,
+}
+
+impl Snapshot {
+    pub fn new(settings: &Settings, display: &DisplayOptions, timeout: Duration, on_error: OnError) -> Self {
+        Self {
+            settings: settings.clone(),
+            display: display.clone(),
+            timeout,
+            on_error,
+            quiet: output::is_quiet(),
+        }
+    }
+
+    /// `(name, value)` for every setting and prompt option, in the order
+    /// the settings menu lists them, followed by the prompt options.
+    fn entries(&self) -> Vec<(&'static str, String)> {
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        let mut entries: Vec<(&'static str, String)> = SETTINGS
+            .iter()
+            .map(|setting| (setting.name, (setting.value)(&self.settings)))
+            .collect();
+        entries.extend([
+            ("\\types", on_off(self.display.show_types)),
+            ("\\x", on_off(self.display.vertical)),
+            (
+                "\\tz",
+                self.display
+                    .timezone
+                    .as_ref()
+                    .map_or_else(|| "server default".to_string(), |zone| zone.to_string()),
+            ),
+            ("ON_ERROR_STOP", on_off(self.on_error == OnError::Stop)),
+            ("\\quiet", on_off(self.quiet)),
+            ("timeout", format!("{}s", self.timeout.as_secs())),
+        ]);
+        entries
+    }
+
+    /// `(name, value here, value in current)` for each entry that differs.
+    pub fn changes(&self, current: &Snapshot) -> Vec<(&'static str, String, String)> {
+        self.entries()
+            .into_iter()
+            .zip(current.entries())
+            .filter(|((_, kept), (_, now))| kept != now)
+            .map(|((name, kept), (_, now))| (name, kept, now))
+            .collect()
+    }
+}
+
+/// Snapshots taken with `\push`, most recent last. Kept in memory for the
+/// session only.
+#[derive(Default)]
+pub struct SettingsStack {
+    snapshots: Vec<Snapshot>,
+}
+
+impl SettingsStack {
+    pub fn push(&mut self, snapshot: Snapshot) {
+        self.snapshots.push(snapshot);
+    }
+
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        self.snapshots.pop()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// The snapshots from the most recent down.
+    pub fn iter(&self) -> impl Iterator<Item = &Snapshot> {
+        self.snapshots.iter().rev()
+    }
+}
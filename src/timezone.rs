@@ -0,0 +1,135 @@
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::error::QgoError;
+
+const ZONE_EXAMPLES: &str = "UTC, Europe/London, America/New_York, Asia/Kolkata, +05:30";
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone)]
+pub enum SessionZone {
+    Named(Tz),
+    Fixed(FixedOffset),
+}
+
+impl SessionZone {
+    pub fn parse(name: &str) -> Result<Self, QgoError> {
+        let trimmed = name.trim();
+
+        if let Ok(tz) = trimmed.parse::<Tz>() {
+            return Ok(SessionZone::Named(tz));
+        }
+
+        if let Some(offset) = parse_offset(trimmed) {
+            return Ok(SessionZone::Fixed(offset));
+        }
+
+        Err(QgoError::InvalidTimeZone(format!(
+            "'{}' is not a known time zone. Examples: {}",
+            trimmed, ZONE_EXAMPLES
+        )))
+    }
+
+    /// The zone name as the database server expects it in `SET TIME ZONE`.
+    pub fn server_name(&self) -> String {
+        match self {
+            SessionZone::Named(tz) => tz.name().to_string(),
+            SessionZone::Fixed(offset) => offset.to_string(),
+        }
+    }
+
+    fn convert(&self, value: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        match self {
+            SessionZone::Named(tz) => value.with_timezone(tz).fixed_offset(),
+            SessionZone::Fixed(offset) => value.with_timezone(offset),
+        }
+    }
+}
+
+impl std::fmt::Display for SessionZone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.server_name())
+    }
+}
+
+fn parse_offset(value: &str) -> Option<FixedOffset> {
+    let (sign, rest) = match value.chars().next()? {
+        '+' => (1, &value[1..]),
+        '-' => (-1, &value[1..]),
+        _ => return None,
+    };
+
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((h, m)) => (h.parse::<i32>().ok()?, m.parse::<i32>().ok()?),
+        None => (rest.parse::<i32>().ok()?, 0),
+    };
+
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Re-formats a cell for display if it holds a timestamp. Values carrying an
+/// offset are converted into the session zone; naive values are assumed to be
+/// in the session zone already and are only re-formatted.
+pub fn format_timestamp(
+    value: &str,
+    zone: Option<&SessionZone>,
+    date_format: Option<&str>,
+) -> Option<String> {
+    if zone.is_none() && date_format.is_none() {
+        return None;
+    }
+
+    let format = date_format.unwrap_or(DEFAULT_DATE_FORMAT);
+
+    if let Some(timestamp) = parse_with_offset(value) {
+        let converted = match zone {
+            Some(zone) => zone.convert(timestamp),
+            None => timestamp,
+        };
+        return Some(converted.format(format).to_string());
+    }
+
+    if date_format.is_some() {
+        if let Some(naive) = parse_naive(value) {
+            return Some(naive.format(format).to_string());
+        }
+    }
+
+    None
+}
+
+fn parse_with_offset(value: &str) -> Option<DateTime<FixedOffset>> {
+    // Require at least a date and a time before attempting any parse
+    if value.len() < 19 || !value.as_bytes()[4].eq(&b'-') {
+        return None;
+    }
+
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Some(timestamp);
+    }
+
+    // PostgreSQL renders offsets as +00 or +05:30 after a space separator
+    for format in ["%Y-%m-%d %H:%M:%S%.f%#z", "%Y-%m-%d %H:%M:%S%#z"] {
+        if let Ok(timestamp) = DateTime::parse_from_str(value, format) {
+            return Some(timestamp);
+        }
+    }
+
+    if let Some(stripped) = value.strip_suffix(" UTC") {
+        if let Some(naive) = parse_naive(stripped) {
+            return Some(Utc.from_utc_datetime(&naive).fixed_offset());
+        }
+    }
+
+    None
+}
+
+fn parse_naive(value: &str) -> Option<NaiveDateTime> {
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+}
@@ -0,0 +1,165 @@
+use anyhow::Result;
+use console::style;
+use std::borrow::Cow;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::Connection;
+use crate::database::QueryResult;
+use qgo::format::{self, DisplayOptions};
+
+/// Keywords followed by a password literal that is masked in the transcript,
+/// as in `ALTER USER ... PASSWORD '...'` and `CREATE USER ... IDENTIFIED BY '...'`.
+const PASSWORD_KEYWORDS: &[&str] = &["password", "identified by"];
+
+/// A record of the statements run in a session and what they returned,
+/// started with `\record <file>` or `--record <file>`. Entries are appended
+/// with a timestamp and written out as soon as they are complete, so a crash
+/// loses at most the statement that was running. Files ending in `.md` are
+/// written as Markdown, anything else as plain text.
+pub struct Transcript {
+    path: PathBuf,
+    file: File,
+    markdown: bool,
+}
+
+impl Transcript {
+    /// Opens `path` for appending, creating it if needed, and notes which
+    /// connection the recording is of. Passwords are never written.
+    pub fn start(path: &Path, connection: &Connection) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let markdown = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"));
+        let mut transcript = Self {
+            path: path.to_path_buf(),
+            file,
+            markdown,
+        };
+
+        let heading = format!(
+            "qgo session on {}, database {}, recording started {}",
+            connection.display_name(),
+            connection.database,
+            timestamp()
+        );
+        let heading = if transcript.markdown {
+            format!("## {}\n\n", heading)
+        } else {
+            format!("==== {}\n\n", heading)
+        };
+        transcript.file.write_all(heading.as_bytes())?;
+        transcript.file.flush()?;
+        Ok(transcript)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records a statement that returned `result`, rendered as it was shown.
+    pub fn record_result(&mut self, statement: &str, elapsed: Option<Duration>, result: &QueryResult, display: &DisplayOptions) {
+        let mut rendered = Vec::new();
+        let _ = format::write_table(&mut rendered, result, display);
+        let rendered = String::from_utf8_lossy(&rendered);
+        self.record(statement, elapsed, &console::strip_ansi_codes(&rendered));
+    }
+
+    /// Records a statement that changed `count` rows.
+    pub fn record_affected(&mut self, statement: &str, elapsed: Duration, count: u64) {
+        self.record(statement, Some(elapsed), &format!("{} rows affected\n", count));
+    }
+
+    /// Records a statement that returned nothing to show, with `note` in
+    /// place of a result.
+    pub fn record_note(&mut self, statement: &str, elapsed: Duration, note: &str) {
+        self.record(statement, Some(elapsed), &format!("{}\n", note));
+    }
+
+    pub fn record_error(&mut self, statement: &str, elapsed: Duration, error: &dyn std::fmt::Display) {
+        self.record(statement, Some(elapsed), &format!("Error: {}\n", error));
+    }
+
+    /// Appends one entry. `elapsed` is `None` for results served from the
+    /// cache. A failed write is reported and recording carries on.
+    fn record(&mut self, statement: &str, elapsed: Option<Duration>, body: &str) {
+        let timing = match elapsed {
+            Some(elapsed) => format!("{:.3}s", elapsed.as_secs_f64()),
+            None => "cached".to_string(),
+        };
+        let statement = redact_passwords(statement.trim());
+        let entry = if self.markdown {
+            format!(
+                "### {} ({})\n\n```sql\n{}\n```\n\n```\n{}```\n\n",
+                timestamp(),
+                timing,
+                statement,
+                body
+            )
+        } else {
+            format!("-- {} ({})\n{}\n\n{}\n", timestamp(), timing, statement, body)
+        };
+
+        if let Err(e) = self.file.write_all(entry.as_bytes()).and_then(|_| self.file.flush()) {
+            eprintln!(
+                "{}",
+                style(format!("Could not write to the transcript {}: {}", self.path.display(), e)).yellow()
+            );
+        }
+    }
+}
+
+fn timestamp() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S %z").to_string()
+}
+
+/// Replaces the quoted literal after each [`PASSWORD_KEYWORDS`] keyword
+/// with `'***'`, so statements that set passwords can be recorded safely.
+fn redact_passwords(statement: &str) -> Cow<'_, str> {
+    let lower = statement.to_ascii_lowercase();
+    let mut redacted = String::new();
+    let mut copied = 0;
+    let mut search = 0;
+
+    while let Some((start, keyword)) = PASSWORD_KEYWORDS
+        .iter()
+        .filter_map(|keyword| lower[search..].find(keyword).map(|at| (search + at, keyword)))
+        .min_by_key(|(at, _)| *at)
+    {
+        search = start + keyword.len();
+        let rest = &statement[search..];
+        let value = rest.trim_start();
+        let value = value.strip_prefix('=').map_or(value, str::trim_start);
+        let Some(quote) = value.chars().next().filter(|c| matches!(c, '\'' | '"')) else {
+            continue;
+        };
+        let literal_start = search + (rest.len() - value.len());
+        // Doubled quotes inside the literal are part of it
+        let mut end = None;
+        let mut chars = value.char_indices().skip(1).peekable();
+        while let Some((index, c)) = chars.next() {
+            if c == quote {
+                if chars.peek().is_some_and(|(_, next)| *next == quote) {
+                    chars.next();
+                    continue;
+                }
+                end = Some(index + 1);
+                break;
+            }
+        }
+        let literal_end = literal_start + end.unwrap_or(value.len());
+
+        redacted.push_str(&statement[copied..literal_start]);
+        redacted.push_str("'***'");
+        copied = literal_end;
+        search = literal_end;
+    }
+
+    if copied == 0 {
+        return Cow::Borrowed(statement);
+    }
+    redacted.push_str(&statement[copied..]);
+    Cow::Owned(redacted)
+}
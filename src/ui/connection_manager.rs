@@ -2,39 +2,115 @@ use anyhow::Result;
 use console::style;
 use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
 use rpassword::prompt_password;
+use std::collections::HashMap;
+use std::io::IsTerminal;
 use std::time::Duration;
+use uuid::Uuid;
 
-use crate::config::{Config, Connection, DatabaseType};
-use crate::database::Database;
+use crate::config::{
+    self, normalize_connection_name, normalize_host, validate_port, validate_sqlite_path, Config, Connection, DatabaseType,
+    Settings, Topology,
+};
+use crate::database::{self, Database, TestReport};
 use crate::error::QgoError;
+use crate::ping::{self, ProbeStatus};
+use crate::recent;
+use crate::secrets;
+use crate::timezone::SessionZone;
+use crate::ui::output;
+use crate::ui::prompts::{self, Ask};
+use crate::ui::settings;
+use qgo::color::{self, ConnectionColor};
+
+/// Shown instead of changing a managed config (see [`Config::managed`]).
+const MANAGED_NOTE: &str = "This configuration is managed and can't be changed from qgo; ask whoever provides it";
+
+/// How many times a rejected password is asked for again.
+const AUTH_RETRIES: usize = 3;
+
+/// Where the password a connection is opened with came from.
+#[derive(Clone, Copy)]
+enum PasswordSource {
+    /// Part of the connection itself, e.g. from a URL or `password_env`.
+    Connection,
+    /// Remembered for this session only.
+    Session,
+    /// The keyring or the encrypted file.
+    Stored,
+    Prompted,
+}
 
 pub struct ConnectionManager {
     config: Config,
     current_database: Option<Database>,
+    /// Passwords remembered for the lifetime of this process only; never written to disk.
+    session_passwords: HashMap<Uuid, String>,
+    /// Whether the main menu still has to probe the saved connections
+    /// (the `probe_connections` setting, unless `--no-probe` was given).
+    probe_pending: bool,
+    /// What the probe found, by connection id, shown next to each entry.
+    probe_results: HashMap<Uuid, ProbeStatus>,
 }
 
 impl ConnectionManager {
     pub fn new(config: Config) -> Self {
         Self {
+            probe_pending: config.settings.probe_connections,
             config,
             current_database: None,
+            session_passwords: HashMap::new(),
+            probe_results: HashMap::new(),
         }
     }
 
+    /// Skips the startup probe of saved connections for this run.
+    pub fn disable_probe(&mut self) {
+        self.probe_pending = false;
+    }
+
+    /// Shows the main menu. Returns whether a session should start; backing
+    /// out of a submenu or the new-connection wizard comes back to the menu,
+    /// while backing out of the menu itself exits.
     pub async fn select_or_manage_connection(&mut self) -> Result<bool> {
-        if self.config.connections.is_empty() {
+        if self.config.connections.is_empty() && self.config.project_connections.is_empty() {
             println!("{}", style("No database connections found.").yellow());
-            self.add_new_connection().await?;
-            return Ok(true);
+            return match self.add_new_connection().await {
+                Err(e) if prompts::is_cancelled(&e) => Ok(false),
+                result => result.map(|()| true),
+            };
+        }
+
+        loop {
+            match self.main_menu().await {
+                Err(e) if prompts::is_cancelled(&e) => println!("{}", style("Cancelled.").dim()),
+                result => return result,
+            }
+        }
+    }
+
+    async fn main_menu(&mut self) -> Result<bool> {
+        // The project's connections come first, so the default is one of them
+        let connections: Vec<Connection> = self
+            .config
+            .project_connections
+            .iter()
+            .chain(&self.config.connections)
+            .cloned()
+            .collect();
+
+        // Probed once per run; the menu can't be redrawn as results arrive,
+        // so it waits, at most for the probe timeout
+        if self.probe_pending {
+            self.probe_pending = false;
+            println!("{}", style("Checking saved connections...").dim());
+            self.probe_results = ping::probe_all(&connections, &self.session_passwords).await;
         }
 
         let mut options = vec!["Add new connection".to_string()];
-        options.extend(
-            self.config
-                .connections
-                .iter()
-                .map(|conn| conn.display_name()),
-        );
+        options.extend(connections.iter().map(|conn| match self.probe_results.get(&conn.id) {
+            Some(status) => format!("{}  {}", conn.display_name(), status.label()),
+            None => conn.display_name(),
+        }));
         options.push("Manage connections".to_string());
         options.push("Settings".to_string());
         options.push("Exit".to_string());
@@ -43,7 +119,7 @@ impl ConnectionManager {
             .with_prompt("Choose an option")
             .items(&options)
             .default(1) // Default to first connection if available
-            .interact()?;
+            .ask_or(options.len() - 1)?;
 
         match selection {
             0 => {
@@ -51,18 +127,18 @@ impl ConnectionManager {
                 self.add_new_connection().await?;
                 Ok(true)
             }
-            n if n > 0 && n <= self.config.connections.len() => {
+            n if n > 0 && n <= connections.len() => {
                 // Connect to existing connection
-                let connection = self.config.connections[n - 1].clone();
+                let connection = connections[n - 1].clone();
                 self.connect_to_database(connection).await?;
                 Ok(true)
             }
-            n if n == self.config.connections.len() + 1 => {
+            n if n == connections.len() + 1 => {
                 // Manage connections
                 self.manage_connections().await?;
                 Ok(false) // Return to main menu
             }
-            n if n == self.config.connections.len() + 2 => {
+            n if n == connections.len() + 2 => {
                 // Settings
                 self.manage_settings().await?;
                 Ok(false) // Return to main menu
@@ -84,21 +160,49 @@ impl ConnectionManager {
         self.connect_to_database(connection).await
     }
 
-    pub async fn connect_to_database(&mut self, mut connection: Connection) -> Result<()> {
-        println!("{}", style(format!("Connecting to {}...", connection.display_name())).cyan());
+    pub async fn connect_to_database(&mut self, requested: Connection) -> Result<()> {
+        output::banner(style(format!("Connecting to {}...", requested.display_name())).cyan());
 
-        // If password is empty, prompt for it
-        if connection.password.is_empty() {
-            println!("Password is required for connection '{}'", connection.name);
-            connection.password = prompt_password("Enter password: ")?;
-        }
+        let topology = self.config.topology(&requested)?;
+        let opened = match topology {
+            Some(ref topology) => self.open_topology(topology).await,
+            None => self.open_endpoint(requested.clone()).await,
+        };
 
-        let timeout = Duration::from_secs(self.config.settings.query_timeout_seconds);
-        
-        match Database::connect(connection, timeout).await {
-            Ok(database) => {
-                println!("{}", style("Connected successfully!").green());
+        match opened {
+            Ok((mut database, prompted)) => {
+                output::banner(style("Connected successfully!").green());
+                let connection = database.get_connection().clone();
+                if let Some(mut topology) = topology {
+                    topology.remember_password(&connection);
+                    database.set_topology(Some(topology));
+                }
+                self.disconnect().await;
                 self.current_database = Some(database);
+                self.record_last_used(&requested).await;
+
+                if connection.is_production() {
+                    eprintln!(
+                        "{}",
+                        style(format!(
+                            "*** PRODUCTION: '{}' is tagged as production. Writes will ask you to type its name first. ***",
+                            connection.name
+                        ))
+                        .red()
+                        .bold()
+                    );
+                }
+
+                self.check_for_mariadb(&connection).await?;
+
+                // A project connection's id is stable, so the keyring can hold its password
+                if prompted && !connection.never_remember_password && (!connection.unsaved || connection.project) {
+                    self.offer_to_remember_password(&connection).await?;
+                }
+
+                if connection.database.is_empty() && connection.requires_password() {
+                    self.choose_database().await?;
+                }
                 Ok(())
             }
             Err(e) => {
@@ -108,25 +212,297 @@ impl ConnectionManager {
         }
     }
 
+    /// Opens the first replica of `topology` that answers. When none does,
+    /// offers to use the primary instead; without a terminal to ask on, the
+    /// primary is used with a warning.
+    async fn open_topology(&mut self, topology: &Topology) -> Result<(Database, bool)> {
+        for replica in &topology.replicas {
+            match self.open_endpoint(replica.clone()).await {
+                Err(e) if !prompts::is_cancelled(&e) => {
+                    eprintln!("{}", style(format!("Replica {} is unreachable: {}", replica.display_name(), e)).yellow());
+                }
+                opened => return opened,
+            }
+        }
+
+        let primary = &topology.primary;
+        let fall_back = !std::io::stdin().is_terminal()
+            || prompts::confirm_default(&format!("No replica answered. Connect to the primary '{}' instead?", primary.name), true);
+        if !fall_back {
+            return Err(QgoError::ConnectionCheck(format!("no replica of '{}' is reachable", primary.name)).into());
+        }
+        eprintln!(
+            "{}",
+            style(format!("Using the primary '{}'; \\replica tries the replicas again.", primary.name)).yellow()
+        );
+        self.open_endpoint(primary.clone()).await
+    }
+
+    /// Opens `connection` with its password from the session, the secret
+    /// store or a prompt, asking again while the server rejects it. Returns
+    /// the database and whether the password was typed.
+    async fn open_endpoint(&mut self, mut connection: Connection) -> Result<(Database, bool)> {
+        let mut source = PasswordSource::Connection;
+        if connection.password.is_empty() && connection.requires_password() {
+            if let Some(password) = self.session_passwords.get(&connection.id) {
+                connection.password = password.clone();
+                source = PasswordSource::Session;
+            } else if let Some(password) = secrets::load_password(&connection) {
+                connection.password = password;
+                source = PasswordSource::Stored;
+            } else {
+                println!("Password is required for connection '{}'", connection.name);
+                connection.password = prompt_password("Enter password: ")?;
+                source = PasswordSource::Prompted;
+            }
+        }
+
+        // A rejected password is asked for again; any other failure is final.
+        // A remembered one is kept until a typed one has worked: MySQL also
+        // reports host-based denials as bad passwords
+        let mut retries = 0;
+        let mut rejected = None;
+        let opened = loop {
+            match open_database(&self.config.settings, connection.clone()).await {
+                Err(e) if retries < AUTH_RETRIES && database::is_auth_failure(&e) && std::io::stdin().is_terminal() => {
+                    eprintln!("{}", style(format!("Failed to connect: {}", e)).red());
+                    if matches!(source, PasswordSource::Session | PasswordSource::Stored) {
+                        rejected = Some(source);
+                    }
+                    retries += 1;
+                    connection.password =
+                        prompt_password(format!("Enter password (attempt {} of {}): ", retries, AUTH_RETRIES))?;
+                    source = PasswordSource::Prompted;
+                }
+                opened => break opened,
+            }
+        };
+        let mut prompted = matches!(source, PasswordSource::Prompted);
+        if let (Ok(_), Some(rejected)) = (&opened, rejected) {
+            self.replace_rejected_password(&connection, rejected)?;
+            prompted = false;
+        }
+        opened.map(|database| (database, prompted))
+    }
+
+    /// Swaps a remembered password the server rejected for the one that
+    /// has just worked: a session one silently, a stored one if the user
+    /// agrees.
+    fn replace_rejected_password(&mut self, connection: &Connection, rejected: PasswordSource) -> Result<()> {
+        match rejected {
+            PasswordSource::Session => {
+                self.session_passwords.insert(connection.id, connection.password.clone());
+            }
+            PasswordSource::Stored => {
+                let replace = Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!(
+                        "Replace the password stored for '{}' in the {} with this one?",
+                        connection.name,
+                        secrets::backend()
+                    ))
+                    .default(true)
+                    .ask_or(false)?;
+                if !replace {
+                    return Ok(());
+                }
+                match secrets::store_password(connection, &connection.password) {
+                    Ok(()) => println!("{}", style(format!("Password updated in the {}.", secrets::backend())).green()),
+                    Err(e) => {
+                        eprintln!(
+                            "{}",
+                            style(format!("Could not use the {} ({}); remembering for this session only.", secrets::backend(), e)).yellow()
+                        );
+                        self.session_passwords.insert(connection.id, connection.password.clone());
+                    }
+                }
+            }
+            PasswordSource::Connection | PasswordSource::Prompted => {}
+        }
+        Ok(())
+    }
+
+    /// Stamps a saved connection with the current time for `qgo --list`. A
+    /// failed save is only a warning; the connection itself is fine.
+    async fn record_last_used(&mut self, connection: &Connection) {
+        if connection.unsaved || config::is_ephemeral() {
+            return;
+        }
+        if let Some(saved) = self.config.get_connection_by_id_mut(&connection.id) {
+            saved.last_used = Some(chrono::Utc::now());
+            if let Err(e) = self.config.save().await {
+                eprintln!("{}", style(format!("Could not record when '{}' was last used: {}", connection.name, e)).yellow());
+            }
+        }
+    }
+
+    /// Warns when a connection labeled MySQL is actually talking to MariaDB,
+    /// and offers to relabel it so MariaDB's metadata queries are used.
+    async fn check_for_mariadb(&mut self, connection: &Connection) -> Result<()> {
+        if !matches!(connection.db_type, DatabaseType::MySQL) {
+            return Ok(());
+        }
+        let Some(database) = self.current_database.as_mut() else {
+            return Ok(());
+        };
+        let version = match database.server_version().await {
+            Ok(version) if version.to_lowercase().contains("mariadb") => version,
+            _ => return Ok(()),
+        };
+
+        eprintln!(
+            "{}",
+            style(format!(
+                "'{}' is set up as MySQL, but the server is MariaDB {}.",
+                connection.name, version
+            ))
+            .yellow()
+        );
+        if !std::io::stdin().is_terminal() {
+            return Ok(());
+        }
+        let relabel = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Relabel it as MariaDB?")
+            .default(true)
+            .ask_or(false)?;
+        if !relabel {
+            return Ok(());
+        }
+
+        database.relabel(DatabaseType::MariaDB);
+        if !connection.unsaved {
+            if let Some(saved) = self.config.get_connection_by_id_mut(&connection.id) {
+                saved.db_type = DatabaseType::MariaDB;
+                self.config.save().await?;
+            }
+        }
+        output::info(format!("'{}' is now labeled MariaDB.", connection.name));
+        Ok(())
+    }
+
+    /// Lets the user pick a database for a connection saved without one, and
+    /// optionally records the choice on the saved connection.
+    async fn choose_database(&mut self) -> Result<()> {
+        let timeout = Duration::from_secs(self.config.settings.query_timeout_seconds);
+        let database = match self.current_database.as_mut() {
+            Some(database) => database,
+            None => return Ok(()),
+        };
+
+        let databases = database.get_databases().await?;
+        if databases.is_empty() {
+            println!("{}", style("No databases found on this server.").yellow());
+            return Ok(());
+        }
+
+        let selection = match Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Choose a database")
+            .items(&databases)
+            .default(0)
+            .ask()
+        {
+            Err(QgoError::Cancelled) => return Ok(()),
+            selection => selection?,
+        };
+        let chosen = &databases[selection];
+
+        database.switch_database(chosen, timeout).await?;
+        output::info(style(format!("Using database '{}'.", chosen)).green());
+
+        let connection_id = database.get_connection().id;
+        if database.get_connection().unsaved {
+            return Ok(());
+        }
+        let save = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Save '{}' as the database for this connection?", chosen))
+            .default(false)
+            .ask_or(false)?;
+
+        if save {
+            if let Some(saved) = self.config.get_connection_by_id_mut(&connection_id) {
+                saved.database = chosen.clone();
+                self.config.save().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn offer_to_remember_password(&mut self, connection: &Connection) -> Result<()> {
+        let store = format!("Remember in the {}", secrets::backend());
+        let options = [
+            store.as_str(),
+            "Remember for this session only",
+            "Don't remember",
+            "Don't remember, and never ask again for this connection",
+        ];
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Remember this password?")
+            .items(&options)
+            .default(1)
+            .ask_or(2)?;
+
+        match selection {
+            0 => match secrets::store_password(connection, &connection.password) {
+                Ok(()) => println!("{}", style(format!("Password saved to the {}.", secrets::backend())).green()),
+                Err(e) => {
+                    eprintln!(
+                        "{}",
+                        style(format!("Could not use the {} ({}); remembering for this session only.", secrets::backend(), e)).yellow()
+                    );
+                    self.session_passwords.insert(connection.id, connection.password.clone());
+                }
+            },
+            1 => {
+                self.session_passwords.insert(connection.id, connection.password.clone());
+            }
+            3 => {
+                if let Some(saved) = self.config.get_connection_by_id_mut(&connection.id) {
+                    saved.never_remember_password = true;
+                    self.config.save().await?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
     async fn add_new_connection(&mut self) -> Result<()> {
         println!("{}", style("Add New Database Connection").bold().blue());
         println!();
 
-        let name: String = Input::with_theme(&ColorfulTheme::default())
-            .with_prompt("Connection name")
-            .interact_text()?;
+        let name = loop {
+            let name: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Connection name")
+                .validate_with(|input: &String| normalize_connection_name(input).map(|_| ()))
+                .ask()?;
+            let name = normalize_connection_name(&name).map_err(QgoError::Input)?;
+
+            if self.config.get_saved_connection_by_name(&name).is_none() {
+                break name;
+            }
+            let overwrite = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("A connection named '{}' already exists. Overwrite it?", name))
+                .default(false)
+                .ask()?;
+            if overwrite {
+                break name;
+            }
+        };
 
-        let db_types = vec!["MySQL", "PostgreSQL", "SQLite"];
+        let db_types = vec!["MySQL", "MariaDB", "PostgreSQL", "SQLite"];
         let db_type_selection = Select::with_theme(&ColorfulTheme::default())
             .with_prompt("Database type")
             .items(&db_types)
             .default(0)
-            .interact()?;
+            .ask()?;
 
         let db_type = match db_type_selection {
             0 => DatabaseType::MySQL,
-            1 => DatabaseType::PostgreSQL,
-            2 => DatabaseType::SQLite,
+            1 => DatabaseType::MariaDB,
+            2 => DatabaseType::PostgreSQL,
+            3 => DatabaseType::SQLite,
             _ => unreachable!(),
         };
 
@@ -134,37 +510,72 @@ impl ConnectionManager {
             DatabaseType::SQLite => {
                 let database: String = Input::with_theme(&ColorfulTheme::default())
                     .with_prompt("Database file path")
-                    .interact_text()?;
-                
+                    .validate_with(|input: &String| validate_sqlite_path(input))
+                    .ask()?;
+                let database = database.trim().to_string();
+
                 ("localhost".to_string(), 0, "".to_string(), "".to_string(), database)
             }
             _ => {
-                let host: String = Input::with_theme(&ColorfulTheme::default())
-                    .with_prompt("Host")
-                    .default("localhost".to_string())
-                    .interact_text()?;
+                // Defaults come from the last connection of this type; the
+                // password is never carried over
+                let latest = self.config.latest_connection_of_type(&db_type);
+                let default_host = latest.map_or_else(|| "localhost".to_string(), |latest| latest.host.clone());
+                let default_port = latest.map_or(
+                    match db_type {
+                        DatabaseType::MySQL | DatabaseType::MariaDB => 3306,
+                        DatabaseType::PostgreSQL => 5432,
+                        _ => 0,
+                    },
+                    |latest| latest.port,
+                );
+                let default_username = latest.map(|latest| latest.username.clone()).filter(|name| !name.is_empty());
+
+                let host = match pick_recent("Host", &self.config.recent_inputs.hosts, &default_host)? {
+                    Some(host) => host,
+                    None => {
+                        let host: String = Input::with_theme(&ColorfulTheme::default())
+                            .with_prompt("Host (name, IPv4, or IPv6 address)")
+                            .default(default_host)
+                            .validate_with(|input: &String| normalize_host(input).map(|_| ()))
+                            .ask()?;
+                        normalize_host(&host).map_err(QgoError::Input)?
+                    }
+                };
 
                 let port: u16 = Input::with_theme(&ColorfulTheme::default())
                     .with_prompt("Port")
-                    .default(match db_type {
-                        DatabaseType::MySQL => 3306,
-                        DatabaseType::PostgreSQL => 5432,
-                        _ => 0,
-                    })
-                    .interact_text()?;
+                    .default(default_port)
+                    .validate_with(|port: &u16| validate_port(*port))
+                    .ask()?;
 
-                let username: String = Input::with_theme(&ColorfulTheme::default())
-                    .with_prompt("Username")
-                    .interact_text()?;
+                let recent_username = pick_recent(
+                    "Username",
+                    &self.config.recent_inputs.usernames,
+                    default_username.as_deref().unwrap_or_default(),
+                )?;
+                let username = match recent_username {
+                    Some(username) => username,
+                    None => {
+                        let theme = ColorfulTheme::default();
+                        let mut input = Input::with_theme(&theme).with_prompt("Username");
+                        if let Some(username) = default_username {
+                            input = input.default(username);
+                        }
+                        input.ask()?
+                    }
+                };
 
                 let database: String = Input::with_theme(&ColorfulTheme::default())
-                    .with_prompt("Database name")
-                    .interact_text()?;
+                    .with_prompt("Database name (leave empty to choose after connecting)")
+                    .allow_empty(true)
+                    .ask()?;
+                let database = database.trim().to_string();
 
                 let test_connection = Confirm::with_theme(&ColorfulTheme::default())
                     .with_prompt("Test connection now?")
                     .default(true)
-                    .interact()?;
+                    .ask()?;
 
                 let password = if test_connection {
                     let password = prompt_password("Password: ")?;
@@ -179,13 +590,15 @@ impl ConnectionManager {
                         password.clone(),
                         database.clone(),
                     );
+                    let test_conn = prepare_connection(&self.config.settings, test_conn)?;
 
                     print!("Testing connection... ");
                     let timeout = Duration::from_secs(self.config.settings.query_timeout_seconds);
                     
-                    match Database::test_connection(&test_conn, timeout).await {
-                        Ok(_) => {
+                    match Database::test_connection(&test_conn, timeout, false).await {
+                        Ok(report) => {
                             println!("{}", style("✓ Connection successful!").green());
+                            print_test_report(&report);
                         }
                         Err(e) => {
                             println!("{}", style(format!("✗ Connection failed: {}", e)).red());
@@ -193,7 +606,7 @@ impl ConnectionManager {
                             let continue_anyway = Confirm::with_theme(&ColorfulTheme::default())
                                 .with_prompt("Save connection anyway?")
                                 .default(false)
-                                .interact()?;
+                                .ask()?;
                             
                             if !continue_anyway {
                                 return Ok(());
@@ -210,7 +623,72 @@ impl ConnectionManager {
             }
         };
 
-        let connection = Connection::new(name, db_type, host, port, username, password, database);
+        let environment: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Environment (dev, staging, prod or any label; leave empty for none)")
+            .allow_empty(true)
+            .ask()?;
+        let environment = Some(environment.trim().to_string()).filter(|environment| !environment.is_empty());
+        let color = pick_color()?;
+
+        let allow_writes = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Allow commands that write data (e.g. \\import) on this connection?")
+            .default(false)
+            .ask()?;
+
+        let save = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Save this connection?")
+            .default(true)
+            .ask()?;
+
+        let mut connection = Connection::new(name, db_type, host, port, username, password, database);
+        connection.allow_writes = allow_writes;
+        connection.environment = environment;
+        connection.color = color;
+
+        if !save {
+            // Used for this session only; \saveconn can still persist it later
+            connection.unsaved = true;
+            return self.connect_to_database(connection).await;
+        }
+
+        if !matches!(connection.db_type, DatabaseType::SQLite) {
+            self.config.recent_inputs.remember(&connection.host, &connection.username);
+        }
+
+        // Overwriting a connection of the same name is already a deliberate choice
+        let duplicate = self
+            .config
+            .find_duplicate(&connection)
+            .filter(|existing| existing.name != connection.name)
+            .cloned();
+        if let Some(existing) = duplicate {
+            println!(
+                "{}",
+                style(format!("'{}' already points at {}.", existing.name, existing.target())).yellow()
+            );
+            let update = format!("Update '{}' with these settings", existing.name);
+            let options = [update.as_str(), "Save as a new connection anyway", "Cancel"];
+            let choice = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Duplicate connection")
+                .items(&options)
+                .default(0)
+                .ask()?;
+            match choice {
+                0 => {
+                    if let Some(saved) = self.config.get_connection_by_id_mut(&existing.id) {
+                        saved.allow_writes = connection.allow_writes;
+                        saved.environment = connection.environment;
+                        saved.color = connection.color;
+                    }
+                    self.config.save().await?;
+                    println!("{}", style(format!("Connection '{}' updated.", existing.name)).green());
+                    return Ok(());
+                }
+                1 => {}
+                _ => return Err(QgoError::Cancelled.into()),
+            }
+        }
+
         self.config.add_connection(connection);
         self.config.save().await?;
 
@@ -225,34 +703,70 @@ impl ConnectionManager {
         }
 
         loop {
-            let mut options = vec!["Back to main menu".to_string()];
+            let mut options = vec!["Back to main menu".to_string(), "Find duplicates".to_string()];
             options.extend(
                 self.config
                     .connections
                     .iter()
-                    .map(|conn| format!("Delete: {}", conn.display_name())),
+                    .map(|conn| conn.display_name()),
             );
 
             let selection = Select::with_theme(&ColorfulTheme::default())
                 .with_prompt("Connection Management")
                 .items(&options)
                 .default(0)
-                .interact()?;
+                .ask_or(0)?;
 
             if selection == 0 {
                 break; // Back to main menu
             }
+            if selection == 1 {
+                if self.config.managed {
+                    println!("{}", style(MANAGED_NOTE).yellow());
+                    continue;
+                }
+                match self.merge_duplicates().await {
+                    Err(e) if prompts::is_cancelled(&e) => {}
+                    result => result?,
+                }
+                continue;
+            }
 
-            let conn_index = selection - 1;
+            let conn_index = selection - 2;
             let connection = &self.config.connections[conn_index];
-            
+
+            let action = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt(connection.name.as_str())
+                .items(&["Show connection URL", "Delete", "Back"])
+                .default(0)
+                .ask_or(2)?;
+            if action == 0 {
+                let mut connection = connection.clone();
+                if connection.password.is_empty() {
+                    if let Some(password) = self.session_passwords.get(&connection.id) {
+                        connection.password = password.clone();
+                    } else if let Some(password) = secrets::load_password(&connection) {
+                        connection.password = password;
+                    }
+                }
+                show_connection_url(&connection)?;
+                continue;
+            } else if action != 1 {
+                continue;
+            } else if self.config.managed {
+                println!("{}", style(MANAGED_NOTE).yellow());
+                continue;
+            }
+
             let confirm = Confirm::with_theme(&ColorfulTheme::default())
                 .with_prompt(format!("Delete connection '{}'?", connection.name))
                 .default(false)
-                .interact()?;
+                .ask_or(false)?;
 
             if confirm {
                 let conn_id = connection.id;
+                secrets::delete_password(connection);
+                self.session_passwords.remove(&conn_id);
                 self.config.remove_connection(&conn_id)?;
                 self.config.save().await?;
                 println!("{}", style("Connection deleted successfully!").green());
@@ -266,75 +780,248 @@ impl ConnectionManager {
         Ok(())
     }
 
-    async fn manage_settings(&mut self) -> Result<()> {
-        loop {
-            let timeout_option = format!("Query timeout: {} seconds", self.config.settings.query_timeout_seconds);
-            let max_rows_option = format!("Max rows display: {:?}", self.config.settings.max_rows_display);
-            let auto_completion_option = format!("Auto completion: {}", self.config.settings.auto_completion);
-            let history_size_option = format!("History size: {}", self.config.settings.history_size);
-            
-            let options = vec![
-                "Back to main menu",
-                &timeout_option,
-                &max_rows_option,
-                &auto_completion_option,
-                &history_size_option,
-            ];
+    /// Finds saved connections pointing at the same place and offers to
+    /// merge each group into one of them. The kept entry keeps its id (so
+    /// its stored password and query history stay attached), takes the most
+    /// recent last-used time, and takes an environment tag from the others
+    /// if it has none. The others' history is moved over, and a stored
+    /// password too if the kept entry has none.
+    async fn merge_duplicates(&mut self) -> Result<()> {
+        let groups: Vec<Vec<Connection>> = self
+            .config
+            .duplicate_groups()
+            .into_iter()
+            .map(|group| group.into_iter().cloned().collect())
+            .collect();
+        if groups.is_empty() {
+            println!("No duplicate connections found.");
+            return Ok(());
+        }
 
-            let selection = Select::with_theme(&ColorfulTheme::default())
-                .with_prompt("Settings")
-                .items(&options)
+        for group in groups {
+            let names: Vec<&str> = group.iter().map(|connection| connection.name.as_str()).collect();
+            println!(
+                "{}",
+                style(format!("{} all point at {}.", names.join(", "), group[0].target())).yellow()
+            );
+            let merge = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("Merge these {} connections into one?", group.len()))
+                .default(true)
+                .ask()?;
+            if !merge {
+                continue;
+            }
+            let keep = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Keep which name?")
+                .items(&names)
                 .default(0)
-                .interact()?;
-
-            match selection {
-                0 => break, // Back to main menu
-                1 => {
-                    let timeout: u64 = Input::with_theme(&ColorfulTheme::default())
-                        .with_prompt("Query timeout (seconds)")
-                        .default(self.config.settings.query_timeout_seconds)
-                        .interact_text()?;
-                    self.config.settings.query_timeout_seconds = timeout;
+                .ask()?;
+
+            let kept = group[keep].clone();
+            let mut merged = kept.clone();
+            let mut password = secrets::load_password(&kept);
+            for other in group.iter().filter(|other| other.id != kept.id) {
+                merged.last_used = merged.last_used.max(other.last_used);
+                if merged.environment.is_none() {
+                    merged.environment = other.environment.clone();
                 }
-                2 => {
-                    let max_rows: String = Input::with_theme(&ColorfulTheme::default())
-                        .with_prompt("Max rows display (enter 'none' for no limit)")
-                        .default(self.config.settings.max_rows_display.map_or_else(|| "none".to_string(), |n| n.to_string()))
-                        .interact_text()?;
-                    
-                    self.config.settings.max_rows_display = if max_rows.to_lowercase() == "none" {
-                        None
-                    } else {
-                        Some(max_rows.parse()?)
-                    };
+                if password.is_none() {
+                    password = secrets::load_password(other);
                 }
-                3 => {
-                    self.config.settings.auto_completion = Confirm::with_theme(&ColorfulTheme::default())
-                        .with_prompt("Enable auto completion")
-                        .default(self.config.settings.auto_completion)
-                        .interact()?;
+                if let Err(e) = recent::merge(other, &kept) {
+                    eprintln!(
+                        "{}",
+                        style(format!("Could not move the query history of '{}': {}", other.name, e)).yellow()
+                    );
+                }
+                if let Some(session_password) = self.session_passwords.remove(&other.id) {
+                    self.session_passwords.entry(kept.id).or_insert(session_password);
                 }
-                4 => {
-                    let history_size: usize = Input::with_theme(&ColorfulTheme::default())
-                        .with_prompt("History size")
-                        .default(self.config.settings.history_size)
-                        .interact_text()?;
-                    self.config.settings.history_size = history_size;
+                secrets::delete_password(other);
+                self.config.remove_connection(&other.id)?;
+            }
+
+            if let Some(password) = password {
+                if secrets::load_password(&kept).is_none() {
+                    if let Err(e) = secrets::store_password(&kept, &password) {
+                        eprintln!("{}", style(format!("Could not keep the stored password: {}", e)).yellow());
+                    }
                 }
-                _ => {}
             }
+            if let Some(saved) = self.config.get_connection_by_id_mut(&kept.id) {
+                *saved = merged;
+            }
+            self.config.save().await?;
+            println!("{}", style(format!("Merged into '{}'.", kept.name)).green());
         }
+        Ok(())
+    }
 
-        self.config.save().await?;
-        println!("{}", style("Settings saved successfully!").green());
+    pub async fn manage_settings(&mut self) -> Result<()> {
+        // Shown, but not offered for editing
+        if self.config.managed {
+            settings::print(&self.config.settings);
+            println!("{}", style(MANAGED_NOTE).yellow());
+            return Ok(());
+        }
+        if settings::edit(&mut self.config.settings)? {
+            self.config.save().await?;
+            println!("{}", style("Settings saved successfully!").green());
+        }
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub fn get_database(&mut self) -> Option<&mut Database> {
         self.current_database.as_mut()
     }
 
+    #[allow(dead_code)]
     pub fn get_config(&self) -> &Config {
         &self.config
     }
+
+    /// Closes the current database's pool, if one is open.
+    pub async fn disconnect(&mut self) {
+        if let Some(database) = self.current_database.take() {
+            database.close().await;
+        }
+    }
+
+    /// Borrows the current database together with the configuration, for
+    /// code that needs both at once (such as the interactive session).
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn session_parts(&mut self) -> Option<(&mut Database, &mut Config)> {
+        let config = &mut self.config;
+        self.current_database.as_mut().map(|database| (database, config))
+    }
+}
+
+/// Fills in what a saved connection needs before it can be opened: the
+/// password (from the keyring, or prompted for) and the application name.
+pub fn prepare_connection(settings: &Settings, mut connection: Connection) -> Result<Connection> {
+    // If password is empty, look in the keyring and fall back to prompting (SQLite files have no password)
+    if connection.password.is_empty() && connection.requires_password() {
+        match secrets::load_password(&connection) {
+            Some(password) => connection.password = password,
+            None => {
+                println!("Password is required for connection '{}'", connection.name);
+                connection.password = prompt_password("Enter password: ")?;
+            }
+        }
+    }
+
+    connection.application_name =
+        Some(connection.resolve_application_name(&settings.application_name));
+
+    Ok(connection)
+}
+
+/// Prints the URL qgo connects with, password masked, and offers to reveal
+/// the real password.
+pub fn show_connection_url(connection: &Connection) -> Result<()> {
+    println!("{}", connection.masked_connection_string());
+    if connection.password.is_empty() {
+        if connection.requires_password() {
+            println!("{}", style("No password is stored for this connection; it is asked for when connecting.").dim());
+        }
+        return Ok(());
+    }
+    if !std::io::stdin().is_terminal() {
+        return Ok(());
+    }
+
+    let reveal = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Reveal the password? It will stay visible in your terminal scrollback")
+        .default(false)
+        .ask_or(false)?;
+    if reveal {
+        println!("{}", connection.connection_string());
+    }
+    Ok(())
+}
+
+/// Opens a database for `connection`, applying the session settings and
+/// prompting for the password when none is saved.
+pub async fn open_database(settings: &Settings, connection: Connection) -> Result<Database> {
+    let connection = prepare_connection(settings, connection)?;
+
+    let timeout = Duration::from_secs(settings.query_timeout_seconds);
+    let timezone = connection
+        .effective_timezone(settings)
+        .map(SessionZone::parse)
+        .transpose()?;
+
+    output::banner(format!(
+        "Connecting to {} database at {}:{}...",
+        connection.db_type, connection.host, connection.port
+    ));
+    let mut database = Database::connect(connection, timeout, timezone).await?;
+    database.set_max_result_memory(settings.max_result_memory_mb);
+    Ok(database)
+}
+
+/// Asks for the color that tints the connection's prompt: none, one of the
+/// named colors (each shown in its color) or a `#rrggbb` value.
+fn pick_color() -> Result<Option<String>> {
+    let mut items = vec!["None".to_string()];
+    items.extend(color::NAMES.iter().map(|name| {
+        let swatch = ConnectionColor::parse(name).map_or_else(|_| "  ".to_string(), ConnectionColor::swatch);
+        format!("{} {}", swatch, name)
+    }));
+    items.push("Hex value (#rrggbb)…".to_string());
+    let choice = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Prompt color, to tell this connection's sessions apart")
+        .items(&items)
+        .default(0)
+        .ask()?;
+    Ok(match choice {
+        0 => None,
+        n if n <= color::NAMES.len() => Some(color::NAMES[n - 1].to_string()),
+        _ => {
+            let hex: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Color (#rrggbb)")
+                .validate_with(|hex: &String| ConnectionColor::parse(hex).map(|_| ()))
+                .ask()?;
+            Some(hex.trim().to_lowercase())
+        }
+    })
+}
+
+/// Offers the values in `recent` for a wizard field, with `default`
+/// preselected when it is one of them and "Other…" when it isn't. `None`
+/// means the user picked "Other…" (or there is nothing to offer) and should
+/// type a value, starting from `default`.
+fn pick_recent(field: &str, recent: &[String], default: &str) -> Result<Option<String>> {
+    if recent.is_empty() {
+        return Ok(None);
+    }
+
+    let mut items: Vec<&str> = recent.iter().map(String::as_str).collect();
+    items.push("Other…");
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(field)
+        .items(&items)
+        .default(
+            recent
+                .iter()
+                .position(|value| value == default)
+                .unwrap_or(if default.is_empty() { 0 } else { recent.len() }),
+        )
+        .ask()?;
+    Ok(recent.get(selection).cloned())
+}
+
+/// Prints the details of a successful connection test under the ✓ line.
+fn print_test_report(report: &TestReport) {
+    println!("  Latency:  {:.1} ms", report.latency.as_secs_f64() * 1000.0);
+    if let Some(ref version) = report.server_version {
+        println!("  Server:   {}", version);
+    }
+    if let Some(ref database) = report.database {
+        println!("  Database: {} (confirmed)", database);
+    }
 }
@@ -0,0 +1,429 @@
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::SearchDirection;
+use rustyline::history::History;
+use rustyline::validate::Validator;
+use rustyline::{Cmd, ConditionalEventHandler, Context, Editor, Event, EventContext, EventHandler, Helper, KeyEvent, RepeatCount};
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use crate::config::DatabaseType;
+use crate::database::{self, SQL_KEYWORDS};
+
+const KEYWORD: &str = "\x1b[1;34m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[33m";
+const COMMENT: &str = "\x1b[2m";
+const ERROR: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Words after which a table name is completed.
+const TABLE_POSITIONS: &[&str] = &["from", "join", "\\d", "describe"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Keyword,
+    Str,
+    Number,
+    Comment,
+    Error,
+}
+
+impl Kind {
+    fn color(self) -> &'static str {
+        match self {
+            Kind::Keyword => KEYWORD,
+            Kind::Str => STRING,
+            Kind::Number => NUMBER,
+            Kind::Comment => COMMENT,
+            Kind::Error => ERROR,
+        }
+    }
+}
+
+/// Readline helper for the interactive session. It highlights SQL as it is
+/// typed and is the place completion and hints hook into.
+pub struct SqlHelper {
+    color: bool,
+    hints: bool,
+    tables: Arc<[String]>,
+    /// How completed table names are quoted; `None` until tables are set.
+    db_type: Option<DatabaseType>,
+    filenames: FilenameCompleter,
+}
+
+impl SqlHelper {
+    /// `color` is the `color_output` setting; `NO_COLOR` overrides it.
+    /// `hints` follows the `auto_completion` setting.
+    pub fn new(color: bool, hints: bool) -> Self {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        Self {
+            color: color && !no_color,
+            hints,
+            tables: Arc::from([]),
+            db_type: None,
+            filenames: FilenameCompleter::new(),
+        }
+    }
+
+    /// Sets the table names completed after `FROM`, `JOIN` and `\d`,
+    /// shared with the database's schema cache, and the database they come
+    /// from.
+    pub fn set_tables(&mut self, tables: Arc<[String]>, db_type: DatabaseType) {
+        self.tables = tables;
+        self.db_type = Some(db_type);
+    }
+
+    /// Where the table name being typed at the end of `line` starts, and
+    /// what has been typed of it without an opening quote, if the word
+    /// before it takes a table.
+    fn table_word<'l>(&self, line: &'l str) -> Option<(usize, &'l str)> {
+        let word_start = line
+            .rfind(|c: char| c.is_whitespace() || c == ',')
+            .map_or(0, |index| index + 1);
+        let (before, word) = line.split_at(word_start);
+        let keyword = before.trim_end().rsplit(char::is_whitespace).next()?;
+        if word_start == 0 || !TABLE_POSITIONS.iter().any(|position| keyword.eq_ignore_ascii_case(position)) {
+            return None;
+        }
+        Some((word_start, word.trim_start_matches(['"', '`'])))
+    }
+
+    /// Tables whose names start with `typed`, ignoring case.
+    fn matching_tables<'a>(&'a self, typed: &'a str) -> impl Iterator<Item = &'a String> {
+        self.tables
+            .iter()
+            .filter(move |table| table.get(..typed.len()).is_some_and(|start| start.eq_ignore_ascii_case(typed)))
+    }
+
+    /// `table` as completion inserts it: the catalog's spelling, quoted
+    /// when it couldn't be written bare.
+    fn completion(&self, table: &str) -> String {
+        self.db_type
+            .as_ref()
+            .and_then(|db_type| database::quote_ident_if_needed(db_type, table).ok())
+            .unwrap_or_else(|| table.to_string())
+    }
+
+    /// Suggests the rest of the first matching table name, when what was
+    /// typed is already spelled (and quoted) as completion would write it.
+    /// Other matches are left to Tab, which replaces the whole word.
+    fn table_hint(&self, line: &str) -> Option<String> {
+        let (start, typed) = self.table_word(line)?;
+        let word = &line[start..];
+        self.matching_tables(typed)
+            .map(|table| self.completion(table))
+            .find(|completion| completion.len() > word.len() && completion.starts_with(word))
+            .map(|completion| completion[word.len()..].to_string())
+    }
+
+    /// Suggests the rest of the most recent history entry starting with `line`.
+    fn history_hint(&self, line: &str, ctx: &Context<'_>) -> Option<String> {
+        let history = ctx.history();
+        let start = history.len().checked_sub(1)?;
+        let found = history
+            .starts_with(line, start, SearchDirection::Reverse)
+            .ok()
+            .flatten()?;
+        let rest = found.entry.get(line.len()..)?;
+        (!rest.is_empty()).then(|| rest.to_string())
+    }
+}
+
+impl Helper for SqlHelper {}
+
+impl Completer for SqlHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        // Table names match in any case and are replaced whole, so typing
+        // `useracc` completes to "UserAccounts" as the catalog spells it
+        if let Some((start, typed)) = self.table_word(&line[..pos]) {
+            let tables: Vec<Pair> = self
+                .matching_tables(typed)
+                .map(|table| Pair {
+                    display: table.clone(),
+                    replacement: self.completion(table),
+                })
+                .collect();
+            if !tables.is_empty() {
+                return Ok((start, tables));
+            }
+        }
+
+        let (start, candidates) = self.filenames.complete_path(line, pos)?;
+        let before = line[..start].trim_end_matches(['"', '\'']);
+        if is_filename_position(before) {
+            Ok((start, candidates))
+        } else {
+            Ok((pos, Vec::new()))
+        }
+    }
+}
+
+/// Whether the word following `before` is the file name argument of a
+/// command that reads or writes a file.
+fn is_filename_position(before: &str) -> bool {
+    let words: Vec<&str> = before.split_whitespace().collect();
+    match words.as_slice() {
+        [command, _format] => command.eq_ignore_ascii_case("export") || *command == "\\import",
+        [command] => *command == "\\i" || *command == "\\o",
+        _ => false,
+    }
+}
+
+impl Hinter for SqlHelper {
+    type Hint = String;
+
+    fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<String> {
+        // Only hint at the very end of the buffer so a multi-line query shows
+        // a single hint after its last line
+        if !self.hints || pos < line.len() || line.trim().is_empty() {
+            return None;
+        }
+
+        self.table_hint(line).or_else(|| self.history_hint(line, ctx))
+    }
+}
+
+impl Validator for SqlHelper {}
+
+impl Highlighter for SqlHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if !self.color || line.is_empty() {
+            return Cow::Borrowed(line);
+        }
+
+        let spans = tokenize(line);
+        if spans.is_empty() {
+            return Cow::Borrowed(line);
+        }
+
+        let mut out = String::with_capacity(line.len() + spans.len() * 10);
+        let mut last = 0;
+        for (start, end, kind) in spans {
+            out.push_str(&line[last..start]);
+            out.push_str(kind.color());
+            out.push_str(&line[start..end]);
+            out.push_str(RESET);
+            last = end;
+        }
+        out.push_str(&line[last..]);
+        Cow::Owned(out)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        if self.color {
+            Cow::Owned(format!("{}{}{}", COMMENT, hint, RESET))
+        } else {
+            Cow::Borrowed(hint)
+        }
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        self.color
+    }
+}
+
+/// Clears the screen and its scrollback, leaving the cursor at the top.
+/// Does nothing when stdout isn't a terminal.
+pub fn clear_screen() {
+    let term = console::Term::stdout();
+    if term.is_term() {
+        // Clearing the screen keeps the scrollback; ESC[3J drops it too
+        let _ = term.clear_screen().and_then(|_| term.write_str("\x1b[3J"));
+    }
+}
+
+/// Undoes what a program killed mid-way (a pager, say) can leave behind:
+/// the alternate screen, a hidden cursor, colors and attributes. Then
+/// clears the screen.
+pub fn reset_terminal() {
+    let term = console::Term::stdout();
+    if term.is_term() {
+        let _ = term.write_str("\x1b[?1049l\x1b[0m").and_then(|_| term.show_cursor());
+        clear_screen();
+    }
+}
+
+/// Ctrl+L at the prompt clears like `clear`, then redraws the line being
+/// typed.
+struct ClearScreenHandler;
+
+impl ConditionalEventHandler for ClearScreenHandler {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, _: &EventContext) -> Option<Cmd> {
+        clear_screen();
+        Some(Cmd::ClearScreen)
+    }
+}
+
+/// Binds Ctrl+L in `editor` to [`clear_screen`].
+pub fn bind_clear_screen<H: Helper, I: History>(editor: &mut Editor<H, I>) {
+    editor.bind_sequence(KeyEvent::ctrl('L'), EventHandler::Conditional(Box::new(ClearScreenHandler)));
+}
+
+/// Splits `line` into colored spans `(start, end, kind)` in a single pass.
+/// Unterminated quotes and unbalanced parentheses become error spans.
+fn tokenize(line: &str) -> Vec<(usize, usize, Kind)> {
+    let bytes = line.as_bytes();
+    let mut spans = Vec::new();
+    let mut open_parens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let b = bytes[i];
+        match b {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                let end = line[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                spans.push((i, end, Kind::Comment));
+                i = end;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let end = line[i + 2..].find("*/").map_or(bytes.len(), |n| i + 2 + n + 2);
+                spans.push((i, end, Kind::Comment));
+                i = end;
+            }
+            b'\'' | b'"' | b'`' => {
+                match closing_quote(bytes, i) {
+                    Some(end) => {
+                        // Quoted identifiers are only checked for balance
+                        if b == b'\'' {
+                            spans.push((i, end, Kind::Str));
+                        }
+                        i = end;
+                    }
+                    None => {
+                        spans.push((i, bytes.len(), Kind::Error));
+                        i = bytes.len();
+                    }
+                }
+            }
+            b'(' => {
+                open_parens.push(spans.len());
+                spans.push((i, i + 1, Kind::Error));
+                i += 1;
+            }
+            b')' => {
+                match open_parens.pop() {
+                    // Matched: the opening paren is no longer an error
+                    Some(index) => spans[index].2 = Kind::Comment,
+                    None => spans.push((i, i + 1, Kind::Error)),
+                }
+                i += 1;
+            }
+            b'0'..=b'9' => {
+                let end = scan(bytes, i, |c| c.is_ascii_digit() || c == b'.');
+                spans.push((i, end, Kind::Number));
+                i = end;
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                let end = scan(bytes, i, |c| c.is_ascii_alphanumeric() || c == b'_' || c == b'$');
+                let word = line[i..end].to_ascii_uppercase();
+                if SQL_KEYWORDS.binary_search(&word.as_str()).is_ok() {
+                    spans.push((i, end, Kind::Keyword));
+                }
+                i = end;
+            }
+            _ => {
+                // Skip over the rest of a multi-byte character in one step
+                i += line[i..].chars().next().map_or(1, char::len_utf8);
+            }
+        }
+    }
+
+    // Balanced parentheses are left uncolored
+    spans.retain(|(start, _, kind)| !(*kind == Kind::Comment && bytes[*start] == b'('));
+    spans
+}
+
+/// Returns the index just past the quote closing the one at `start`, treating
+/// a doubled quote as an escaped quote character.
+fn closing_quote(bytes: &[u8], start: usize) -> Option<usize> {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        if bytes[i] == quote {
+            if bytes.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return Some(i + 1);
+        }
+        i += 1;
+    }
+    None
+}
+
+fn scan(bytes: &[u8], start: usize, accept: impl Fn(u8) -> bool) -> usize {
+    let mut end = start;
+    while end < bytes.len() && accept(bytes[end]) {
+        end += 1;
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::history::MemHistory;
+
+    fn helper(db_type: DatabaseType) -> SqlHelper {
+        let mut helper = SqlHelper::new(false, true);
+        let tables: Vec<String> = ["MixedCase", "mixed_lower", "orders", "select"].iter().map(|t| t.to_string()).collect();
+        helper.set_tables(Arc::from(tables), db_type);
+        helper
+    }
+
+    fn complete(helper: &SqlHelper, line: &str) -> (usize, Vec<(String, String)>) {
+        let history = MemHistory::new();
+        let (start, pairs) = helper.complete(line, line.len(), &Context::new(&history)).unwrap();
+        (start, pairs.into_iter().map(|pair| (pair.display, pair.replacement)).collect())
+    }
+
+    fn pair(display: &str, replacement: &str) -> (String, String) {
+        (display.to_string(), replacement.to_string())
+    }
+
+    #[test]
+    fn completes_mixed_case_tables_in_any_case_with_catalog_spelling() {
+        let postgres = helper(DatabaseType::PostgreSQL);
+        assert_eq!(
+            complete(&postgres, "SELECT * FROM mixed"),
+            (14, vec![pair("MixedCase", "\"MixedCase\""), pair("mixed_lower", "mixed_lower")])
+        );
+        assert_eq!(complete(&postgres, "select * from \"Mixedc"), (14, vec![pair("MixedCase", "\"MixedCase\"")]));
+        assert_eq!(complete(&postgres, "\\d MIXEDC"), (3, vec![pair("MixedCase", "\"MixedCase\"")]));
+        assert_eq!(complete(&postgres, "SELECT * FROM orders o JOIN sel"), (28, vec![pair("select", "\"select\"")]));
+
+        // SQLite folds case itself, so only keywords need quotes
+        let sqlite = helper(DatabaseType::SQLite);
+        assert_eq!(complete(&sqlite, "DESCRIBE mixedc"), (9, vec![pair("MixedCase", "MixedCase")]));
+        assert_eq!(complete(&sqlite, "SELECT * FROM a JOIN sel"), (21, vec![pair("select", "\"select\"")]));
+
+        let mysql = helper(DatabaseType::MySQL);
+        assert_eq!(complete(&mysql, "SELECT * FROM `mixedc"), (14, vec![pair("MixedCase", "MixedCase")]));
+        assert_eq!(complete(&mysql, "SELECT * FROM sel"), (14, vec![pair("select", "`select`")]));
+    }
+
+    #[test]
+    fn tables_complete_only_after_table_keywords() {
+        let postgres = helper(DatabaseType::PostgreSQL);
+        assert!(complete(&postgres, "SELECT mixed").1.is_empty());
+        assert!(complete(&postgres, "mixed").1.is_empty());
+        assert!(complete(&postgres, "SELECT * FROM nothing").1.is_empty());
+    }
+
+    #[test]
+    fn hints_extend_only_what_is_spelled_as_completion_would() {
+        let postgres = helper(DatabaseType::PostgreSQL);
+        assert_eq!(postgres.table_hint("SELECT * FROM \"Mix").as_deref(), Some("edCase\""));
+        assert_eq!(postgres.table_hint("SELECT * FROM mixed_"), Some("lower".to_string()));
+        // Tab would rewrite `mixedc` as "MixedCase", so there is no hint
+        assert_eq!(postgres.table_hint("SELECT * FROM mixedc"), None);
+
+        let sqlite = helper(DatabaseType::SQLite);
+        assert_eq!(sqlite.table_hint("\\d Mixed").as_deref(), Some("Case"));
+        assert_eq!(sqlite.table_hint("\\d mixedc"), None);
+    }
+}
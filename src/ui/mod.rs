@@ -1,3 +1,6 @@
 pub mod connection_manager;
+pub mod editor;
 pub mod prompts;
-pub mod table_display;
+pub mod settings;
+
+pub use qgo::output;
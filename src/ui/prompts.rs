@@ -1,9 +1,154 @@
-use dialoguer::{theme::ColorfulTheme, Confirm};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
+use std::io::{ErrorKind, IsTerminal};
+use std::str::FromStr;
+
+use crate::config::Connection;
+use crate::error::QgoError;
+use crate::interrupt;
+
+/// Runs a dialoguer prompt, reporting Ctrl+C, Escape and the end of input
+/// as [`QgoError::Cancelled`] so callers can go back a step instead of
+/// failing.
+pub trait Ask {
+    type Answer;
+
+    fn ask(self) -> Result<Self::Answer, QgoError>;
+
+    /// Like [`Ask::ask`], but a cancelled prompt answers `fallback`.
+    fn ask_or(self, fallback: Self::Answer) -> Result<Self::Answer, QgoError>
+    where
+        Self: Sized,
+    {
+        match self.ask() {
+            Err(QgoError::Cancelled) => Ok(fallback),
+            answer => answer,
+        }
+    }
+}
+
+impl Ask for Select<'_> {
+    type Answer = usize;
+
+    fn ask(self) -> Result<usize, QgoError> {
+        let claim = interrupt::claim();
+        answered(self.interact_opt(), &claim)?.ok_or(QgoError::Cancelled)
+    }
+}
+
+impl Ask for Confirm<'_> {
+    type Answer = bool;
+
+    fn ask(self) -> Result<bool, QgoError> {
+        let claim = interrupt::claim();
+        answered(self.interact_opt(), &claim)?.ok_or(QgoError::Cancelled)
+    }
+}
+
+impl<T> Ask for Input<'_, T>
+where
+    T: Clone + ToString + FromStr,
+    <T as FromStr>::Err: ToString,
+{
+    type Answer = T;
+
+    fn ask(self) -> Result<T, QgoError> {
+        let claim = interrupt::claim();
+        answered(self.interact_text(), &claim)
+    }
+}
+
+fn answered<T>(result: dialoguer::Result<T>, claim: &interrupt::Claim) -> Result<T, QgoError> {
+    let dialoguer::Error::IO(error) = match result {
+        Ok(answer) => return Ok(answer),
+        Err(error) => error,
+    };
+    match error.kind() {
+        ErrorKind::Interrupted => {
+            claim.consume();
+            // dialoguer leaves the cursor hidden when a prompt is interrupted
+            let _ = console::Term::stderr().show_cursor();
+            eprintln!();
+            Err(QgoError::Cancelled)
+        }
+        ErrorKind::UnexpectedEof => Err(QgoError::Cancelled),
+        _ => Err(QgoError::Input(error.to_string())),
+    }
+}
+
+/// Whether `error` is a prompt the user backed out of.
+pub fn is_cancelled(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<QgoError>(), Some(QgoError::Cancelled))
+}
+
+/// Before writes on a production connection, makes the user type the
+/// connection's name. Without a terminal to ask on, the writes are refused.
+pub fn confirm_production_write(connection: &Connection) -> Result<(), QgoError> {
+    if !connection.is_production() {
+        return Ok(());
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(QgoError::InvalidQuery(format!(
+            "'{}' is a production connection; writes must be confirmed at a terminal",
+            connection.name
+        )));
+    }
+
+    eprintln!(
+        "{}",
+        style(format!("You are about to write to PRODUCTION ('{}').", connection.name)).red().bold()
+    );
+    let typed: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("Type '{}' to continue", connection.name))
+        .allow_empty(true)
+        .ask()?;
+    if typed.trim() != connection.name {
+        return Err(QgoError::InvalidQuery("the name didn't match; nothing was run".to_string()));
+    }
+    Ok(())
+}
 
 pub fn confirm(message: &str) -> bool {
-    Confirm::with_theme(&ColorfulTheme::default())
+    confirm_default(message, false)
+}
+
+/// Like [`confirm`], with `default` preselected. A cancelled prompt counts as
+/// no; any other failure is reported and also counts as no.
+pub fn confirm_default(message: &str, default: bool) -> bool {
+    let answer = Confirm::with_theme(&ColorfulTheme::default())
         .with_prompt(message)
-        .default(false)
-        .interact()
-        .unwrap_or(false)
+        .default(default)
+        .ask();
+    answer_or(answer, false)
+}
+
+/// Returns the index of the chosen item, or `None` if the prompt was cancelled.
+pub fn select(message: &str, items: &[&str]) -> Option<usize> {
+    let answer = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(message)
+        .items(items)
+        .default(0)
+        .ask()
+        .map(Some);
+    answer_or(answer, None)
+}
+
+pub fn input(message: &str, default: &str) -> Option<String> {
+    let answer = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(message)
+        .default(default.to_string())
+        .ask()
+        .map(Some);
+    answer_or(answer, None)
+}
+
+fn answer_or<T>(answer: Result<T, QgoError>, fallback: T) -> T {
+    match answer {
+        Ok(answer) => answer,
+        Err(QgoError::Cancelled) => fallback,
+        Err(e) => {
+            eprintln!("{}", style(e.to_string()).red());
+            fallback
+        }
+    }
 }
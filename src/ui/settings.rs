@@ -0,0 +1,514 @@
+use anyhow::Result;
+use chrono::format::{Item, StrftimeItems};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::config::{OnError, PasswordStorage, Settings};
+use crate::lint::Rule;
+use crate::secrets;
+use crate::timezone::SessionZone;
+use crate::ui::prompts::{self, Ask};
+use qgo::formatter;
+
+/// The groups the settings menu is split into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Category {
+    Display,
+    Query,
+    Export,
+    Security,
+}
+
+impl Category {
+    const ALL: [Category; 4] = [Category::Display, Category::Query, Category::Export, Category::Security];
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Category::Display => write!(f, "Display"),
+            Category::Query => write!(f, "Query"),
+            Category::Export => write!(f, "Export"),
+            Category::Security => write!(f, "Security"),
+        }
+    }
+}
+
+/// One entry of the settings menu. Every field of [`Settings`] a user may
+/// change has one in [`SETTINGS`], which is all the menu needs to offer it.
+pub struct Setting {
+    /// The key in the config file.
+    pub name: &'static str,
+    pub category: Category,
+    /// Shown when the setting is picked, and used as the prompt.
+    pub description: &'static str,
+    pub value: fn(&Settings) -> String,
+    /// Asks for a new value, given the description to prompt with.
+    pub edit: fn(&mut Settings, &str) -> Result<()>,
+}
+
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        name: "max_rows_display",
+        category: Category::Display,
+        description: "Rows shown of a result before the rest waits for \\more",
+        value: |s| optional(s.max_rows_display),
+        edit: |s, prompt| edit_optional_number(&mut s.max_rows_display, prompt, 1),
+    },
+    Setting {
+        name: "max_column_width",
+        category: Category::Display,
+        description: "Longer cell values are cut short in result tables",
+        value: |s| optional(s.max_column_width),
+        edit: |s, prompt| edit_optional_number(&mut s.max_column_width, prompt, 4),
+    },
+    Setting {
+        name: "show_types",
+        category: Category::Display,
+        description: "Show each column's type under its name",
+        value: |s| on_off(s.show_types),
+        edit: |s, prompt| edit_bool(&mut s.show_types, prompt),
+    },
+    Setting {
+        name: "scalar_display",
+        category: Category::Display,
+        description: "Print single-value results as column: value",
+        value: |s| on_off(s.scalar_display),
+        edit: |s, prompt| edit_bool(&mut s.scalar_display, prompt),
+    },
+    Setting {
+        name: "force_grid",
+        category: Category::Display,
+        description: "Keep the grid on terminals too narrow for the result",
+        value: |s| on_off(s.force_grid),
+        edit: |s, prompt| edit_bool(&mut s.force_grid, prompt),
+    },
+    Setting {
+        name: "result_format",
+        category: Category::Display,
+        description: "Print results in this export format (csv, json, yaml, ...) instead of the grid",
+        value: |s| optional(s.result_format.as_ref()),
+        edit: |s, prompt| edit_optional_text(&mut s.result_format, prompt, check_format),
+    },
+    Setting {
+        name: "date_format",
+        category: Category::Display,
+        description: "strftime format for dates and times, e.g. %Y-%m-%d %H:%M",
+        value: |s| optional(s.date_format.as_ref()),
+        edit: |s, prompt| edit_optional_text(&mut s.date_format, prompt, check_date_format),
+    },
+    Setting {
+        name: "timezone",
+        category: Category::Display,
+        description: "Time zone for sessions, e.g. UTC or Europe/Berlin",
+        value: |s| optional(s.timezone.as_ref()),
+        edit: |s, prompt| edit_optional_text(&mut s.timezone, prompt, |zone| SessionZone::parse(zone).map(|_| ()).map_err(|e| e.to_string())),
+    },
+    Setting {
+        name: "large_result_threshold",
+        category: Category::Display,
+        description: "Results with more rows ask before rendering",
+        value: |s| optional(s.large_result_threshold),
+        edit: |s, prompt| edit_optional_number(&mut s.large_result_threshold, prompt, 1),
+    },
+    Setting {
+        name: "chart_max_rows",
+        category: Category::Display,
+        description: "The most rows \\chart draws",
+        value: |s| s.chart_max_rows.to_string(),
+        edit: |s, prompt| edit_number(&mut s.chart_max_rows, prompt, 1),
+    },
+    Setting {
+        name: "color_output",
+        category: Category::Display,
+        description: "Highlight SQL while typing",
+        value: |s| on_off(s.color_output),
+        edit: |s, prompt| edit_bool(&mut s.color_output, prompt),
+    },
+    Setting {
+        name: "auto_completion",
+        category: Category::Display,
+        description: "Complete table names and keywords with Tab",
+        value: |s| on_off(s.auto_completion),
+        edit: |s, prompt| edit_bool(&mut s.auto_completion, prompt),
+    },
+    Setting {
+        name: "history_size",
+        category: Category::Display,
+        description: "Lines kept in the input history",
+        value: |s| s.history_size.to_string(),
+        edit: |s, prompt| edit_number(&mut s.history_size, prompt, 1),
+    },
+    Setting {
+        name: "normalize_input",
+        category: Category::Display,
+        description: "Fix line endings and offer to replace curly quotes in pasted input",
+        value: |s| on_off(s.normalize_input),
+        edit: |s, prompt| edit_bool(&mut s.normalize_input, prompt),
+    },
+    Setting {
+        name: "show_banner",
+        category: Category::Display,
+        description: "Print the connect and goodbye messages",
+        value: |s| on_off(s.show_banner),
+        edit: |s, prompt| edit_bool(&mut s.show_banner, prompt),
+    },
+    Setting {
+        name: "ask_to_save_session",
+        category: Category::Display,
+        description: "Offer on exit to save options changed with \\types, \\tz or \\set",
+        value: |s| on_off(s.ask_to_save_session),
+        edit: |s, prompt| edit_bool(&mut s.ask_to_save_session, prompt),
+    },
+    Setting {
+        name: "query_timeout_seconds",
+        category: Category::Query,
+        description: "Seconds a query may run before it is cancelled",
+        value: |s| s.query_timeout_seconds.to_string(),
+        edit: |s, prompt| edit_number(&mut s.query_timeout_seconds, prompt, 1),
+    },
+    Setting {
+        name: "on_error",
+        category: Category::Query,
+        description: "What scripts and multi-statement input do after a failing statement",
+        value: |s| on_error_name(s.on_error).to_string(),
+        edit: |s, prompt| {
+            let current = [OnError::Stop, OnError::Continue].iter().position(|mode| *mode == s.on_error).unwrap_or(0);
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt(prompt)
+                .items(&["stop", "continue"])
+                .default(current)
+                .ask()?;
+            s.on_error = [OnError::Stop, OnError::Continue][selection];
+            Ok(())
+        },
+    },
+    Setting {
+        name: "row_estimate_warning",
+        category: Category::Query,
+        description: "SELECTs from tables estimated to have more rows ask first",
+        value: |s| optional(s.row_estimate_warning),
+        edit: |s, prompt| edit_optional_number(&mut s.row_estimate_warning, prompt, 1),
+    },
+    Setting {
+        name: "max_result_memory_mb",
+        category: Category::Query,
+        description: "Queries whose result takes more memory (MB) are stopped",
+        value: |s| optional(s.max_result_memory_mb),
+        edit: |s, prompt| edit_optional_number(&mut s.max_result_memory_mb, prompt, 1),
+    },
+    Setting {
+        name: "result_cache_entries",
+        category: Category::Query,
+        description: "Read-only results kept for instant re-display; 0 turns the cache off",
+        value: |s| s.result_cache_entries.to_string(),
+        edit: |s, prompt| edit_number(&mut s.result_cache_entries, prompt, 0),
+    },
+    Setting {
+        name: "result_cache_ttl_secs",
+        category: Category::Query,
+        description: "Seconds a cached result is served before the query runs again",
+        value: |s| s.result_cache_ttl_secs.to_string(),
+        edit: |s, prompt| edit_number(&mut s.result_cache_ttl_secs, prompt, 1),
+    },
+    Setting {
+        name: "keepalive_interval_secs",
+        category: Category::Query,
+        description: "Idle seconds before a keepalive ping; none or 0 turns it off",
+        value: |s| optional(s.keepalive_interval_secs.filter(|secs| *secs > 0)),
+        edit: |s, prompt| edit_optional_number(&mut s.keepalive_interval_secs, prompt, 0),
+    },
+    Setting {
+        name: "application_name",
+        category: Category::Query,
+        description: "Name reported to the server; {connection} and {user} are filled in",
+        value: |s| s.application_name.clone(),
+        edit: |s, prompt| {
+            s.application_name = Input::<String>::with_theme(&ColorfulTheme::default())
+                .with_prompt(prompt)
+                .default(s.application_name.clone())
+                .validate_with(|name: &String| if name.trim().is_empty() { Err("can't be empty") } else { Ok(()) })
+                .ask()?
+                .trim()
+                .to_string();
+            Ok(())
+        },
+    },
+    Setting {
+        name: "recent_queries_max_bytes",
+        category: Category::Query,
+        description: "Size at which a connection's recent-queries file is rotated",
+        value: |s| s.recent_queries_max_bytes.to_string(),
+        edit: |s, prompt| edit_number(&mut s.recent_queries_max_bytes, prompt, 1024),
+    },
+    Setting {
+        name: "lint",
+        category: Category::Query,
+        description: "Warn about likely mistakes before running a query",
+        value: |s| on_off(s.lint),
+        edit: |s, prompt| edit_bool(&mut s.lint, prompt),
+    },
+    Setting {
+        name: "lint_max_star_columns",
+        category: Category::Query,
+        description: "SELECT * is linted when it returns more columns than this",
+        value: |s| s.lint_max_star_columns.to_string(),
+        edit: |s, prompt| edit_number(&mut s.lint_max_star_columns, prompt, 1),
+    },
+    Setting {
+        name: "lint_disabled",
+        category: Category::Query,
+        description: "Lint rules not to report, separated by commas",
+        value: |s| if s.lint_disabled.is_empty() { "none".to_string() } else { s.lint_disabled.join(", ") },
+        edit: |s, prompt| {
+            let names = Input::<String>::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("{} ({})", prompt, Rule::ALL.map(Rule::name).join(", ")))
+                .default(s.lint_disabled.join(", "))
+                .allow_empty(true)
+                .validate_with(|names: &String| match list(names).find(|name| !Rule::ALL.iter().any(|rule| rule.name() == *name)) {
+                    Some(name) => Err(format!("unknown rule '{}'", name)),
+                    None => Ok(()),
+                })
+                .ask()?;
+            s.lint_disabled = list(&names).map(str::to_string).collect();
+            Ok(())
+        },
+    },
+    Setting {
+        name: "confirm_each_query",
+        category: Category::Query,
+        description: "Show each statement's kind and tables and ask before running it",
+        value: |s| on_off(s.confirm_each_query),
+        edit: |s, prompt| edit_bool(&mut s.confirm_each_query, prompt),
+    },
+    Setting {
+        name: "probe_connections",
+        category: Category::Query,
+        description: "Check which saved connections are reachable before the main menu",
+        value: |s| on_off(s.probe_connections),
+        edit: |s, prompt| edit_bool(&mut s.probe_connections, prompt),
+    },
+    Setting {
+        name: "export_format",
+        category: Category::Export,
+        description: "Format of exports whose file name doesn't say",
+        value: |s| s.export_format.clone(),
+        edit: |s, prompt| {
+            let mut name = formatter::find(&s.export_format).map_or(formatter::CSV.name, |format| format.name);
+            edit_choice(&mut name, prompt, &formatter::names())?;
+            s.export_format = name.to_string();
+            Ok(())
+        },
+    },
+    Setting {
+        name: "export_directory",
+        category: Category::Export,
+        description: "Directory relative export file names are placed in",
+        value: |s| optional(s.export_directory.as_ref()),
+        edit: |s, prompt| edit_optional_text(&mut s.export_directory, prompt, |_| Ok(())),
+    },
+    Setting {
+        name: "max_export_rows",
+        category: Category::Export,
+        description: "The most rows an export writes",
+        value: |s| optional(s.max_export_rows),
+        edit: |s, prompt| edit_optional_number(&mut s.max_export_rows, prompt, 1),
+    },
+    Setting {
+        name: "csv_bom",
+        category: Category::Export,
+        description: "Start CSV exports with a byte order mark (for Excel)",
+        value: |s| on_off(s.csv_bom),
+        edit: |s, prompt| edit_bool(&mut s.csv_bom, prompt),
+    },
+    Setting {
+        name: "csv_crlf",
+        category: Category::Export,
+        description: "End CSV export lines with CRLF",
+        value: |s| on_off(s.csv_crlf),
+        edit: |s, prompt| edit_bool(&mut s.csv_crlf, prompt),
+    },
+    Setting {
+        name: "password_storage",
+        category: Category::Security,
+        description: "Where remembered passwords are kept",
+        value: |s| s.password_storage.to_string(),
+        edit: |s, prompt| {
+            let before = s.password_storage;
+            edit_choice(&mut s.password_storage, prompt, &[PasswordStorage::Keyring, PasswordStorage::Encrypted])?;
+            if s.password_storage != before {
+                secrets::set_backend(s.password_storage);
+                println!(
+                    "{}",
+                    style("Passwords already remembered stay where they are; `qgo secrets migrate` moves them.").dim()
+                );
+            }
+            Ok(())
+        },
+    },
+];
+
+/// Lets the user pick a category, then a setting in it, and change it,
+/// until they go back. Each value is checked as it is typed. Returns
+/// whether anything changed.
+pub fn edit(settings: &mut Settings) -> Result<bool> {
+    let before = serde_json::to_value(&*settings)?;
+    loop {
+        let mut items: Vec<String> = Category::ALL.iter().map(Category::to_string).collect();
+        items.push("Done".to_string());
+        let choice = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Settings")
+            .items(&items)
+            .default(0)
+            .ask_or(Category::ALL.len())?;
+        let Some(&category) = Category::ALL.get(choice) else {
+            break;
+        };
+        edit_category(settings, category)?;
+    }
+    Ok(serde_json::to_value(&*settings)? != before)
+}
+
+fn edit_category(settings: &mut Settings, category: Category) -> Result<()> {
+    let entries: Vec<&Setting> = SETTINGS.iter().filter(|setting| setting.category == category).collect();
+    let mut selected = 0;
+    loop {
+        let mut items: Vec<String> = entries
+            .iter()
+            .map(|setting| format!("{:<26} {}", setting.name, (setting.value)(settings)))
+            .collect();
+        items.push("Back".to_string());
+        selected = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("{} settings", category))
+            .items(&items)
+            .default(selected)
+            .ask_or(entries.len())?;
+        let Some(setting) = entries.get(selected) else {
+            return Ok(());
+        };
+        // Backing out of a setting's prompt leaves it unchanged
+        match (setting.edit)(settings, setting.description) {
+            Err(e) if prompts::is_cancelled(&e) => {}
+            result => result?,
+        }
+    }
+}
+
+/// Prints every setting with its value, by category.
+pub fn print(settings: &Settings) {
+    for category in Category::ALL {
+        println!("{}", style(category).bold());
+        for setting in SETTINGS.iter().filter(|setting| setting.category == category) {
+            println!("  {:<26} {}", setting.name, (setting.value)(settings));
+        }
+    }
+}
+
+fn on_off(value: bool) -> String {
+    if value { "on" } else { "off" }.to_string()
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "none".to_string(), |value| value.to_string())
+}
+
+fn on_error_name(on_error: OnError) -> &'static str {
+    match on_error {
+        OnError::Stop => "stop",
+        OnError::Continue => "continue",
+    }
+}
+
+fn list(names: &str) -> impl Iterator<Item = &str> {
+    names.split(',').map(str::trim).filter(|name| !name.is_empty())
+}
+
+fn check_date_format(format: &str) -> Result<(), String> {
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("'{}' is not a valid strftime format", format));
+    }
+    Ok(())
+}
+
+fn check_format(name: &str) -> Result<(), String> {
+    match formatter::find(name) {
+        Some(_) => Ok(()),
+        None => Err(format!("Not a format; pick one of {}", formatter::names().join(", "))),
+    }
+}
+
+fn edit_bool(value: &mut bool, prompt: &str) -> Result<()> {
+    *value = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(*value)
+        .ask()?;
+    Ok(())
+}
+
+fn edit_number<T>(value: &mut T, prompt: &str, min: T) -> Result<()>
+where
+    T: Copy + Display + PartialOrd + FromStr + ToString,
+    <T as FromStr>::Err: ToString,
+{
+    *value = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(*value)
+        .validate_with(|n: &T| if *n >= min { Ok(()) } else { Err(format!("must be at least {}", min)) })
+        .ask()?;
+    Ok(())
+}
+
+/// Like [`edit_number`], where `none` (or `off`) means no value.
+fn edit_optional_number<T>(value: &mut Option<T>, prompt: &str, min: T) -> Result<()>
+where
+    T: Copy + Display + PartialOrd + FromStr,
+{
+    let parse = |text: &str| -> Result<Option<T>, String> {
+        match text.trim().to_lowercase().as_str() {
+            "none" | "off" => Ok(None),
+            text => match text.parse::<T>() {
+                Ok(n) if n >= min => Ok(Some(n)),
+                Ok(_) => Err(format!("must be at least {}, or none", min)),
+                Err(_) => Err("enter a number, or none".to_string()),
+            },
+        }
+    };
+    let answer = Input::<String>::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("{} (none for no value)", prompt))
+        .default(optional(*value))
+        .validate_with(|text: &String| parse(text).map(|_| ()))
+        .ask()?;
+    *value = parse(&answer).map_err(|e| anyhow::anyhow!(e))?;
+    Ok(())
+}
+
+/// Asks for a text value that `check` accepts; `none` or nothing clears it.
+fn edit_optional_text(value: &mut Option<String>, prompt: &str, check: fn(&str) -> Result<(), String>) -> Result<()> {
+    let answer = Input::<String>::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("{} (none to clear)", prompt))
+        .default(value.clone().unwrap_or_default())
+        .allow_empty(true)
+        .validate_with(|text: &String| match text.trim() {
+            "" | "none" => Ok(()),
+            text => check(text),
+        })
+        .ask()?;
+    *value = match answer.trim() {
+        "" | "none" => None,
+        text => Some(text.to_string()),
+    };
+    Ok(())
+}
+
+fn edit_choice<T: Copy + PartialEq + Display>(value: &mut T, prompt: &str, choices: &[T]) -> Result<()> {
+    let current = choices.iter().position(|choice| choice == value).unwrap_or(0);
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(choices)
+        .default(current)
+        .ask()?;
+    *value = choices[selection];
+    Ok(())
+}
@@ -0,0 +1,271 @@
+use anyhow::Result;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::DatabaseType;
+use crate::database;
+use crate::error::QgoError;
+use crate::script;
+
+/// A value stored with `\into` or loaded as a parameter, and where it
+/// came from.
+pub struct Variable {
+    pub value: String,
+    /// A string parameter, which `:name` inserts as a quoted literal; other
+    /// values are inserted as they are.
+    pub text: bool,
+    pub origin: String,
+}
+
+/// Client-side variables, substituted into SQL before it is sent: `:name`
+/// inserts the value as is (for numbers) or, for string parameters, as a
+/// quoted string literal; `:'name'` always inserts a quoted string literal
+/// and `:"name"` a quoted identifier.
+#[derive(Default)]
+pub struct Variables {
+    values: BTreeMap<String, Variable>,
+}
+
+impl Variables {
+    pub fn set(&mut self, name: &str, value: String, origin: &str) {
+        self.insert(name, value, false, origin.trim());
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Variable)> {
+        self.values.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Loads a parameter file, JSON or TOML by its extension, whose
+    /// top-level keys become variables. Strings keep their type: `:name`
+    /// inserts them as quoted string literals. Numbers and booleans are
+    /// inserted as they are, and null as `NULL`. Returns how many were set.
+    pub fn load_params(&mut self, path: &Path) -> Result<usize> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| QgoError::Usage(format!("Cannot read parameter file '{}': {}", path.display(), e)))?;
+        let origin = format!("--params {}", path.display());
+        let invalid = |e: String| QgoError::Usage(format!("Invalid parameter file '{}': {}", path.display(), e));
+
+        let mut params = Vec::new();
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("toml")) {
+            let table: toml::Table = toml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+            for (name, value) in table {
+                let param = match value {
+                    toml::Value::String(text) => (text, true),
+                    toml::Value::Integer(n) => (n.to_string(), false),
+                    toml::Value::Float(n) => (n.to_string(), false),
+                    toml::Value::Boolean(b) => (b.to_string(), false),
+                    toml::Value::Datetime(datetime) => (datetime.to_string(), true),
+                    toml::Value::Array(_) | toml::Value::Table(_) => return Err(nested_error(&name)),
+                };
+                params.push((name, param));
+            }
+        } else {
+            let value: serde_json::Value = serde_json::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+            let serde_json::Value::Object(object) = value else {
+                return Err(invalid("expected an object of name-value pairs".to_string()).into());
+            };
+            for (name, value) in object {
+                let param = match value {
+                    serde_json::Value::String(text) => (text, true),
+                    serde_json::Value::Number(n) => (n.to_string(), false),
+                    serde_json::Value::Bool(b) => (b.to_string(), false),
+                    serde_json::Value::Null => ("NULL".to_string(), false),
+                    serde_json::Value::Array(_) | serde_json::Value::Object(_) => return Err(nested_error(&name)),
+                };
+                params.push((name, param));
+            }
+        }
+
+        for (name, _) in &params {
+            check_name(name)?;
+        }
+        let count = params.len();
+        for (name, (value, text)) in params {
+            self.insert(&name, value, text, &origin);
+        }
+        Ok(count)
+    }
+
+    /// Sets a variable from `--var name=value`. A value that reads as a
+    /// number, `true`, `false` or `null` keeps that type; anything else is a
+    /// string, as if it came from a parameter file.
+    pub fn set_from_arg(&mut self, arg: &str) -> Result<()> {
+        let (name, value) = arg
+            .split_once('=')
+            .ok_or_else(|| QgoError::Usage(format!("Invalid --var '{}'; expected name=value", arg)))?;
+        check_name(name)?;
+        let (value, text) = match serde_json::from_str::<serde_json::Value>(value) {
+            Ok(serde_json::Value::Number(n)) => (n.to_string(), false),
+            Ok(serde_json::Value::Bool(b)) => (b.to_string(), false),
+            Ok(serde_json::Value::Null) => ("NULL".to_string(), false),
+            _ => (value.to_string(), true),
+        };
+        self.insert(name, value, text, "--var");
+        Ok(())
+    }
+
+    fn insert(&mut self, name: &str, value: String, text: bool, origin: &str) {
+        self.values.insert(
+            name.to_string(),
+            Variable {
+                value,
+                text,
+                origin: origin.to_string(),
+            },
+        );
+    }
+
+    /// Names referenced as `:name` in `sql` that are not set, each once.
+    pub fn undefined(&self, sql: &str) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for reference in references(sql) {
+            if !self.values.contains_key(reference.name) && !names.iter().any(|name| name == reference.name) {
+                names.push(reference.name.to_string());
+            }
+        }
+        names
+    }
+
+    /// Replaces references to known variables in `sql`. References inside
+    /// quotes, comments and dollar-quoted bodies, `::` casts and unknown
+    /// names are left alone.
+    pub fn substitute<'a>(&self, db_type: &DatabaseType, sql: &'a str) -> Result<Cow<'a, str>> {
+        if self.values.is_empty() || !sql.contains(':') {
+            return Ok(Cow::Borrowed(sql));
+        }
+
+        let mut out = String::with_capacity(sql.len());
+        let mut copied = 0;
+        for reference in references(sql) {
+            let Some(variable) = self.values.get(reference.name) else {
+                continue;
+            };
+            let replacement = match reference.quote {
+                Some('\'') => database::quote_literal(db_type, &variable.value)?,
+                Some(_) => database::quote_ident(db_type, &variable.value)?,
+                None if variable.text => database::quote_literal(db_type, &variable.value)?,
+                None => variable.value.clone(),
+            };
+            out.push_str(&sql[copied..reference.start]);
+            out.push_str(&replacement);
+            copied = reference.end;
+        }
+
+        if copied == 0 {
+            return Ok(Cow::Borrowed(sql));
+        }
+        out.push_str(&sql[copied..]);
+        Ok(Cow::Owned(out))
+    }
+}
+
+/// A `:name`, `:'name'` or `:"name"` in SQL, spanning `start..end`.
+struct Reference<'a> {
+    start: usize,
+    end: usize,
+    name: &'a str,
+    quote: Option<char>,
+}
+
+/// The variable references in `sql`, skipping quotes, comments,
+/// dollar-quoted bodies and `::` casts.
+fn references(sql: &str) -> Vec<Reference<'_>> {
+    let bytes = sql.as_bytes();
+    let mut found = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = sql[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = sql[i + 2..].find("*/").map_or(bytes.len(), |n| i + 2 + n + 2);
+                continue;
+            }
+            quote @ (b'\'' | b'"' | b'`') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            b'$' => {
+                if let Some(tag_len) = script::dollar_tag(&sql[i..]) {
+                    let tag = &sql[i..i + tag_len];
+                    i = sql[i + tag_len..]
+                        .find(tag)
+                        .map_or(bytes.len(), |n| i + tag_len + n + tag_len);
+                    continue;
+                }
+            }
+            b':' if bytes.get(i + 1) == Some(&b':') => {
+                i += 2;
+                continue;
+            }
+            b':' => {
+                if let Some(reference) = reference_at(sql, i) {
+                    i = reference.end;
+                    found.push(reference);
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    found
+}
+
+/// The reference starting with the `:` at `start`, if a valid name
+/// follows it.
+fn reference_at(sql: &str, start: usize) -> Option<Reference<'_>> {
+    let rest = &sql[start + 1..];
+    let quote = rest.chars().next().filter(|c| matches!(c, '\'' | '"'));
+    let name_start = start + 1 + quote.map_or(0, char::len_utf8);
+    let name_len = sql[name_start..]
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(sql.len() - name_start);
+    let name = &sql[name_start..name_start + name_len];
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return None;
+    }
+    let mut end = name_start + name_len;
+    if let Some(quote) = quote {
+        if !sql[end..].starts_with(quote) {
+            return None;
+        }
+        end += 1;
+    }
+    Some(Reference { start, end, name, quote })
+}
+
+fn nested_error(name: &str) -> anyhow::Error {
+    QgoError::Usage(format!(
+        "Parameter '{}' is a list or table; only strings, numbers, booleans and null can be substituted",
+        name
+    ))
+    .into()
+}
+
+/// Checks that `name` can be referenced as `:name`.
+pub fn check_name(name: &str) -> Result<()> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(QgoError::Usage(format!(
+            "Invalid variable name '{}'; use letters, digits and underscores",
+            name
+        ))
+        .into());
+    }
+    Ok(())
+}
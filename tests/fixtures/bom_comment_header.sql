@@ -0,0 +1,14 @@
+﻿/*
+ * Nightly cleanup
+ * Exported on Windows
+ */
+
+-- create the table
+CREATE TABLE t (x INTEGER);
+
+-- seed; with a semicolon
+INSERT INTO t VALUES (1);
+/* the next one fails */
+INSERT INTO missing VALUES (2);
+
+  INSERT INTO t VALUES (3);